@@ -0,0 +1,5 @@
+alter table repositories
+    add column if not exists upstream_repository_id uuid
+        references repositories (id) on delete set null,
+    add column if not exists upstream_topic_id text,
+    add column if not exists forked_at timestamp with time zone;
@@ -0,0 +1,17 @@
+create table if not exists change_requests (
+    id uuid primary key default gen_random_uuid(),
+    author_id uuid not null references users (id) on delete cascade,
+    source_repository_id uuid not null references repositories (id) on delete cascade,
+    target_repository_id uuid not null references repositories (id) on delete cascade,
+    title character varying(256) not null,
+    description text not null default '',
+    status character varying(16) not null default 'open',
+    payload jsonb not null,
+    reviewer_id uuid references users (id) on delete set null,
+    reviewed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+create index if not exists change_requests_target_status_idx
+    on change_requests (target_repository_id, status);
@@ -0,0 +1,373 @@
+use async_graphql::dataloader::*;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::git;
+use crate::graphql;
+use crate::prelude::*;
+use crate::psql;
+
+mod system;
+pub use system::{GithubAccount, SystemRepo};
+
+// Where users, sessions, organizations, repos, memberships and change requests are kept.  Content
+// is always in git.
+#[derive(Clone, Debug)]
+pub enum Accounts {
+    Git(Arc<SystemRepo>),
+    Postgres(PgPool),
+}
+
+impl Accounts {
+    // Records a personal repo that has just been created for a user.  Postgres users are given
+    // their repos by other means.
+    pub async fn add_personal_repo(&self, user_id: &str, repo_id: RepoId) -> Result<()> {
+        match self {
+            Self::Git(system) => {
+                let user_id = user_id.to_owned();
+                system
+                    .run(move |system| system.add_personal_repo(&user_id, repo_id))
+                    .await
+            }
+            Self::Postgres(_) => Ok(()),
+        }
+    }
+
+    pub async fn close_change_request(
+        &self,
+        actor: Arc<Viewer>,
+        id: String,
+        status: graphql::ChangeRequestStatus,
+    ) -> Result<psql::CloseChangeRequestResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.close_change_request(&actor, &id, status))
+                    .await
+            }
+            Self::Postgres(pool) => {
+                psql::CloseChangeRequest::new(actor, id, status)
+                    .call(pool)
+                    .await
+            }
+        }
+    }
+
+    pub async fn reopen_change_request(&self, actor: Arc<Viewer>, id: String) -> Result<()> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.reopen_change_request(&actor, &id))
+                    .await
+            }
+            Self::Postgres(pool) => psql::ReopenChangeRequest::new(actor, id).call(pool).await,
+        }
+    }
+
+    pub async fn create_change_request(
+        &self,
+        actor: Arc<Viewer>,
+        source_repo_id: RepoId,
+        target_repo_id: RepoId,
+        title: String,
+        description: String,
+        payload: git::ChangeRequestPayload,
+    ) -> Result<psql::CreateChangeRequestResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| {
+                        system.create_change_request(
+                            &actor,
+                            source_repo_id,
+                            target_repo_id,
+                            title,
+                            description,
+                            payload,
+                        )
+                    })
+                    .await
+            }
+            Self::Postgres(pool) => {
+                psql::CreateChangeRequest::new(
+                    actor,
+                    source_repo_id,
+                    target_repo_id,
+                    title,
+                    description,
+                    payload,
+                )
+                .call(pool)
+                .await
+            }
+        }
+    }
+
+    pub async fn create_fork_repository(
+        &self,
+        actor: Arc<Viewer>,
+        fork_repo_id: RepoId,
+        name: String,
+        upstream_repo_id: RepoId,
+        upstream_topic_id: Option<ExternalId>,
+    ) -> Result<psql::CreateForkRepositoryResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| {
+                        system.create_fork_repository(
+                            &actor,
+                            fork_repo_id,
+                            name,
+                            upstream_repo_id,
+                            upstream_topic_id,
+                        )
+                    })
+                    .await
+            }
+            Self::Postgres(pool) => {
+                psql::CreateForkRepository::new(
+                    actor,
+                    fork_repo_id,
+                    name,
+                    upstream_repo_id,
+                    upstream_topic_id,
+                )
+                .call(pool)
+                .await
+            }
+        }
+    }
+
+    pub async fn create_github_session(
+        &self,
+        input: graphql::CreateGithubSessionInput,
+    ) -> Result<psql::CreateSessionResult> {
+        match self {
+            Self::Git(system) => {
+                let account = GithubAccount::from(&input);
+                system
+                    .run(move |system| system.create_github_session(&account))
+                    .await
+            }
+            Self::Postgres(pool) => psql::CreateGithubSession::new(input).call(pool).await,
+        }
+    }
+
+    pub async fn delete_account(
+        &self,
+        actor: Arc<Viewer>,
+        user_id: String,
+    ) -> Result<psql::DeleteAccountResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.delete_account(&actor, &user_id))
+                    .await
+            }
+            Self::Postgres(pool) => psql::DeleteAccount::new(actor, user_id).call(pool).await,
+        }
+    }
+
+    pub async fn delete_session(
+        &self,
+        viewer: Arc<Viewer>,
+        session_id: String,
+    ) -> Result<psql::DeleteSessionResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.delete_session(&viewer, &session_id))
+                    .await
+            }
+            Self::Postgres(pool) => {
+                psql::DeleteSession::new(viewer, session_id)
+                    .call(pool)
+                    .await
+            }
+        }
+    }
+
+    pub async fn fetch_account_info(
+        &self,
+        user_id: String,
+    ) -> Result<psql::FetchAccountInfoResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.fetch_account_info(&user_id))
+                    .await
+            }
+            Self::Postgres(pool) => psql::FetchAccountInfo { user_id }.call(pool).await,
+        }
+    }
+
+    pub async fn fetch_change_requests(
+        &self,
+        viewer: Arc<Viewer>,
+        id: Option<String>,
+        status: Option<graphql::ChangeRequestStatus>,
+        limit: i64,
+    ) -> Result<Vec<graphql::ChangeRequest>> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| {
+                        system.fetch_change_requests(&viewer, id.as_deref(), status, limit)
+                    })
+                    .await
+            }
+            Self::Postgres(pool) => {
+                let fetch = match id {
+                    Some(id) => psql::FetchChangeRequests::by_id(viewer, id),
+                    None => psql::FetchChangeRequests::new(viewer, status, limit),
+                };
+                fetch.call(pool).await
+            }
+        }
+    }
+
+    // Looks up the repos a user can write to so that a command-line tool can act on their behalf
+    // without a session
+    pub async fn fetch_viewer(&self, user_id: String) -> Result<Viewer> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.fetch_viewer(&user_id))
+                    .await
+            }
+            Self::Postgres(pool) => psql::FetchViewer { user_id }.call(pool).await,
+        }
+    }
+
+    pub async fn fetch_writeable_repositories_for_user(
+        &self,
+        viewer: Arc<Viewer>,
+        user_id: String,
+    ) -> Result<Vec<graphql::Repository>> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| {
+                        system.fetch_writeable_repositories_for_user(&viewer, &user_id)
+                    })
+                    .await
+            }
+            Self::Postgres(pool) => {
+                psql::FetchWriteableRepositoriesForUser::new(viewer, user_id)
+                    .call(pool)
+                    .await
+            }
+        }
+    }
+
+    pub async fn select_repository(
+        &self,
+        actor: Arc<Viewer>,
+        repo_id: Option<RepoId>,
+    ) -> Result<psql::SelectRepositoryResult> {
+        match self {
+            Self::Git(system) => {
+                system
+                    .run(move |system| system.select_repository(&actor, repo_id))
+                    .await
+            }
+            Self::Postgres(pool) => psql::SelectRepository::new(actor, repo_id).call(pool).await,
+        }
+    }
+}
+
+pub struct OrganizationLoader {
+    accounts: Accounts,
+    viewer: Arc<Viewer>,
+}
+
+impl OrganizationLoader {
+    pub fn new(viewer: Arc<Viewer>, accounts: Accounts) -> Self {
+        Self { accounts, viewer }
+    }
+}
+
+impl Loader<String> for OrganizationLoader {
+    type Value = graphql::Organization;
+    type Error = Error;
+
+    async fn load(&self, ids: &[String]) -> Result<HashMap<String, Self::Value>> {
+        match &self.accounts {
+            Accounts::Git(system) => {
+                let (viewer, ids) = (Arc::clone(&self.viewer), ids.to_vec());
+                system
+                    .run(move |system| system.organizations(&viewer, &ids))
+                    .await
+            }
+            Accounts::Postgres(pool) => {
+                psql::OrganizationLoader::new(Arc::clone(&self.viewer), pool.clone())
+                    .load(ids)
+                    .await
+            }
+        }
+    }
+}
+
+pub struct RepositoryLoader {
+    accounts: Accounts,
+    viewer: Arc<Viewer>,
+}
+
+impl RepositoryLoader {
+    pub fn new(viewer: Arc<Viewer>, accounts: Accounts) -> Self {
+        Self { accounts, viewer }
+    }
+}
+
+impl Loader<String> for RepositoryLoader {
+    type Value = graphql::Repository;
+    type Error = Error;
+
+    async fn load(&self, ids: &[String]) -> Result<HashMap<String, Self::Value>> {
+        match &self.accounts {
+            Accounts::Git(system) => {
+                let (viewer, ids) = (Arc::clone(&self.viewer), ids.to_vec());
+                system
+                    .run(move |system| system.repositories(&viewer, &ids))
+                    .await
+            }
+            Accounts::Postgres(pool) => {
+                psql::RepositoryLoader::new(Arc::clone(&self.viewer), pool.clone())
+                    .load(ids)
+                    .await
+            }
+        }
+    }
+}
+
+pub struct UserLoader {
+    accounts: Accounts,
+    viewer: Arc<Viewer>,
+}
+
+impl UserLoader {
+    pub fn new(viewer: Arc<Viewer>, accounts: Accounts) -> Self {
+        Self { accounts, viewer }
+    }
+}
+
+impl Loader<String> for UserLoader {
+    type Value = psql::user::Row;
+    type Error = Error;
+
+    async fn load(&self, ids: &[String]) -> Result<HashMap<String, Self::Value>> {
+        match &self.accounts {
+            Accounts::Git(system) => {
+                let ids = ids.to_vec();
+                system.run(move |system| system.users(&ids)).await
+            }
+            Accounts::Postgres(pool) => {
+                psql::UserLoader::new(Arc::clone(&self.viewer), pool.clone())
+                    .load(ids)
+                    .await
+            }
+        }
+    }
+}
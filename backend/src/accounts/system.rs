@@ -0,0 +1,951 @@
+use async_graphql::ID;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::git::{self, core};
+use crate::graphql::{self, ChangeRequestStatus};
+use crate::prelude::*;
+use crate::psql;
+use crate::types::{random_id, sha256_base64};
+
+// The directory under the data root that holds the system repo.  Content repos are named by their
+// uuid, and anything that walks the data root looking for them skips dot directories.
+const SYSTEM_REPO_DIRECTORY: &str = ".system";
+
+// The details of a GitHub account that a session is being created for
+#[derive(Clone, Debug)]
+pub struct GithubAccount {
+    pub avatar_url: String,
+    pub name: String,
+    pub primary_email: String,
+    pub username: String,
+}
+
+impl From<&graphql::CreateGithubSessionInput> for GithubAccount {
+    fn from(input: &graphql::CreateGithubSessionInput) -> Self {
+        Self {
+            avatar_url: input.github_avatar_url.to_owned(),
+            name: input.name.to_owned(),
+            primary_email: input.primary_email.to_owned(),
+            username: input.github_username.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Membership {
+    can_write: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    avatar_url: String,
+    github_username: Option<String>,
+    id: Uuid,
+    login: Option<String>,
+    name: String,
+    organization_id: Option<Uuid>,
+    #[serde(default)]
+    personal_repo_ids: Vec<RepoId>,
+    primary_email: Option<String>,
+    registered_at: Option<Timestamp>,
+    #[serde(default)]
+    repos: BTreeMap<RepoId, Membership>,
+    selected_repo_id: Option<RepoId>,
+}
+
+impl User {
+    fn path(id: &Uuid) -> String {
+        format!("users/{id}.yaml")
+    }
+
+    fn write_repo_ids(&self) -> Vec<RepoId> {
+        self.repos
+            .iter()
+            .filter(|(_, membership)| membership.can_write)
+            .map(|(&repo_id, _)| repo_id)
+            .collect()
+    }
+
+    fn to_row(&self) -> psql::user::Row {
+        psql::user::Row {
+            avatar_url: self.avatar_url.to_owned(),
+            id: self.id,
+            login: self.login.to_owned(),
+            name: self.name.to_owned(),
+            selected_repository_id: self.selected_repo_id.map(Uuid::from),
+            write_repository_ids: self.write_repo_ids().into_iter().map(Uuid::from).collect(),
+        }
+    }
+
+    fn to_viewer(&self, session_id: Option<String>) -> Viewer {
+        let repo_ids = self.write_repo_ids();
+        Viewer {
+            context_repo_id: self.selected_repo_id.unwrap_or_else(RepoId::wiki),
+            read_repo_ids: repo_ids.to_owned().into(),
+            session_id,
+            super_user: false,
+            user_id: self.id.to_string(),
+            write_repo_ids: repo_ids.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GithubLogin {
+    user_id: Uuid,
+}
+
+impl GithubLogin {
+    fn path(username: &str) -> String {
+        format!("github/{}.yaml", sha256_base64(username))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Organization {
+    id: Uuid,
+    login: String,
+    name: String,
+    owner_id: Option<Uuid>,
+    public: bool,
+}
+
+impl Organization {
+    fn path(id: &Uuid) -> String {
+        format!("organizations/{id}.yaml")
+    }
+
+    fn to_organization(&self) -> graphql::Organization {
+        graphql::Organization::Selected {
+            id: ID(self.id.to_string()),
+            login: self.login.to_owned(),
+            name: self.name.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Repository {
+    forked_at: Option<Timestamp>,
+    id: RepoId,
+    name: String,
+    organization_id: Uuid,
+    owner_id: Option<Uuid>,
+    private: bool,
+    upstream_repo_id: Option<RepoId>,
+    upstream_topic_id: Option<ExternalId>,
+}
+
+impl Repository {
+    fn path(id: &RepoId) -> String {
+        format!("repositories/{id}.yaml")
+    }
+
+    fn to_repository(&self) -> graphql::Repository {
+        graphql::Repository::Fetched {
+            id: self.id.to_string(),
+            name: self.name.to_owned(),
+            organization_id: self.organization_id.to_string(),
+            owner_id: self.owner_id.map(|id| id.to_string()).unwrap_or_default(),
+            private: self.private,
+            upstream_repo_id: self.upstream_repo_id.map(|id| id.to_string()),
+        }
+    }
+}
+
+// Only a hash of the session id is kept, so that the id cannot be recovered from the history of
+// the repo after the session has been deleted
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    created_at: Timestamp,
+    user_id: Uuid,
+}
+
+impl Session {
+    fn path(session_id: &str) -> String {
+        format!("sessions/{}.yaml", sha256_base64(session_id))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeletedUser {
+    deleted_at: Timestamp,
+    user_id: Uuid,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeRequest {
+    author_id: Uuid,
+    created_at: Timestamp,
+    description: String,
+    id: Uuid,
+    payload: git::ChangeRequestPayload,
+    reviewed_at: Option<Timestamp>,
+    reviewer_id: Option<Uuid>,
+    source_repo_id: RepoId,
+    status: String,
+    target_repo_id: RepoId,
+    title: String,
+}
+
+impl ChangeRequest {
+    fn path(id: &Uuid) -> String {
+        format!("change-requests/{id}.yaml")
+    }
+
+    fn status(&self) -> Result<ChangeRequestStatus> {
+        ChangeRequestStatus::from_str(&self.status)
+            .map_err(|err| Error::Parse(format!("{}: {}", self.status, err)))
+    }
+
+    fn to_change_request(&self) -> Result<graphql::ChangeRequest> {
+        Ok(graphql::ChangeRequest {
+            author_id: self.author_id.to_string(),
+            created_at: self.created_at,
+            description: self.description.to_owned(),
+            id: self.id.to_string(),
+            payload: self.payload.to_owned(),
+            reviewed_at: self.reviewed_at,
+            reviewer_id: self.reviewer_id.map(|id| id.to_string()),
+            source_repo_id: self.source_repo_id.to_string(),
+            status: self.status()?,
+            target_repo_id: self.target_repo_id.to_string(),
+            title: self.title.to_owned(),
+        })
+    }
+}
+
+fn parse_uuid(id: &str) -> Option<Uuid> {
+    Uuid::parse_str(id).ok()
+}
+
+// Files to add, replace or remove in a single commit to the system repo
+struct Update<'r> {
+    repo: &'r core::Repo,
+    tree: core::Tree,
+}
+
+impl<'r> Update<'r> {
+    fn new(repo: &'r core::Repo) -> Self {
+        Self {
+            repo,
+            tree: core::Tree::new(),
+        }
+    }
+
+    fn put<T: Serialize>(&mut self, path: &str, record: &T) -> Result<()> {
+        let oid = self
+            .repo
+            .add_blob(serde_yaml::to_string(record)?.as_bytes())?;
+        self.tree
+            .add_blob(&mut core::deque_from_path(Path::new(path)), &Some(oid));
+        Ok(())
+    }
+
+    // The file must exist
+    fn remove(&mut self, path: &str) {
+        self.tree
+            .add_blob(&mut core::deque_from_path(Path::new(path)), &None);
+    }
+
+    fn write(self, message: &str) -> Result<()> {
+        let inner = &self.repo.inner;
+        let head = inner.find_reference("HEAD")?;
+        let before = head.peel_to_tree()?;
+        let oid = self.tree.write(inner, Some(before))?;
+        let tree = inner.find_tree(oid)?;
+        let parent = head.peel_to_commit()?;
+        let sig = git2::Signature::now("digraph-bot", "noreply@digraph.app")?;
+        inner.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])?;
+        Ok(())
+    }
+}
+
+fn read<T: DeserializeOwned>(repo: &core::Repo, path: &str) -> Result<Option<T>> {
+    let tree = repo.inner.head()?.peel_to_tree()?;
+    let entry = match tree.get_path(Path::new(path)) {
+        Ok(entry) => entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let blob = repo.inner.find_blob(entry.id())?;
+    Ok(Some(serde_yaml::from_slice(blob.content())?))
+}
+
+fn list<T: DeserializeOwned>(repo: &core::Repo, dir: &str) -> Result<Vec<(String, T)>> {
+    let tree = repo.inner.head()?.peel_to_tree()?;
+    let entry = match tree.get_path(Path::new(dir)) {
+        Ok(entry) => entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut records = vec![];
+    for entry in repo.inner.find_tree(entry.id())?.iter() {
+        let Some(name) = entry.name() else {
+            continue;
+        };
+        let blob = repo.inner.find_blob(entry.id())?;
+        records.push((
+            format!("{dir}/{name}"),
+            serde_yaml::from_slice(blob.content())?,
+        ));
+    }
+
+    Ok(records)
+}
+
+fn fetch_user(repo: &core::Repo, user_id: &str) -> Result<User> {
+    parse_uuid(user_id)
+        .map(|id| read::<User>(repo, &User::path(&id)))
+        .transpose()?
+        .flatten()
+        .ok_or_else(|| Error::NotFound(format!("user not found: {user_id}")))
+}
+
+// Users, sessions, organizations, repos, memberships and change requests kept as YAML files in a
+// git repo of their own, so that the API can run with nothing more than a data directory.  Handles
+// on the repo come from the pool of the data root, so reads do not wait on each other or on
+// writes.  Writes are serialized by a lock and each one is saved as a commit.
+pub struct SystemRepo {
+    path: PathBuf,
+    pool: Arc<core::RepoPool>,
+    writing: Mutex<()>,
+}
+
+impl std::fmt::Debug for SystemRepo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemRepo")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl SystemRepo {
+    pub fn path(root: &git::DataRoot) -> PathBuf {
+        root.path.join(SYSTEM_REPO_DIRECTORY)
+    }
+
+    // Opens the system repo, creating it along with the wiki organization and repo if needed
+    pub fn open(root: &git::DataRoot) -> Result<Arc<Self>> {
+        let system = Self {
+            path: Self::path(root),
+            pool: Arc::clone(&root.pool),
+            writing: Mutex::new(()),
+        };
+        let repo = system.repo()?;
+
+        let org_id = Uuid::parse_str(WIKI_ORGANIZATION_ID)?;
+        if read::<Organization>(&repo, &Organization::path(&org_id))?.is_none() {
+            log::info!("adding the wiki to the system repo");
+            let mut update = Update::new(&repo);
+            update.put(
+                &Organization::path(&org_id),
+                &Organization {
+                    id: org_id,
+                    login: "wiki".to_owned(),
+                    name: "wiki".to_owned(),
+                    owner_id: None,
+                    public: true,
+                },
+            )?;
+            update.put(
+                &Repository::path(&RepoId::wiki()),
+                &Repository {
+                    forked_at: None,
+                    id: RepoId::wiki(),
+                    name: "Wiki".to_owned(),
+                    organization_id: org_id,
+                    owner_id: None,
+                    private: false,
+                    upstream_repo_id: None,
+                    upstream_topic_id: None,
+                },
+            )?;
+            update.write("Add the wiki")?;
+        }
+
+        Ok(Arc::new(system))
+    }
+
+    // Runs a call on the blocking pool, since libgit2 blocks the thread while it reads and writes
+    pub async fn run<T, F>(self: &Arc<Self>, call: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> Result<T> + Send + 'static,
+    {
+        let system = Arc::clone(self);
+        tokio::task::spawn_blocking(move || call(&system)).await?
+    }
+
+    pub fn add_personal_repo(&self, user_id: &str, repo_id: RepoId) -> Result<()> {
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let mut user = fetch_user(&repo, user_id)?;
+        let organization_id = user
+            .organization_id
+            .ok_or_else(|| Error::NotFound(format!("no personal org for {user_id}")))?;
+
+        log::info!("registering personal repo {} for {}", repo_id, user_id);
+        let mut update = Update::new(&repo);
+        update.put(
+            &Repository::path(&repo_id),
+            &Repository {
+                forked_at: None,
+                id: repo_id,
+                name: DEFAULT_REPOSITORY_NAME.to_owned(),
+                organization_id,
+                owner_id: Some(user.id),
+                private: true,
+                upstream_repo_id: None,
+                upstream_topic_id: None,
+            },
+        )?;
+        user.personal_repo_ids.push(repo_id);
+        user.repos.insert(repo_id, Membership { can_write: true });
+        update.put(&User::path(&user.id), &user)?;
+        update.write(&format!("Add personal repo for {user_id}"))
+    }
+
+    pub fn close_change_request(
+        &self,
+        actor: &Viewer,
+        id: &str,
+        status: ChangeRequestStatus,
+    ) -> Result<psql::CloseChangeRequestResult> {
+        if status == ChangeRequestStatus::Open {
+            return Err(Error::Repo("a change request cannot be reopened".into()));
+        }
+
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let not_found = || Error::NotFound(format!("no open change request: {id}"));
+        let uuid = parse_uuid(id).ok_or_else(not_found)?;
+        let mut change_request =
+            read::<ChangeRequest>(&repo, &ChangeRequest::path(&uuid))?.ok_or_else(not_found)?;
+
+        let allowed = actor.write_repo_ids.include(change_request.target_repo_id)
+            || (status == ChangeRequestStatus::Rejected
+                && change_request.author_id.to_string() == actor.user_id);
+        if change_request.status()? != ChangeRequestStatus::Open || !allowed {
+            return Err(not_found());
+        }
+
+        log::info!(
+            "marking change request {} as {} for {}",
+            id,
+            status,
+            actor.user_id
+        );
+        change_request.status = status.to_string();
+        change_request.reviewer_id = parse_uuid(&actor.user_id);
+        change_request.reviewed_at = Some(chrono::Utc::now());
+
+        let mut update = Update::new(&repo);
+        update.put(&ChangeRequest::path(&uuid), &change_request)?;
+        update.write(&format!("Mark change request {id} as {status}"))?;
+
+        Ok(psql::CloseChangeRequestResult {
+            change_request: change_request.to_change_request()?,
+        })
+    }
+
+    pub fn reopen_change_request(&self, actor: &Viewer, id: &str) -> Result<()> {
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let not_found = || Error::NotFound(format!("no accepted change request: {id}"));
+        let uuid = parse_uuid(id).ok_or_else(not_found)?;
+        let mut change_request =
+            read::<ChangeRequest>(&repo, &ChangeRequest::path(&uuid))?.ok_or_else(not_found)?;
+
+        if change_request.status()? != ChangeRequestStatus::Accepted
+            || change_request.reviewer_id != parse_uuid(&actor.user_id)
+        {
+            return Err(not_found());
+        }
+
+        log::info!("reopening change request {} for {}", id, actor.user_id);
+        change_request.status = ChangeRequestStatus::Open.to_string();
+        change_request.reviewer_id = None;
+        change_request.reviewed_at = None;
+
+        let mut update = Update::new(&repo);
+        update.put(&ChangeRequest::path(&uuid), &change_request)?;
+        update.write(&format!("Reopen change request {id}"))
+    }
+
+    pub fn create_change_request(
+        &self,
+        actor: &Viewer,
+        source_repo_id: RepoId,
+        target_repo_id: RepoId,
+        title: String,
+        description: String,
+        payload: git::ChangeRequestPayload,
+    ) -> Result<psql::CreateChangeRequestResult> {
+        if actor.is_guest() {
+            return Err(Error::RBAC("log in to propose changes".into()));
+        }
+        let author_id = parse_uuid(&actor.user_id)
+            .ok_or_else(|| Error::NotFound(format!("user not found: {}", actor.user_id)))?;
+
+        log::info!(
+            "creating change request from {} to {} for {}",
+            source_repo_id,
+            target_repo_id,
+            actor.user_id
+        );
+        let change_request = ChangeRequest {
+            author_id,
+            created_at: chrono::Utc::now(),
+            description,
+            id: Uuid::new_v4(),
+            payload,
+            reviewed_at: None,
+            reviewer_id: None,
+            source_repo_id,
+            status: ChangeRequestStatus::Open.to_string(),
+            target_repo_id,
+            title,
+        };
+
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let mut update = Update::new(&repo);
+        update.put(&ChangeRequest::path(&change_request.id), &change_request)?;
+        update.write(&format!("Add change request {}", change_request.id))?;
+
+        Ok(psql::CreateChangeRequestResult {
+            change_request: change_request.to_change_request()?,
+        })
+    }
+
+    pub fn create_fork_repository(
+        &self,
+        actor: &Viewer,
+        fork_repo_id: RepoId,
+        name: String,
+        upstream_repo_id: RepoId,
+        upstream_topic_id: Option<ExternalId>,
+    ) -> Result<psql::CreateForkRepositoryResult> {
+        if actor.is_guest() {
+            return Err(Error::RBAC("log in to fork a repo".into()));
+        }
+
+        log::info!(
+            "registering fork {} of {} for {}",
+            fork_repo_id,
+            upstream_repo_id,
+            actor.user_id
+        );
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let mut user = fetch_user(&repo, &actor.user_id)?;
+        let organization_id = user
+            .organization_id
+            .ok_or_else(|| Error::NotFound(format!("no personal org for {}", actor.user_id)))?;
+
+        let fork = Repository {
+            forked_at: Some(chrono::Utc::now()),
+            id: fork_repo_id,
+            name,
+            organization_id,
+            owner_id: Some(user.id),
+            private: true,
+            upstream_repo_id: Some(upstream_repo_id),
+            upstream_topic_id,
+        };
+
+        // So that the fork is cleaned up along with the rest of the account
+        user.personal_repo_ids.push(fork_repo_id);
+        user.repos
+            .insert(fork_repo_id, Membership { can_write: true });
+
+        let mut update = Update::new(&repo);
+        update.put(&Repository::path(&fork_repo_id), &fork)?;
+        update.put(&User::path(&user.id), &user)?;
+        update.write(&format!("Add fork {fork_repo_id} of {upstream_repo_id}"))?;
+
+        Ok(psql::CreateForkRepositoryResult {
+            repo: fork.to_repository(),
+        })
+    }
+
+    pub fn create_github_session(
+        &self,
+        account: &GithubAccount,
+    ) -> Result<psql::CreateSessionResult> {
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let mut update = Update::new(&repo);
+
+        let existing = match read::<GithubLogin>(&repo, &GithubLogin::path(&account.username))? {
+            Some(login) => read::<User>(&repo, &User::path(&login.user_id))?,
+            None => None,
+        };
+
+        let user = match existing {
+            Some(user) => user,
+            None => {
+                log::info!("user {} not found, creating", account.username);
+                let user = self.register(&mut update, account)?;
+                update.put(
+                    &GithubLogin::path(&account.username),
+                    &GithubLogin { user_id: user.id },
+                )?;
+                user
+            }
+        };
+
+        let session_id = random_id();
+        update.put(
+            &Session::path(&session_id),
+            &Session {
+                created_at: chrono::Utc::now(),
+                user_id: user.id,
+            },
+        )?;
+        update.write(&format!("Add session for {}", user.id))?;
+        log::debug!("session created for user {:?}", user.name);
+
+        Ok(psql::CreateSessionResult {
+            alerts: vec![],
+            personal_repo_ids: user.personal_repo_ids.to_owned(),
+            session_id,
+            user: user.to_row(),
+        })
+    }
+
+    pub fn delete_account(
+        &self,
+        actor: &Viewer,
+        user_id: &str,
+    ) -> Result<psql::DeleteAccountResult> {
+        if user_id != actor.user_id {
+            return Err(Error::RBAC("not allowed to delete account".into()));
+        }
+
+        log::warn!("deleting account {}", user_id);
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let user = fetch_user(&repo, user_id)?;
+        let mut update = Update::new(&repo);
+
+        update.put(
+            &format!("deleted-users/{}.yaml", user.id),
+            &DeletedUser {
+                deleted_at: chrono::Utc::now(),
+                user_id: user.id,
+            },
+        )?;
+
+        if let Some(organization_id) = user.organization_id {
+            if read::<Organization>(&repo, &Organization::path(&organization_id))?.is_some() {
+                log::warn!("deleting default organization {:?}", user.login);
+                update.remove(&Organization::path(&organization_id));
+            }
+        }
+
+        for repo_id in &user.personal_repo_ids {
+            if read::<Repository>(&repo, &Repository::path(repo_id))?.is_some() {
+                update.remove(&Repository::path(repo_id));
+            }
+        }
+
+        if let Some(username) = &user.github_username {
+            if read::<GithubLogin>(&repo, &GithubLogin::path(username))?.is_some() {
+                update.remove(&GithubLogin::path(username));
+            }
+        }
+
+        for (path, session) in list::<Session>(&repo, "sessions")? {
+            if session.user_id == user.id {
+                update.remove(&path);
+            }
+        }
+
+        update.remove(&User::path(&user.id));
+        update.write(&format!("Delete account {user_id}"))?;
+        log::warn!("account {} has been deleted", user_id);
+
+        let alert = Alert::Success("Your account has been deleted".into());
+        Ok(psql::DeleteAccountResult {
+            alerts: vec![alert],
+            deleted_user_id: user_id.to_owned(),
+        })
+    }
+
+    pub fn delete_session(
+        &self,
+        viewer: &Viewer,
+        session_id: &str,
+    ) -> Result<psql::DeleteSessionResult> {
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let path = Session::path(session_id);
+
+        match read::<Session>(&repo, &path)? {
+            Some(session) if session.user_id.to_string() == viewer.user_id => {
+                let mut update = Update::new(&repo);
+                update.remove(&path);
+                update.write(&format!("Delete session for {}", viewer.user_id))?;
+                log::info!("session deleted for {}", viewer.user_id);
+            }
+            _ => {
+                log::warn!("no session found to delete for {}", viewer.user_id);
+            }
+        }
+
+        Ok(psql::DeleteSessionResult {
+            deleted_session_id: session_id.to_owned(),
+        })
+    }
+
+    pub fn fetch_account_info(&self, user_id: &str) -> Result<psql::FetchAccountInfoResult> {
+        let user = fetch_user(&*self.repo()?, user_id)?;
+        Ok(psql::FetchAccountInfoResult {
+            personal_repos: user.personal_repo_ids.into(),
+        })
+    }
+
+    // Change requests are visible to their author and to anyone who can read the target repo
+    pub fn fetch_change_requests(
+        &self,
+        viewer: &Viewer,
+        id: Option<&str>,
+        status: Option<ChangeRequestStatus>,
+        limit: i64,
+    ) -> Result<Vec<graphql::ChangeRequest>> {
+        let repo = self.repo()?;
+        let mut change_requests = match id {
+            Some(id) => match parse_uuid(id) {
+                Some(uuid) => read::<ChangeRequest>(&repo, &ChangeRequest::path(&uuid))?
+                    .into_iter()
+                    .collect(),
+                None => vec![],
+            },
+            None => list::<ChangeRequest>(&repo, "change-requests")?
+                .into_iter()
+                .map(|(_, change_request)| change_request)
+                .collect::<Vec<_>>(),
+        };
+
+        change_requests.retain(|change_request| {
+            change_request.author_id.to_string() == viewer.user_id
+                || viewer.read_repo_ids.include(change_request.target_repo_id)
+        });
+        change_requests.sort_by_key(|change_request| std::cmp::Reverse(change_request.created_at));
+
+        let mut result = vec![];
+        for change_request in change_requests {
+            if result.len() as i64 >= limit {
+                break;
+            }
+            let change_request = change_request.to_change_request()?;
+            if status.is_none_or(|status| status == change_request.status) {
+                result.push(change_request);
+            }
+        }
+
+        Ok(result)
+    }
+
+    // The viewer for a user, without a session, so that a command-line tool can act on their behalf
+    pub fn fetch_viewer(&self, user_id: &str) -> Result<Viewer> {
+        Ok(fetch_user(&*self.repo()?, user_id)?.to_viewer(None))
+    }
+
+    pub fn fetch_writeable_repositories_for_user(
+        &self,
+        viewer: &Viewer,
+        user_id: &str,
+    ) -> Result<Vec<graphql::Repository>> {
+        log::debug!("fetching repositories for user {:?}", user_id);
+        let repo = self.repo()?;
+        let user = fetch_user(&repo, user_id)?;
+
+        let mut repos = vec![];
+        for repo_id in user.write_repo_ids() {
+            if !viewer.write_repo_ids.include(repo_id) {
+                continue;
+            }
+            if let Some(repository) = read::<Repository>(&repo, &Repository::path(&repo_id))? {
+                repos.push(repository.to_repository());
+            }
+        }
+
+        Ok(repos)
+    }
+
+    pub fn organizations(
+        &self,
+        viewer: &Viewer,
+        ids: &[String],
+    ) -> Result<HashMap<String, graphql::Organization>> {
+        let repo = self.repo()?;
+        let mut map = HashMap::new();
+
+        for id in ids {
+            let Some(uuid) = parse_uuid(id) else {
+                continue;
+            };
+            if let Some(org) = read::<Organization>(&repo, &Organization::path(&uuid))? {
+                let owned = org.owner_id.map(|id| id.to_string()).as_ref() == Some(&viewer.user_id);
+                if org.public || owned {
+                    map.insert(id.to_owned(), org.to_organization());
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn repositories(
+        &self,
+        viewer: &Viewer,
+        ids: &[String],
+    ) -> Result<HashMap<String, graphql::Repository>> {
+        let repo = self.repo()?;
+        let mut map = HashMap::new();
+
+        for id in ids {
+            let Ok(repo_id) = RepoId::try_from(id) else {
+                continue;
+            };
+            if !viewer.read_repo_ids.include(repo_id) {
+                continue;
+            }
+            if let Some(repository) = read::<Repository>(&repo, &Repository::path(&repo_id))? {
+                map.insert(id.to_owned(), repository.to_repository());
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn select_repository(
+        &self,
+        actor: &Viewer,
+        repo_id: Option<RepoId>,
+    ) -> Result<psql::SelectRepositoryResult> {
+        log::info!("selecting repository {:?} for viewer {:?}", repo_id, actor);
+
+        let _writing = self.lock();
+        let repo = self.repo()?;
+        let mut user = fetch_user(&repo, &actor.user_id)?;
+
+        let repository = match repo_id {
+            Some(repo_id) => {
+                let repository = read::<Repository>(&repo, &Repository::path(&repo_id))?
+                    .filter(|_| actor.write_repo_ids.include(repo_id))
+                    .ok_or_else(|| Error::NotFound(format!("repo not found: {repo_id}")))?;
+                Some(repository.to_repository())
+            }
+            None => None,
+        };
+
+        user.selected_repo_id = repo_id;
+        let mut update = Update::new(&repo);
+        update.put(&User::path(&user.id), &user)?;
+        update.write(&format!("Select repository for {}", user.id))?;
+
+        Ok(psql::SelectRepositoryResult {
+            actor: user.to_row(),
+            repo: repository,
+        })
+    }
+
+    // Looks up the viewer for a session, if the session exists and belongs to the user given
+    pub fn session_viewer(&self, user_id: &str, session_id: &str) -> Result<Option<Viewer>> {
+        let repo = self.repo()?;
+
+        let session = match read::<Session>(&repo, &Session::path(session_id))? {
+            Some(session) if session.user_id.to_string() == user_id => session,
+            _ => return Ok(None),
+        };
+
+        Ok(read::<User>(&repo, &User::path(&session.user_id))?
+            .map(|user| user.to_viewer(Some(session_id.to_owned()))))
+    }
+
+    pub fn users(&self, ids: &[String]) -> Result<HashMap<String, psql::user::Row>> {
+        let repo = self.repo()?;
+        let mut map = HashMap::new();
+
+        for id in ids {
+            let Some(uuid) = parse_uuid(id) else {
+                continue;
+            };
+            if let Some(user) = read::<User>(&repo, &User::path(&uuid))? {
+                map.insert(id.to_owned(), user.to_row());
+            }
+        }
+
+        Ok(map)
+    }
+
+    // A handle for reading or, while the write lock is held, writing
+    fn repo(&self) -> Result<core::PooledRepo> {
+        self.pool.checkout(&self.path)
+    }
+
+    // Held from the first read of a write to its commit, so that no other write is based on the
+    // commit before it.  A panic during a write cannot leave a commit half-made, so a poisoned lock
+    // is used as it is.
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.writing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Adds a user along with their personal organization, and lets them write to the wiki.  Their
+    // personal repo is added once it has been created.
+    fn register(&self, update: &mut Update<'_>, account: &GithubAccount) -> Result<User> {
+        log::info!("completing registration for {}", account.name);
+        let id = Uuid::new_v4();
+
+        log::info!(
+            "creating default org {} for {}",
+            account.username,
+            account.name
+        );
+        let organization = Organization {
+            id: Uuid::new_v4(),
+            login: account.username.to_owned(),
+            name: DEFAULT_ORGANIZATION_NAME.to_owned(),
+            owner_id: Some(id),
+            public: false,
+        };
+        update.put(&Organization::path(&organization.id), &organization)?;
+
+        let user = User {
+            avatar_url: account.avatar_url.to_owned(),
+            github_username: Some(account.username.to_owned()),
+            id,
+            login: Some(account.username.to_owned()),
+            name: account.name.to_owned(),
+            organization_id: Some(organization.id),
+            personal_repo_ids: vec![],
+            primary_email: Some(account.primary_email.to_owned()),
+            registered_at: Some(chrono::Utc::now()),
+            repos: BTreeMap::from([(RepoId::wiki(), Membership { can_write: true })]),
+            selected_repo_id: None,
+        };
+        update.put(&User::path(&id), &user)?;
+
+        Ok(user)
+    }
+}
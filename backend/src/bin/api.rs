@@ -2,12 +2,12 @@ use async_graphql::extensions;
 use async_graphql::EmptySubscription;
 use async_graphql::Schema;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
-use axum::http::{header, request, HeaderValue, StatusCode};
-use axum::response::IntoResponse;
+use axum::http::{header, request, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::Json;
 use axum::{
-    extract::{Extension, State},
+    extract::{Extension, Path, Query, State},
     routing::get,
     Router,
 };
@@ -16,16 +16,17 @@ use axum_extra::{
     TypedHeader,
 };
 use digraph::{
+    accounts::Accounts,
     config::Config,
     db, git,
     graphql::{MutationRoot, QueryRoot},
     prelude::*,
-    redis,
     types::Timespec,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::signal;
 use tower_http::cors::{AllowOrigin, CorsLayer};
@@ -57,6 +58,16 @@ pub(crate) async fn health() -> impl IntoResponse {
     (StatusCode::OK, Json(health))
 }
 
+// Hits and misses of the object cache since the server started
+async fn object_cache_metrics(State(state): State<digraph::graphql::State>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.object_cache.metrics()))
+}
+
+// How often an open repo was reused rather than opened again
+async fn repo_pool_metrics(State(state): State<digraph::graphql::State>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.root.pool.metrics()))
+}
+
 async fn graphql_handler(
     schema: Extension<ServiceSchema>,
     auth: Option<TypedHeader<Authorization<Basic>>>,
@@ -70,6 +81,83 @@ async fn graphql_handler(
     response.into()
 }
 
+// Lets a topic and everything under it be downloaded as a reading list
+#[derive(Deserialize)]
+struct ExportParams {
+    locale: Option<String>,
+}
+
+// Topic names are given in the locale asked for in the query string, falling back to the
+// languages the browser accepts and then to English
+fn export_locale(params: &ExportParams, headers: &HeaderMap) -> Result<Locale> {
+    if let Some(locale) = &params.locale {
+        return Locale::from_str(&locale.to_lowercase())
+            .map_err(|_| Error::Parse(format!("unknown locale: {locale}")));
+    }
+
+    let locale = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::from_accept_language);
+    Ok(locale.unwrap_or(Locale::EN))
+}
+
+// Lets a topic and everything under it be downloaded as a reading list
+async fn export_handler(
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+    State(state): State<digraph::graphql::State>,
+    Path((repo_id, topic_id, format)): Path<(String, String, String)>,
+    Query(params): Query<ExportParams>,
+    headers: HeaderMap,
+) -> Response {
+    let request = match (
+        RepoId::try_from(repo_id.as_str()),
+        ExternalId::try_from(&topic_id),
+        git::ExportFormat::from_str(&format),
+        export_locale(&params, &headers),
+    ) {
+        (Ok(repo_id), Ok(topic_id), Ok(format), Ok(locale)) => git::ExportTopic {
+            format,
+            locale,
+            repo_id,
+            topic_id,
+        },
+        _ => return (StatusCode::BAD_REQUEST, "bad export request").into_response(),
+    };
+
+    let viewer = viewer_from_header(auth, &state).await;
+    let client = state.git(Arc::new(viewer), &Timespec);
+
+    // Every topic and link under the topic is read, so the export is kept off of the async worker
+    // threads
+    let content_type = request.format.content_type().to_owned();
+    let topic_id = request.topic_id.to_owned();
+    let export = tokio::task::spawn_blocking(move || request.call(&client))
+        .await
+        .map_err(Error::from)
+        .and_then(|result| result);
+
+    match export {
+        Ok(git::ExportTopicResult { body, filename }) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, content_type),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{filename}\""),
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(Error::NotFound(_)) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(err) => {
+            log::error!("failed to export {}: {}", topic_id, err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "export failed").into_response()
+        }
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -101,30 +189,40 @@ async fn main() -> async_graphql::Result<()> {
     let config = Config::load()?;
     env_logger::init();
 
-    log::info!("setting up database connection");
-    let pool = db::db_connection(&config).await?;
-
     log::info!("reading data from {}", config.digraph_data_directory);
     let root = git::DataRoot::new(PathBuf::from(&config.digraph_data_directory));
 
-    log::info!("running migrations");
-    sqlx::migrate!("./db/migrations").run(&pool).await?;
+    log::info!("using {:?} account backend", config.digraph_account_backend);
+    let accounts = db::accounts(&config, &root).await?;
+
+    if let Accounts::Postgres(pool) = &accounts {
+        log::info!("running migrations");
+        sqlx::migrate!("./db/migrations").run(pool).await?;
+    }
 
     log::info!("loading graphql schema");
     let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
         .extension(extensions::Logger)
         .finish();
 
-    log::info!("loading graphql schema");
-    let redis = Arc::new(redis::Redis::new(config.digraph_redis_url.to_owned())?);
+    log::info!("using {:?} cache backend", config.digraph_cache_backend);
+    let cache = config.cache()?;
+
+    let fetch_policy = Arc::new(digraph::http::FetchPolicy::new(
+        config.fetch_policy_options(),
+    ));
+
+    let object_cache = Arc::new(config.object_cache());
 
     log::info!("setting up app state");
     let state = digraph::graphql::State::new(
-        pool,
+        accounts,
         root,
         schema.clone(),
         config.digraph_server_secret,
-        redis,
+        cache,
+        fetch_policy,
+        object_cache,
     );
 
     let socket = env::var("LISTEN_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_owned());
@@ -145,7 +243,10 @@ async fn main() -> async_graphql::Result<()> {
     log::info!("starting server");
     let app = Router::new()
         .route("/health", get(health))
+        .route("/metrics/object-cache", get(object_cache_metrics))
+        .route("/metrics/repo-pool", get(repo_pool_metrics))
         .route("/graphql", post(graphql_handler))
+        .route("/export/:repo_id/:topic_id/:format", get(export_handler))
         .layer(Extension(schema))
         .layer(cors)
         .with_state(state);
@@ -0,0 +1,117 @@
+use getopts::Options;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, Diff, DiffResult, DiffSide};
+use digraph::prelude::*;
+use digraph::types::Timespec;
+
+struct Opts {
+    base: DiffSide,
+    head: DiffSide,
+    json: bool,
+    root: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "c",
+        "base-commit",
+        "commit of the base repo to compare from (default: HEAD)",
+        "REV",
+    );
+    opts.optopt(
+        "C",
+        "head-commit",
+        "commit of the head repo to compare to (default: HEAD)",
+        "REV",
+    );
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optflag("j", "json", "print one JSON object per line");
+    opts.optopt(
+        "r",
+        "base-repo",
+        "id of the repo to compare from (default: wiki)",
+        "REPO_ID",
+    );
+    opts.optopt(
+        "R",
+        "head-repo",
+        "id of the repo to compare to (default: the base repo)",
+        "REPO_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let base_repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    let head_repo_id = match matches.opt_str("R") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => base_repo_id,
+    };
+
+    Ok(Opts {
+        base: DiffSide {
+            repo_id: base_repo_id,
+            rev: matches.opt_str("c"),
+        },
+        head: DiffSide {
+            repo_id: head_repo_id,
+            rev: matches.opt_str("C"),
+        },
+        json: matches.opt_present("j"),
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let DiffResult {
+        base_commit,
+        entries,
+        head_commit,
+    } = Diff {
+        base: opts.base,
+        head: opts.head,
+        locale: Locale::EN,
+    }
+    .call(&client)?;
+
+    for entry in &entries {
+        if opts.json {
+            println!("{}", serde_json::to_string(entry)?);
+        } else {
+            println!("{entry}");
+        }
+    }
+
+    log::info!(
+        "{} differences between {} and {}",
+        entries.len(),
+        base_commit,
+        head_commit
+    );
+    Ok(())
+}
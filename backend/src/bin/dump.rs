@@ -0,0 +1,95 @@
+use getopts::Options;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, DumpRepo, DumpRepoResult};
+use digraph::prelude::*;
+use digraph::types::Timespec;
+
+struct Opts {
+    allow_skipped: bool,
+    output: Option<PathBuf>,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optflag(
+        "",
+        "allow-skipped",
+        "write the dump even if some objects or changes cannot be parsed",
+    );
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt("o", "output", "file to write to (default: stdout)", "FILE");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to dump (default: wiki)",
+        "REPO_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    Ok(Opts {
+        allow_skipped: matches.opt_present("allow-skipped"),
+        output: matches.opt_str("o").map(PathBuf::from),
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let mut writer: Box<dyn Write> = match &opts.output {
+        Some(filename) => Box::new(BufWriter::new(File::create(filename)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let DumpRepoResult {
+        changes,
+        objects,
+        skipped,
+    } = DumpRepo {
+        allow_skipped: opts.allow_skipped,
+        repo_id: opts.repo_id,
+    }
+    .call(&client, &mut writer)?;
+
+    for path in &skipped {
+        log::warn!("left out of the dump: {}", path);
+    }
+
+    log::info!(
+        "dumped {} objects and {} changes from {}",
+        objects,
+        changes,
+        opts.repo_id
+    );
+    Ok(())
+}
@@ -129,6 +129,8 @@ impl TryFrom<&LinkMetadataRow> for RepoLinkMetadata {
                 title: row.title.clone(),
                 url: row.url.clone(),
             }),
+            pending: false,
+            snapshot: None,
         })
     }
 }
@@ -448,6 +450,8 @@ impl RepoLinks {
                 added: self.link.added(),
                 id: self.link.id().to_owned(),
                 details: None,
+                pending: false,
+                snapshot: None,
             },
             parent_topics: BTreeSet::new(),
         })
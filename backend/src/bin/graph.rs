@@ -0,0 +1,115 @@
+use getopts::Options;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, GraphExport, GraphExportResult, GraphFormat, GraphScope};
+use digraph::prelude::*;
+use digraph::types::Timespec;
+
+struct Opts {
+    format: GraphFormat,
+    include_links: bool,
+    output: Option<PathBuf>,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+    topic_id: Option<ExternalId>,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt("f", "format", "graphml or dot (default: graphml)", "FORMAT");
+    opts.optflag("l", "links", "include links as well as topics");
+    opts.optopt("o", "output", "file to write to (default: stdout)", "FILE");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to export (default: wiki)",
+        "REPO_ID",
+    );
+    opts.optopt(
+        "t",
+        "topic",
+        "only export the downset of this topic (default: the whole repo)",
+        "TOPIC_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let format = match matches.opt_str("f") {
+        Some(format) => GraphFormat::from_str(&format)
+            .map_err(|_| Error::Parse(format!("unknown format: {format}")))?,
+        None => GraphFormat::Graphml,
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    let topic_id = match matches.opt_str("t") {
+        Some(topic_id) => Some(ExternalId::try_from(&topic_id)?),
+        None => None,
+    };
+
+    Ok(Opts {
+        format,
+        include_links: matches.opt_present("l"),
+        output: matches.opt_str("o").map(PathBuf::from),
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+        topic_id,
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let scope = match &opts.topic_id {
+        Some(topic_id) => match client.topic_path(opts.repo_id, topic_id)? {
+            Some(path) => GraphScope::Downset(path),
+            None => return Err(Error::NotFound(format!("topic not found: {topic_id}"))),
+        },
+        None => GraphScope::Repo(opts.repo_id),
+    };
+
+    let GraphExportResult {
+        body,
+        edge_count,
+        node_count,
+    } = GraphExport {
+        format: opts.format,
+        include_links: opts.include_links,
+        locale: Locale::EN,
+        scope,
+    }
+    .call(&client)?;
+
+    match &opts.output {
+        Some(filename) => fs::write(filename, body)?,
+        None => io::stdout().write_all(body.as_bytes())?,
+    }
+
+    log::info!("exported {} nodes and {} edges", node_count, edge_count);
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use getopts::Options;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{
+    Client, DataRoot, ImportBookmarks, ImportBookmarksResult, ImportFormat, IndexMode,
+};
+use digraph::prelude::*;
+use digraph::redis;
+use digraph::types::Timespec;
+
+struct Opts {
+    filename: PathBuf,
+    format: Option<ImportFormat>,
+    parent_topic_id: ExternalId,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+}
+
+fn usage(opts: &Options) -> String {
+    opts.usage("Usage: import [options] BOOKMARKS_FILE")
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt(
+        "f",
+        "format",
+        "netscape, pocket, pinboard or csv (default: guessed from the file)",
+        "FORMAT",
+    );
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to import into (default: wiki)",
+        "REPO_ID",
+    );
+    opts.optopt(
+        "t",
+        "topic",
+        "id of the topic to place the imported folders and tags under (default: root topic)",
+        "TOPIC_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let filename = match matches.free.first() {
+        Some(filename) => PathBuf::from(filename),
+        None => return Err(Error::Command(usage(&opts))),
+    };
+
+    let format = match matches.opt_str("f") {
+        Some(format) => Some(
+            ImportFormat::from_str(&format)
+                .map_err(|_| Error::Parse(format!("unknown format: {format}")))?,
+        ),
+        None => None,
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    let parent_topic_id = match matches.opt_str("t") {
+        Some(topic_id) => ExternalId::try_from(&topic_id)?,
+        None => ExternalId::root_topic(),
+    };
+
+    Ok(Opts {
+        filename,
+        format,
+        parent_topic_id,
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let input = fs::read_to_string(&opts.filename)?;
+    let format = opts.format.unwrap_or_else(|| ImportFormat::detect(&input));
+    let bookmarks = format.parse(&input)?;
+    log::info!(
+        "importing {} bookmarks in {} format from {:?} into {} under {:?}",
+        bookmarks.len(),
+        format,
+        opts.filename,
+        opts.repo_id,
+        root
+    );
+    let root = DataRoot::new(root);
+
+    let actor = Arc::new(Viewer::service_account());
+    let client = Client::new(Arc::clone(&actor), &root, Timespec);
+
+    let ImportBookmarksResult {
+        links,
+        skipped,
+        topics_created,
+    } = ImportBookmarks {
+        actor,
+        bookmarks,
+        locale: Locale::EN,
+        parent_topic_id: opts.parent_topic_id,
+        repo_id: opts.repo_id,
+    }
+    .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+
+    println!("{links} links imported, {topics_created} topics created, {skipped} skipped");
+    Ok(())
+}
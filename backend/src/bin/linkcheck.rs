@@ -0,0 +1,92 @@
+use getopts::Options;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use digraph::config::Config;
+use digraph::git::{CheckLinks, CheckLinksResult, Client, DataRoot, IndexMode};
+use digraph::http;
+use digraph::prelude::*;
+use digraph::redis;
+use digraph::types::Timespec;
+
+struct Opts {
+    interval: Duration,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to check (default: wiki)",
+        "REPO_ID",
+    );
+    opts.optopt(
+        "i",
+        "interval",
+        "milliseconds to wait between requests to the same host (default: 1000)",
+        "MILLIS",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    let interval = match matches.opt_str("i") {
+        Some(millis) => millis
+            .parse::<u64>()
+            .map_err(|err| Error::Parse(format!("bad interval: {err}")))?,
+        None => 1000,
+    };
+
+    Ok(Opts {
+        interval: Duration::from_millis(interval),
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    log::info!("checking links in {} under {:?}", opts.repo_id, root);
+    let root = DataRoot::new(root);
+
+    let policy = Arc::new(http::FetchPolicy::new(http::PolicyOptions {
+        min_interval: opts.interval,
+        ..config.fetch_policy_options()
+    }));
+
+    let actor = Arc::new(Viewer::service_account());
+    let client = Client::new(Arc::clone(&actor), &root, Timespec);
+
+    let CheckLinksResult { broken, checked } = CheckLinks {
+        actor,
+        checker: Box::new(http::UrlChecker::new(policy)),
+        repo_id: opts.repo_id,
+    }
+    .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+
+    println!("{checked} links checked, {broken} broken");
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use getopts::Options;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, PublishSite, PublishSiteResult};
+use digraph::prelude::*;
+use digraph::types::Timespec;
+
+struct Opts {
+    base_url: String,
+    output: PathBuf,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+}
+
+fn usage(opts: &Options) -> String {
+    opts.usage("Usage: publish [options] --base-url URL OUTPUT_DIR")
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "b",
+        "base-url",
+        "url the site will be served from, used in the sitemap",
+        "URL",
+    );
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to publish (default: wiki)",
+        "REPO_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let (base_url, output) = match (matches.opt_str("b"), matches.free.first()) {
+        (Some(base_url), Some(output)) => (base_url, PathBuf::from(output)),
+        _ => return Err(Error::Command(usage(&opts))),
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    Ok(Opts {
+        base_url,
+        output,
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let PublishSiteResult { links, topics } = PublishSite {
+        base_url: opts.base_url,
+        locale: Locale::EN,
+        output: opts.output.to_owned(),
+        repo_id: opts.repo_id,
+    }
+    .call(&client)?;
+
+    println!(
+        "published {topics} topics and {links} links to {:?}",
+        opts.output
+    );
+    Ok(())
+}
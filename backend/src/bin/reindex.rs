@@ -0,0 +1,93 @@
+use getopts::Options;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, Reindex, ReindexResult};
+use digraph::prelude::*;
+use digraph::redis;
+use digraph::types::Timespec;
+
+struct Opts {
+    json: bool,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+    verify: bool,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optflag("j", "json", "print one JSON object per line");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the repo to reindex (default: wiki)",
+        "REPO_ID",
+    );
+    opts.optflag(
+        "",
+        "verify",
+        "compare the rebuilt indexes with the committed ones without writing anything",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    Ok(Opts {
+        json: matches.opt_present("j"),
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+        verify: matches.opt_present("verify"),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let ReindexResult { drift, indexes } = Reindex {
+        repo_id: opts.repo_id,
+        verify: opts.verify,
+    }
+    .call(&client, &redis::Noop)?;
+
+    for finding in &drift {
+        if opts.json {
+            println!("{}", serde_json::to_string(finding)?);
+        } else {
+            println!("{finding}");
+        }
+    }
+
+    log::info!(
+        "{} problems found with the {} indexes of {}",
+        drift.len(),
+        indexes,
+        opts.repo_id
+    );
+
+    if !drift.is_empty() && opts.verify {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use getopts::Options;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, DumpRecord, RestoreRepo, RestoreRepoResult};
+use digraph::prelude::*;
+use digraph::redis;
+use digraph::types::Timespec;
+
+struct Opts {
+    input: Option<PathBuf>,
+    repo_id: Option<RepoId>,
+    root: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Opts> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt(
+        "r",
+        "repo",
+        "id of the empty repo to restore into (default: the repo that was dumped)",
+        "REPO_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => Some(RepoId::try_from(repo_id.as_str())?),
+        None => None,
+    };
+
+    Ok(Opts {
+        input: matches.free.first().map(PathBuf::from),
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+    })
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    let root = opts
+        .root
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+
+    let mut reader: Box<dyn BufRead> = match &opts.input {
+        Some(filename) => Box::new(BufReader::new(File::open(filename)?)),
+        None => Box::new(BufReader::new(io::stdin().lock())),
+    };
+
+    // Look ahead at the header so that the dump can be restored into the repo it came from
+    let repo_id = match opts.repo_id {
+        Some(repo_id) => repo_id,
+        None => {
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            let repo_id = match serde_json::from_str(&header)? {
+                DumpRecord::Header { repo_id, .. } => repo_id,
+                _ => return Err(Error::Repo("expected a dump header".into())),
+            };
+            reader = Box::new(io::Cursor::new(header).chain(reader));
+            repo_id
+        }
+    };
+
+    let RestoreRepoResult {
+        changes,
+        objects,
+        source_repo_id,
+    } = RestoreRepo { repo_id }.call(&client, reader, &redis::Noop)?;
+
+    println!(
+        "restored {objects} objects and {changes} changes from {source_repo_id} into {repo_id}"
+    );
+    Ok(())
+}
@@ -1,17 +1,49 @@
+use getopts::Options;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
 use std::env;
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use digraph::config::Config;
 use digraph::git::*;
 use digraph::prelude::*;
-use digraph::types::Timespec;
+use digraph::types::{Downset, Timespec, TopicPath};
+use digraph::{db, http, redis};
+
+const USAGE: &str = "\
+Usage: show [options] COMMAND [ARGS]
+
+Commands:
+    show [REPO_ID] ID           print a topic or link
+    tree TOPIC_ID               print the topics (and links with -l) under a topic
+    search QUERY                search under the root topic, or under -t TOPIC_ID
+    add-topic NAME [PARENT_ID]  add a topic under a parent topic (default: root)
+    add-link URL [PARENT_ID]    add a link under a parent topic (default: root)
+    move ID FROM_ID TO_ID       move a topic or link from one parent topic to another
+    history ID                  print the most recent changes to a topic or link
+    stats                       print topic and link counts
+
+For backwards compatibility, a path to an object.yaml file can be given in place of a command.";
 
 struct Opts {
-    filename: String,
+    args: Vec<String>,
+    command: String,
+    format: Option<ExportFormat>,
+    include_links: bool,
+    json: bool,
+    limit: usize,
+    repo_id: RepoId,
+    root: Option<PathBuf>,
+    title: Option<String>,
+    topic_id: Option<ExternalId>,
+    user_id: Option<String>,
 }
 
 struct ConsoleOutput<'r> {
-    git: &'r mut Client,
+    git: &'r Client,
     buf: String,
     repo_id: RepoId,
 }
@@ -123,36 +155,460 @@ impl<'r> ConsoleOutput<'r> {
     }
 }
 
-fn parse_args() -> Opts {
+// A line of output for the tree and search commands
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Line {
+    depth: usize,
+    id: ExternalId,
+    kind: Kind,
+    name: String,
+    url: Option<String>,
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        match &self.url {
+            Some(url) => write!(f, "{}- [{}]({}) {}", indent, self.name, url, self.id),
+            None => write!(f, "{}- {} {}", indent, self.name, self.id),
+        }
+    }
+}
+
+impl From<(usize, &RepoObject)> for Line {
+    fn from((depth, object): (usize, &RepoObject)) -> Self {
+        match object {
+            RepoObject::Topic(topic) => Self {
+                depth,
+                id: topic.topic_id().to_owned(),
+                kind: Kind::Topic,
+                name: topic.name(Locale::EN),
+                url: None,
+            },
+            RepoObject::Link(link) => Self {
+                depth,
+                id: link.id().to_owned(),
+                kind: Kind::Link,
+                name: link.title().to_owned(),
+                url: Some(link.url().to_owned()),
+            },
+        }
+    }
+}
+
+// Computes downsets directly from Git, so that searches can be run without Redis
+struct FetchDownset<'c>(&'c Client);
+
+impl<'c> Downset for FetchDownset<'c> {
+    fn intersection(&self, topic_paths: &[TopicPath]) -> Result<HashSet<ExternalId>> {
+        let mut sets = topic_paths.iter().map(|path| self.downset(path));
+        let mut set = match sets.next() {
+            Some(set) => set,
+            None => return Ok(HashSet::new()),
+        };
+        for other in sets {
+            set.retain(|id| other.contains(id));
+        }
+        Ok(set)
+    }
+
+    fn downset(&self, path: &TopicPath) -> HashSet<ExternalId> {
+        self.0.downset_ids(path)
+    }
+}
+
+fn parse_args() -> Result<Opts> {
     let args: Vec<String> = env::args().collect();
-    let filename = args.get(1).expect("a file is required").to_owned();
 
-    Opts { filename }
+    let mut opts = Options::new();
+    opts.optopt("d", "data-dir", "root directory of the repos", "DIRNAME");
+    opts.optopt(
+        "f",
+        "format",
+        "export a topic and everything under it (markdown, netscape or opml)",
+        "FORMAT",
+    );
+    opts.optflag("h", "help", "print this message");
+    opts.optflag("j", "json", "print JSON instead of text");
+    opts.optflag("l", "links", "include links in the tree");
+    opts.optopt(
+        "n",
+        "limit",
+        "how many search results or changes to print (default: 20)",
+        "COUNT",
+    );
+    opts.optopt("r", "repo", "id of the repo (default: wiki)", "REPO_ID");
+    opts.optopt(
+        "t",
+        "topic",
+        "topic to search under (default: root)",
+        "TOPIC_ID",
+    );
+    opts.optopt("T", "title", "title of a link that is added", "TITLE");
+    opts.optopt(
+        "u",
+        "user",
+        "act as this user instead of the service account",
+        "USER_ID",
+    );
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("{}", f.to_string())
+        }
+    };
+
+    if matches.opt_present("h") || matches.free.is_empty() {
+        print!("{}", opts.usage(USAGE));
+        std::process::exit(0);
+    }
+
+    let format = match matches.opt_str("f") {
+        Some(format) => Some(
+            ExportFormat::from_str(&format)
+                .map_err(|_| Error::Parse(format!("unknown format: {format}")))?,
+        ),
+        None => None,
+    };
+
+    let limit = match matches.opt_str("n") {
+        Some(limit) => limit
+            .parse::<usize>()
+            .map_err(|err| Error::Parse(format!("{limit}: {err}")))?,
+        None => 20,
+    };
+
+    let repo_id = match matches.opt_str("r") {
+        Some(repo_id) => RepoId::try_from(repo_id.as_str())?,
+        None => RepoId::wiki(),
+    };
+
+    let topic_id = match matches.opt_str("t") {
+        Some(topic_id) => Some(ExternalId::try_from(&topic_id)?),
+        None => None,
+    };
+
+    let mut free = matches.free.to_owned();
+    let command = free.remove(0);
+
+    Ok(Opts {
+        args: free,
+        command,
+        format,
+        include_links: matches.opt_present("l"),
+        json: matches.opt_present("j"),
+        limit,
+        repo_id,
+        root: matches.opt_str("d").map(PathBuf::from),
+        title: matches.opt_str("T"),
+        topic_id,
+        user_id: matches.opt_str("u"),
+    })
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let opts = parse_args();
-    let (root_directory, repo_id, oid) = parse_path(&opts.filename)?;
+fn arg(opts: &Opts, index: usize, name: &str) -> Result<String> {
+    opts.args
+        .get(index)
+        .cloned()
+        .ok_or_else(|| Error::Parse(format!("{} requires {}", opts.command, name)))
+}
 
-    let actor = Arc::new(Viewer::service_account());
-    let mut git = Client::new(actor, &root_directory, Timespec);
-    let object = git.fetch(repo_id, &oid);
-    if object.is_none() {
-        return Err(Error::NotFound(format!(
-            "{root_directory} does not contain {repo_id}/{oid}"
-        )));
+fn id_arg(opts: &Opts, index: usize, name: &str) -> Result<ExternalId> {
+    ExternalId::try_from(&arg(opts, index, name)?)
+}
+
+fn parent_arg(opts: &Opts, index: usize) -> Result<ExternalId> {
+    match opts.args.get(index) {
+        Some(id) => ExternalId::try_from(id),
+        None => Ok(ExternalId::root_topic()),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+fn print_object(opts: &Opts, client: &Client, repo_id: RepoId, id: &ExternalId) -> Result<()> {
+    let object = client
+        .fetch(repo_id, id)
+        .ok_or_else(|| Error::NotFound(format!("{repo_id} does not contain {id}")))?;
+
+    if let Some(format) = opts.format {
+        let ExportTopicResult { body, .. } = ExportTopic {
+            format,
+            locale: Locale::EN,
+            repo_id,
+            topic_id: id.to_owned(),
+        }
+        .call(client)?;
+        io::stdout().write_all(body.as_bytes())?;
+        return Ok(());
+    }
+
+    if opts.json {
+        return print_json(&object);
     }
-    let object = object.unwrap();
 
     let mut output = ConsoleOutput {
         buf: String::new(),
-        git: &mut git,
+        git: client,
         repo_id,
     };
 
     object.accept(&mut output)?;
     io::stdout().write_all(output.as_bytes())?;
+    Ok(())
+}
+
+fn print_line(opts: &Opts, line: Line) -> Result<()> {
+    if opts.json {
+        print_json(&line)
+    } else {
+        println!("{line}");
+        Ok(())
+    }
+}
+
+fn show(opts: &Opts, client: &Client) -> Result<()> {
+    match opts.args.len() {
+        2 => {
+            let repo_id = RepoId::try_from(opts.args[0].as_str())?;
+            print_object(opts, client, repo_id, &id_arg(opts, 1, "an id")?)
+        }
+        _ => print_object(opts, client, opts.repo_id, &id_arg(opts, 0, "an id")?),
+    }
+}
+
+fn tree(opts: &Opts, client: &Client) -> Result<()> {
+    let topic_id = id_arg(opts, 0, "a topic id")?;
+    let topic = client
+        .fetch_topic(opts.repo_id, &topic_id)
+        .ok_or_else(|| Error::NotFound(format!("topic not found: {topic_id}")))?;
+
+    // A topic can be reached along more than one path, so each one is only expanded the first
+    // time it is seen
+    let mut seen = HashSet::from([topic_id]);
+    let mut stack = vec![(0, RepoObject::Topic(topic))];
+
+    while let Some((depth, object)) = stack.pop() {
+        print_line(opts, Line::from((depth, &object)))?;
+
+        if let RepoObject::Topic(topic) = &object {
+            for child in topic.children.iter().rev() {
+                if child.kind == Kind::Link && !opts.include_links {
+                    continue;
+                }
+                if !seen.insert(child.id.to_owned()) {
+                    continue;
+                }
+                if let Some(child) = client.fetch(opts.repo_id, &child.id) {
+                    stack.push((depth + 1, child));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn search(opts: &Opts, client: &Client) -> Result<()> {
+    let query = opts.args.join(" ");
+    let FindMatchesResult { matches } = FindMatches {
+        context_repo_id: opts.repo_id,
+        limit: opts.limit,
+        locale: Locale::EN,
+        recursive: true,
+        search: Search::parse(&query)?,
+        timespec: Timespec,
+        topic_id: opts
+            .topic_id
+            .to_owned()
+            .unwrap_or_else(ExternalId::root_topic),
+        viewer: Arc::clone(&client.viewer),
+    }
+    .call(client, &FetchDownset(client))?;
+
+    for SearchMatch { object, .. } in matches {
+        if let Some(object) = client.fetch(opts.repo_id, object.id()) {
+            print_line(opts, Line::from((0, &object)))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_topic(opts: &Opts, client: &Client) -> Result<()> {
+    let name = arg(opts, 0, "a name")?;
+    let UpsertTopicResult {
+        matching_repo_topics,
+        repo_topic,
+        ..
+    } = UpsertTopic {
+        actor: Arc::clone(&client.viewer),
+        locale: Locale::EN,
+        name,
+        on_matching_synonym: OnMatchingSynonym::Ask,
+        parent_topic_id: parent_arg(opts, 1)?,
+        repo_id: opts.repo_id,
+    }
+    .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+
+    match repo_topic {
+        Some(topic) => print_line(opts, Line::from((0, &RepoObject::Topic(topic)))),
+        None => {
+            let ids = matching_repo_topics
+                .iter()
+                .map(|topic| topic.entry.id.to_string())
+                .collect::<Vec<_>>();
+            Err(Error::Repo(format!(
+                "a topic with this name already exists: {}",
+                ids.join(", ")
+            )))
+        }
+    }
+}
+
+fn add_link(opts: &Opts, client: &Client, config: &Config) -> Result<()> {
+    let policy = Arc::new(http::FetchPolicy::new(config.fetch_policy_options()));
+    let UpsertLinkResult { link, .. } = UpsertLink {
+        actor: Arc::clone(&client.viewer),
+        add_parent_topic_id: Some(parent_arg(opts, 1)?),
+        capture_snapshot: false,
+        fetcher: Box::new(http::Fetcher::new(policy)),
+        fetch_queue: None,
+        repo_id: opts.repo_id,
+        title: opts.title.to_owned(),
+        url: arg(opts, 0, "a url")?,
+    }
+    .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+
+    match link {
+        Some(link) => print_line(opts, Line::from((0, &RepoObject::Link(link)))),
+        None => Err(Error::Repo("the link was not saved".into())),
+    }
+}
+
+fn move_object(opts: &Opts, client: &Client) -> Result<()> {
+    let id = id_arg(opts, 0, "an id")?;
+    let from_id = id_arg(opts, 1, "the parent topic to move from")?;
+    let to_id = id_arg(opts, 2, "the parent topic to move to")?;
+
+    let object = client
+        .fetch(opts.repo_id, &id)
+        .ok_or_else(|| Error::NotFound(format!("not found: {id}")))?;
+
+    let mut parent_topic_ids = object
+        .parent_topics()
+        .iter()
+        .map(|parent| parent.id.to_owned())
+        .collect::<BTreeSet<_>>();
+    if !parent_topic_ids.remove(&from_id) {
+        return Err(Error::NotFound(format!(
+            "{from_id} is not a parent of {id}"
+        )));
+    }
+    parent_topic_ids.insert(to_id);
+
+    let object = match object {
+        RepoObject::Topic(_) => {
+            let parent_topic_ids = parent_topic_ids.into_iter().collect::<Vec<_>>();
+            let UpdateTopicParentTopicsResult { repo_topic, .. } = UpdateTopicParentTopics {
+                actor: Arc::clone(&client.viewer),
+                parent_topic_ids: &parent_topic_ids,
+                repo_id: opts.repo_id,
+                topic_id: &id,
+            }
+            .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+            RepoObject::Topic(repo_topic)
+        }
 
+        RepoObject::Link(_) => {
+            let UpdateLinkParentTopicsResult { link, .. } = UpdateLinkParentTopics {
+                actor: Arc::clone(&client.viewer),
+                link_id: id,
+                parent_topic_ids,
+                repo_id: opts.repo_id,
+            }
+            .call(client.mutation(IndexMode::Update)?, &redis::Noop)?;
+            RepoObject::Link(link)
+        }
+    };
+
+    print_line(opts, Line::from((0, &object)))
+}
+
+fn history(opts: &Opts, client: &Client) -> Result<()> {
+    let id = id_arg(opts, 0, "an id")?;
+    for change in client.fetch_activity(opts.repo_id, &id, opts.limit)? {
+        if opts.json {
+            print_json(&change)?;
+        } else {
+            let markdown = change.markdown(Locale::EN, &change.actor_id(), Some(&id));
+            println!("{} {}", change.date().to_rfc3339(), markdown);
+        }
+    }
     Ok(())
 }
+
+fn stats(opts: &Opts, client: &Client) -> Result<()> {
+    let stats = client.view(opts.repo_id)?.stats()?;
+    if opts.json {
+        return print_json(&stats);
+    }
+
+    println!("topics: {}", stats.topic_count.unwrap_or_default());
+    println!("links: {}", stats.link_count.unwrap_or_default());
+    Ok(())
+}
+
+async fn viewer(opts: &Opts, config: &Config, root: &DataRoot) -> Result<Viewer> {
+    match &opts.user_id {
+        Some(user_id) => {
+            db::accounts(config, root)
+                .await?
+                .fetch_viewer(user_id.to_owned())
+                .await
+        }
+        None => Ok(Viewer::service_account()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+    let opts = parse_args()?;
+
+    // Older usage: a path to an object.yaml file in a data directory
+    if opts.command.ends_with(".yaml") {
+        let (root, repo_id, id) = parse_path(&opts.command)?;
+        let client = Client::new(Arc::new(Viewer::service_account()), &root, Timespec);
+        return print_object(&opts, &client, repo_id, &id);
+    }
+
+    let root = opts
+        .root
+        .to_owned()
+        .unwrap_or_else(|| PathBuf::from(&config.digraph_data_directory));
+    let root = DataRoot::new(root);
+    let client = Client::new(
+        Arc::new(viewer(&opts, &config, &root).await?),
+        &root,
+        Timespec,
+    );
+
+    match opts.command.as_str() {
+        "add-link" => add_link(&opts, &client, &config),
+        "add-topic" => add_topic(&opts, &client),
+        "history" => history(&opts, &client),
+        "move" => move_object(&opts, &client),
+        "search" => search(&opts, &client),
+        "show" => show(&opts, &client),
+        "stats" => stats(&opts, &client),
+        "tree" => tree(&opts, &client),
+        command => Err(Error::Parse(format!("unknown command: {command}"))),
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use digraph::config::Config;
+use digraph::git::{Client, DataRoot, FetchLinkJob, FetchLinkTitle, IndexMode};
+use digraph::http;
+use digraph::prelude::*;
+use digraph::redis::{Redis, LINK_FETCH_HEARTBEAT_INTERVAL_SECS};
+use digraph::types::{random_id, Timespec};
+
+const POLL_TIMEOUT_SECS: usize = 5;
+
+fn fetch_title(
+    root: &DataRoot,
+    redis: &Arc<Redis>,
+    policy: &Arc<http::FetchPolicy>,
+    job: FetchLinkJob,
+    capture_snapshot: bool,
+) -> Result<()> {
+    let actor = Arc::new(Viewer::service_account());
+    let client = Client::new(Arc::clone(&actor), root, Timespec);
+
+    let link_id = job.link_id.to_owned();
+    let result = FetchLinkTitle {
+        actor,
+        capture_snapshot,
+        fetcher: Box::new(http::Fetcher::new(Arc::clone(policy))),
+        job,
+    }
+    .call(client.mutation(IndexMode::Update)?, redis)?;
+
+    if let Some(link) = result.link {
+        log::info!("title fetched for {}: {}", link_id, link.title());
+    }
+
+    Ok(())
+}
+
+fn requeue(redis: &Redis) {
+    match redis.requeue_link_fetches() {
+        Ok(0) => {}
+        Ok(count) => log::info!("requeued {} unfinished title fetches", count),
+        Err(err) => log::error!("failed to requeue unfinished title fetches: {}", err),
+    }
+}
+
+// Keeps the worker checked in while it is busy with a fetch, which can take longer than the
+// worker timeout when a host is slow or the fetch policy makes the worker wait its turn
+fn start_heartbeat(redis: Arc<Redis>, worker_id: String) {
+    thread::spawn(move || loop {
+        if let Err(err) = redis.link_fetch_heartbeat(&worker_id) {
+            log::error!("failed to check in: {}", err);
+        }
+        thread::sleep(Duration::from_secs(LINK_FETCH_HEARTBEAT_INTERVAL_SECS));
+    });
+}
+
+fn main() -> Result<()> {
+    let config = Config::load()?;
+    env_logger::init();
+
+    log::info!("reading data from {}", config.digraph_data_directory);
+    let root = DataRoot::new(PathBuf::from(&config.digraph_data_directory));
+    let redis = Arc::new(config.redis()?);
+    let policy = Arc::new(http::FetchPolicy::new(config.fetch_policy_options()));
+
+    // A new id on each start, so that a restarted worker is seen as a new one and the jobs left
+    // over from its last run are picked up once that run has timed out
+    let worker_id = random_id();
+    start_heartbeat(Arc::clone(&redis), worker_id.to_owned());
+    requeue(&redis);
+
+    log::info!("waiting for links to fetch");
+    loop {
+        let delivery = match redis.next_link_fetch(&worker_id, POLL_TIMEOUT_SECS) {
+            Ok(Some(delivery)) => delivery,
+            Ok(None) => {
+                requeue(&redis);
+                continue;
+            }
+            Err(err) => {
+                log::error!("problem reading from the queue: {}", err);
+                thread::sleep(Duration::from_secs(POLL_TIMEOUT_SECS as u64));
+                continue;
+            }
+        };
+
+        let job = delivery.job.to_owned();
+        if let Err(err) = fetch_title(
+            &root,
+            &redis,
+            &policy,
+            job,
+            config.digraph_capture_snapshots,
+        ) {
+            log::error!(
+                "failed to fetch title for {}: {}",
+                delivery.job.link_id,
+                err
+            );
+        }
+
+        if let Err(err) = redis.ack_link_fetch(&delivery) {
+            log::error!("failed to acknowledge {}: {}", delivery.job.link_id, err);
+        }
+    }
+}
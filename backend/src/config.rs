@@ -1,13 +1,56 @@
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use super::git;
+use super::http;
 use super::prelude::*;
+use super::redis;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountBackend {
+    // Kept in a system repo in the data directory, so that Postgres is not needed
+    Git,
+    #[default]
+    Postgres,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    // Kept in the process, for development, tests and small deployments without a Redis server
+    Memory,
+    #[default]
+    Redis,
+}
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
+    // Where users, sessions, organizations and repos are kept
+    #[serde(default)]
+    pub digraph_account_backend: AccountBackend,
+    // Where downsets, repo stats and the wiki activity feed are cached
+    #[serde(default)]
+    pub digraph_cache_backend: CacheBackend,
+    // Keep a copy of each page that is fetched when a link is added
+    #[serde(default)]
+    pub digraph_capture_snapshots: bool,
     pub digraph_data_directory: String,
-    pub digraph_postgres_connection: String,
-    pub digraph_redis_url: String,
+    // Pages that are fetched are kept here so that they can be revalidated with a conditional
+    // request the next time they are needed
+    pub digraph_http_cache_directory: Option<String>,
+    // Roughly how many bytes of fetched pages to keep in the http cache
+    pub digraph_http_cache_size: Option<u64>,
+    // The number of downsets and repo stats to keep when the cache is in memory
+    pub digraph_memory_cache_size: Option<usize>,
+    // Roughly how many bytes of parsed objects and indexes to keep in memory across requests,
+    // going by the sizes of the blobs they were parsed from.  Zero turns the cache off.
+    pub digraph_object_cache_size: Option<usize>,
+    // Only needed when the account backend is Postgres
+    pub digraph_postgres_connection: Option<String>,
+    // Only needed when the cache backend is Redis
+    pub digraph_redis_url: Option<String>,
     pub digraph_server_secret: String,
 }
 
@@ -26,4 +69,43 @@ impl Config {
         dotenv::dotenv().ok();
         Ok(envy::from_env::<Self>()?)
     }
+
+    pub fn object_cache(&self) -> git::ObjectCache {
+        git::ObjectCache::new(
+            self.digraph_object_cache_size
+                .unwrap_or(git::DEFAULT_OBJECT_CACHE_SIZE),
+        )
+    }
+
+    pub fn cache(&self) -> Result<Arc<dyn redis::Cache>> {
+        Ok(match self.digraph_cache_backend {
+            CacheBackend::Memory => Arc::new(redis::Memory::new(
+                self.digraph_memory_cache_size
+                    .unwrap_or(redis::DEFAULT_MEMORY_CACHE_SIZE),
+            )),
+            CacheBackend::Redis => Arc::new(self.redis()?),
+        })
+    }
+
+    pub fn redis(&self) -> Result<redis::Redis> {
+        match &self.digraph_redis_url {
+            Some(url) => redis::Redis::new(url.to_owned()),
+            None => Err(Error::Redis("DIGRAPH_REDIS_URL is not set".into())),
+        }
+    }
+
+    pub fn fetch_policy_options(&self) -> http::PolicyOptions {
+        let cache_dir = match &self.digraph_http_cache_directory {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::temp_dir().join("digraph-http-cache"),
+        };
+
+        http::PolicyOptions {
+            cache_dir: Some(cache_dir),
+            cache_max_bytes: self
+                .digraph_http_cache_size
+                .unwrap_or(http::DEFAULT_HTTP_CACHE_SIZE),
+            ..Default::default()
+        }
+    }
 }
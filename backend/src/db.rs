@@ -2,9 +2,22 @@ use sqlx::postgres::Postgres;
 use sqlx::Error;
 use sqlx::Pool;
 
-use crate::config::Config;
+use crate::accounts::{Accounts, SystemRepo};
+use crate::config::{AccountBackend, Config};
+use crate::git::DataRoot;
 
 pub async fn db_connection(config: &Config) -> Result<Pool<Postgres>, Error> {
-    let database_url = config.digraph_postgres_connection.clone();
-    Pool::<Postgres>::connect(&database_url).await
+    let database_url = config
+        .digraph_postgres_connection
+        .as_ref()
+        .ok_or_else(|| Error::Configuration("DIGRAPH_POSTGRES_CONNECTION is not set".into()))?;
+    Pool::<Postgres>::connect(database_url).await
+}
+
+// Opens the account backend selected in the config.  Postgres migrations are left to the caller.
+pub async fn accounts(config: &Config, root: &DataRoot) -> crate::types::Result<Accounts> {
+    Ok(match config.digraph_account_backend {
+        AccountBackend::Git => Accounts::Git(SystemRepo::open(root)?),
+        AccountBackend::Postgres => Accounts::Postgres(db_connection(config).await?),
+    })
 }
@@ -23,6 +23,10 @@ quick_error! {
             from()
         }
 
+        Fetch(err: String) {
+            display("fetch not allowed: {}", err)
+        }
+
         Deserialization(err: String) {
             from(err: serde_json::Error) -> (format!("{err:?}"))
         }
@@ -67,6 +71,10 @@ quick_error! {
             from()
         }
 
+        Task(err: String) {
+            from(err: tokio::task::JoinError) -> (format!("{err}"))
+        }
+
         Ureq(err: String) {
             from(err: ureq::Error) -> (format!("{err}"))
         }
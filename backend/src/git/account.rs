@@ -70,27 +70,7 @@ impl EnsurePersonalRepo {
 
         if !mutation.exists(repo_id, &topic_id)? {
             log::info!("creating root topic: {}", topic_id);
-            let added = chrono::Utc::now();
-
-            let root = RepoTopic {
-                api_version: API_VERSION.into(),
-                metadata: RepoTopicMetadata {
-                    added,
-                    id: topic_id,
-                    details: Some(RepoTopicDetails {
-                        root: false,
-                        synonyms: vec![Synonym {
-                            added,
-                            locale: Locale::EN,
-                            name: DEFAULT_ROOT_TOPIC_NAME.to_owned(),
-                        }],
-                        timerange: None,
-                    }),
-                },
-                parent_topics: BTreeSet::new(),
-                children: BTreeSet::new(),
-            };
-
+            let root = root_topic(repo_id, chrono::Utc::now());
             mutation.save_topic(repo_id, &root)?;
             mutation.write(&redis::Noop)?;
         }
@@ -102,3 +82,25 @@ impl EnsurePersonalRepo {
         })
     }
 }
+
+// The topic that everything else in a new repo hangs from
+pub(crate) fn root_topic(repo_id: RepoId, added: Timestamp) -> RepoTopic {
+    RepoTopic {
+        api_version: API_VERSION.into(),
+        metadata: RepoTopicMetadata {
+            added,
+            id: repo_id.root_topic_id(),
+            details: Some(RepoTopicDetails {
+                root: false,
+                synonyms: vec![Synonym {
+                    added,
+                    locale: Locale::EN,
+                    name: DEFAULT_ROOT_TOPIC_NAME.to_owned(),
+                }],
+                timerange: None,
+            }),
+        },
+        parent_topics: BTreeSet::new(),
+        children: BTreeSet::new(),
+    }
+}
@@ -1239,6 +1239,12 @@ pub trait ActivityForPrefix {
     fn fetch_activity(&self, repo_id: RepoId, first: usize) -> Result<Vec<Change>>;
 }
 
+impl<T: ActivityForPrefix + ?Sized> ActivityForPrefix for Arc<T> {
+    fn fetch_activity(&self, repo_id: RepoId, first: usize) -> Result<Vec<Change>> {
+        (**self).fetch_activity(repo_id, first)
+    }
+}
+
 impl FetchActivity {
     pub fn call<F>(&self, git: &Arc<Client>, fetch: &F) -> Result<FetchActivityResult>
     where
@@ -0,0 +1,271 @@
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::prelude::*;
+
+// In bytes of the blobs that the cached values were parsed from
+pub const DEFAULT_OBJECT_CACHE_SIZE: usize = 128 * 1024 * 1024;
+
+type Key = (git2::Oid, TypeId);
+type Value = Arc<dyn Any + Send + Sync>;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectCacheMetrics {
+    pub bytes: usize,
+    pub capacity: usize,
+    pub entries: usize,
+    pub evictions: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    // The last time each entry was used, so that the least recently used one can be found
+    used: BTreeMap<u64, Key>,
+    values: HashMap<Key, (u64, usize, Value)>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl Entries {
+    fn get(&mut self, key: &Key) -> Option<Value> {
+        self.clock += 1;
+        let (used, _, value) = self.values.get_mut(key)?;
+        self.used.remove(used);
+        self.used.insert(self.clock, *key);
+        *used = self.clock;
+        Some(Arc::clone(value))
+    }
+
+    // Returns the number of entries that were evicted to make room
+    fn insert(&mut self, key: Key, value: Value, size: usize, capacity: usize) -> u64 {
+        self.clock += 1;
+        self.bytes += size;
+        if let Some((used, size, _)) = self.values.insert(key, (self.clock, size, value)) {
+            self.used.remove(&used);
+            self.bytes -= size;
+        }
+        self.used.insert(self.clock, key);
+
+        let mut evicted = 0;
+        while self.bytes > capacity {
+            match self.used.pop_first() {
+                Some((_, key)) => {
+                    if let Some((_, size, _)) = self.values.remove(&key) {
+                        self.bytes -= size;
+                    }
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+        evicted
+    }
+}
+
+// Parsed objects and indexes, shared by every request that the process handles.  Entries are
+// keyed by the id of the blob they were parsed from, along with the type they were parsed into.
+// A blob id is a hash of the contents of the blob, so an entry never needs to be invalidated, and
+// it can be shared across repos and viewers.  Read permissions are checked before a blob is
+// looked up.  Values are handed out behind an Arc, so that a hit does not copy them, and the cache
+// is bounded by the sizes of the blobs the values were parsed from, which is a rough guide to how
+// much memory the values take up.
+pub struct ObjectCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    evictions: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl std::fmt::Debug for ObjectCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectCache")
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+impl Default for ObjectCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_OBJECT_CACHE_SIZE)
+    }
+}
+
+impl ObjectCache {
+    // The capacity is in bytes.  A capacity of zero turns caching off.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+            evictions: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // Returns the value parsed from the blob earlier, or else calls `load` and keeps what it
+    // returns.  `load` returns the value along with the size of the blob it was parsed from.  The
+    // lock is not held while `load` runs, so two requests that miss on the same blob at the same
+    // time will both parse it.
+    pub(crate) fn get_or_load<T, F>(&self, oid: git2::Oid, load: F) -> Result<Arc<T>>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> Result<(T, usize)>,
+    {
+        let key = (oid, TypeId::of::<T>());
+
+        if self.capacity > 0 {
+            let cached = self.lock().get(&key);
+            if let Some(value) = cached.and_then(|value| value.downcast::<T>().ok()) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(value);
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let (value, size) = load()?;
+        let value = Arc::new(value);
+
+        if self.capacity > 0 {
+            let evicted = self
+                .lock()
+                .insert(key, Arc::clone(&value) as Value, size, self.capacity);
+            self.evictions.fetch_add(evicted, Ordering::Relaxed);
+        }
+
+        Ok(value)
+    }
+
+    pub fn metrics(&self) -> ObjectCacheMetrics {
+        let entries = self.lock();
+        ObjectCacheMetrics {
+            bytes: entries.bytes,
+            capacity: self.capacity,
+            entries: entries.values.len(),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    // A panic while the lock was held cannot leave the entries half-updated in a way that
+    // matters, so a poisoned lock is used as it is
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(n: u8) -> git2::Oid {
+        git2::Oid::from_bytes(&[n; 20]).unwrap()
+    }
+
+    // Each blob is taken to be 10 bytes
+    fn load(cache: &ObjectCache, n: u8) -> Arc<String> {
+        cache
+            .get_or_load(oid(n), || Ok((format!("blob {n}"), 10)))
+            .unwrap()
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let cache = ObjectCache::new(100);
+        let first = load(&cache, 1);
+        assert_eq!(*first, "blob 1");
+        let second = load(&cache, 1);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(*load(&cache, 2), "blob 2");
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.hits, 1);
+        assert_eq!(metrics.misses, 2);
+        assert_eq!(metrics.entries, 2);
+        assert_eq!(metrics.bytes, 20);
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let cache = ObjectCache::new(20);
+        load(&cache, 1);
+        load(&cache, 2);
+        // Blob 1 is now more recently used than blob 2
+        load(&cache, 1);
+        load(&cache, 3);
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.entries, 2);
+        assert_eq!(metrics.evictions, 1);
+
+        load(&cache, 1);
+        assert_eq!(cache.metrics().hits, 2);
+        load(&cache, 2);
+        assert_eq!(cache.metrics().misses, 4);
+    }
+
+    #[test]
+    fn large_values_evict_several() {
+        let cache = ObjectCache::new(30);
+        load(&cache, 1);
+        load(&cache, 2);
+        load(&cache, 3);
+        let value = cache.get_or_load(oid(4), || Ok((4, 25))).unwrap();
+        assert_eq!(*value, 4);
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.entries, 1);
+        assert_eq!(metrics.evictions, 3);
+        assert_eq!(metrics.bytes, 25);
+
+        // A value larger than the whole cache is not kept
+        cache.get_or_load(oid(5), || Ok((5, 40))).unwrap();
+        let metrics = cache.metrics();
+        assert_eq!(metrics.entries, 0);
+        assert_eq!(metrics.bytes, 0);
+    }
+
+    #[test]
+    fn types_are_kept_apart() {
+        let cache = ObjectCache::new(100);
+        load(&cache, 1);
+        let len: Arc<usize> = cache.get_or_load(oid(1), || Ok((7, 10))).unwrap();
+        assert_eq!(*len, 7);
+        assert_eq!(*load(&cache, 1), "blob 1");
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.hits, 1);
+        assert_eq!(metrics.misses, 2);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let cache = ObjectCache::new(100);
+        let result: Result<Arc<String>> =
+            cache.get_or_load(oid(1), || Err(Error::Repo("unreadable".into())));
+        assert!(result.is_err());
+        assert_eq!(*load(&cache, 1), "blob 1");
+        assert_eq!(cache.metrics().misses, 2);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let cache = ObjectCache::new(0);
+        load(&cache, 1);
+        load(&cache, 1);
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.hits, 0);
+        assert_eq!(metrics.misses, 2);
+        assert_eq!(metrics.entries, 0);
+    }
+}
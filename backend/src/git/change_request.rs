@@ -0,0 +1,640 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+use super::activity::{self, LinkInfo, LinkInfoList, TopicInfo, TopicInfoList};
+use super::{
+    core, Client, Kind, Mutation, ParentTopic, RepoObject, RepoTopic, SaveChangesForPrefix,
+    TopicChild,
+};
+use crate::prelude::*;
+
+// What a change request proposes: the state of each topic and link in the source repo at the time
+// the request was made.  Topics and links that are only references take their details from the
+// target repo when the request is applied.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRequestPayload {
+    pub objects: Vec<RepoObject>,
+    pub source_commit: String,
+}
+
+pub enum ChangeRequestSource {
+    // The topics and links touched by these changes in the source repo
+    Changes(Vec<ExternalId>),
+    // Every topic and link in the source repo that differs from the target repo
+    Diff,
+}
+
+pub struct PackageChangeRequest {
+    pub actor: Arc<Viewer>,
+    pub source: ChangeRequestSource,
+    pub source_repo_id: RepoId,
+    pub target_repo_id: RepoId,
+}
+
+impl PackageChangeRequest {
+    pub fn call(&self, client: &Client) -> Result<ChangeRequestPayload> {
+        for repo_id in [self.source_repo_id, self.target_repo_id] {
+            if !self.actor.can_read(repo_id) {
+                return Err(Error::NotFound(format!("not found: {repo_id}")));
+            }
+        }
+
+        if self.source_repo_id == self.target_repo_id {
+            return Err(Error::Repo(
+                "changes must be proposed from another repo".into(),
+            ));
+        }
+
+        let source = client.view(self.source_repo_id)?;
+        let target = client.view(self.target_repo_id)?;
+
+        let mut objects = BTreeMap::new();
+        match &self.source {
+            ChangeRequestSource::Changes(change_ids) => {
+                for change_id in change_ids {
+                    let change = source.change(change_id)?;
+
+                    for id in change.ids() {
+                        if objects.contains_key(id) {
+                            continue;
+                        }
+                        if let Some(object) = source.object(id)? {
+                            objects.insert(id.to_owned(), object);
+                        }
+                    }
+                }
+            }
+
+            ChangeRequestSource::Diff => {
+                source.each_object(|object| {
+                    let before = target.object(object.id())?;
+                    if differs(before.as_ref(), &object) {
+                        objects.insert(object.id().to_owned(), object);
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+
+        // The root topic of a repo cannot be replaced, although it can still be a parent topic
+        objects.remove(&self.target_repo_id.root_topic_id());
+
+        if objects.is_empty() {
+            return Err(Error::Repo("there are no changes to propose".into()));
+        }
+
+        Ok(ChangeRequestPayload {
+            objects: objects.into_values().collect(),
+            source_commit: source.commit.to_string(),
+        })
+    }
+}
+
+fn parent_ids(object: &RepoObject) -> BTreeSet<ExternalId> {
+    object
+        .parent_topics()
+        .iter()
+        .map(|parent| parent.id.to_owned())
+        .collect()
+}
+
+// Whether applying the proposed object would change anything.  Children are left out, since they
+// follow from the parent topics of other objects.
+fn differs(before: Option<&RepoObject>, after: &RepoObject) -> bool {
+    let before = match before {
+        Some(before) => before,
+        None => return true,
+    };
+
+    if parent_ids(before) != parent_ids(after) {
+        return true;
+    }
+
+    match (before, after) {
+        (RepoObject::Topic(before), RepoObject::Topic(after)) => match after.details() {
+            Some(details) => {
+                let synonyms = |topic: &RepoTopic| topic.synonyms().to_vec();
+                synonyms(before) != details.synonyms || before.timerange() != &details.timerange
+            }
+            None => false,
+        },
+
+        (RepoObject::Link(before), RepoObject::Link(after)) => match after.details() {
+            Some(details) => before.details() != Some(details),
+            None => false,
+        },
+
+        _ => true,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProposedAction {
+    Add,
+    Update,
+}
+
+// A structured description of what accepting a change request would do to one topic or link in
+// the target repo, for reviewers
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProposedChange {
+    pub action: ProposedAction,
+    pub id: ExternalId,
+    pub kind: Kind,
+    pub name: String,
+    pub parent_topics_added: Vec<ExternalId>,
+    pub parent_topics_removed: Vec<ExternalId>,
+    pub previous_name: Option<String>,
+    pub synonyms_added: Vec<String>,
+    pub synonyms_removed: Vec<String>,
+    pub url: Option<String>,
+}
+
+// A proposed object together with the current state of the object in the target repo
+struct Resolved {
+    after: RepoObject,
+    before: Option<RepoObject>,
+}
+
+impl Resolved {
+    fn parent_topics_added(&self) -> Vec<ExternalId> {
+        let before = self.before.as_ref().map(parent_ids).unwrap_or_default();
+        parent_ids(&self.after)
+            .difference(&before)
+            .cloned()
+            .collect()
+    }
+
+    fn parent_topics_removed(&self) -> Vec<ExternalId> {
+        let before = self.before.as_ref().map(parent_ids).unwrap_or_default();
+        before
+            .difference(&parent_ids(&self.after))
+            .cloned()
+            .collect()
+    }
+
+    // The activity records for what applying the object does to the target repo, as they would
+    // have been written had the same edits been made there directly
+    fn changes<T>(&self, actor_id: &str, date: Timestamp, topic_info: T) -> Vec<activity::Change>
+    where
+        T: Fn(&ExternalId) -> Option<TopicInfo>,
+    {
+        let topic_infos = |ids: &Vec<ExternalId>| {
+            TopicInfoList::from(&ids.iter().filter_map(&topic_info).collect::<Vec<_>>())
+        };
+        let parent_ids = parent_ids(&self.after);
+        let added = self.parent_topics_added();
+        let removed = self.parent_topics_removed();
+        let mut changes = vec![];
+
+        match (&self.before, &self.after) {
+            (None, RepoObject::Topic(topic)) => {
+                changes.push(activity::Change::ImportTopic(activity::ImportTopic {
+                    actor_id: actor_id.to_owned(),
+                    child_links: LinkInfoList::from(&Vec::<LinkInfo>::new()),
+                    child_topics: TopicInfoList::new(),
+                    date,
+                    id: activity::Change::new_id(),
+                    imported_topic: TopicInfo::from(topic),
+                    parent_topics: topic_infos(&added),
+                }));
+            }
+
+            (None, RepoObject::Link(link)) => {
+                changes.push(activity::Change::ImportLink(activity::ImportLink {
+                    actor_id: actor_id.to_owned(),
+                    date,
+                    id: activity::Change::new_id(),
+                    imported_link: LinkInfo::from(link),
+                    parent_topics: topic_infos(&added),
+                }));
+            }
+
+            (Some(RepoObject::Topic(before)), RepoObject::Topic(after)) => {
+                if !added.is_empty() || !removed.is_empty() {
+                    changes.push(activity::Change::UpdateTopicParentTopics(
+                        activity::UpdateTopicParentTopics {
+                            actor_id: actor_id.to_owned(),
+                            added_parent_topics: topic_infos(&added),
+                            date,
+                            id: activity::Change::new_id(),
+                            parent_topic_ids: parent_ids.to_owned(),
+                            removed_parent_topics: topic_infos(&removed),
+                            updated_topic: TopicInfo::from(after),
+                        },
+                    ));
+                }
+
+                if before.synonyms() != after.synonyms() {
+                    let names = |topic: &RepoTopic| -> HashSet<(String, Locale)> {
+                        topic
+                            .synonyms()
+                            .iter()
+                            .map(|synonym| (synonym.name.to_owned(), synonym.locale))
+                            .collect()
+                    };
+                    let (before_names, after_names) = (names(before), names(after));
+                    let added: HashSet<_> =
+                        after_names.difference(&before_names).cloned().collect();
+                    let removed: HashSet<_> =
+                        before_names.difference(&after_names).cloned().collect();
+
+                    changes.push(activity::Change::UpdateTopicSynonyms(
+                        activity::UpdateTopicSynonyms {
+                            actor_id: actor_id.to_owned(),
+                            reordered: before_names == after_names,
+                            added_synonyms: activity::SynonymList::from(&added),
+                            date,
+                            id: activity::Change::new_id(),
+                            parent_topics: parent_ids.to_owned(),
+                            removed_synonyms: activity::SynonymList::from(&removed),
+                            updated_topic: TopicInfo::from(after),
+                        },
+                    ));
+                }
+
+                if before.timerange() != after.timerange() {
+                    let previous_timerange = before.timerange().to_owned();
+                    changes.push(match after.timerange() {
+                        Some(timerange) => {
+                            activity::Change::UpsertTopicTimerange(activity::UpsertTopicTimerange {
+                                actor_id: actor_id.to_owned(),
+                                date,
+                                id: activity::Change::new_id(),
+                                parent_topics: parent_ids.to_owned(),
+                                previous_timerange,
+                                updated_timerange: timerange.to_owned(),
+                                updated_topic: TopicInfo::from(after),
+                            })
+                        }
+                        None => {
+                            activity::Change::RemoveTopicTimerange(activity::RemoveTopicTimerange {
+                                actor_id: actor_id.to_owned(),
+                                date,
+                                id: activity::Change::new_id(),
+                                parent_topics: parent_ids.to_owned(),
+                                previous_timerange,
+                                updated_topic: TopicInfo::from(after),
+                            })
+                        }
+                    });
+                }
+            }
+
+            (Some(RepoObject::Link(before)), RepoObject::Link(after)) => {
+                if !added.is_empty() || !removed.is_empty() {
+                    changes.push(activity::Change::UpdateLinkParentTopics(
+                        activity::UpdateLinkParentTopics {
+                            actor_id: actor_id.to_owned(),
+                            added_parent_topics: topic_infos(&added),
+                            date,
+                            id: activity::Change::new_id(),
+                            removed_parent_topics: topic_infos(&removed),
+                            updated_link: LinkInfo::from(after),
+                        },
+                    ));
+                }
+
+                if before.details() != after.details() {
+                    changes.push(activity::Change::UpsertLink(activity::UpsertLink {
+                        actor_id: actor_id.to_owned(),
+                        add_parent_topic: None,
+                        date,
+                        id: activity::Change::new_id(),
+                        parent_topics: parent_ids.to_owned(),
+                        previous_title: (before.title() != after.title())
+                            .then(|| before.title().to_owned()),
+                        upserted_link: LinkInfo::from(after),
+                    }));
+                }
+            }
+
+            // Ruled out by resolve
+            _ => {}
+        }
+
+        changes
+    }
+
+    fn to_proposed_change(&self, locale: Locale) -> ProposedChange {
+        let names = |object: &RepoObject| -> BTreeSet<String> {
+            match object {
+                RepoObject::Topic(topic) => topic
+                    .synonyms()
+                    .iter()
+                    .map(|synonym| synonym.name.to_owned())
+                    .collect(),
+                RepoObject::Link(_) => BTreeSet::new(),
+            }
+        };
+        let name = |object: &RepoObject| match object {
+            RepoObject::Topic(topic) => topic.name(locale),
+            RepoObject::Link(link) => link.title().to_owned(),
+        };
+
+        let before = self.before.as_ref().map(names).unwrap_or_default();
+        let after = names(&self.after);
+
+        ProposedChange {
+            action: match self.before {
+                Some(_) => ProposedAction::Update,
+                None => ProposedAction::Add,
+            },
+            id: self.after.id().to_owned(),
+            kind: self.after.kind(),
+            name: name(&self.after),
+            parent_topics_added: self.parent_topics_added(),
+            parent_topics_removed: self.parent_topics_removed(),
+            previous_name: self.before.as_ref().map(name),
+            synonyms_added: after.difference(&before).cloned().collect(),
+            synonyms_removed: before.difference(&after).cloned().collect(),
+            url: match &self.after {
+                RepoObject::Link(link) => Some(link.url().to_owned()),
+                RepoObject::Topic(_) => None,
+            },
+        }
+    }
+}
+
+// Works out the state that each proposed object would have in the target repo.  Parent topics that
+// exist in neither the target repo nor the proposal are dropped, and an object that would be left
+// without a parent topic is placed under the root topic.
+fn resolve(
+    view: &core::View,
+    target_repo_id: RepoId,
+    payload: &ChangeRequestPayload,
+) -> Result<Vec<Resolved>> {
+    let proposed = payload
+        .objects
+        .iter()
+        .map(|object| object.id().to_owned())
+        .collect::<BTreeSet<_>>();
+    let root_id = target_repo_id.root_topic_id();
+
+    let mut resolved = vec![];
+    for object in &payload.objects {
+        if object.id() == &root_id {
+            continue;
+        }
+
+        let before = view.object(object.id())?;
+
+        let mut parent_topics = BTreeSet::new();
+        for parent in object.parent_topics() {
+            if parent.id == root_id
+                || proposed.contains(&parent.id)
+                || view.object_exists(&parent.id)?
+            {
+                parent_topics.insert(parent.to_owned());
+            }
+        }
+        if parent_topics.is_empty() {
+            parent_topics = match &before {
+                Some(before) => before.parent_topics().to_owned(),
+                None => BTreeSet::from([ParentTopic {
+                    id: root_id.to_owned(),
+                }]),
+            };
+        }
+
+        let after = match (object.to_owned(), &before) {
+            (RepoObject::Topic(mut topic), before) => {
+                let before = match before {
+                    Some(RepoObject::Topic(before)) => Some(before),
+                    Some(RepoObject::Link(_)) => {
+                        return Err(Error::Repo(format!("not a topic: {}", object.id())))
+                    }
+                    None => None,
+                };
+                topic.parent_topics = parent_topics;
+                topic.children = before.map(|t| t.children.to_owned()).unwrap_or_default();
+                if topic.metadata.details.is_none() {
+                    topic.metadata.details = before.and_then(|t| t.metadata.details.to_owned());
+                }
+                RepoObject::Topic(topic)
+            }
+
+            (RepoObject::Link(mut link), before) => {
+                let before = match before {
+                    Some(RepoObject::Link(before)) => Some(before),
+                    Some(RepoObject::Topic(_)) => {
+                        return Err(Error::Repo(format!("not a link: {}", object.id())))
+                    }
+                    None => None,
+                };
+                link.parent_topics = parent_topics;
+                // Snapshots live in the repo that captured them
+                link.metadata.snapshot = before.and_then(|l| l.metadata.snapshot.to_owned());
+                if link.metadata.details.is_none() {
+                    link.metadata.details = before.and_then(|l| l.metadata.details.to_owned());
+                }
+                RepoObject::Link(link)
+            }
+        };
+
+        resolved.push(Resolved { after, before });
+    }
+
+    Ok(resolved)
+}
+
+pub struct ReviewChangeRequest<'p> {
+    pub locale: Locale,
+    pub payload: &'p ChangeRequestPayload,
+    pub target_repo_id: RepoId,
+}
+
+impl ReviewChangeRequest<'_> {
+    pub fn call(&self, client: &Client) -> Result<Vec<ProposedChange>> {
+        if !client.viewer.can_read(self.target_repo_id) {
+            return Err(Error::NotFound(format!(
+                "not found: {}",
+                self.target_repo_id
+            )));
+        }
+
+        let view = client.view(self.target_repo_id)?;
+        Ok(resolve(&view, self.target_repo_id, self.payload)?
+            .iter()
+            .map(|resolved| resolved.to_proposed_change(self.locale))
+            .collect())
+    }
+}
+
+// Writes the proposed topics and links to the target repo, updating the children of parent topics
+// that were added or removed along the way.  The activity that is recorded is attributed to the
+// author of the change request.
+pub struct ApplyChangeRequest<'p> {
+    pub actor_id: String,
+    pub payload: &'p ChangeRequestPayload,
+    pub target_repo_id: RepoId,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ApplyChangeRequestResult {
+    pub links: usize,
+    pub topics: usize,
+}
+
+impl ApplyChangeRequest<'_> {
+    pub fn call<S>(&self, mut mutation: Mutation, store: &S) -> Result<ApplyChangeRequestResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        let repo_id = self.target_repo_id;
+        let view = mutation.view(repo_id)?;
+        let resolved = resolve(&view, repo_id, self.payload)?;
+
+        let mut topics = BTreeMap::new();
+        let mut links = vec![];
+        for Resolved { after, .. } in &resolved {
+            match after {
+                RepoObject::Topic(topic) => {
+                    topics.insert(topic.topic_id().to_owned(), topic.to_owned());
+                }
+                RepoObject::Link(link) => links.push(link.to_owned()),
+            }
+        }
+
+        // The proposal is checked as a whole, since one of its edges can close a cycle through
+        // another, and a removal can break a cycle that an addition would otherwise close
+        if let Some(id) = find_cycle(&view, &topics)? {
+            return Err(Error::Repo(format!(
+                "applying the change request would make {id} a parent topic of itself"
+            )));
+        }
+
+        let added = chrono::Utc::now();
+        let mut result = ApplyChangeRequestResult::default();
+
+        for resolved in &resolved {
+            let id = resolved.after.id();
+
+            for parent_id in resolved.parent_topics_added() {
+                let parent = parent_topic(&mut topics, &view, &parent_id)?;
+                parent.children.insert(TopicChild {
+                    added,
+                    kind: resolved.after.kind(),
+                    id: id.to_owned(),
+                });
+            }
+
+            for parent_id in resolved.parent_topics_removed() {
+                let parent = parent_topic(&mut topics, &view, &parent_id)?;
+                parent.children.retain(|child| &child.id != id);
+            }
+
+            match resolved.after.kind() {
+                Kind::Topic => result.topics += 1,
+                Kind::Link => result.links += 1,
+            }
+        }
+
+        let topic_info = |id: &ExternalId| match topics.get(id) {
+            Some(topic) => Some(TopicInfo::from(topic)),
+            None => view.topic(id).ok().flatten().as_ref().map(TopicInfo::from),
+        };
+        let changes = resolved
+            .iter()
+            .flat_map(|resolved| resolved.changes(&self.actor_id, added, topic_info))
+            .collect::<Vec<_>>();
+
+        for topic in topics.values() {
+            mutation.save_topic(repo_id, topic)?;
+        }
+        for link in &links {
+            mutation.save_link(repo_id, link)?;
+        }
+        for change in &changes {
+            mutation.add_change(repo_id, change)?;
+        }
+        mutation.write(store)?;
+
+        log::info!(
+            "applied change request to {}: {} topics and {} links",
+            repo_id,
+            result.topics,
+            result.links
+        );
+
+        Ok(result)
+    }
+}
+
+// Looks for a cycle among the parent topics of the proposed topics, with the proposed topics taking
+// the place of the ones in the target repo.  Any cycle in the result would have to pass through a
+// proposed topic, so only the topics above them are looked at.
+fn find_cycle(
+    view: &core::View,
+    proposed: &BTreeMap<ExternalId, RepoTopic>,
+) -> Result<Option<ExternalId>> {
+    enum State {
+        Visiting,
+        Done,
+    }
+
+    let parent_ids = |id: &ExternalId| -> Result<Vec<ExternalId>> {
+        let topic = match proposed.get(id) {
+            Some(topic) => Some(topic.to_owned()),
+            None => view.topic(id)?,
+        };
+        Ok(topic
+            .map(|topic| {
+                topic
+                    .parent_topics
+                    .iter()
+                    .map(|parent| parent.id.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default())
+    };
+
+    let mut states = HashMap::new();
+    for start_id in proposed.keys() {
+        if states.contains_key(start_id) {
+            continue;
+        }
+
+        states.insert(start_id.to_owned(), State::Visiting);
+        let mut stack = vec![(start_id.to_owned(), parent_ids(start_id)?)];
+
+        while let Some((id, pending)) = stack.last_mut() {
+            match pending.pop() {
+                Some(parent_id) => match states.get(&parent_id) {
+                    Some(State::Visiting) => return Ok(Some(parent_id)),
+                    Some(State::Done) => {}
+                    None => {
+                        states.insert(parent_id.to_owned(), State::Visiting);
+                        let parents = parent_ids(&parent_id)?;
+                        stack.push((parent_id, parents));
+                    }
+                },
+                None => {
+                    states.insert(id.to_owned(), State::Done);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn parent_topic<'t>(
+    topics: &'t mut BTreeMap<ExternalId, RepoTopic>,
+    view: &core::View,
+    parent_id: &ExternalId,
+) -> Result<&'t mut RepoTopic> {
+    if !topics.contains_key(parent_id) {
+        let parent = view
+            .topic(parent_id)?
+            .ok_or_else(|| Error::NotFound(format!("not found: {parent_id}")))?;
+        topics.insert(parent_id.to_owned(), parent);
+    }
+
+    topics
+        .get_mut(parent_id)
+        .ok_or_else(|| Error::NotFound(format!("not found: {parent_id}")))
+}
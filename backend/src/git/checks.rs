@@ -1,38 +1,214 @@
-use std::env;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
 
-use super::Client;
+use super::client::parse_object_path;
+use super::index::normalize_index;
+use super::table::Table;
+use super::{core, Client, RepoObject};
 use crate::prelude::*;
 use crate::types::RepoId;
 
-pub struct LeakedData;
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LeakField {
+    Children,
+    Content,
+    Index,
+    ParentTopics,
+}
+
+// A reference from a public repo to a private one, either to an object that only the private repo
+// has or to the id of the private repo itself
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Leak {
+    pub field: LeakField,
+    // The leaked object id, if it is an object that leaked rather than the repo id
+    pub id: Option<ExternalId>,
+    pub path: String,
+    pub private_repo_id: RepoId,
+}
+
+// Scans the tree of a public repo at a given commit for references into the other repos in the
+// data root.  Everything is read from Git, so files that have not been checked out are covered
+// and the working directory is left alone.
+pub struct LeakedData {
+    pub repo_id: RepoId,
+    pub rev: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct LeakedDataResult {
+    pub commit: String,
+    pub leaks: Vec<Leak>,
+}
+
+// Ids and repo ids are made up of these characters, so anything else separates one candidate
+// from the next
+fn tokens(content: &[u8]) -> impl Iterator<Item = &str> {
+    content
+        .split(|b| !(b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_'))
+        .filter(|token| !token.is_empty())
+        .filter_map(|token| std::str::from_utf8(token).ok())
+}
 
 impl LeakedData {
-    pub fn call(&self, client: &Client) -> Result<Vec<(RepoId, String)>> {
-        let root = client.root.path.to_owned();
-        let repos = self.repos(&root)?;
-        env::set_current_dir(&root)?;
-
-        let mut leaks = vec![];
-        for repo in repos {
-            let output = Command::new("grep")
-                .arg("-rl")
-                .arg(repo.to_string())
-                .arg(".")
-                .output()?;
-            let pat = format!(".{repo}");
-
-            let result = String::from_utf8(output.stdout)?;
-            for line in result.lines() {
-                if !line.starts_with(&pat) {
-                    leaks.push((repo.to_owned(), line.to_owned()));
+    pub fn call(&self, client: &Client) -> Result<LeakedDataResult> {
+        let root = &client.root;
+        let public = match &self.rev {
+            Some(rev) => core::View::at(root, self.repo_id, rev)?,
+            None => core::View::ensure(root, self.repo_id, &client.timespec)?,
+        };
+        let public_ids = public
+            .object_oids()?
+            .keys()
+            .filter_map(|path| parse_object_path(path).ok())
+            .collect::<HashSet<_>>();
+
+        // Object ids that only a private repo has, and the repo they belong to
+        let mut private_ids: HashMap<ExternalId, RepoId> = HashMap::new();
+        let mut repo_ids: HashMap<String, RepoId> = HashMap::new();
+        for repo_id in self.repos(&root.path)? {
+            if repo_id == self.repo_id {
+                continue;
+            }
+            repo_ids.insert(repo_id.to_string(), repo_id);
+
+            let view = core::View::ensure(root, repo_id, &client.timespec)?;
+            for path in view.object_oids()?.keys() {
+                if let Ok(id) = parse_object_path(path) {
+                    if !public_ids.contains(&id) {
+                        private_ids.insert(id, repo_id);
+                    }
+                }
+            }
+        }
+
+        let mut leaks = BTreeSet::new();
+        let tree = public.repo.commit(public.commit)?.tree()?;
+        let mut result = Ok(());
+
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() != Some(git2::ObjectType::Blob) {
+                return git2::TreeWalkResult::Ok;
+            }
+            let path = format!("{}{}", dir, entry.name().unwrap_or_default());
+
+            let blob = match public.repo.inner.find_blob(entry.id()) {
+                Ok(blob) => blob,
+                Err(err) => {
+                    result = Err(Error::from(err));
+                    return git2::TreeWalkResult::Abort;
+                }
+            };
+            // Ids in an index table sit next to binary lengths, so they are read from the
+            // decoded entries instead
+            let decoded = if Table::is_table(blob.content()) {
+                match normalize_index(&path, blob.content()) {
+                    Ok(decoded) => Some(decoded),
+                    Err(err) => {
+                        log::warn!("unable to read {}: {}", path, err);
+                        return git2::TreeWalkResult::Ok;
+                    }
+                }
+            } else {
+                None
+            };
+            let content = match &decoded {
+                Some(decoded) => decoded.as_bytes(),
+                None => blob.content(),
+            };
+
+            for token in tokens(content) {
+                if let Some(repo_id) = repo_ids.get(token) {
+                    leaks.insert(Leak {
+                        field: LeakField::Content,
+                        id: None,
+                        path: path.to_owned(),
+                        private_repo_id: *repo_id,
+                    });
                 }
             }
+
+            if entry.name() == Some("object.yaml") {
+                let object: Result<RepoObject> = blob.try_into();
+                match object {
+                    Ok(object) => self.check_object(&object, &path, &private_ids, &mut leaks),
+                    Err(err) => log::warn!("unable to read {}: {}", path, err),
+                }
+                return git2::TreeWalkResult::Ok;
+            }
+
+            let field = if path.starts_with("indexes/") {
+                LeakField::Index
+            } else {
+                LeakField::Content
+            };
+
+            for token in tokens(content) {
+                let Ok(id) = ExternalId::try_from(token) else {
+                    continue;
+                };
+                if let Some(repo_id) = private_ids.get(&id) {
+                    leaks.insert(Leak {
+                        field,
+                        id: Some(id),
+                        path: path.to_owned(),
+                        private_repo_id: *repo_id,
+                    });
+                }
+            }
+
+            git2::TreeWalkResult::Ok
+        })?;
+        result?;
+
+        log::info!(
+            "found {} leaks in {} at {}",
+            leaks.len(),
+            self.repo_id,
+            public.commit
+        );
+
+        Ok(LeakedDataResult {
+            commit: public.commit.to_string(),
+            leaks: leaks.into_iter().collect(),
+        })
+    }
+
+    fn check_object(
+        &self,
+        object: &RepoObject,
+        path: &str,
+        private_ids: &HashMap<ExternalId, RepoId>,
+        leaks: &mut BTreeSet<Leak>,
+    ) {
+        let mut references = object
+            .parent_topics()
+            .iter()
+            .map(|parent| (LeakField::ParentTopics, &parent.id))
+            .collect::<Vec<_>>();
+        if let RepoObject::Topic(topic) = object {
+            references.extend(
+                topic
+                    .children
+                    .iter()
+                    .map(|child| (LeakField::Children, &child.id)),
+            );
         }
 
-        Ok(leaks)
+        for (field, id) in references {
+            if let Some(repo_id) = private_ids.get(id) {
+                leaks.insert(Leak {
+                    field,
+                    id: Some(id.to_owned()),
+                    path: path.to_owned(),
+                    private_repo_id: *repo_id,
+                });
+            }
+        }
     }
 
     fn repos(&self, root: &PathBuf) -> Result<Vec<RepoId>> {
@@ -56,10 +232,37 @@ impl LeakedData {
                 }
             };
 
-            let id = filename.to_string();
-            repos.push(RepoId::try_from(&id)?);
+            // The system repo and anything else that is not a content repo
+            if filename.starts_with('.') {
+                continue;
+            }
+
+            match RepoId::try_from(filename) {
+                Ok(repo_id) => repos.push(repo_id),
+                Err(err) => log::warn!("skipping {:?}: {}", path, err),
+            }
         }
 
         Ok(repos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_split_on_punctuation() {
+        let content = b"id: \"00001\"\nrepo: 32212616-fc1b-11e8-8eda-b70af6d8d09f/objects";
+        assert_eq!(
+            tokens(content).collect::<Vec<_>>(),
+            vec![
+                "id",
+                "00001",
+                "repo",
+                "32212616-fc1b-11e8-8eda-b70af6d8d09f",
+                "objects"
+            ]
+        );
+    }
+}
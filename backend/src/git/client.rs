@@ -1,18 +1,20 @@
 use git2;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use super::checks::LeakedData;
+use super::checks::{Leak, LeakedData};
+use super::closure::ClosureMap;
 use super::index::{
-    ActivityIndex, GitIndexKey, IndexMode, IndexType, Indexer, Phrase, SaveChangesForPrefix,
-    SearchEntry, SynonymEntry, SynonymMatch,
+    ActivityIndex, GitIndexKey, Index, IndexFile, IndexMode, IndexType, Indexer, Phrase,
+    SaveChangesForPrefix, SearchEntry, SynonymEntry, SynonymMatch,
 };
 use super::{
-    activity, core, DownsetIter, ObjectBuilders, RepoLink, RepoObject, RepoStats, RepoTopic,
-    Search, SearchTokenIndex, SynonymIndex, TopicDownsetIter,
+    activity, core, DownsetIter, LinkHealth, LinkHealthIndex, ObjectBuilders, ObjectCache,
+    RepoLink, RepoObject, RepoStats, RepoTopic, Search, SearchTokenIndex, Snapshot, SnapshotRef,
+    SnapshotStore, SynonymIndex, TopicDownsetIter,
 };
 use crate::prelude::*;
 use crate::types::{Timespec, TopicPath};
@@ -20,6 +22,8 @@ use crate::types::{Timespec, TopicPath};
 #[derive(Clone, Debug, Default)]
 pub struct DataRoot {
     pub path: PathBuf,
+    // Shared by every client given a copy of the root
+    pub pool: Arc<core::RepoPool>,
 }
 
 impl std::fmt::Display for DataRoot {
@@ -60,9 +64,29 @@ pub fn parse_path(input: &str) -> Result<(DataRoot, RepoId, ExternalId)> {
     Ok((DataRoot::new(root), repo_id, oid))
 }
 
+// Reads the id of an object from where it is stored within a repo, e.g.,
+// "objects/ab/cd/ef/object.yaml"
+pub(crate) fn parse_object_path(path: &str) -> Result<ExternalId> {
+    let parts = path.split('/').collect::<Vec<_>>();
+    match parts.as_slice() {
+        ["objects", part1, part2, part3, "object.yaml"] => {
+            ExternalId::try_from(format!("{part1}{part2}{part3}").as_str())
+        }
+        _ => Err(Error::Repo(format!("bad path: {path}"))),
+    }
+}
+
 impl DataRoot {
     pub fn new(root: PathBuf) -> Self {
-        Self { path: root }
+        Self {
+            path: root,
+            pool: Arc::new(core::RepoPool::default()),
+        }
+    }
+
+    // An open repo from the pool, which is returned to the pool when it is dropped
+    pub fn checkout(&self, repo_id: RepoId) -> Result<core::PooledRepo> {
+        self.pool.checkout(&self.repo_path(repo_id))
     }
 
     pub fn repo_path(&self, repo_id: RepoId) -> PathBuf {
@@ -79,6 +103,12 @@ pub trait GitPaths {
         Ok(self.relative_path("changes")?.join("change.yaml"))
     }
 
+    fn closure_filename(&self) -> Result<PathBuf> {
+        Ok(self
+            .relative_path("indexes/closure")?
+            .with_extension("yaml"))
+    }
+
     fn relative_path(&self, subdirectory: &str) -> Result<PathBuf> {
         let (part1, part2, part3) = self.parts()?;
         let relative_path = PathBuf::from([subdirectory, part1, part2, part3].join("/"));
@@ -100,6 +130,7 @@ impl GitPaths for ExternalId {
 
 #[derive(Clone, Debug)]
 pub struct Client {
+    pub cache: Arc<ObjectCache>,
     pub root: DataRoot,
     pub timespec: Timespec,
     pub viewer: Arc<Viewer>,
@@ -108,12 +139,18 @@ pub struct Client {
 impl Client {
     pub fn new(viewer: Arc<Viewer>, root: &DataRoot, timespec: Timespec) -> Self {
         Self {
+            cache: Arc::new(ObjectCache::default()),
             root: root.to_owned(),
             timespec,
             viewer,
         }
     }
 
+    // Shares a cache of parsed objects with other clients, e.g., across requests
+    pub fn with_cache(self, cache: Arc<ObjectCache>) -> Self {
+        Self { cache, ..self }
+    }
+
     pub fn appears_in(
         &self,
         repo_id: RepoId,
@@ -133,6 +170,25 @@ impl Client {
         Ok(true)
     }
 
+    pub fn broken_links(&self, repo_id: RepoId) -> Result<Vec<(ExternalId, LinkHealth)>> {
+        if !self.viewer.can_read(repo_id) {
+            return Ok(vec![]);
+        }
+
+        let view = self.view(repo_id)?;
+        let mut links = vec![];
+
+        for index in LinkHealthIndex::load_all(&view)? {
+            for (link_id, health) in index.iter() {
+                if health.is_broken() {
+                    links.push((link_id.to_owned(), health.to_owned()));
+                }
+            }
+        }
+
+        Ok(links)
+    }
+
     // How to handle path visibility?
     fn cycle_exists(
         &self,
@@ -140,6 +196,13 @@ impl Client {
         descendant_id: &ExternalId,
         ancestor_id: &ExternalId,
     ) -> Result<bool> {
+        // Making the ancestor a parent of the descendant closes a cycle if the descendant is
+        // already above it
+        let view = self.view(repo_id)?;
+        if let Some(closure) = ClosureMap::load(&view, ancestor_id)? {
+            return Ok(descendant_id == ancestor_id || closure.ancestors.contains(descendant_id));
+        }
+
         let mut i = 0;
 
         if let Some(descendant_path) = self.topic_path(repo_id, descendant_id)? {
@@ -156,7 +219,13 @@ impl Client {
         Ok(false)
     }
 
-    pub fn downset(&self, topic_path: &TopicPath) -> DownsetIter {
+    // The topic and the topics and links below it.  The closure of a repo only records what is
+    // above each object, so this walks the graph, and callers cache the result by topic oid.
+    pub fn downset_ids(&self, topic_path: &TopicPath) -> HashSet<ExternalId> {
+        self.downset(topic_path).collect()
+    }
+
+    pub fn downset(&self, topic_path: &TopicPath) -> DownsetIter<'_> {
         DownsetIter::new(
             self,
             topic_path.repo_id,
@@ -239,32 +308,112 @@ impl Client {
         }
     }
 
-    pub fn fetch_all(&self, keys: &[Okey]) -> ObjectBuilders {
-        let mut objects = ObjectBuilders::new();
+    pub fn fetch_link_health(
+        &self,
+        repo_id: RepoId,
+        link_id: &ExternalId,
+    ) -> Result<Option<LinkHealth>> {
+        if !self.viewer.can_read(repo_id) {
+            return Ok(None);
+        }
 
-        for &repo_id in self.viewer.read_repo_ids.iter() {
-            for key in keys {
-                let object = self.fetch(repo_id, &key.0);
-                if object.is_none() {
-                    continue;
+        let view = self.view(repo_id)?;
+        let filename = LinkHealthIndex::filename_for(link_id)?;
+        let index = LinkHealthIndex::load(&filename, &view)?;
+        Ok(index.get(link_id).cloned())
+    }
+
+    pub fn fetch_snapshot(
+        &self,
+        repo_id: RepoId,
+        reference: &SnapshotRef,
+    ) -> Result<Option<Snapshot>> {
+        if !self.viewer.can_read(repo_id) {
+            return Ok(None);
+        }
+
+        let store = SnapshotStore::new(&self.root, repo_id);
+        match store.fetch(reference) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(Error::NotFound(err)) => {
+                log::warn!("snapshot not found: {}", err);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // A batch of keys is grouped by repo, so that each repo is opened and its tree resolved once
+    // per batch rather than once per key.  The repos are read in parallel on the blocking thread
+    // pool, since a viewer can have many of them.
+    pub async fn fetch_all(self: &Arc<Self>, keys: &[Okey]) -> ObjectBuilders {
+        let mut keys_by_id: HashMap<ExternalId, Vec<&Okey>> = HashMap::new();
+        for key in keys {
+            keys_by_id.entry(key.0.to_owned()).or_default().push(key);
+        }
+        let ids = Arc::new(keys_by_id.keys().cloned().collect::<BTreeSet<_>>());
+
+        let tasks = self
+            .viewer
+            .read_repo_ids
+            .iter()
+            .map(|&repo_id| {
+                let client = Arc::clone(self);
+                let ids = Arc::clone(&ids);
+                let task =
+                    tokio::task::spawn_blocking(move || client.fetch_from_repo(repo_id, &ids));
+                (repo_id, task)
+            })
+            .collect::<Vec<_>>();
+
+        let mut objects = ObjectBuilders::new();
+        for (repo_id, task) in tasks {
+            match task.await {
+                Ok(found) => {
+                    for (id, object) in found {
+                        for &key in keys_by_id.get(&id).into_iter().flatten() {
+                            objects.add(key.to_owned(), repo_id, object.to_owned());
+                        }
+                    }
                 }
-                objects.add(key.to_owned(), repo_id, object.unwrap());
+                Err(err) => log::error!("failed to fetch objects from {}: {}", repo_id, err),
             }
         }
 
         objects
     }
 
-    pub fn fetch_synonym_index(&self, repo_id: RepoId, filename: &PathBuf) -> Result<SynonymIndex> {
-        let view = self.view(repo_id)?;
-        let result = view.find_blob_by_filename(filename)?;
-        match result {
-            Some(blob) => {
-                let index = blob.try_into()?;
-                Ok(SynonymIndex::make(filename.to_owned(), index))
+    // Looks up each of the ids in a single view of the repo, skipping those that are not found
+    pub fn fetch_from_repo(
+        &self,
+        repo_id: RepoId,
+        ids: &BTreeSet<ExternalId>,
+    ) -> Vec<(ExternalId, RepoObject)> {
+        if !self.viewer.can_read(repo_id) {
+            return vec![];
+        }
+
+        let view = match self.view(repo_id) {
+            Ok(view) => view,
+            Err(err) => {
+                log::error!("failed to open repo {}: {:?}", repo_id, err);
+                return vec![];
+            }
+        };
+
+        let mut objects = vec![];
+        for id in ids {
+            match view.object(id) {
+                Ok(Some(object)) => objects.push((id.to_owned(), object)),
+                Ok(None) => {}
+                Err(err) => log::error!("failed to fetch {}: {:?}", id, err),
             }
-            None => Ok(SynonymIndex::new(filename)),
         }
+        objects
+    }
+
+    pub fn fetch_synonym_index(&self, repo_id: RepoId, filename: &PathBuf) -> Result<SynonymIndex> {
+        SynonymIndex::load(filename, &self.view(repo_id)?)
     }
 
     pub fn fetch_token_index(
@@ -272,15 +421,7 @@ impl Client {
         repo_id: RepoId,
         filename: &PathBuf,
     ) -> Result<SearchTokenIndex> {
-        let view = self.view(repo_id)?;
-        let result = view.find_blob_by_filename(filename)?;
-        match result {
-            Some(blob) => Ok(SearchTokenIndex::make(
-                filename.to_owned(),
-                blob.try_into()?,
-            )),
-            None => Ok(SearchTokenIndex::new(filename)),
-        }
+        SearchTokenIndex::load(filename, &self.view(repo_id)?)
     }
 
     pub fn fetch_topic(&self, repo_id: RepoId, topic_id: &ExternalId) -> Option<RepoTopic> {
@@ -290,8 +431,13 @@ impl Client {
         }
     }
 
-    pub fn leaked_data(&self) -> Result<Vec<(RepoId, String)>> {
-        LeakedData.call(self)
+    pub fn leaked_data(&self) -> Result<Vec<Leak>> {
+        let result = LeakedData {
+            repo_id: RepoId::wiki(),
+            rev: None,
+        }
+        .call(self)?;
+        Ok(result.leaks)
     }
 
     fn link_searches(&self, link: Option<RepoLink>) -> Result<BTreeSet<Search>> {
@@ -312,6 +458,26 @@ impl Client {
         Ok(searches)
     }
 
+    // Only links with a saved snapshot have page text to index
+    fn link_text_tokens(&self, repo_id: RepoId, link: Option<&RepoLink>) -> BTreeSet<Phrase> {
+        let reference = match link.and_then(|link| link.metadata.snapshot.as_ref()) {
+            Some(reference) => reference,
+            None => return BTreeSet::new(),
+        };
+
+        match self.snapshots(repo_id).fetch(reference) {
+            Ok(snapshot) => Phrase::text_tokens(&snapshot.text),
+            Err(err) => {
+                log::warn!("unable to load snapshot text for indexing: {}", err);
+                BTreeSet::new()
+            }
+        }
+    }
+
+    pub fn snapshots(&self, repo_id: RepoId) -> SnapshotStore {
+        SnapshotStore::new(&self.root, repo_id)
+    }
+
     pub fn topic_path(&self, repo_id: RepoId, topic_id: &ExternalId) -> Result<Option<TopicPath>> {
         let topic_oid = self.repo(repo_id)?.topic_oid(&self.timespec, topic_id)?;
 
@@ -326,8 +492,8 @@ impl Client {
         Ok(None)
     }
 
-    fn repo(&self, repo_id: RepoId) -> Result<core::Repo> {
-        core::Repo::ensure(&self.root, repo_id)
+    fn repo(&self, repo_id: RepoId) -> Result<core::PooledRepo> {
+        self.root.checkout(repo_id)
     }
 
     // The "prefix" argument tells us which repo to look in.  The "prefix" in the method name
@@ -342,6 +508,17 @@ impl Client {
         Ok(index.prefix_matches(token))
     }
 
+    // Looks for links whose saved page text contains a word starting with the token
+    pub fn text_token_prefix_matches(
+        &self,
+        repo_id: RepoId,
+        token: &Phrase,
+    ) -> Result<HashSet<SearchEntry>> {
+        let key = repo_id.index_key(token)?;
+        let index = key.text_index(self, IndexMode::ReadOnly)?;
+        Ok(index.prefix_matches(token))
+    }
+
     pub fn view_stats(&self, repo_id: RepoId) -> Result<RepoStats> {
         self.view(repo_id)?.stats()
     }
@@ -403,7 +580,7 @@ impl Client {
         }
     }
 
-    pub fn topic_downset(&self, topic_path: &TopicPath) -> TopicDownsetIter {
+    pub fn topic_downset(&self, topic_path: &TopicPath) -> TopicDownsetIter<'_> {
         TopicDownsetIter::new(
             self,
             topic_path.repo_id,
@@ -450,7 +627,8 @@ impl Client {
     }
 
     pub fn view(&self, repo_id: RepoId) -> Result<core::View> {
-        core::View::ensure(&self.root, repo_id, &self.timespec)
+        let view = core::View::ensure(&self.root, repo_id, &self.timespec)?;
+        Ok(view.with_cache(Arc::clone(&self.cache)))
     }
 }
 
@@ -528,6 +706,75 @@ impl Mutation {
         self.client.fetch_topic(repo_id, topic_id)
     }
 
+    // Adds the change to the activity logs without writing the change itself
+    pub fn index_change(&mut self, repo_id: RepoId, change: &activity::Change) -> Result<()> {
+        self.indexer.add_activity(repo_id, &self.client, change)
+    }
+
+    // The serialized indexes and activity logs that will be written, along with any changes
+    pub fn index_files(&self) -> Result<Vec<IndexFile>> {
+        self.indexer.files()
+    }
+
+    // Updates the indexes for the object without writing the object itself
+    pub fn index_object(&mut self, repo_id: RepoId, object: &RepoObject) -> Result<()> {
+        match object {
+            RepoObject::Topic(topic) => self.index_topic(repo_id, topic),
+            RepoObject::Link(link) => self.index_link(repo_id, link),
+        }
+    }
+
+    fn index_link(&mut self, repo_id: RepoId, link: &RepoLink) -> Result<()> {
+        self.indexer
+            .update_parents(&self.client, repo_id, link.id(), &link.parent_topics)?;
+
+        let view = self.client.view(repo_id)?;
+        let entry = link.to_search_entry();
+        let before = view.link(link.id())?;
+
+        // Avoid reading the page text again when the snapshot has not changed
+        let snapshot_before = before.as_ref().and_then(|l| l.metadata.snapshot.as_ref());
+        let snapshot_changed = snapshot_before != link.metadata.snapshot.as_ref();
+        if self.indexer.index_text && (self.indexer.mode == IndexMode::Replace || snapshot_changed)
+        {
+            let text_before = self.client.link_text_tokens(repo_id, before.as_ref());
+            let text_after = self.client.link_text_tokens(repo_id, Some(link));
+            self.indexer
+                .update_text(&self.client, repo_id, &entry, &text_before, &text_after)?;
+        }
+
+        let before = self.client.link_searches(before)?;
+        let after = self.client.link_searches(Some(link.to_owned()))?;
+        self.indexer
+            .update(&self.client, repo_id, &entry, &before, &after)
+    }
+
+    fn index_topic(&mut self, repo_id: RepoId, topic: &RepoTopic) -> Result<()> {
+        self.indexer.update_parents(
+            &self.client,
+            repo_id,
+            topic.topic_id(),
+            &topic.parent_topics,
+        )?;
+
+        let view = self.client.view(repo_id)?;
+        let before = view.topic(topic.topic_id())?;
+        self.indexer
+            .update_synonyms(&self.client, repo_id, &before, topic)?;
+
+        let before = self.client.topic_searches(repo_id, before)?;
+        let after = self
+            .client
+            .topic_searches(repo_id, Some(topic.to_owned()))?;
+        self.indexer.update(
+            &self.client,
+            repo_id,
+            &topic.to_search_entry(),
+            &before,
+            &after,
+        )
+    }
+
     pub fn mark_deleted(&mut self, repo_id: RepoId, id: &ExternalId) -> Result<()> {
         self.check_can_update(repo_id)?;
 
@@ -542,11 +789,19 @@ impl Mutation {
     }
 
     pub fn remove(&mut self, repo_id: RepoId, id: &ExternalId) -> Result<()> {
+        self.indexer.remove_parents(&self.client, repo_id, id)?;
         let filename = id.object_filename()?;
         self.files.insert((repo_id, filename), None);
         Ok(())
     }
 
+    // Removes a file that is not an object, such as an index that is no longer needed
+    pub fn remove_file(&mut self, repo_id: RepoId, filename: &Path) -> Result<()> {
+        self.check_can_update(repo_id)?;
+        self.files.insert((repo_id, filename.to_owned()), None);
+        Ok(())
+    }
+
     pub fn remove_link(
         &mut self,
         repo_id: RepoId,
@@ -555,13 +810,20 @@ impl Mutation {
     ) -> Result<()> {
         self.check_can_update(repo_id)?;
 
+        let entry = link.to_search_entry();
         let searches = self.client.link_searches(Some(link.to_owned()))?;
-        self.indexer.remove_searches(
+        self.indexer
+            .remove_searches(&self.client, repo_id, &entry, searches.iter())?;
+
+        let text_tokens = self.client.link_text_tokens(repo_id, Some(link));
+        self.indexer.update_text(
             &self.client,
             repo_id,
-            &link.to_search_entry(),
-            searches.iter(),
+            &entry,
+            &text_tokens,
+            &BTreeSet::new(),
         )?;
+
         self.remove(repo_id, link_id)
     }
 
@@ -594,10 +856,26 @@ impl Mutation {
         Ok(())
     }
 
-    pub fn repo(&self, repo_id: RepoId) -> Result<core::Repo> {
+    // Leaves the full-text index as it is
+    pub fn without_text_index(mut self) -> Self {
+        self.indexer.index_text = false;
+        self
+    }
+
+    // A new mutation with the same client and index mode, for changes that have to follow this
+    // one once it has been written
+    pub fn next(&self) -> Result<Mutation> {
+        self.client.mutation(self.indexer.mode)
+    }
+
+    pub fn repo(&self, repo_id: RepoId) -> Result<core::PooledRepo> {
         self.client.repo(repo_id)
     }
 
+    pub fn view(&self, repo_id: RepoId) -> Result<core::View> {
+        self.client.view(repo_id)
+    }
+
     pub fn write<S>(&self, store: &S) -> Result<()>
     where
         S: SaveChangesForPrefix,
@@ -613,15 +891,26 @@ impl Mutation {
 
         let index_files = self.indexer.files()?;
 
-        // Write activity logs
-        for (prefix, filename, ser) in index_files {
-            let repo = self.repo(prefix)?;
-            let oid = repo.add_blob(ser.as_bytes())?;
-            update.add(prefix, &filename, &Some(oid))?;
+        // Write indexes and activity logs
+        for (prefix, filename, content) in index_files {
+            let oid = match content {
+                Some(content) => Some(self.repo(prefix)?.add_blob(&content)?),
+                None => None,
+            };
+            update.add(prefix, &filename, &oid)?;
         }
 
         let sig = git2::Signature::now("digraph-bot", "digraph-bot@digraph.app")?;
-        update.write(&self.client.root, &sig, &self.commit_message())
+        update.write(&self.client.root, &sig, &self.commit_message())?;
+
+        // Written once the commit has gone through, so that the full-text index does not point
+        // at links that were never saved
+        for (repo_id, filename, content) in self.indexer.text_files()? {
+            self.snapshots(repo_id)
+                .write_text_index(&filename, content.as_deref())?;
+        }
+
+        Ok(())
     }
 
     pub fn save_change(&mut self, repo_id: RepoId, change: &activity::Change) -> Result<()> {
@@ -636,25 +925,28 @@ impl Mutation {
         Ok(())
     }
 
+    pub fn save_index<I>(&mut self, repo_id: RepoId, index: &I) -> Result<()>
+    where
+        I: Index,
+    {
+        self.check_can_update(repo_id)?;
+
+        let s = index.serialize()?;
+        let oid = self.repo(repo_id)?.add_blob(s.as_bytes())?;
+        self.files
+            .insert((repo_id, index.filename().to_owned()), Some(oid));
+
+        Ok(())
+    }
+
     pub fn save_link(&mut self, repo_id: RepoId, link: &RepoLink) -> Result<()> {
         self.check_can_update(repo_id)?;
+        self.index_link(repo_id, link)?;
 
-        let view = self.client.view(repo_id)?;
-        let link_id = link.id();
-        let before = view.link(link_id)?;
-        let before = self.client.link_searches(before)?;
-        let after = self.client.link_searches(Some(link.to_owned()))?;
-        self.indexer.update(
-            &self.client,
-            repo_id,
-            &link.to_search_entry(),
-            &before,
-            &after,
-        )?;
         let s = serde_yaml::to_string(&link)?;
         let oid = self.client.repo(repo_id)?.add_blob(s.as_bytes())?;
 
-        self.save_object(repo_id, link_id, oid)
+        self.save_object(repo_id, link.id(), oid)
     }
 
     fn save_object(&mut self, repo_id: RepoId, id: &ExternalId, oid: git2::Oid) -> Result<()> {
@@ -665,28 +957,16 @@ impl Mutation {
 
     pub fn save_topic(&mut self, repo_id: RepoId, topic: &RepoTopic) -> Result<()> {
         self.check_can_update(repo_id)?;
+        self.index_topic(repo_id, topic)?;
 
-        let topic_id = topic.topic_id();
-        let view = self.client.view(repo_id)?;
-        let before = view.topic(topic_id)?;
-        self.indexer
-            .update_synonyms(&self.client, repo_id, &before, topic)?;
-
-        let before = self.client.topic_searches(repo_id, before)?;
-        let after = self
-            .client
-            .topic_searches(repo_id, Some(topic.to_owned()))?;
-        self.indexer.update(
-            &self.client,
-            repo_id,
-            &topic.to_search_entry(),
-            &before,
-            &after,
-        )?;
         let s = serde_yaml::to_string(&topic)?;
         let oid = self.client.repo(repo_id)?.add_blob(s.as_bytes())?;
 
-        self.save_object(repo_id, topic_id, oid)
+        self.save_object(repo_id, topic.topic_id(), oid)
+    }
+
+    pub fn snapshots(&self, repo_id: RepoId) -> SnapshotStore {
+        self.client.snapshots(repo_id)
     }
 
     pub fn synonym_phrase_matches(
@@ -702,6 +982,15 @@ impl Mutation {
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_object_path_works() {
+        assert_eq!(
+            parse_object_path("objects/12/34/5678/object.yaml").unwrap(),
+            ExternalId::try_from("12345678").unwrap()
+        );
+        assert!(parse_object_path("changes/12/34/5678/change.yaml").is_err());
+    }
+
     #[test]
     fn parse_path_works() {
         let result = parse_path("../../12345/12/34/5678/object.yaml");
@@ -0,0 +1,516 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::index::IndexFile;
+use super::{core, Client, GitPaths, IndexMode};
+use crate::prelude::*;
+
+// Written once the closure of a repo has been materialized, after which it is kept up to date as
+// parent topics change.  Until then, lookups fall back to walking the graph.
+const META_FILENAME: &str = "indexes/closure/meta.yaml";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub struct ClosureMeta {
+    pub api_version: String,
+}
+
+// The transitive closure of the parent topics of an object, as of the commit it is read from.
+// Only the topics above an object are kept.  The topics and links below a topic are found by
+// walking the graph and cached along with other downsets, since keeping them here would mean
+// rewriting the entry of every topic above an object each time a link or topic is added.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub struct ClosureMap {
+    pub api_version: String,
+    // Every topic above the object
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub ancestors: BTreeSet<ExternalId>,
+}
+
+impl Default for ClosureMap {
+    fn default() -> Self {
+        Self {
+            api_version: API_VERSION.to_owned(),
+            ancestors: BTreeSet::new(),
+        }
+    }
+}
+
+impl ClosureMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The closure of the object, or None if the closure of the repo has not been materialized
+    pub fn load(view: &core::View, id: &ExternalId) -> Result<Option<Arc<Self>>> {
+        if !is_materialized(view)? {
+            return Ok(None);
+        }
+
+        match view.blob_oid_by_filename(&id.closure_filename()?)? {
+            Some(oid) => {
+                Ok(Some(view.cached(oid, |blob| {
+                    Ok(serde_yaml::from_slice(blob.content())?)
+                })?))
+            }
+            None => Ok(Some(Arc::new(Self::new()))),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ancestors.is_empty()
+    }
+}
+
+pub(crate) fn is_materialized(view: &core::View) -> Result<bool> {
+    view.blob_exists(&PathBuf::from(META_FILENAME))
+}
+
+pub(crate) fn normalize_closure(filename: &str, content: &[u8]) -> Result<String> {
+    let normalized = if filename == META_FILENAME {
+        serde_yaml::to_string(&serde_yaml::from_slice::<ClosureMeta>(content)?)?
+    } else {
+        serde_yaml::to_string(&serde_yaml::from_slice::<ClosureMap>(content)?)?
+    };
+    Ok(normalized)
+}
+
+struct Entry {
+    changed: bool,
+    committed: bool,
+    map: ClosureMap,
+}
+
+// The part of the closure of a repo touched by a mutation
+struct RepoClosure {
+    entries: HashMap<ExternalId, Entry>,
+    materialized: bool,
+    mode: IndexMode,
+    // The objects placed under a topic by the mutation, which is not reflected in the children of
+    // the topic until the mutation has been written
+    children: HashMap<ExternalId, BTreeSet<ExternalId>>,
+    // The parent topics of objects saved or removed by the mutation, which may not have been
+    // committed yet
+    parents: HashMap<ExternalId, BTreeSet<ExternalId>>,
+    removed: HashSet<ExternalId>,
+    repo_id: RepoId,
+}
+
+impl RepoClosure {
+    fn new(client: &Client, repo_id: RepoId, mode: IndexMode) -> Result<Self> {
+        let materialized = match mode {
+            IndexMode::Replace => true,
+            _ => is_materialized(&client.view(repo_id)?)?,
+        };
+
+        Ok(Self {
+            children: HashMap::new(),
+            entries: HashMap::new(),
+            materialized,
+            mode,
+            parents: HashMap::new(),
+            removed: HashSet::new(),
+            repo_id,
+        })
+    }
+
+    fn entry(&mut self, client: &Client, id: &ExternalId) -> Result<&mut Entry> {
+        if !self.entries.contains_key(id) {
+            let view = client.view(self.repo_id)?;
+            let filename = id.closure_filename()?;
+            let entry = match view.find_blob_by_filename(&filename)? {
+                Some(blob) => Entry {
+                    changed: false,
+                    committed: true,
+                    map: serde_yaml::from_slice(blob.content())?,
+                },
+                None => Entry {
+                    changed: false,
+                    committed: false,
+                    map: ClosureMap::new(),
+                },
+            };
+            self.entries.insert(id.to_owned(), entry);
+        }
+
+        Ok(self.entries.get_mut(id).expect("entry was just added"))
+    }
+
+    fn ancestors(&mut self, client: &Client, id: &ExternalId) -> Result<BTreeSet<ExternalId>> {
+        Ok(self.entry(client, id)?.map.ancestors.to_owned())
+    }
+
+    fn children(&self, client: &Client, id: &ExternalId) -> BTreeSet<ExternalId> {
+        let mut children = client
+            .fetch_topic(self.repo_id, id)
+            .map(|topic| {
+                topic
+                    .children
+                    .iter()
+                    .map(|child| child.id.to_owned())
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+
+        // Objects that the mutation has moved out from under the topic
+        children.retain(|child_id| match self.parents.get(child_id) {
+            Some(parents) => parents.contains(id),
+            None => true,
+        });
+        children.extend(self.children.get(id).into_iter().flatten().cloned());
+        children
+    }
+
+    // Everything below the object, found by walking the graph
+    fn descendants(&self, client: &Client, id: &ExternalId) -> BTreeSet<ExternalId> {
+        let mut descendants = BTreeSet::new();
+        let mut pending = vec![id.to_owned()];
+
+        while let Some(id) = pending.pop() {
+            for child_id in self.children(client, &id) {
+                if descendants.insert(child_id.to_owned()) {
+                    pending.push(child_id);
+                }
+            }
+        }
+
+        descendants.remove(id);
+        descendants
+    }
+
+    fn parents(&self, client: &Client, id: &ExternalId) -> BTreeSet<ExternalId> {
+        if let Some(parents) = self.parents.get(id) {
+            return parents.to_owned();
+        }
+
+        match client.fetch(self.repo_id, id) {
+            Some(object) => object
+                .parent_topics()
+                .iter()
+                .map(|parent| parent.id.to_owned())
+                .collect(),
+            None => BTreeSet::new(),
+        }
+    }
+
+    fn set_parents(
+        &mut self,
+        client: &Client,
+        id: &ExternalId,
+        after: BTreeSet<ExternalId>,
+    ) -> Result<()> {
+        // The whole closure is computed at the end when it is being replaced, and there is
+        // nothing to update until it has been
+        if self.mode == IndexMode::Replace || !self.materialized {
+            self.parents.insert(id.to_owned(), after);
+            return Ok(());
+        }
+
+        let before = self.parents(client, id);
+        for parent_id in &before {
+            if let Some(children) = self.children.get_mut(parent_id) {
+                children.remove(id);
+            }
+        }
+        for parent_id in &after {
+            self.children
+                .entry(parent_id.to_owned())
+                .or_default()
+                .insert(id.to_owned());
+        }
+        self.parents.insert(id.to_owned(), after.to_owned());
+
+        for parent_id in after.difference(&before) {
+            self.add_edge(client, parent_id, id)?;
+        }
+
+        if before.difference(&after).next().is_some() {
+            self.recompute(client, id)?;
+        }
+
+        Ok(())
+    }
+
+    // Everything at or above the parent is now above everything at or below the child
+    fn add_edge(
+        &mut self,
+        client: &Client,
+        parent_id: &ExternalId,
+        child_id: &ExternalId,
+    ) -> Result<()> {
+        let mut above = self.ancestors(client, parent_id)?;
+        above.insert(parent_id.to_owned());
+        let mut below = self.descendants(client, child_id);
+        below.insert(child_id.to_owned());
+
+        for id in &below {
+            let entry = self.entry(client, id)?;
+            if !entry.map.ancestors.is_superset(&above) {
+                entry.map.ancestors.extend(above.iter().cloned());
+                entry.changed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    // After a parent topic is removed, the ancestors of the object and of everything below it are
+    // worked out again from their parent topics.  Nothing outside of the subgraph below the
+    // object can have lost an ancestor.
+    fn recompute(&mut self, client: &Client, id: &ExternalId) -> Result<()> {
+        let mut below = self.descendants(client, id);
+        below.insert(id.to_owned());
+
+        let mut computed = HashMap::new();
+        for node in &below {
+            self.compute_ancestors(client, node, &below, &mut computed, &mut HashSet::new())?;
+        }
+
+        for (node, after) in computed {
+            let entry = self.entry(client, &node)?;
+            if entry.map.ancestors != after {
+                entry.map.ancestors = after;
+                entry.changed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compute_ancestors(
+        &mut self,
+        client: &Client,
+        id: &ExternalId,
+        below: &BTreeSet<ExternalId>,
+        computed: &mut HashMap<ExternalId, BTreeSet<ExternalId>>,
+        visiting: &mut HashSet<ExternalId>,
+    ) -> Result<BTreeSet<ExternalId>> {
+        if let Some(ancestors) = computed.get(id) {
+            return Ok(ancestors.to_owned());
+        }
+        // A cycle that made it into the repo somehow
+        if !visiting.insert(id.to_owned()) {
+            return Ok(BTreeSet::new());
+        }
+
+        let mut ancestors = BTreeSet::new();
+        for parent_id in self.parents(client, id) {
+            let above = if below.contains(&parent_id) {
+                self.compute_ancestors(client, &parent_id, below, computed, visiting)?
+            } else {
+                self.ancestors(client, &parent_id)?
+            };
+            ancestors.extend(above);
+            ancestors.insert(parent_id);
+        }
+
+        visiting.remove(id);
+        computed.insert(id.to_owned(), ancestors.to_owned());
+        Ok(ancestors)
+    }
+
+    fn remove(&mut self, client: &Client, id: &ExternalId) -> Result<()> {
+        self.set_parents(client, id, BTreeSet::new())?;
+        self.removed.insert(id.to_owned());
+        Ok(())
+    }
+
+    fn files(&self) -> Result<Vec<IndexFile>> {
+        if self.mode == IndexMode::Replace {
+            return self.replacement_files();
+        }
+
+        let mut files = vec![];
+        if !self.materialized {
+            return Ok(files);
+        }
+
+        for (id, entry) in &self.entries {
+            let removed = self.removed.contains(id) || entry.map.is_empty();
+            let content = if removed {
+                None
+            } else if entry.changed {
+                Some(serde_yaml::to_string(&entry.map)?.into_bytes())
+            } else {
+                continue;
+            };
+
+            // There is nothing to remove if the entry was never written
+            if content.is_some() || entry.committed {
+                files.push((self.repo_id, id.closure_filename()?, content));
+            }
+        }
+
+        Ok(files)
+    }
+
+    // The closure of every object in the repo, computed from the parent topics that were recorded
+    fn replacement_files(&self) -> Result<Vec<IndexFile>> {
+        let mut maps: HashMap<ExternalId, ClosureMap> = HashMap::new();
+        let mut computed = HashMap::new();
+
+        for id in self.parents.keys() {
+            let ancestors = self.replacement_ancestors(id, &mut computed, &mut HashSet::new());
+            maps.entry(id.to_owned()).or_default().ancestors = ancestors;
+        }
+
+        let meta = ClosureMeta {
+            api_version: API_VERSION.to_owned(),
+        };
+        let mut files = vec![(
+            self.repo_id,
+            PathBuf::from(META_FILENAME),
+            Some(serde_yaml::to_string(&meta)?.into_bytes()),
+        )];
+
+        for (id, map) in maps {
+            if !map.is_empty() {
+                files.push((
+                    self.repo_id,
+                    id.closure_filename()?,
+                    Some(serde_yaml::to_string(&map)?.into_bytes()),
+                ));
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn replacement_ancestors(
+        &self,
+        id: &ExternalId,
+        computed: &mut HashMap<ExternalId, BTreeSet<ExternalId>>,
+        visiting: &mut HashSet<ExternalId>,
+    ) -> BTreeSet<ExternalId> {
+        if let Some(ancestors) = computed.get(id) {
+            return ancestors.to_owned();
+        }
+        if !visiting.insert(id.to_owned()) {
+            return BTreeSet::new();
+        }
+
+        let mut ancestors = BTreeSet::new();
+        for parent_id in self.parents.get(id).into_iter().flatten() {
+            ancestors.extend(self.replacement_ancestors(parent_id, computed, visiting));
+            ancestors.insert(parent_id.to_owned());
+        }
+
+        visiting.remove(id);
+        computed.insert(id.to_owned(), ancestors.to_owned());
+        ancestors
+    }
+}
+
+// Keeps the materialized closures of the repos touched by a mutation in step with the parent
+// topics of the objects that are saved and removed
+pub(crate) struct Closure {
+    mode: IndexMode,
+    repos: HashMap<RepoId, RepoClosure>,
+}
+
+impl Closure {
+    pub fn new(mode: IndexMode) -> Self {
+        Self {
+            mode,
+            repos: HashMap::new(),
+        }
+    }
+
+    fn repo(&mut self, client: &Client, repo_id: RepoId) -> Result<&mut RepoClosure> {
+        if !self.repos.contains_key(&repo_id) {
+            let closure = RepoClosure::new(client, repo_id, self.mode)?;
+            self.repos.insert(repo_id, closure);
+        }
+        Ok(self.repos.get_mut(&repo_id).expect("repo was just added"))
+    }
+
+    pub fn set_parents(
+        &mut self,
+        client: &Client,
+        repo_id: RepoId,
+        id: &ExternalId,
+        parents: BTreeSet<ExternalId>,
+    ) -> Result<()> {
+        self.repo(client, repo_id)?.set_parents(client, id, parents)
+    }
+
+    pub fn remove(&mut self, client: &Client, repo_id: RepoId, id: &ExternalId) -> Result<()> {
+        self.repo(client, repo_id)?.remove(client, id)
+    }
+
+    pub fn files(&self) -> Result<Vec<IndexFile>> {
+        let mut files = vec![];
+        for closure in self.repos.values() {
+            files.extend(closure.files()?);
+        }
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> ExternalId {
+        ExternalId::try_from(s).unwrap()
+    }
+
+    fn ids(list: &[&str]) -> BTreeSet<ExternalId> {
+        list.iter().map(|s| id(s)).collect()
+    }
+
+    fn replacement(edges: &[(&str, &[&str])]) -> HashMap<PathBuf, ClosureMap> {
+        let mut closure = RepoClosure {
+            children: HashMap::new(),
+            entries: HashMap::new(),
+            materialized: true,
+            mode: IndexMode::Replace,
+            parents: HashMap::new(),
+            removed: HashSet::new(),
+            repo_id: RepoId::wiki(),
+        };
+        for (child, parents) in edges {
+            closure.parents.insert(id(child), ids(parents));
+        }
+
+        closure
+            .replacement_files()
+            .unwrap()
+            .into_iter()
+            .filter(|(_, path, _)| path != &PathBuf::from(META_FILENAME))
+            .map(|(_, path, content)| (path, serde_yaml::from_slice(&content.unwrap()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn full_closure() {
+        let root = ExternalId::root_topic().to_string();
+        let maps = replacement(&[
+            (&root, &[]),
+            ("00001", &[&root]),
+            ("00002", &["00001"]),
+            ("00003", &["00001", "00002"]),
+            ("00004", &[]),
+        ]);
+
+        let closure = |s: &str| maps.get(&id(s).closure_filename().unwrap());
+        assert_eq!(closure("00001").unwrap().ancestors, ids(&[&root]));
+        assert_eq!(
+            closure("00003").unwrap().ancestors,
+            ids(&[&root, "00001", "00002"])
+        );
+        // Objects with nothing above them have no entry
+        assert!(closure(&root).is_none());
+        assert!(closure("00004").is_none());
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let maps = replacement(&[("00001", &["00002"]), ("00002", &["00001"])]);
+        let closure = maps.get(&id("00001").closure_filename().unwrap()).unwrap();
+        assert!(closure.ancestors.contains(&id("00002")));
+    }
+}
@@ -1,15 +1,19 @@
 use git2;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use crate::prelude::*;
 use crate::types::Timespec;
 
+mod pool;
+pub use pool::{Head, PooledRepo, RepoPool, RepoPoolMetrics};
+
 use super::{
     activity::{self},
-    DataRoot, GitPaths, RepoLink, RepoObject, RepoStats, RepoTopic,
+    DataRoot, GitPaths, ObjectCache, RepoLink, RepoObject, RepoStats, RepoTopic,
 };
 
 pub fn deque_from_path(path: &Path) -> VecDeque<String> {
@@ -24,6 +28,13 @@ pub fn deque_from_path(path: &Path) -> VecDeque<String> {
     deque
 }
 
+fn has_subsequence(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
 pub struct Repo {
     path: PathBuf,
     pub inner: git2::Repository,
@@ -54,6 +65,7 @@ impl Repo {
     pub fn delete(root: &DataRoot, repo_id: RepoId) -> Result<()> {
         let path = root.repo_path(repo_id);
         log::warn!("deleting repo {} at {:?}", repo_id, path);
+        root.pool.evict(&path);
 
         match std::fs::remove_dir_all(&path) {
             Ok(()) => log::warn!("deleted {:?}", path),
@@ -174,15 +186,47 @@ impl<'repo> TryInto<RepoTopic> for git2::Blob<'repo> {
 
 #[derive(Debug)]
 pub struct View {
-    pub repo: Repo,
+    pub repo: PooledRepo,
     pub commit: git2::Oid,
+    cache: Option<Arc<ObjectCache>>,
+    // The root tree of the commit, resolved once for all of the lookups made through the view
+    tree: git2::Oid,
 }
 
 impl View {
-    pub fn ensure(root: &DataRoot, repo_id: RepoId, timespec: &Timespec) -> Result<Self> {
-        let repo = Repo::ensure(root, repo_id)?;
-        let commit = repo.commit_oid(timespec)?;
-        Ok(Self { repo, commit })
+    // Reads from HEAD, with a repo taken from the pool
+    pub fn ensure(root: &DataRoot, repo_id: RepoId, _timespec: &Timespec) -> Result<Self> {
+        let repo = root.checkout(repo_id)?;
+        let Head { commit, tree } = root.pool.head(&repo)?;
+        Ok(Self {
+            repo,
+            commit,
+            cache: None,
+            tree,
+        })
+    }
+
+    // A view of the repo as of an earlier commit, given as anything git can resolve to one
+    pub fn at(root: &DataRoot, repo_id: RepoId, rev: &str) -> Result<Self> {
+        let repo = root.checkout(repo_id)?;
+        let (commit, tree) = {
+            let commit = repo.inner.revparse_single(rev)?.peel_to_commit()?;
+            (commit.id(), commit.tree_id())
+        };
+        Ok(Self {
+            repo,
+            commit,
+            cache: None,
+            tree,
+        })
+    }
+
+    // Objects and indexes read through the view are kept in the cache provided
+    pub fn with_cache(self, cache: Arc<ObjectCache>) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
     }
 
     pub fn blob_exists(&self, filename: &Path) -> Result<bool> {
@@ -200,24 +244,69 @@ impl View {
 
     pub fn duplicate(&self) -> Result<Self> {
         Ok(Self {
-            repo: self.repo.duplicate()?,
+            repo: self.repo.duplicate()?.into(),
             commit: self.commit,
+            cache: self.cache.clone(),
+            tree: self.tree,
         })
     }
 
-    pub(crate) fn find_blob_by_filename(&self, filename: &Path) -> Result<Option<git2::Blob>> {
-        let mut path = deque_from_path(filename);
-        let tree = self.repo.commit(self.commit)?.tree()?;
+    fn tree(&self) -> Result<git2::Tree<'_>> {
+        Ok(self.repo.inner.find_tree(self.tree)?)
+    }
+
+    // The paths of the blobs found directly under the directory provided
+    pub fn filenames(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut path = deque_from_path(dir);
+        let tree = self.tree()?;
 
-        if let Some(oid) = self.repo.path_to_oid(tree, &mut path) {
+        let oid = match self.repo.path_to_oid(tree, &mut path) {
+            Some(oid) => oid,
+            None => return Ok(vec![]),
+        };
+
+        let subtree = self.repo.inner.find_tree(oid)?;
+        let filenames = subtree
+            .iter()
+            .filter(|entry| entry.kind() == Some(git2::ObjectType::Blob))
+            .filter_map(|entry| entry.name().map(|name| dir.join(name)))
+            .collect();
+
+        Ok(filenames)
+    }
+
+    pub(crate) fn blob_oid_by_filename(&self, filename: &Path) -> Result<Option<git2::Oid>> {
+        let mut path = deque_from_path(filename);
+        let tree = self.tree()?;
+        Ok(self.repo.path_to_oid(tree, &mut path))
+    }
+
+    // Parses the blob with the function provided, or returns the value parsed from it earlier if
+    // the view has a cache
+    pub(crate) fn cached<T, F>(&self, oid: git2::Oid, parse: F) -> Result<Arc<T>>
+    where
+        T: std::any::Any + Send + Sync,
+        F: FnOnce(git2::Blob) -> Result<T>,
+    {
+        let load = || {
             let blob = self.repo.inner.find_blob(oid)?;
-            return Ok(Some(blob));
+            let size = blob.size();
+            Ok((parse(blob)?, size))
+        };
+        match &self.cache {
+            Some(cache) => cache.get_or_load(oid, load),
+            None => Ok(Arc::new(load()?.0)),
         }
+    }
 
-        Ok(None)
+    pub(crate) fn find_blob_by_filename(&self, filename: &Path) -> Result<Option<git2::Blob<'_>>> {
+        match self.blob_oid_by_filename(filename)? {
+            Some(oid) => Ok(Some(self.repo.inner.find_blob(oid)?)),
+            None => Ok(None),
+        }
     }
 
-    fn find_blob(&self, id: &ExternalId) -> Result<Option<git2::Blob>> {
+    fn find_blob(&self, id: &ExternalId) -> Result<Option<git2::Blob<'_>>> {
         self.find_blob_by_filename(&id.object_filename()?)
     }
 
@@ -229,12 +318,127 @@ impl View {
         Ok(link)
     }
 
+    pub fn links(&self) -> Result<Vec<RepoLink>> {
+        self.objects_of_kind(b"kind: RepoLink\n")
+    }
+
+    // Calls the closure provided with each change in the repo, in no particular order, and
+    // returns the paths of any changes that could not be parsed
+    pub fn each_change<F>(&self, f: F) -> Result<Vec<String>>
+    where
+        F: FnMut(activity::Change) -> Result<()>,
+    {
+        self.each_blob("change.yaml", b"", f)
+    }
+
+    // Calls the closure provided with each topic and link in the repo, in no particular order, and
+    // returns the paths of any objects that could not be parsed
+    pub fn each_object<F>(&self, f: F) -> Result<Vec<String>>
+    where
+        F: FnMut(RepoObject) -> Result<()>,
+    {
+        self.each_blob("object.yaml", b"", f)
+    }
+
+    // Walks the whole tree, passing the blobs with the filename provided that contain the marker
+    // to the closure.  The walk stops at the first error from the closure.  Blobs that cannot be
+    // parsed are skipped, and their paths returned.
+    fn each_blob<'a, T, F>(&'a self, filename: &str, marker: &[u8], mut f: F) -> Result<Vec<String>>
+    where
+        git2::Blob<'a>: TryInto<T, Error = Error>,
+        F: FnMut(T) -> Result<()>,
+    {
+        let tree = self.tree()?;
+        let mut result = Ok(());
+        let mut skipped = vec![];
+
+        let walk = tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.name() != Some(filename) {
+                return git2::TreeWalkResult::Ok;
+            }
+
+            match self.repo.inner.find_blob(entry.id()) {
+                Ok(blob) => {
+                    if has_subsequence(blob.content(), marker) {
+                        match blob.try_into() {
+                            Ok(object) => {
+                                if let Err(err) = f(object) {
+                                    result = Err(err);
+                                    return git2::TreeWalkResult::Abort;
+                                }
+                            }
+                            Err(err) => {
+                                let path = format!("{root}{filename}");
+                                log::error!("failed to parse {}: {}", path, err);
+                                skipped.push(path);
+                            }
+                        }
+                    }
+                }
+
+                Err(err) => {
+                    log::error!("failed to find blob: {}", err);
+                }
+            }
+
+            git2::TreeWalkResult::Ok
+        });
+
+        // An error from the closure takes precedence over the error from the aborted walk
+        result?;
+        walk?;
+        Ok(skipped)
+    }
+
+    fn objects_of_kind<'a, T>(&'a self, marker: &[u8]) -> Result<Vec<T>>
+    where
+        git2::Blob<'a>: TryInto<T, Error = Error>,
+    {
+        let mut objects = vec![];
+        self.each_blob("object.yaml", marker, |object| {
+            objects.push(object);
+            Ok(())
+        })?;
+        Ok(objects)
+    }
+
     pub fn object(&self, path: &ExternalId) -> Result<Option<RepoObject>> {
-        let object = match self.find_blob(path)? {
-            Some(blob) => Some(blob.try_into()?),
-            None => None,
-        };
-        Ok(object)
+        match self.blob_oid_by_filename(&path.object_filename()?)? {
+            Some(oid) => Ok(Some(Arc::unwrap_or_clone(self.object_by_oid(oid)?))),
+            None => Ok(None),
+        }
+    }
+
+    // The blob ids of the files in the repo whose paths are accepted by the filter provided, by
+    // path
+    pub fn blob_oids<F>(&self, filter: F) -> Result<BTreeMap<String, git2::Oid>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let tree = self.tree()?;
+        let mut oids = BTreeMap::new();
+
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                let path = format!("{}{}", root, entry.name().unwrap_or_default());
+                if filter(&path) {
+                    oids.insert(path, entry.id());
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        Ok(oids)
+    }
+
+    // The blob ids of every topic and link in the repo, by path.  Since blob ids are hashes of the
+    // contents, two views can be compared without reading the objects that did not change.
+    pub fn object_oids(&self) -> Result<BTreeMap<String, git2::Oid>> {
+        self.blob_oids(|path| path.ends_with("/object.yaml"))
+    }
+
+    pub fn object_by_oid(&self, oid: git2::Oid) -> Result<Arc<RepoObject>> {
+        self.cached(oid, |blob| blob.try_into())
     }
 
     pub fn object_exists(&self, id: &ExternalId) -> Result<bool> {
@@ -244,17 +448,11 @@ impl View {
 
     pub fn stats(&self) -> Result<RepoStats> {
         log::info!("computing stats for view {:?}", self);
-        let tree = self.repo.commit(self.commit)?.tree()?;
+        let tree = self.tree()?;
 
         let mut topic_count = 0;
         let mut link_count = 0;
 
-        fn has_subsequence(haystack: &[u8], needle: &[u8]) -> bool {
-            haystack
-                .windows(needle.len())
-                .any(|window| window == needle)
-        }
-
         tree.walk(git2::TreeWalkMode::PreOrder, |_root, entry| {
             if let Some(name) = entry.name() {
                 if name != "object.yaml" {
@@ -289,6 +487,10 @@ impl View {
         })
     }
 
+    pub fn topics(&self) -> Result<Vec<RepoTopic>> {
+        self.objects_of_kind(b"kind: RepoTopic\n")
+    }
+
     pub fn topic(&self, id: &ExternalId) -> Result<Option<RepoTopic>> {
         let topic = match self.find_blob(id)? {
             Some(blob) => Some(blob.try_into()?),
@@ -372,7 +574,7 @@ impl Update {
     // Writes should only target HEAD
     pub fn write(&self, root: &DataRoot, sig: &git2::Signature, message: &str) -> Result<()> {
         for (repo_id, tree) in &self.0 {
-            let repo = Repo::ensure(root, *repo_id)?;
+            let repo = root.checkout(*repo_id)?;
             let head = repo.inner.find_reference("HEAD")?;
             let before = head.peel_to_tree()?;
             let oid = tree.write(&repo.inner, Some(before))?;
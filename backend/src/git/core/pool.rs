@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::Repo;
+use crate::prelude::*;
+
+// Beyond this, a handle that is given back is closed rather than kept
+const MAX_IDLE_PER_REPO: usize = 8;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Head {
+    pub commit: git2::Oid,
+    pub tree: git2::Oid,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoPoolMetrics {
+    pub head_moves: u64,
+    pub idle: usize,
+    pub opened: u64,
+    pub reused: u64,
+}
+
+#[derive(Default)]
+struct Repos {
+    // Bumped each time a repo is evicted, so that handles checked out before then are closed when
+    // they come back instead of being kept for a repo that has been deleted or replaced
+    generations: HashMap<PathBuf, u64>,
+    heads: HashMap<PathBuf, Head>,
+    idle: HashMap<PathBuf, Vec<Repo>>,
+}
+
+// Open repos that are handed out to one view at a time and then kept for the next one, so that a
+// request does not pay to open the same repo for each object it fetches.  A git2 repo cannot be
+// shared between threads, so a view that needs a repo while all of the idle ones are in use opens
+// another.  The commit and tree that HEAD resolves to are remembered for each repo, and are looked
+// up again only when HEAD moves.
+#[derive(Default)]
+pub struct RepoPool {
+    head_moves: AtomicU64,
+    opened: AtomicU64,
+    repos: Mutex<Repos>,
+    reused: AtomicU64,
+}
+
+impl std::fmt::Debug for RepoPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RepoPool")
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+impl RepoPool {
+    pub fn checkout(self: &Arc<Self>, path: &Path) -> Result<PooledRepo> {
+        let (generation, idle) = {
+            let mut repos = self.lock();
+            let generation = repos.generations.get(path).copied().unwrap_or_default();
+            (generation, repos.idle.get_mut(path).and_then(Vec::pop))
+        };
+
+        let repo = match idle {
+            Some(repo) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                repo
+            }
+            None => {
+                self.opened.fetch_add(1, Ordering::Relaxed);
+                Repo::open(path.to_owned())?
+            }
+        };
+
+        Ok(PooledRepo {
+            generation,
+            pool: Some(Arc::clone(self)),
+            repo: Some(repo),
+        })
+    }
+
+    // Closes the idle handles of a repo that is being removed and forgets its HEAD.  Handles that
+    // are checked out are closed when they are given back.
+    pub fn evict(&self, path: &Path) {
+        let mut repos = self.lock();
+        *repos.generations.entry(path.to_owned()).or_default() += 1;
+        repos.idle.remove(path);
+        repos.heads.remove(path);
+    }
+
+    pub fn head(&self, repo: &Repo) -> Result<Head> {
+        let commit = repo.inner.refname_to_id("HEAD")?;
+        if let Some(head) = self.lock().heads.get(&repo.path) {
+            if head.commit == commit {
+                return Ok(*head);
+            }
+        }
+
+        let tree = repo.commit(commit)?.tree_id();
+        let head = Head { commit, tree };
+        if let Some(before) = self.lock().heads.insert(repo.path.to_owned(), head) {
+            log::debug!(
+                "HEAD of {:?} moved from {} to {}",
+                repo.path,
+                before.commit,
+                commit
+            );
+            self.head_moves.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(head)
+    }
+
+    pub fn metrics(&self) -> RepoPoolMetrics {
+        RepoPoolMetrics {
+            head_moves: self.head_moves.load(Ordering::Relaxed),
+            idle: self.lock().idle.values().map(Vec::len).sum(),
+            opened: self.opened.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+        }
+    }
+
+    fn checkin(&self, repo: Repo, generation: u64) {
+        let mut repos = self.lock();
+        if repos
+            .generations
+            .get(&repo.path)
+            .copied()
+            .unwrap_or_default()
+            != generation
+        {
+            log::debug!("closing handle to evicted repo {:?}", repo.path);
+            return;
+        }
+        let idle = repos.idle.entry(repo.path.to_owned()).or_default();
+        if idle.len() < MAX_IDLE_PER_REPO {
+            idle.push(repo);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Repos> {
+        self.repos
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// A repo that goes back to the pool it came from when it is dropped
+pub struct PooledRepo {
+    generation: u64,
+    pool: Option<Arc<RepoPool>>,
+    repo: Option<Repo>,
+}
+
+impl std::fmt::Debug for PooledRepo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.repo.fmt(f)
+    }
+}
+
+impl From<Repo> for PooledRepo {
+    fn from(repo: Repo) -> Self {
+        Self {
+            generation: 0,
+            pool: None,
+            repo: Some(repo),
+        }
+    }
+}
+
+impl Deref for PooledRepo {
+    type Target = Repo;
+
+    fn deref(&self) -> &Repo {
+        self.repo.as_ref().expect("repo has already been returned")
+    }
+}
+
+impl Drop for PooledRepo {
+    fn drop(&mut self) {
+        if let (Some(pool), Some(repo)) = (self.pool.take(), self.repo.take()) {
+            pool.checkin(repo, self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Repo, message: &str) -> git2::Oid {
+        let sig = git2::Signature::now("digraph-bot", "noreply@digraph.app").unwrap();
+        let blob = repo.add_blob(message.as_bytes()).unwrap();
+        let mut builder = repo.inner.treebuilder(None).unwrap();
+        builder.insert("file", blob, 0o100644).unwrap();
+        let tree = repo.inner.find_tree(builder.write().unwrap()).unwrap();
+        let parent = repo.inner.head().unwrap().peel_to_commit().unwrap();
+        repo.inner
+            .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+            .unwrap()
+    }
+
+    #[test]
+    fn handles_are_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repo");
+        let pool = Arc::new(RepoPool::default());
+
+        let first = pool.checkout(&path).unwrap();
+        let second = pool.checkout(&path).unwrap();
+        assert_eq!(pool.metrics().opened, 2);
+
+        drop(first);
+        drop(second);
+        assert_eq!(pool.metrics().idle, 2);
+
+        let _third = pool.checkout(&path).unwrap();
+        let metrics = pool.metrics();
+        assert_eq!(metrics.opened, 2);
+        assert_eq!(metrics.reused, 1);
+        assert_eq!(metrics.idle, 1);
+
+        pool.evict(&path);
+        assert_eq!(pool.metrics().idle, 0);
+    }
+
+    #[test]
+    fn handles_checked_out_during_eviction_are_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repo");
+        let pool = Arc::new(RepoPool::default());
+
+        let before = pool.checkout(&path).unwrap();
+        pool.evict(&path);
+        let after = pool.checkout(&path).unwrap();
+
+        drop(before);
+        assert_eq!(pool.metrics().idle, 0);
+
+        drop(after);
+        assert_eq!(pool.metrics().idle, 1);
+    }
+
+    #[test]
+    fn head_is_refreshed_when_it_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repo");
+        let pool = Arc::new(RepoPool::default());
+
+        let repo = pool.checkout(&path).unwrap();
+        let before = pool.head(&repo).unwrap();
+        assert_eq!(pool.head(&repo).unwrap(), before);
+
+        // A commit made through another handle
+        let other = Repo::open(path.to_owned()).unwrap();
+        let oid = commit(&other, "second");
+
+        let after = pool.head(&repo).unwrap();
+        assert_eq!(after.commit, oid);
+        assert_ne!(after.tree, before.tree);
+        assert_eq!(pool.metrics().head_moves, 1);
+    }
+}
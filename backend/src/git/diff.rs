@@ -0,0 +1,373 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use super::{core, Client, Kind, RepoLink, RepoObject, RepoTopic};
+use crate::prelude::*;
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase", tag = "change")]
+pub enum DiffEntry {
+    #[serde(rename_all = "camelCase")]
+    ObjectAdded {
+        id: ExternalId,
+        kind: Kind,
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ObjectRemoved {
+        id: ExternalId,
+        kind: Kind,
+        name: String,
+    },
+    // The fields are the ones that changed, other than synonyms and parent topics, which have
+    // their own entries
+    #[serde(rename_all = "camelCase")]
+    ObjectChanged {
+        id: ExternalId,
+        kind: Kind,
+        name: String,
+        fields: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    ParentTopicAdded {
+        id: ExternalId,
+        kind: Kind,
+        parent_topic_id: ExternalId,
+    },
+    #[serde(rename_all = "camelCase")]
+    ParentTopicRemoved {
+        id: ExternalId,
+        kind: Kind,
+        parent_topic_id: ExternalId,
+    },
+    #[serde(rename_all = "camelCase")]
+    SynonymAdded {
+        id: ExternalId,
+        locale: Locale,
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SynonymRemoved {
+        id: ExternalId,
+        locale: Locale,
+        name: String,
+    },
+}
+
+impl DiffEntry {
+    pub fn id(&self) -> &ExternalId {
+        match self {
+            Self::ObjectAdded { id, .. }
+            | Self::ObjectRemoved { id, .. }
+            | Self::ObjectChanged { id, .. }
+            | Self::ParentTopicAdded { id, .. }
+            | Self::ParentTopicRemoved { id, .. }
+            | Self::SynonymAdded { id, .. }
+            | Self::SynonymRemoved { id, .. } => id,
+        }
+    }
+}
+
+impl std::fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ObjectAdded { id, kind, name } => {
+                write!(f, "+ {} {} {:?}", kind, id, name)
+            }
+            Self::ObjectRemoved { id, kind, name } => {
+                write!(f, "- {} {} {:?}", kind, id, name)
+            }
+            Self::ObjectChanged {
+                id,
+                kind,
+                name,
+                fields,
+            } => write!(f, "~ {} {} {:?} ({})", kind, id, name, fields.join(", ")),
+            Self::ParentTopicAdded {
+                id,
+                kind,
+                parent_topic_id,
+            } => write!(f, "+ parent {} {} -> {}", kind, id, parent_topic_id),
+            Self::ParentTopicRemoved {
+                id,
+                kind,
+                parent_topic_id,
+            } => write!(f, "- parent {} {} -> {}", kind, id, parent_topic_id),
+            Self::SynonymAdded { id, locale, name } => {
+                write!(f, "+ synonym topic {id} {locale} {name:?}")
+            }
+            Self::SynonymRemoved { id, locale, name } => {
+                write!(f, "- synonym topic {id} {locale} {name:?}")
+            }
+        }
+    }
+}
+
+fn name(object: &RepoObject, locale: Locale) -> String {
+    match object {
+        RepoObject::Topic(topic) => topic.name(locale),
+        RepoObject::Link(link) => link.title().to_owned(),
+    }
+}
+
+fn parent_edges(
+    entries: &mut Vec<DiffEntry>,
+    base: Option<&RepoObject>,
+    head: Option<&RepoObject>,
+) {
+    let parents = |object: Option<&RepoObject>| -> BTreeSet<ExternalId> {
+        object
+            .map(|object| {
+                object
+                    .parent_topics()
+                    .iter()
+                    .map(|p| p.id.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (before, after) = (parents(base), parents(head));
+    let object = head.or(base).expect("an object on one side or the other");
+    let (id, kind) = (object.id(), object.kind());
+
+    for parent_topic_id in after.difference(&before) {
+        entries.push(DiffEntry::ParentTopicAdded {
+            id: id.to_owned(),
+            kind,
+            parent_topic_id: parent_topic_id.to_owned(),
+        });
+    }
+
+    for parent_topic_id in before.difference(&after) {
+        entries.push(DiffEntry::ParentTopicRemoved {
+            id: id.to_owned(),
+            kind,
+            parent_topic_id: parent_topic_id.to_owned(),
+        });
+    }
+}
+
+fn topic_changes(entries: &mut Vec<DiffEntry>, base: &RepoTopic, head: &RepoTopic) -> Vec<String> {
+    let synonyms = |topic: &RepoTopic| -> BTreeSet<(Locale, String)> {
+        topic
+            .synonyms()
+            .iter()
+            .map(|synonym| (synonym.locale, synonym.name.to_owned()))
+            .collect()
+    };
+    let (before, after) = (synonyms(base), synonyms(head));
+    let id = head.topic_id();
+
+    for (locale, name) in after.difference(&before) {
+        entries.push(DiffEntry::SynonymAdded {
+            id: id.to_owned(),
+            locale: *locale,
+            name: name.to_owned(),
+        });
+    }
+
+    for (locale, name) in before.difference(&after) {
+        entries.push(DiffEntry::SynonymRemoved {
+            id: id.to_owned(),
+            locale: *locale,
+            name: name.to_owned(),
+        });
+    }
+
+    let mut fields = vec![];
+    if base.has_details() != head.has_details() {
+        fields.push("details".to_owned());
+    }
+    if base.timerange() != head.timerange() {
+        fields.push("timerange".to_owned());
+    }
+    fields
+}
+
+fn link_changes(base: &RepoLink, head: &RepoLink) -> Vec<String> {
+    let mut fields = vec![];
+    if base.has_details() != head.has_details() {
+        fields.push("details".to_owned());
+    }
+    if base.title() != head.title() {
+        fields.push("title".to_owned());
+    }
+    if base.url() != head.url() {
+        fields.push("url".to_owned());
+    }
+    fields
+}
+
+// Compares the topics and links in two views, which can be of different repos or of different
+// commits of the same repo.  Children are left out, since they mirror the parent topics of other
+// objects.
+pub fn compare(base: &core::View, head: &core::View, locale: Locale) -> Result<Vec<DiffEntry>> {
+    let base_oids = base.object_oids()?;
+    let head_oids = head.object_oids()?;
+    let paths = base_oids
+        .keys()
+        .chain(head_oids.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut entries = vec![];
+    for path in paths {
+        let (before, after) = match (base_oids.get(path), head_oids.get(path)) {
+            (Some(before), Some(after)) if before == after => continue,
+            (before, after) => (
+                before.map(|oid| base.object_by_oid(*oid)).transpose()?,
+                after.map(|oid| head.object_by_oid(*oid)).transpose()?,
+            ),
+        };
+
+        match (before.as_deref(), after.as_deref()) {
+            (None, Some(after)) => {
+                entries.push(DiffEntry::ObjectAdded {
+                    id: after.id().to_owned(),
+                    kind: after.kind(),
+                    name: name(after, locale),
+                });
+                parent_edges(&mut entries, None, Some(after));
+            }
+
+            (Some(before), None) => {
+                entries.push(DiffEntry::ObjectRemoved {
+                    id: before.id().to_owned(),
+                    kind: before.kind(),
+                    name: name(before, locale),
+                });
+                parent_edges(&mut entries, Some(before), None);
+            }
+
+            (Some(before), Some(after)) => {
+                let fields = match (before, after) {
+                    (RepoObject::Topic(before), RepoObject::Topic(after)) => {
+                        topic_changes(&mut entries, before, after)
+                    }
+                    (RepoObject::Link(before), RepoObject::Link(after)) => {
+                        link_changes(before, after)
+                    }
+                    _ => vec!["kind".to_owned()],
+                };
+
+                if !fields.is_empty() {
+                    entries.push(DiffEntry::ObjectChanged {
+                        id: after.id().to_owned(),
+                        kind: after.kind(),
+                        name: name(after, locale),
+                        fields,
+                    });
+                }
+                parent_edges(&mut entries, Some(before), Some(after));
+            }
+
+            (None, None) => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+// One side of a diff: a repo, optionally at an earlier commit
+#[derive(Clone, Debug)]
+pub struct DiffSide {
+    pub repo_id: RepoId,
+    pub rev: Option<String>,
+}
+
+impl DiffSide {
+    fn view(&self, client: &Client) -> Result<core::View> {
+        if !client.viewer.can_read(self.repo_id) {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        match &self.rev {
+            Some(rev) => core::View::at(&client.root, self.repo_id, rev),
+            None => client.view(self.repo_id),
+        }
+    }
+}
+
+pub struct Diff {
+    pub base: DiffSide,
+    pub head: DiffSide,
+    pub locale: Locale,
+}
+
+#[derive(Debug)]
+pub struct DiffResult {
+    pub base_commit: String,
+    pub entries: Vec<DiffEntry>,
+    pub head_commit: String,
+}
+
+impl Diff {
+    pub fn call(&self, client: &Client) -> Result<DiffResult> {
+        let base = self.base.view(client)?;
+        let head = self.head.view(client)?;
+
+        Ok(DiffResult {
+            base_commit: base.commit.to_string(),
+            entries: compare(&base, &head, self.locale)?,
+            head_commit: head.commit.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let id = ExternalId::try_from("00001").unwrap();
+        let parent_topic_id = ExternalId::try_from("00002").unwrap();
+
+        let entries = [
+            DiffEntry::ObjectAdded {
+                id: id.to_owned(),
+                kind: Kind::Topic,
+                name: "Tide pools".into(),
+            },
+            DiffEntry::ObjectChanged {
+                id: id.to_owned(),
+                kind: Kind::Link,
+                name: "A page".into(),
+                fields: vec!["title".into(), "url".into()],
+            },
+            DiffEntry::ParentTopicRemoved {
+                id: id.to_owned(),
+                kind: Kind::Link,
+                parent_topic_id,
+            },
+            DiffEntry::SynonymAdded {
+                id,
+                locale: Locale::EN,
+                name: "Rock pools".into(),
+            },
+        ];
+
+        let lines = entries.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "+ topic 00001 \"Tide pools\"",
+                "~ link 00001 \"A page\" (title, url)",
+                "- parent link 00001 -> 00002",
+                "+ synonym topic 00001 en \"Rock pools\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn json() {
+        let entry = DiffEntry::ParentTopicAdded {
+            id: ExternalId::try_from("00001").unwrap(),
+            kind: Kind::Topic,
+            parent_topic_id: ExternalId::try_from("00002").unwrap(),
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"change":"parentTopicAdded","id":"00001","kind":"Topic","parentTopicId":"00002"}"#
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+use super::{activity, Client, IndexMode, RepoObject, SaveChangesForPrefix};
+use crate::prelude::*;
+
+// Bump this when a change to the records below would prevent an older dump from being restored
+pub const DUMP_VERSION: u32 = 1;
+
+// A dump is a JSON Lines file that starts with a header, followed by one line per topic or link
+// and then one line per change
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum DumpRecord {
+    #[serde(rename_all = "camelCase")]
+    Header {
+        dumped_at: Timestamp,
+        repo_id: RepoId,
+        version: u32,
+    },
+    Object {
+        object: RepoObject,
+    },
+    Change {
+        change: activity::Change,
+    },
+}
+
+fn write_record<W>(writer: &mut W, record: &DumpRecord) -> Result<()>
+where
+    W: Write,
+{
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+pub struct DumpRepo {
+    // Write out what can be read when some of the objects or changes cannot be parsed, rather
+    // than failing
+    pub allow_skipped: bool,
+    pub repo_id: RepoId,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct DumpRepoResult {
+    pub changes: usize,
+    pub objects: usize,
+    // The paths of objects and changes that could not be parsed and were left out
+    pub skipped: Vec<String>,
+}
+
+impl DumpRepo {
+    pub fn call<W>(&self, client: &Client, writer: &mut W) -> Result<DumpRepoResult>
+    where
+        W: Write,
+    {
+        if !client.viewer.can_read(self.repo_id) {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        let view = client.view(self.repo_id)?;
+        let mut result = DumpRepoResult::default();
+
+        write_record(
+            writer,
+            &DumpRecord::Header {
+                dumped_at: chrono::Utc::now(),
+                repo_id: self.repo_id,
+                version: DUMP_VERSION,
+            },
+        )?;
+
+        let skipped = view.each_object(|object| {
+            result.objects += 1;
+            write_record(writer, &DumpRecord::Object { object })
+        })?;
+        result.skipped.extend(skipped);
+
+        let skipped = view.each_change(|change| {
+            result.changes += 1;
+            write_record(writer, &DumpRecord::Change { change })
+        })?;
+        result.skipped.extend(skipped);
+
+        writer.flush()?;
+
+        // A dump that quietly leaves things out cannot be relied on to restore the repo
+        if !self.allow_skipped && !result.skipped.is_empty() {
+            result.skipped.sort();
+            return Err(Error::Repo(format!(
+                "{} files in {} cannot be parsed and were left out of the dump: {}",
+                result.skipped.len(),
+                self.repo_id,
+                result.skipped.join(", ")
+            )));
+        }
+
+        Ok(result)
+    }
+}
+
+// Restores a dump into an empty repo, which need not be the repo that the dump was taken from.
+// The search, synonym and activity indexes are rebuilt from the restored objects rather than
+// copied over.
+pub struct RestoreRepo {
+    pub repo_id: RepoId,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct RestoreRepoResult {
+    pub changes: usize,
+    pub objects: usize,
+    pub source_repo_id: RepoId,
+}
+
+impl RestoreRepo {
+    pub fn call<R, S>(&self, client: &Client, reader: R, store: &S) -> Result<RestoreRepoResult>
+    where
+        R: BufRead,
+        S: SaveChangesForPrefix,
+    {
+        let stats = client.view(self.repo_id)?.stats()?;
+        if stats.topic_count.unwrap_or_default() > 0 || stats.link_count.unwrap_or_default() > 0 {
+            return Err(Error::Repo(format!(
+                "repo must be empty before restoring into it: {}",
+                self.repo_id
+            )));
+        }
+
+        let mut mutation = client.mutation(IndexMode::Replace)?;
+        let mut source_repo_id = None;
+        let mut changes = 0;
+        let mut objects = 0;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: DumpRecord = serde_json::from_str(&line)
+                .map_err(|err| Error::Repo(format!("line {}: {}", i + 1, err)))?;
+
+            match (record, source_repo_id) {
+                (
+                    DumpRecord::Header {
+                        repo_id, version, ..
+                    },
+                    None,
+                ) => {
+                    if version != DUMP_VERSION {
+                        return Err(Error::Repo(format!("unsupported dump version: {version}")));
+                    }
+                    source_repo_id = Some(repo_id);
+                }
+
+                (DumpRecord::Header { .. }, Some(_)) => {
+                    return Err(Error::Repo(format!("line {}: unexpected header", i + 1)));
+                }
+
+                (_, None) => {
+                    return Err(Error::Repo("expected a dump header".into()));
+                }
+
+                (DumpRecord::Object { object }, Some(_)) => {
+                    match &object {
+                        RepoObject::Topic(topic) => mutation.save_topic(self.repo_id, topic)?,
+                        RepoObject::Link(link) => mutation.save_link(self.repo_id, link)?,
+                    }
+                    objects += 1;
+                }
+
+                (DumpRecord::Change { change }, Some(_)) => {
+                    mutation.save_change(self.repo_id, &change)?;
+                    changes += 1;
+                }
+            }
+        }
+
+        let source_repo_id =
+            source_repo_id.ok_or_else(|| Error::Repo("expected a dump header".into()))?;
+
+        mutation.write(store)?;
+        log::info!(
+            "restored {} objects and {} changes from {} into {}",
+            objects,
+            changes,
+            source_repo_id,
+            self.repo_id
+        );
+
+        Ok(RestoreRepoResult {
+            changes,
+            objects,
+            source_repo_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_format() {
+        let record = DumpRecord::Header {
+            dumped_at: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            repo_id: RepoId::wiki(),
+            version: DUMP_VERSION,
+        };
+
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            line,
+            format!(
+                r#"{{"type":"header","dumpedAt":"1970-01-01T00:00:00Z","repoId":"{}","version":1}}"#,
+                RepoId::wiki()
+            )
+        );
+
+        let record: DumpRecord = serde_json::from_str(&line).unwrap();
+        assert!(matches!(record, DumpRecord::Header { version: 1, .. }));
+    }
+}
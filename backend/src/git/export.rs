@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use strum_macros::EnumString;
+use unidecode::unidecode;
+
+use super::{Client, RepoLink, RepoObject, RepoTopic, Visitor};
+use crate::prelude::*;
+
+#[derive(Copy, Clone, Debug, EnumString, Eq, PartialEq, strum_macros::Display)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Netscape,
+    Opml,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Netscape => "text/html; charset=utf-8",
+            Self::Opml => "text/x-opml; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Netscape => "html",
+            Self::Opml => "opml",
+        }
+    }
+
+    fn writer(&self, locale: Locale) -> Box<dyn OutlineFormat> {
+        match self {
+            Self::Markdown => Box::new(Markdown::new(locale)),
+            Self::Netscape => Box::new(Netscape::new(locale)),
+            Self::Opml => Box::new(Opml::new(locale)),
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Each format receives the topics and links of the outline in document order
+trait OutlineFormat {
+    fn start_topic(&mut self, topic: &RepoTopic, depth: usize);
+
+    fn end_topic(&mut self, topic: &RepoTopic, depth: usize);
+
+    fn link(&mut self, link: &RepoLink, depth: usize);
+
+    fn finish(&mut self) -> String;
+}
+
+struct Markdown {
+    buf: String,
+    locale: Locale,
+}
+
+impl Markdown {
+    fn new(locale: Locale) -> Self {
+        Self {
+            buf: String::new(),
+            locale,
+        }
+    }
+
+    fn escape(value: &str) -> String {
+        value.replace('[', "\\[").replace(']', "\\]")
+    }
+}
+
+impl OutlineFormat for Markdown {
+    fn start_topic(&mut self, topic: &RepoTopic, depth: usize) {
+        let name = Self::escape(&topic.name(self.locale));
+        if depth == 0 {
+            self.buf.push_str(&format!("# {name}\n\n"));
+        } else {
+            let indent = "  ".repeat(depth - 1);
+            self.buf.push_str(&format!("{indent}- **{name}**\n"));
+        }
+    }
+
+    fn end_topic(&mut self, _topic: &RepoTopic, _depth: usize) {}
+
+    fn link(&mut self, link: &RepoLink, depth: usize) {
+        let indent = "  ".repeat(depth.saturating_sub(1));
+        self.buf.push_str(&format!(
+            "{}- [{}](<{}>)\n",
+            indent,
+            Self::escape(link.title()),
+            link.url()
+        ));
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+// The format that browsers use for exported bookmarks, which can be imported with
+// `parse_netscape_bookmarks`
+struct Netscape {
+    buf: String,
+    locale: Locale,
+}
+
+impl Netscape {
+    fn new(locale: Locale) -> Self {
+        let buf = "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+            <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+            <TITLE>Bookmarks</TITLE>\n\
+            <H1>Bookmarks</H1>\n\
+            <DL><p>\n"
+            .to_owned();
+        Self { buf, locale }
+    }
+}
+
+impl OutlineFormat for Netscape {
+    fn start_topic(&mut self, topic: &RepoTopic, depth: usize) {
+        let indent = "    ".repeat(depth + 1);
+        self.buf.push_str(&format!(
+            "{indent}<DT><H3 ADD_DATE=\"{}\">{}</H3>\n{indent}<DL><p>\n",
+            topic.metadata.added.timestamp(),
+            escape(&topic.name(self.locale)),
+        ));
+    }
+
+    fn end_topic(&mut self, _topic: &RepoTopic, depth: usize) {
+        let indent = "    ".repeat(depth + 1);
+        self.buf.push_str(&format!("{indent}</DL><p>\n"));
+    }
+
+    fn link(&mut self, link: &RepoLink, depth: usize) {
+        let indent = "    ".repeat(depth + 1);
+        self.buf.push_str(&format!(
+            "{indent}<DT><A HREF=\"{}\" ADD_DATE=\"{}\">{}</A>\n",
+            escape(link.url()),
+            link.metadata.added.timestamp(),
+            escape(link.title()),
+        ));
+    }
+
+    fn finish(&mut self) -> String {
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.push_str("</DL><p>\n");
+        buf
+    }
+}
+
+struct Opml {
+    buf: String,
+    locale: Locale,
+}
+
+impl Opml {
+    fn new(locale: Locale) -> Self {
+        Self {
+            buf: String::new(),
+            locale,
+        }
+    }
+}
+
+impl OutlineFormat for Opml {
+    fn start_topic(&mut self, topic: &RepoTopic, depth: usize) {
+        let name = escape(&topic.name(self.locale));
+
+        if depth == 0 {
+            self.buf.push_str(&format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <opml version=\"2.0\">\n\
+                \x20 <head>\n\
+                \x20   <title>{name}</title>\n\
+                \x20 </head>\n\
+                \x20 <body>\n"
+            ));
+            return;
+        }
+
+        let indent = "  ".repeat(depth + 1);
+        self.buf
+            .push_str(&format!("{indent}<outline text=\"{name}\">\n"));
+    }
+
+    fn end_topic(&mut self, _topic: &RepoTopic, depth: usize) {
+        if depth > 0 {
+            let indent = "  ".repeat(depth + 1);
+            self.buf.push_str(&format!("{indent}</outline>\n"));
+        }
+    }
+
+    fn link(&mut self, link: &RepoLink, depth: usize) {
+        let indent = "  ".repeat(depth + 1);
+        self.buf.push_str(&format!(
+            "{indent}<outline text=\"{}\" type=\"link\" url=\"{}\"/>\n",
+            escape(link.title()),
+            escape(link.url()),
+        ));
+    }
+
+    fn finish(&mut self) -> String {
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.push_str("  </body>\n</opml>\n");
+        buf
+    }
+}
+
+// Walks the downset of a topic, handing each topic and link to the output format.  Topics that
+// have more than one parent within the downset appear under each of them.
+struct OutlineVisitor<'c> {
+    client: &'c Client,
+    depth: usize,
+    downset: HashSet<ExternalId>,
+    format: Box<dyn OutlineFormat>,
+    locale: Locale,
+    path: Vec<ExternalId>,
+    repo_id: RepoId,
+}
+
+impl Visitor for &mut OutlineVisitor<'_> {
+    fn visit_topic(&mut self, topic: &RepoTopic) -> Result<()> {
+        self.format.start_topic(topic, self.depth);
+        self.path.push(topic.topic_id().to_owned());
+        self.depth += 1;
+
+        let mut topics = vec![];
+        let mut links = vec![];
+        for child in &topic.children {
+            if !self.downset.contains(&child.id) || self.path.contains(&child.id) {
+                continue;
+            }
+
+            match self.client.fetch(self.repo_id, &child.id) {
+                Some(RepoObject::Topic(topic)) => topics.push(topic),
+                Some(RepoObject::Link(link)) => links.push(link),
+                None => {}
+            }
+        }
+
+        topics.sort_by_key(|topic| topic.name(self.locale).to_lowercase());
+        links.sort_by_key(|link| link.title().to_lowercase());
+
+        for child in topics {
+            RepoObject::Topic(child).accept(&mut **self)?;
+        }
+
+        for child in links {
+            RepoObject::Link(child).accept(&mut **self)?;
+        }
+
+        self.depth -= 1;
+        self.path.pop();
+        self.format.end_topic(topic, self.depth);
+        Ok(())
+    }
+
+    fn visit_link(&mut self, link: &RepoLink) -> Result<()> {
+        self.format.link(link, self.depth);
+        Ok(())
+    }
+}
+
+pub struct ExportTopic {
+    pub format: ExportFormat,
+    pub locale: Locale,
+    pub repo_id: RepoId,
+    pub topic_id: ExternalId,
+}
+
+pub struct ExportTopicResult {
+    pub body: String,
+    pub filename: String,
+}
+
+impl ExportTopic {
+    pub fn call(&self, client: &Client) -> Result<ExportTopicResult> {
+        let not_found = || Error::NotFound(format!("not found: {}", self.topic_id));
+
+        let topic = client
+            .fetch_topic(self.repo_id, &self.topic_id)
+            .ok_or_else(not_found)?;
+        let path = client
+            .topic_path(self.repo_id, &self.topic_id)?
+            .ok_or_else(not_found)?;
+
+        let mut visitor = OutlineVisitor {
+            client,
+            depth: 0,
+            downset: client.downset_ids(&path),
+            format: self.format.writer(self.locale),
+            locale: self.locale,
+            path: vec![],
+            repo_id: self.repo_id,
+        };
+
+        let name = topic.name(self.locale);
+        RepoObject::Topic(topic).accept(&mut visitor)?;
+
+        Ok(ExportTopicResult {
+            body: visitor.format.finish(),
+            filename: format!("{}.{}", filename(&name), self.format.extension()),
+        })
+    }
+}
+
+// Kept to ASCII, since the name goes into a Content-Disposition header as it is
+fn filename(name: &str) -> String {
+    let name = unidecode(name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    let name = name
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+
+    if name.is_empty() {
+        "topic".to_owned()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_format() {
+        assert_eq!(ExportFormat::from_str("OPML").unwrap(), ExportFormat::Opml);
+        assert_eq!(
+            ExportFormat::from_str("markdown").unwrap(),
+            ExportFormat::Markdown
+        );
+        assert!(ExportFormat::from_str("pdf").is_err());
+        assert_eq!(ExportFormat::Netscape.to_string(), "netscape");
+    }
+
+    #[test]
+    fn filenames() {
+        assert_eq!(
+            filename("Climate change & society"),
+            "climate-change-society"
+        );
+        assert_eq!(filename("???"), "topic");
+        assert_eq!(filename("Économie sociale"), "economie-sociale");
+        assert_eq!(filename("日本"), "ri-ben");
+        assert_eq!(filename("\"\r\n"), "topic");
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 
 use super::{
     Kind, Phrase, RepoLink, RepoLinkDetails, RepoObject, RepoTopic, RepoTopicDetails, Search,
-    SearchMatch, SortKey, Synonym,
+    SearchMatch, SnapshotRef, SortKey, Synonym,
 };
 use crate::prelude::*;
 
@@ -430,6 +430,14 @@ impl RepoLinkWrapper {
         self.repo_id.is_wiki()
     }
 
+    pub fn is_pending(&self) -> bool {
+        self.repo_link.is_pending()
+    }
+
+    pub fn snapshot(&self) -> Option<&SnapshotRef> {
+        self.repo_link.metadata.snapshot.as_ref()
+    }
+
     pub fn link_id(&self) -> &ExternalId {
         self.repo_link.id()
     }
@@ -537,6 +545,14 @@ impl Link {
         self.key.1
     }
 
+    pub fn display_repo_id(&self) -> RepoId {
+        self.display_link.repo_id
+    }
+
+    pub fn display_snapshot(&self) -> Option<&SnapshotRef> {
+        self.display_link.snapshot()
+    }
+
     pub fn display_title(&self) -> &str {
         self.display_link.title()
     }
@@ -557,6 +573,10 @@ impl Link {
         self.repo_links.iter().any(|link| link.repo_id == repo_id)
     }
 
+    pub fn is_pending(&self) -> bool {
+        self.display_link.is_pending()
+    }
+
     pub fn parent_topic_ids(&self) -> Vec<ExternalId> {
         self.repo_links
             .iter()
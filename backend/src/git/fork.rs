@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::account::root_topic;
+use super::index::Index;
+use super::{
+    Client, IndexMode, Kind, ParentTopic, RepoLink, RepoObject, RepoTopic, SaveChangesForPrefix,
+    TopicChild,
+};
+use crate::prelude::*;
+
+const PROVENANCE_FILENAME: &str = "fork.yaml";
+
+// Where a fork came from, so that changes made upstream since the fork can later be found and
+// pulled in
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkProvenance {
+    pub forked_at: Timestamp,
+    pub forked_by: String,
+    pub upstream_commit: String,
+    pub upstream_repo_id: RepoId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_topic_id: Option<ExternalId>,
+}
+
+impl ForkProvenance {
+    pub fn fetch(client: &Client, repo_id: RepoId) -> Result<Option<Self>> {
+        if !client.viewer.can_read(repo_id) {
+            return Err(Error::NotFound(format!("not found: {repo_id}")));
+        }
+
+        let view = client.view(repo_id)?;
+        let provenance = match view.find_blob_by_filename(&PathBuf::from(PROVENANCE_FILENAME))? {
+            Some(blob) => Some(serde_yaml::from_slice(blob.content())?),
+            None => None,
+        };
+        Ok(provenance)
+    }
+}
+
+struct ProvenanceFile {
+    filename: PathBuf,
+    provenance: ForkProvenance,
+}
+
+impl Index for ProvenanceFile {
+    fn filename(&self) -> &PathBuf {
+        &self.filename
+    }
+
+    fn serialize(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&self.provenance)?)
+    }
+}
+
+// The topics and links that will be copied into the fork
+#[derive(Default)]
+struct Selection {
+    links: BTreeMap<ExternalId, RepoLink>,
+    topics: BTreeMap<ExternalId, RepoTopic>,
+}
+
+impl Selection {
+    fn contains(&self, id: &ExternalId) -> bool {
+        self.topics.contains_key(id) || self.links.contains_key(id)
+    }
+
+    // Drops parent topics and children that were left behind upstream, so that the fork has no
+    // references to objects it does not have
+    fn trim(&mut self) {
+        let topic_ids = self.topics.keys().cloned().collect::<BTreeSet<_>>();
+        let child_ids = topic_ids
+            .iter()
+            .chain(self.links.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        for topic in self.topics.values_mut() {
+            topic.parent_topics.retain(|p| topic_ids.contains(&p.id));
+            topic.children.retain(|c| child_ids.contains(&c.id));
+        }
+
+        for link in self.links.values_mut() {
+            link.parent_topics.retain(|p| topic_ids.contains(&p.id));
+        }
+    }
+}
+
+// Copies the topics and links of a repo, or of the downset of one of its topics, into a new repo
+// that belongs to the actor.  Page snapshots and the activity log stay behind in the upstream
+// repo.
+pub struct ForkRepo {
+    pub actor: Arc<Viewer>,
+    pub fork_repo_id: RepoId,
+    pub topic_id: Option<ExternalId>,
+    pub upstream_repo_id: RepoId,
+}
+
+#[derive(Debug)]
+pub struct ForkRepoResult {
+    pub links: usize,
+    pub provenance: ForkProvenance,
+    pub topics: usize,
+}
+
+impl ForkRepo {
+    pub fn call<S>(&self, client: &Client, store: &S) -> Result<ForkRepoResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        if !self.actor.can_read(self.upstream_repo_id) {
+            return Err(Error::NotFound(format!(
+                "not found: {}",
+                self.upstream_repo_id
+            )));
+        }
+
+        if self.fork_repo_id == self.upstream_repo_id {
+            return Err(Error::Repo("a repo cannot be forked into itself".into()));
+        }
+
+        let client = Client {
+            cache: Arc::clone(&client.cache),
+            root: client.root.to_owned(),
+            timespec: client.timespec.to_owned(),
+            viewer: Arc::new(self.actor.with_repo(self.fork_repo_id)),
+        };
+
+        let stats = client.view(self.fork_repo_id)?.stats()?;
+        if stats.topic_count.unwrap_or_default() > 0 || stats.link_count.unwrap_or_default() > 0 {
+            return Err(Error::Repo(format!(
+                "repo must be empty before forking into it: {}",
+                self.fork_repo_id
+            )));
+        }
+
+        let upstream = client.view(self.upstream_repo_id)?;
+        let mut selection = match &self.topic_id {
+            Some(topic_id) => self.downset(&client, topic_id)?,
+            None => {
+                let mut selection = Selection::default();
+                upstream.each_object(|object| {
+                    match object {
+                        RepoObject::Topic(topic) => {
+                            selection.topics.insert(topic.topic_id().to_owned(), topic);
+                        }
+                        RepoObject::Link(link) => {
+                            selection.links.insert(link.id().to_owned(), link);
+                        }
+                    }
+                    Ok(())
+                })?;
+                selection
+            }
+        };
+        selection.trim();
+
+        let provenance = ForkProvenance {
+            forked_at: chrono::Utc::now(),
+            forked_by: self.actor.user_id.to_owned(),
+            upstream_commit: upstream.commit.to_string(),
+            upstream_repo_id: self.upstream_repo_id,
+            upstream_topic_id: self.topic_id.to_owned(),
+        };
+
+        let mut mutation = client.mutation(IndexMode::Replace)?;
+        for topic in selection.topics.values() {
+            mutation.save_topic(self.fork_repo_id, topic)?;
+        }
+        for link in selection.links.values() {
+            let mut link = link.to_owned();
+            link.metadata.snapshot = None;
+            mutation.save_link(self.fork_repo_id, &link)?;
+        }
+        mutation.save_index(
+            self.fork_repo_id,
+            &ProvenanceFile {
+                filename: PathBuf::from(PROVENANCE_FILENAME),
+                provenance: provenance.to_owned(),
+            },
+        )?;
+        mutation.write(store)?;
+
+        log::info!(
+            "forked {} topics and {} links from {} into {}",
+            selection.topics.len(),
+            selection.links.len(),
+            self.upstream_repo_id,
+            self.fork_repo_id
+        );
+
+        Ok(ForkRepoResult {
+            links: selection.links.len(),
+            provenance,
+            topics: selection.topics.len(),
+        })
+    }
+
+    // The downset of the topic, placed under the root topic of the fork
+    fn downset(&self, client: &Client, topic_id: &ExternalId) -> Result<Selection> {
+        let not_found = || Error::NotFound(format!("not found: {topic_id}"));
+        let path = client
+            .topic_path(self.upstream_repo_id, topic_id)?
+            .ok_or_else(not_found)?;
+        let view = client.view(self.upstream_repo_id)?;
+
+        let mut selection = Selection::default();
+        for topic in client.topic_downset(&path) {
+            for child in &topic.children {
+                if child.kind != Kind::Link || selection.contains(&child.id) {
+                    continue;
+                }
+                if let Some(link) = view.link(&child.id)? {
+                    selection.links.insert(child.id.to_owned(), link);
+                }
+            }
+            selection.topics.insert(topic.topic_id().to_owned(), topic);
+        }
+
+        if !selection.topics.contains_key(topic_id) {
+            return Err(not_found());
+        }
+
+        let root_id = self.fork_repo_id.root_topic_id();
+        if topic_id != &root_id {
+            let mut root = root_topic(self.fork_repo_id, chrono::Utc::now());
+            root.children.insert(TopicChild {
+                added: root.metadata.added,
+                kind: Kind::Topic,
+                id: topic_id.to_owned(),
+            });
+            selection.topics.insert(root_id.to_owned(), root);
+
+            if let Some(topic) = selection.topics.get_mut(topic_id) {
+                topic.parent_topics = BTreeSet::from([ParentTopic { id: root_id }]);
+            }
+        }
+
+        Ok(selection)
+    }
+}
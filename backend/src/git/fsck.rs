@@ -0,0 +1,624 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::client::parse_object_path;
+use super::{
+    Client, GitPaths, IndexMode, Kind, ParentTopic, RepoLink, RepoObject, RepoTopic,
+    SaveChangesForPrefix, TopicChild,
+};
+use crate::prelude::*;
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase", tag = "problem")]
+pub enum Finding {
+    // A topic lists a child that does not list the topic as a parent
+    #[serde(rename_all = "camelCase")]
+    MissingParentTopic {
+        id: ExternalId,
+        kind: Kind,
+        parent_topic_id: ExternalId,
+    },
+    // An object lists a parent topic that does not list the object as a child
+    #[serde(rename_all = "camelCase")]
+    MissingChild {
+        id: ExternalId,
+        kind: Kind,
+        parent_topic_id: ExternalId,
+    },
+    #[serde(rename_all = "camelCase")]
+    DanglingChild {
+        child_id: ExternalId,
+        topic_id: ExternalId,
+    },
+    #[serde(rename_all = "camelCase")]
+    DanglingParentTopic {
+        id: ExternalId,
+        kind: Kind,
+        parent_topic_id: ExternalId,
+    },
+    #[serde(rename_all = "camelCase")]
+    Cycle { topic_ids: Vec<ExternalId> },
+    #[serde(rename_all = "camelCase")]
+    Unreachable { id: ExternalId, kind: Kind },
+    #[serde(rename_all = "camelCase")]
+    WrongPath { id: ExternalId, path: String },
+    #[serde(rename_all = "camelCase")]
+    Unreadable { path: String, error: String },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingParentTopic {
+                id,
+                kind,
+                parent_topic_id,
+            } => write!(
+                f,
+                "{} {} is a child of {} but does not list it as a parent topic",
+                kind, id, parent_topic_id
+            ),
+            Self::MissingChild {
+                id,
+                kind,
+                parent_topic_id,
+            } => write!(
+                f,
+                "{} {} lists {} as a parent topic but is not one of its children",
+                kind, id, parent_topic_id
+            ),
+            Self::DanglingChild { child_id, topic_id } => {
+                write!(f, "topic {topic_id} has a missing child {child_id}")
+            }
+            Self::DanglingParentTopic {
+                id,
+                kind,
+                parent_topic_id,
+            } => write!(
+                f,
+                "{} {} has a missing parent topic {}",
+                kind, id, parent_topic_id
+            ),
+            Self::Cycle { topic_ids } => {
+                let ids = topic_ids
+                    .iter()
+                    .map(ExternalId::to_string)
+                    .collect::<Vec<_>>();
+                write!(f, "cycle: {}", ids.join(" -> "))
+            }
+            Self::Unreachable { id, kind } => {
+                write!(f, "{} {} cannot be reached from the root topic", kind, id)
+            }
+            Self::WrongPath { id, path } => write!(f, "object {id} is stored at {path}"),
+            Self::Unreadable { path, error } => write!(f, "{path} cannot be read: {error}"),
+        }
+    }
+}
+
+// The objects of a repo, loaded into memory so that the edges between them can be checked in
+// both directions
+#[derive(Default)]
+struct Objects {
+    links: BTreeMap<ExternalId, RepoLink>,
+    misplaced: BTreeMap<PathBuf, ExternalId>,
+    topics: BTreeMap<ExternalId, RepoTopic>,
+}
+
+impl Objects {
+    fn get(&self, id: &ExternalId) -> Option<RepoObject> {
+        if let Some(topic) = self.topics.get(id) {
+            return Some(RepoObject::Topic(topic.to_owned()));
+        }
+        self.links
+            .get(id)
+            .map(|link| RepoObject::Link(link.to_owned()))
+    }
+
+    fn kind(&self, id: &ExternalId) -> Option<Kind> {
+        if self.topics.contains_key(id) {
+            Some(Kind::Topic)
+        } else if self.links.contains_key(id) {
+            Some(Kind::Link)
+        } else {
+            None
+        }
+    }
+
+    fn parent_topics(&self, id: &ExternalId) -> Option<&BTreeSet<ParentTopic>> {
+        match self.topics.get(id) {
+            Some(topic) => Some(&topic.parent_topics),
+            None => self.links.get(id).map(|link| &link.parent_topics),
+        }
+    }
+
+    fn parent_topics_mut(&mut self, id: &ExternalId) -> Option<&mut BTreeSet<ParentTopic>> {
+        match self.topics.get_mut(id) {
+            Some(topic) => Some(&mut topic.parent_topics),
+            None => self.links.get_mut(id).map(|link| &mut link.parent_topics),
+        }
+    }
+
+    fn ids(&self) -> impl Iterator<Item = &ExternalId> {
+        self.topics.keys().chain(self.links.keys())
+    }
+}
+
+// Finds the cycles among topics by following child edges depth first.  Each cycle is reported
+// once, starting from its smallest id.
+fn cycles(objects: &Objects) -> BTreeSet<Vec<ExternalId>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Visiting,
+        Done,
+    }
+
+    let mut state: BTreeMap<&ExternalId, State> = BTreeMap::new();
+    let mut cycles = BTreeSet::new();
+
+    for start in objects.topics.keys() {
+        if state.contains_key(start) {
+            continue;
+        }
+
+        let mut path: Vec<&ExternalId> = vec![start];
+        let mut stack = vec![objects.topics[start]
+            .children
+            .iter()
+            .filter(|c| c.kind == Kind::Topic)
+            .map(|c| &c.id)
+            .collect::<Vec<_>>()];
+        state.insert(start, State::Visiting);
+
+        while let Some(children) = stack.last_mut() {
+            match children.pop() {
+                Some(child_id) => match state.get(child_id) {
+                    Some(State::Visiting) => {
+                        let i = path.iter().position(|id| *id == child_id).unwrap_or(0);
+                        let mut cycle = path[i..]
+                            .iter()
+                            .map(|id| (*id).to_owned())
+                            .collect::<Vec<_>>();
+                        let min = cycle
+                            .iter()
+                            .enumerate()
+                            .min_by_key(|(_, id)| *id)
+                            .map(|(i, _)| i)
+                            .unwrap_or(0);
+                        cycle.rotate_left(min);
+                        cycles.insert(cycle);
+                    }
+                    Some(State::Done) => {}
+                    None => {
+                        if let Some(child) = objects.topics.get(child_id) {
+                            state.insert(child_id, State::Visiting);
+                            path.push(child_id);
+                            stack.push(
+                                child
+                                    .children
+                                    .iter()
+                                    .filter(|c| c.kind == Kind::Topic)
+                                    .map(|c| &c.id)
+                                    .collect(),
+                            );
+                        }
+                    }
+                },
+                None => {
+                    stack.pop();
+                    if let Some(id) = path.pop() {
+                        state.insert(id, State::Done);
+                    }
+                }
+            }
+        }
+    }
+
+    cycles
+}
+
+// Everything that can be reached from the root topic, following an edge if either side of it
+// is present
+fn reachable(objects: &Objects, root_id: &ExternalId) -> BTreeSet<ExternalId> {
+    let mut children: BTreeMap<&ExternalId, BTreeSet<&ExternalId>> = BTreeMap::new();
+    for (topic_id, topic) in &objects.topics {
+        for child in &topic.children {
+            children.entry(topic_id).or_default().insert(&child.id);
+        }
+    }
+    for id in objects.ids() {
+        for parent in objects.parent_topics(id).into_iter().flatten() {
+            children.entry(&parent.id).or_default().insert(id);
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut queue = VecDeque::from([root_id]);
+    while let Some(id) = queue.pop_front() {
+        if objects.kind(id).is_none() || !seen.insert(id.to_owned()) {
+            continue;
+        }
+        for child_id in children.get(id).into_iter().flatten() {
+            queue.push_back(child_id);
+        }
+    }
+
+    seen
+}
+
+fn check(objects: &Objects, root_id: &ExternalId) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for (path, id) in &objects.misplaced {
+        findings.push(Finding::WrongPath {
+            id: id.to_owned(),
+            path: path.display().to_string(),
+        });
+    }
+
+    for (topic_id, topic) in &objects.topics {
+        for child in &topic.children {
+            match objects.parent_topics(&child.id) {
+                Some(parents) => {
+                    if !parents.iter().any(|p| &p.id == topic_id) {
+                        findings.push(Finding::MissingParentTopic {
+                            id: child.id.to_owned(),
+                            kind: child.kind,
+                            parent_topic_id: topic_id.to_owned(),
+                        });
+                    }
+                }
+                None => findings.push(Finding::DanglingChild {
+                    child_id: child.id.to_owned(),
+                    topic_id: topic_id.to_owned(),
+                }),
+            }
+        }
+    }
+
+    for id in objects.ids() {
+        let kind = objects.kind(id).unwrap_or(Kind::Topic);
+        for parent in objects.parent_topics(id).into_iter().flatten() {
+            match objects.topics.get(&parent.id) {
+                Some(topic) => {
+                    if !topic.has_child(id) {
+                        findings.push(Finding::MissingChild {
+                            id: id.to_owned(),
+                            kind,
+                            parent_topic_id: parent.id.to_owned(),
+                        });
+                    }
+                }
+                None => findings.push(Finding::DanglingParentTopic {
+                    id: id.to_owned(),
+                    kind,
+                    parent_topic_id: parent.id.to_owned(),
+                }),
+            }
+        }
+    }
+
+    for topic_ids in cycles(objects) {
+        findings.push(Finding::Cycle { topic_ids });
+    }
+
+    let reachable = reachable(objects, root_id);
+    for id in objects.ids() {
+        if !reachable.contains(id) {
+            findings.push(Finding::Unreachable {
+                id: id.to_owned(),
+                kind: objects.kind(id).unwrap_or(Kind::Topic),
+            });
+        }
+    }
+
+    findings
+}
+
+// Makes the edges that were found to be one-sided symmetric, drops references to objects that do
+// not exist, reattaches parentless objects to the root topic and moves misplaced objects to
+// where they belong.  Cycles are left for a person to sort out, since there is no way of telling
+// which edge is the wrong one.  Returns the ids of the objects that were changed.
+fn repair(
+    objects: &mut Objects,
+    findings: &[Finding],
+    root_id: &ExternalId,
+) -> BTreeSet<ExternalId> {
+    let now = chrono::Utc::now();
+    let mut changed = BTreeSet::new();
+
+    for finding in findings {
+        match finding {
+            Finding::MissingParentTopic {
+                id,
+                parent_topic_id,
+                ..
+            } => {
+                if let Some(parents) = objects.parent_topics_mut(id) {
+                    parents.insert(ParentTopic {
+                        id: parent_topic_id.to_owned(),
+                    });
+                    changed.insert(id.to_owned());
+                }
+            }
+
+            Finding::MissingChild {
+                id,
+                kind,
+                parent_topic_id,
+            } => {
+                if let Some(topic) = objects.topics.get_mut(parent_topic_id) {
+                    topic.children.insert(TopicChild {
+                        added: now,
+                        kind: *kind,
+                        id: id.to_owned(),
+                    });
+                    changed.insert(parent_topic_id.to_owned());
+                }
+            }
+
+            Finding::DanglingChild { child_id, topic_id } => {
+                if let Some(topic) = objects.topics.get_mut(topic_id) {
+                    topic.children.retain(|child| &child.id != child_id);
+                    changed.insert(topic_id.to_owned());
+                }
+            }
+
+            Finding::DanglingParentTopic {
+                id,
+                parent_topic_id,
+                ..
+            } => {
+                if let Some(parents) = objects.parent_topics_mut(id) {
+                    parents.retain(|parent| &parent.id != parent_topic_id);
+                    changed.insert(id.to_owned());
+                }
+            }
+
+            Finding::WrongPath { id, .. } => {
+                changed.insert(id.to_owned());
+            }
+
+            Finding::Cycle { .. } | Finding::Unreachable { .. } | Finding::Unreadable { .. } => {}
+        }
+    }
+
+    // Objects that were left without a parent topic go under the root topic
+    let orphans = objects
+        .ids()
+        .filter(|id| *id != root_id)
+        .filter(|id| {
+            objects
+                .parent_topics(id)
+                .map(|parents| parents.is_empty())
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for id in orphans {
+        let kind = objects.kind(&id).unwrap_or(Kind::Topic);
+        if let Some(parents) = objects.parent_topics_mut(&id) {
+            parents.insert(ParentTopic {
+                id: root_id.to_owned(),
+            });
+        }
+        if let Some(root) = objects.topics.get_mut(root_id) {
+            root.children.insert(TopicChild {
+                added: now,
+                kind,
+                id: id.to_owned(),
+            });
+        }
+        changed.insert(id);
+        changed.insert(root_id.to_owned());
+    }
+
+    changed
+}
+
+// Checks that the topics and links of a repo are consistent with one another: that parent and
+// child edges are recorded on both sides, that there are no references to missing objects, that
+// the topic graph has no cycles, that everything can be reached from the root topic and that
+// each object is stored at the path for its id.
+pub struct Fsck {
+    pub repair: bool,
+    pub repo_id: RepoId,
+}
+
+#[derive(Debug)]
+pub struct FsckResult {
+    pub findings: Vec<Finding>,
+    pub repaired: usize,
+}
+
+impl Fsck {
+    pub fn call<S>(&self, client: &Client, store: &S) -> Result<FsckResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        if !client.viewer.can_read(self.repo_id) {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        let root_id = self.repo_id.root_topic_id();
+        let view = client.view(self.repo_id)?;
+        let mut objects = Objects::default();
+        let mut unreadable = vec![];
+
+        for (path, oid) in view.object_oids()? {
+            let object = match view.object_by_oid(oid) {
+                Ok(object) => object,
+                Err(err) => {
+                    unreadable.push(Finding::Unreadable {
+                        path,
+                        error: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let id = object.id().to_owned();
+            if id.object_filename()?.as_path() != std::path::Path::new(&path) {
+                objects
+                    .misplaced
+                    .insert(PathBuf::from(&path), id.to_owned());
+            }
+
+            match Arc::unwrap_or_clone(object) {
+                RepoObject::Topic(topic) => {
+                    objects.topics.insert(id, topic);
+                }
+                RepoObject::Link(link) => {
+                    objects.links.insert(id, link);
+                }
+            }
+        }
+
+        let mut findings = check(&objects, &root_id);
+        findings.extend(unreadable);
+        findings.sort();
+        log::info!("found {} problems in {}", findings.len(), self.repo_id);
+
+        if !self.repair || findings.is_empty() {
+            return Ok(FsckResult {
+                findings,
+                repaired: 0,
+            });
+        }
+
+        let changed = repair(&mut objects, &findings, &root_id);
+        let mut mutation = client.mutation(IndexMode::Update)?;
+
+        for path in objects.misplaced.keys() {
+            let path = path.display().to_string();
+            mutation.remove(self.repo_id, &parse_object_path(&path)?)?;
+        }
+
+        for id in &changed {
+            match objects.get(id) {
+                Some(RepoObject::Topic(topic)) => mutation.save_topic(self.repo_id, &topic)?,
+                Some(RepoObject::Link(link)) => mutation.save_link(self.repo_id, &link)?,
+                None => {}
+            }
+        }
+        mutation.write(store)?;
+        log::info!("repaired {} objects in {}", changed.len(), self.repo_id);
+
+        Ok(FsckResult {
+            findings,
+            repaired: changed.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::testing::*;
+
+    fn id(s: &str) -> ExternalId {
+        ExternalId::try_from(s).unwrap()
+    }
+
+    // The test helpers give objects parent topics that are not wanted here
+    fn bare_topic(name: &str) -> RepoTopic {
+        let mut topic = topic(name);
+        topic.parent_topics.clear();
+        topic
+    }
+
+    fn link_under(name: &str, parent: &RepoTopic) -> RepoLink {
+        let mut link = link(name, "https://www.google.com");
+        link.parent_topics = BTreeSet::from([parent.to_parent_topic()]);
+        link
+    }
+
+    fn add_child(parent: &mut RepoTopic, child: &RepoTopic) {
+        parent
+            .children
+            .insert(child.to_topic_child(chrono::Utc::now()));
+    }
+
+    fn objects(topics: Vec<RepoTopic>, links: Vec<RepoLink>) -> Objects {
+        Objects {
+            links: links
+                .into_iter()
+                .map(|link| (link.id().to_owned(), link))
+                .collect(),
+            misplaced: BTreeMap::new(),
+            topics: topics
+                .into_iter()
+                .map(|topic| (topic.topic_id().to_owned(), topic))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn one_sided_edges() {
+        let mut root = bare_topic("Root");
+        let child = bare_topic("Child");
+        add_child(&mut root, &child);
+        let link = link_under("Page", &root);
+        let root_id = root.topic_id().to_owned();
+
+        let objects = objects(vec![root, child.to_owned()], vec![link.to_owned()]);
+        let findings = check(&objects, &root_id);
+
+        assert!(findings.contains(&Finding::MissingParentTopic {
+            id: child.topic_id().to_owned(),
+            kind: Kind::Topic,
+            parent_topic_id: root_id.to_owned(),
+        }));
+        assert!(findings.contains(&Finding::MissingChild {
+            id: link.id().to_owned(),
+            kind: Kind::Link,
+            parent_topic_id: root_id,
+        }));
+    }
+
+    #[test]
+    fn cycles_found_once() {
+        let mut a = bare_topic("A");
+        let mut b = bare_topic("B");
+        let mut c = bare_topic("C");
+        add_child(&mut a, &b);
+        add_child(&mut b, &c);
+        add_child(&mut c, &a);
+
+        let objects = objects(vec![a, b, c], vec![]);
+        assert_eq!(cycles(&objects).len(), 1);
+        let cycle = cycles(&objects).into_iter().next().unwrap();
+        assert_eq!(cycle.len(), 3);
+        assert_eq!(&cycle[0], cycle.iter().min().unwrap());
+    }
+
+    #[test]
+    fn repair_makes_edges_symmetric() {
+        let mut root = bare_topic("Root");
+        let child = bare_topic("Child");
+        let orphan = bare_topic("Orphan");
+        add_child(&mut root, &child);
+        root.children.insert(TopicChild {
+            added: chrono::Utc::now(),
+            kind: Kind::Topic,
+            id: id("00009"),
+        });
+        let root_id = root.topic_id().to_owned();
+
+        let mut objects = objects(vec![root, child.to_owned(), orphan.to_owned()], vec![]);
+        let findings = check(&objects, &root_id);
+        assert!(findings.contains(&Finding::DanglingChild {
+            child_id: id("00009"),
+            topic_id: root_id.to_owned(),
+        }));
+        assert!(findings.contains(&Finding::Unreachable {
+            id: orphan.topic_id().to_owned(),
+            kind: Kind::Topic,
+        }));
+
+        repair(&mut objects, &findings, &root_id);
+        assert_eq!(check(&objects, &root_id), vec![]);
+    }
+}
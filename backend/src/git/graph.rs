@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, BTreeSet};
+use strum_macros::EnumString;
+
+use super::{core, Client, Kind, RepoLink, RepoTopic};
+use crate::prelude::*;
+use crate::types::{TimerangePrefix, TimerangePrefixFormat, TopicPath};
+
+#[derive(Copy, Clone, Debug, EnumString, Eq, PartialEq, strum_macros::Display)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum GraphFormat {
+    Dot,
+    Graphml,
+}
+
+impl GraphFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Dot => "text/vnd.graphviz; charset=utf-8",
+            Self::Graphml => "application/graphml+xml; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::Graphml => "graphml",
+        }
+    }
+}
+
+// What part of the graph to export: everything in a repo, including topics that have become
+// disconnected from the root topic, or the downset of a topic
+pub enum GraphScope {
+    Repo(RepoId),
+    Downset(TopicPath),
+}
+
+impl GraphScope {
+    fn repo_id(&self) -> RepoId {
+        match self {
+            Self::Repo(repo_id) => *repo_id,
+            Self::Downset(path) => path.repo_id,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    kind: Kind,
+    label: String,
+    repos: BTreeSet<RepoId>,
+    synonyms: Vec<String>,
+    timerange: Option<String>,
+    url: Option<String>,
+}
+
+impl Node {
+    fn kind(&self) -> &'static str {
+        match self.kind {
+            Kind::Link => "link",
+            Kind::Topic => "topic",
+        }
+    }
+
+    fn repos(&self) -> String {
+        self.repos
+            .iter()
+            .map(RepoId::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn synonyms(&self) -> String {
+        self.synonyms.join("; ")
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    edges: BTreeSet<(ExternalId, ExternalId)>,
+    nodes: BTreeMap<ExternalId, Node>,
+}
+
+impl Graph {
+    fn add_topic(&mut self, topic: &RepoTopic, locale: Locale, include_links: bool) {
+        let timerange = topic.timerange().as_ref().map(|timerange| {
+            let prefix = TimerangePrefix::from(timerange);
+            match timerange.prefix_format {
+                TimerangePrefixFormat::None => prefix.date_string(),
+                _ => prefix.prefix().unwrap_or_else(|| prefix.date_string()),
+            }
+        });
+
+        self.nodes.insert(
+            topic.topic_id().to_owned(),
+            Node {
+                kind: Kind::Topic,
+                label: topic.name(locale),
+                repos: BTreeSet::new(),
+                synonyms: topic
+                    .synonyms()
+                    .iter()
+                    .map(|synonym| format!("{} ({})", synonym.name, synonym.locale))
+                    .collect(),
+                timerange,
+                url: None,
+            },
+        );
+
+        for child in &topic.children {
+            if child.kind == Kind::Link && !include_links {
+                continue;
+            }
+            self.edges
+                .insert((topic.topic_id().to_owned(), child.id.to_owned()));
+        }
+    }
+
+    fn add_link(&mut self, link: &RepoLink) {
+        self.nodes.insert(
+            link.id().to_owned(),
+            Node {
+                kind: Kind::Link,
+                label: link.title().to_owned(),
+                repos: BTreeSet::new(),
+                synonyms: vec![],
+                timerange: None,
+                url: Some(link.url().to_owned()),
+            },
+        );
+    }
+
+    // Edges to objects that are outside of the scope of the export are dropped, since most tools
+    // will not load a graph with dangling edges
+    fn edges(&self) -> impl Iterator<Item = &(ExternalId, ExternalId)> {
+        self.edges.iter().filter(|(source, target)| {
+            self.nodes.contains_key(source) && self.nodes.contains_key(target)
+        })
+    }
+
+    fn to_dot(&self) -> String {
+        fn quote(value: &str) -> String {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("\"{value}\"")
+        }
+
+        let mut buf = String::from("digraph topics {\n");
+
+        for (id, node) in &self.nodes {
+            let mut attrs = vec![
+                format!("label={}", quote(&node.label)),
+                format!("kind={}", quote(node.kind())),
+                format!("repos={}", quote(&node.repos())),
+            ];
+            if node.kind == Kind::Link {
+                attrs.push("shape=box".to_owned());
+            }
+            if !node.synonyms.is_empty() {
+                attrs.push(format!("synonyms={}", quote(&node.synonyms())));
+            }
+            if let Some(timerange) = &node.timerange {
+                attrs.push(format!("timerange={}", quote(timerange)));
+            }
+            if let Some(url) = &node.url {
+                attrs.push(format!("URL={}", quote(url)));
+            }
+
+            buf.push_str(&format!(
+                "  {} [{}];\n",
+                quote(id.as_str()),
+                attrs.join(", ")
+            ));
+        }
+
+        for (source, target) in self.edges() {
+            buf.push_str(&format!(
+                "  {} -> {};\n",
+                quote(source.as_str()),
+                quote(target.as_str())
+            ));
+        }
+
+        buf.push_str("}\n");
+        buf
+    }
+
+    fn to_graphml(&self) -> String {
+        fn escape(value: &str) -> String {
+            value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+
+        fn data(buf: &mut String, key: &str, value: &str) {
+            buf.push_str(&format!(
+                "      <data key=\"{}\">{}</data>\n",
+                key,
+                escape(value)
+            ));
+        }
+
+        let mut buf = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+
+        for key in ["label", "kind", "repos", "synonyms", "timerange", "url"] {
+            buf.push_str(&format!(
+                "  <key id=\"{key}\" for=\"node\" attr.name=\"{key}\" attr.type=\"string\"/>\n"
+            ));
+        }
+        buf.push_str("  <graph id=\"topics\" edgedefault=\"directed\">\n");
+
+        for (id, node) in &self.nodes {
+            buf.push_str(&format!("    <node id=\"{}\">\n", escape(id.as_str())));
+            data(&mut buf, "label", &node.label);
+            data(&mut buf, "kind", node.kind());
+            data(&mut buf, "repos", &node.repos());
+            if !node.synonyms.is_empty() {
+                data(&mut buf, "synonyms", &node.synonyms());
+            }
+            if let Some(timerange) = &node.timerange {
+                data(&mut buf, "timerange", timerange);
+            }
+            if let Some(url) = &node.url {
+                data(&mut buf, "url", url);
+            }
+            buf.push_str("    </node>\n");
+        }
+
+        for (source, target) in self.edges() {
+            buf.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"/>\n",
+                escape(source.as_str()),
+                escape(target.as_str())
+            ));
+        }
+
+        buf.push_str("  </graph>\n</graphml>\n");
+        buf
+    }
+}
+
+pub struct GraphExport {
+    pub format: GraphFormat,
+    pub include_links: bool,
+    pub locale: Locale,
+    pub scope: GraphScope,
+}
+
+#[derive(Debug)]
+pub struct GraphExportResult {
+    pub body: String,
+    pub edge_count: usize,
+    pub node_count: usize,
+}
+
+impl GraphExport {
+    pub fn call(&self, client: &Client) -> Result<GraphExportResult> {
+        let repo_id = self.scope.repo_id();
+        if !client.viewer.can_read(repo_id) {
+            return Err(Error::NotFound(format!("not found: {repo_id}")));
+        }
+
+        let view = client.view(repo_id)?;
+        let mut graph = Graph::default();
+
+        match &self.scope {
+            GraphScope::Repo(_) => {
+                for topic in view.topics()? {
+                    graph.add_topic(&topic, self.locale, self.include_links);
+                }
+
+                if self.include_links {
+                    for link in view.links()? {
+                        graph.add_link(&link);
+                    }
+                }
+            }
+
+            GraphScope::Downset(path) => {
+                for topic in client.topic_downset(path) {
+                    graph.add_topic(&topic, self.locale, self.include_links);
+
+                    if !self.include_links {
+                        continue;
+                    }
+
+                    for child in &topic.children {
+                        if child.kind != Kind::Link || graph.nodes.contains_key(&child.id) {
+                            continue;
+                        }
+                        if let Some(link) = view.link(&child.id)? {
+                            graph.add_link(&link);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.add_repos(client, &mut graph)?;
+
+        let body = match self.format {
+            GraphFormat::Dot => graph.to_dot(),
+            GraphFormat::Graphml => graph.to_graphml(),
+        };
+
+        Ok(GraphExportResult {
+            body,
+            edge_count: graph.edges().count(),
+            node_count: graph.nodes.len(),
+        })
+    }
+
+    // Records which of the repos the viewer can see have a copy of each node
+    fn add_repos(&self, client: &Client, graph: &mut Graph) -> Result<()> {
+        let mut views: Vec<(RepoId, core::View)> = vec![];
+        for &repo_id in client.viewer.read_repo_ids.iter() {
+            views.push((repo_id, client.view(repo_id)?));
+        }
+
+        let repo_id = self.scope.repo_id();
+        for (id, node) in graph.nodes.iter_mut() {
+            node.repos.insert(repo_id);
+
+            for (other_id, view) in &views {
+                if *other_id != repo_id && view.object_exists(id)? {
+                    node.repos.insert(*other_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn graph() -> Graph {
+        let mut graph = Graph::default();
+        let topic_id = ExternalId::try_from("00001").unwrap();
+        let link_id = ExternalId::try_from("00002").unwrap();
+
+        graph.nodes.insert(
+            topic_id.to_owned(),
+            Node {
+                kind: Kind::Topic,
+                label: "Climate \"change\"".into(),
+                repos: BTreeSet::from([RepoId::wiki()]),
+                synonyms: vec!["Climate change (en)".into()],
+                timerange: Some("2010".into()),
+                url: None,
+            },
+        );
+        graph.nodes.insert(
+            link_id.to_owned(),
+            Node {
+                kind: Kind::Link,
+                label: "Q&A".into(),
+                repos: BTreeSet::from([RepoId::wiki()]),
+                synonyms: vec![],
+                timerange: None,
+                url: Some("https://example.com".into()),
+            },
+        );
+        graph.edges.insert((topic_id.to_owned(), link_id));
+        graph
+            .edges
+            .insert((topic_id, ExternalId::try_from("00003").unwrap()));
+        graph
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!(
+            GraphFormat::from_str("GraphML").unwrap(),
+            GraphFormat::Graphml
+        );
+        assert_eq!(GraphFormat::from_str("dot").unwrap(), GraphFormat::Dot);
+        assert!(GraphFormat::from_str("gexf").is_err());
+    }
+
+    #[test]
+    fn dangling_edges_dropped() {
+        assert_eq!(graph().edges().count(), 1);
+    }
+
+    #[test]
+    fn dot() {
+        let dot = graph().to_dot();
+        assert!(dot.starts_with("digraph topics {\n"));
+        assert!(dot.contains(r#"label="Climate \"change\"", kind="topic""#));
+        assert!(dot.contains(r#"timerange="2010""#));
+        assert!(dot.contains(r#"  "00001" -> "00002";"#));
+        assert!(!dot.contains("00003"));
+    }
+
+    #[test]
+    fn graphml() {
+        let graphml = graph().to_graphml();
+        assert!(graphml.contains(r#"<data key="label">Climate &quot;change&quot;</data>"#));
+        assert!(graphml.contains(r#"<data key="label">Q&amp;A</data>"#));
+        assert!(graphml.contains(r#"<edge source="00001" target="00002"/>"#));
+        assert!(!graphml.contains("00003"));
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::index::Index;
+use super::{core, Mutation, SaveChangesForPrefix};
+use crate::http::{self, RepoUrl};
+use crate::prelude::*;
+
+const HEALTH_INDEX_DIR: &str = "indexes/health";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkHealth {
+    pub checked: Timestamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub url: String,
+}
+
+impl LinkHealth {
+    pub fn is_broken(&self) -> bool {
+        match self.status {
+            Some(status) => status >= 400,
+            None => self.error.is_some(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkHealthIndexMap {
+    api_version: String,
+    kind: String,
+    links: BTreeMap<ExternalId, LinkHealth>,
+}
+
+impl TryInto<LinkHealthIndexMap> for git2::Blob<'_> {
+    type Error = Error;
+
+    fn try_into(self) -> Result<LinkHealthIndexMap> {
+        Ok(serde_yaml::from_slice(self.content())?)
+    }
+}
+
+// Health checks are sharded on the first two characters of the link id so that looking up the
+// status of a single link does not require loading the results for the entire repo.
+#[derive(Clone, Debug)]
+pub struct LinkHealthIndex {
+    filename: PathBuf,
+    index: LinkHealthIndexMap,
+}
+
+impl Index for LinkHealthIndex {
+    fn filename(&self) -> &PathBuf {
+        &self.filename
+    }
+
+    fn serialize(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&self.index)?)
+    }
+}
+
+impl LinkHealthIndex {
+    pub fn filename_for(link_id: &ExternalId) -> Result<PathBuf> {
+        let (basename, _, _) = link_id.parts()?;
+        Ok(Path::new(HEALTH_INDEX_DIR).join(format!("{basename}.yaml")))
+    }
+
+    pub fn new(filename: &Path) -> Self {
+        Self {
+            filename: filename.to_owned(),
+            index: LinkHealthIndexMap {
+                api_version: API_VERSION.to_owned(),
+                kind: "LinkHealthIndexMap".to_owned(),
+                links: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub fn load(filename: &Path, view: &core::View) -> Result<Self> {
+        let index = match view.find_blob_by_filename(filename)? {
+            Some(blob) => Self {
+                filename: filename.to_owned(),
+                index: blob.try_into()?,
+            },
+            None => Self::new(filename),
+        };
+        Ok(index)
+    }
+
+    pub fn load_all(view: &core::View) -> Result<Vec<Self>> {
+        view.filenames(Path::new(HEALTH_INDEX_DIR))?
+            .iter()
+            .map(|filename| Self::load(filename, view))
+            .collect()
+    }
+
+    pub fn add(&mut self, link_id: &ExternalId, health: LinkHealth) {
+        self.index.links.insert(link_id.to_owned(), health);
+    }
+
+    pub fn get(&self, link_id: &ExternalId) -> Option<&LinkHealth> {
+        self.index.links.get(link_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ExternalId, &LinkHealth)> {
+        self.index.links.iter()
+    }
+}
+
+pub struct CheckLinks {
+    pub actor: Arc<Viewer>,
+    pub checker: Box<dyn http::CheckUrl + Send + Sync>,
+    pub repo_id: RepoId,
+}
+
+pub struct CheckLinksResult {
+    pub broken: usize,
+    pub checked: usize,
+}
+
+impl CheckLinks {
+    pub fn call<S>(&self, mut mutation: Mutation, store: &S) -> Result<CheckLinksResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        let view = mutation.view(self.repo_id)?;
+        let mut indexes: HashMap<PathBuf, LinkHealthIndex> = HashMap::new();
+        let mut checked = 0;
+        let mut broken = 0;
+
+        for link in view.links()? {
+            if link.is_reference() {
+                continue;
+            }
+
+            let url = match RepoUrl::parse(link.url()) {
+                Ok(url) => url,
+                Err(err) => {
+                    log::warn!("skipping link with bad url {}: {}", link.url(), err);
+                    continue;
+                }
+            };
+
+            let health = match self.check(&url) {
+                Ok(health) => health,
+                Err(err) => {
+                    log::info!("skipping {}: {}", url, err);
+                    continue;
+                }
+            };
+            if health.is_broken() {
+                log::info!("broken link {}: {:?}", url, health);
+                broken += 1;
+            }
+            checked += 1;
+
+            let filename = LinkHealthIndex::filename_for(link.id())?;
+            let index = match indexes.entry(filename) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let index = LinkHealthIndex::load(entry.key(), &view)?;
+                    entry.insert(index)
+                }
+            };
+            index.add(link.id(), health);
+        }
+
+        for index in indexes.values() {
+            mutation.save_index(self.repo_id, index)?;
+        }
+        mutation.write(store)?;
+
+        log::info!(
+            "{} links checked in {}, {} broken",
+            checked,
+            self.repo_id,
+            broken
+        );
+        Ok(CheckLinksResult { broken, checked })
+    }
+
+    fn check(&self, url: &RepoUrl) -> Result<LinkHealth> {
+        let http::UrlStatus {
+            error,
+            location,
+            status,
+        } = self.checker.check(url)?;
+
+        Ok(LinkHealth {
+            checked: Utc::now(),
+            error,
+            redirect: location,
+            status,
+            url: url.normalized.to_owned(),
+        })
+    }
+}
@@ -0,0 +1,856 @@
+use chrono::TimeZone;
+use itertools::Itertools;
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use strum_macros::EnumString;
+
+use super::activity::{self, LinkInfo, LinkInfoList, TopicInfo, TopicInfoList};
+use super::{
+    Mutation, ParentTopic, Phrase, RepoLink, RepoTopic, RepoTopicDetails, RepoTopicMetadata,
+    SaveChangesForPrefix, Synonym, UpsertLink, UpsertTopic,
+};
+use crate::http;
+use crate::prelude::*;
+
+// A bookmark read from an export file, along with the folders it was found in and any tags it was
+// given
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bookmark {
+    pub added: Option<Timestamp>,
+    pub folder: Vec<String>,
+    pub tags: Vec<String>,
+    pub title: Option<String>,
+    pub url: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumString, Eq, PartialEq, strum_macros::Display)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Netscape,
+    Pinboard,
+    Pocket,
+}
+
+impl ImportFormat {
+    // Makes a guess at the format of an export file from its contents
+    pub fn detect(input: &str) -> Self {
+        let start = input.trim_start();
+        if start.starts_with('[') || start.starts_with('{') {
+            return Self::Pinboard;
+        }
+
+        let head = start.chars().take(500).collect::<String>().to_lowercase();
+        if head.contains("netscape-bookmark-file") {
+            Self::Netscape
+        } else if head.starts_with('<') {
+            Self::Pocket
+        } else {
+            Self::Csv
+        }
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Vec<Bookmark>> {
+        match self {
+            Self::Csv => parse_csv_bookmarks(input),
+            Self::Netscape => Ok(parse_netscape_bookmarks(input)),
+            Self::Pinboard => parse_pinboard_bookmarks(input),
+            Self::Pocket => Ok(parse_pocket_bookmarks(input)),
+        }
+    }
+}
+
+fn text(element: ElementRef<'_>) -> String {
+    element
+        .text()
+        .collect::<String>()
+        .split_whitespace()
+        .join(" ")
+}
+
+fn timestamp(secs: &str) -> Option<Timestamp> {
+    let secs = secs.trim().parse::<i64>().ok()?;
+    chrono::Utc.timestamp_opt(secs, 0).single()
+}
+
+// Dates in CSV and JSON exports are found as unix timestamps, RFC 3339 timestamps and plain dates
+fn parse_date(date: &str) -> Option<Timestamp> {
+    let date = date.trim();
+    if let Some(ts) = timestamp(date) {
+        return Some(ts);
+    }
+
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(ts.with_timezone(&chrono::Utc));
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(chrono::Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+fn split_tags(tags: &str, separators: &[char]) -> Vec<String> {
+    tags.split(separators)
+        .map(|tag| tag.split_whitespace().join(" "))
+        .filter(|tag| !tag.is_empty())
+        .unique()
+        .collect()
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.split_whitespace().join(" ");
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+// The Netscape bookmark format is what browsers produce when bookmarks are exported.  The <DT>
+// and <p> elements are never closed, but the html parser nests the <DL> of each folder inside the
+// <DT> that holds the folder's <H3>, so the folders of a bookmark are the headings of the <DT>
+// elements above it.
+pub fn parse_netscape_bookmarks(html: &str) -> Vec<Bookmark> {
+    let document = Html::parse_document(html);
+    let sel = Selector::parse("a[href]").expect("failed to parse selector");
+
+    document
+        .select(&sel)
+        .filter_map(|link| {
+            let element = link.value();
+            let url = element.attr("href")?.trim().to_owned();
+
+            let mut folder = link
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .filter(|ancestor| ancestor.value().name() == "dt")
+                .filter_map(|dt| {
+                    dt.children()
+                        .filter_map(ElementRef::wrap)
+                        .find(|child| child.value().name() == "h3")
+                })
+                .map(text)
+                .filter(|name| !name.is_empty())
+                .collect_vec();
+            folder.reverse();
+
+            Some(Bookmark {
+                added: element.attr("add_date").and_then(timestamp),
+                folder,
+                tags: split_tags(element.attr("tags").unwrap_or_default(), &[',']),
+                title: non_empty(&text(link)),
+                url,
+            })
+        })
+        .collect()
+}
+
+// Pocket's html export is a list of links under "Unread" and "Read Archive" headings, with the
+// date saved and a comma-separated list of tags on each link
+pub fn parse_pocket_bookmarks(html: &str) -> Vec<Bookmark> {
+    let document = Html::parse_document(html);
+    let sel = Selector::parse("a[href]").expect("failed to parse selector");
+
+    document
+        .select(&sel)
+        .filter_map(|link| {
+            let element = link.value();
+            let url = element.attr("href")?.trim().to_owned();
+
+            Some(Bookmark {
+                added: element.attr("time_added").and_then(timestamp),
+                folder: vec![],
+                tags: split_tags(element.attr("tags").unwrap_or_default(), &[',']),
+                title: non_empty(&text(link)),
+                url,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct PinboardPost {
+    #[serde(default)]
+    description: String,
+    href: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    time: String,
+}
+
+// Pinboard's json export is an array of posts with space-separated tags
+pub fn parse_pinboard_bookmarks(json: &str) -> Result<Vec<Bookmark>> {
+    let posts: Vec<PinboardPost> = serde_json::from_str(json)
+        .map_err(|err| Error::Parse(format!("failed to parse pinboard export: {err}")))?;
+
+    let bookmarks = posts
+        .into_iter()
+        .map(|post| Bookmark {
+            added: parse_date(&post.time),
+            folder: vec![],
+            tags: split_tags(&post.tags, &[' ']),
+            title: non_empty(&post.description),
+            url: post.href,
+        })
+        .collect();
+
+    Ok(bookmarks)
+}
+
+// A csv file with a header row naming the url, title, tags and date columns, in any order.  Tags
+// can be separated by commas, semicolons or pipes.  Pocket's newer csv export, which has a
+// "time_added" column, is also accepted.
+pub fn parse_csv_bookmarks(input: &str) -> Result<Vec<Bookmark>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(input.trim_start_matches('\u{feff}').as_bytes());
+
+    let mut records = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|err| Error::Parse(format!("failed to parse csv: {err}")))?;
+        if record.iter().any(|field| !field.trim().is_empty()) {
+            records.push(record.iter().map(str::to_owned).collect_vec());
+        }
+    }
+    let mut records = records.into_iter();
+
+    let header = match records.next() {
+        Some(header) => header,
+        None => return Ok(vec![]),
+    };
+
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|name| names.contains(&name.trim().to_lowercase().as_str()))
+    };
+
+    let url_column = column(&["url", "href", "link"])
+        .ok_or_else(|| Error::Parse("csv file has no url column".into()))?;
+    let title_column = column(&["title", "name", "description"]);
+    let tags_column = column(&["tags", "tag"]);
+    let date_column = column(&["date", "time_added", "added", "created", "time"]);
+
+    let field = |record: &[String], column: Option<usize>| -> String {
+        column
+            .and_then(|i| record.get(i))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let bookmarks = records
+        .filter_map(|record| {
+            let url = field(&record, Some(url_column)).trim().to_owned();
+            if url.is_empty() {
+                return None;
+            }
+
+            Some(Bookmark {
+                added: parse_date(&field(&record, date_column)),
+                folder: vec![],
+                tags: split_tags(&field(&record, tags_column), &[',', ';', '|']),
+                title: non_empty(&field(&record, title_column)),
+                url,
+            })
+        })
+        .collect();
+
+    Ok(bookmarks)
+}
+
+// Tracks what happened to a topic during the import so that an activity record can be written
+// for it
+#[derive(Default)]
+struct TopicImport {
+    child_links: BTreeSet<ExternalId>,
+    child_topics: BTreeSet<ExternalId>,
+    parent_topics: BTreeSet<ExternalId>,
+}
+
+pub struct ImportBookmarks {
+    pub actor: Arc<Viewer>,
+    pub bookmarks: Vec<Bookmark>,
+    pub locale: Locale,
+    pub parent_topic_id: ExternalId,
+    pub repo_id: RepoId,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ImportBookmarksResult {
+    pub links: usize,
+    pub skipped: usize,
+    pub topics_created: usize,
+}
+
+// The objects that have been changed so far, which have not been written yet
+struct Import<'a> {
+    command: &'a ImportBookmarks,
+    date: Timestamp,
+    folders: BTreeMap<(ExternalId, Phrase), ExternalId>,
+    imported_topics: BTreeMap<ExternalId, TopicImport>,
+    links: BTreeMap<ExternalId, (RepoLink, BTreeSet<ExternalId>)>,
+    mutation: &'a Mutation,
+    new_topics: BTreeMap<Phrase, ExternalId>,
+    result: ImportBookmarksResult,
+    topics: BTreeMap<ExternalId, RepoTopic>,
+}
+
+impl ImportBookmarks {
+    pub fn call<S>(&self, mut mutation: Mutation, store: &S) -> Result<ImportBookmarksResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        if !self.actor.can_update(self.repo_id) {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        let mut import = Import {
+            command: self,
+            date: chrono::Utc::now(),
+            folders: BTreeMap::new(),
+            imported_topics: BTreeMap::new(),
+            links: BTreeMap::new(),
+            mutation: &mutation,
+            new_topics: BTreeMap::new(),
+            result: ImportBookmarksResult::default(),
+            topics: BTreeMap::new(),
+        };
+
+        for bookmark in &self.bookmarks {
+            import.add(bookmark)?;
+        }
+
+        let Import {
+            imported_topics,
+            links,
+            result,
+            topics,
+            ..
+        } = import;
+
+        let changes = self.changes(&imported_topics, &links, &topics);
+
+        for topic in topics.values() {
+            mutation.save_topic(self.repo_id, topic)?;
+        }
+
+        for (link, _) in links.values() {
+            mutation.save_link(self.repo_id, link)?;
+        }
+
+        for change in &changes {
+            mutation.add_change(self.repo_id, change)?;
+        }
+
+        mutation.write(store)?;
+
+        log::info!(
+            "{} links imported into {}, {} topics created, {} bookmarks skipped",
+            result.links,
+            self.repo_id,
+            result.topics_created,
+            result.skipped
+        );
+        Ok(result)
+    }
+
+    fn changes(
+        &self,
+        imported_topics: &BTreeMap<ExternalId, TopicImport>,
+        links: &BTreeMap<ExternalId, (RepoLink, BTreeSet<ExternalId>)>,
+        topics: &BTreeMap<ExternalId, RepoTopic>,
+    ) -> Vec<activity::Change> {
+        let date = chrono::Utc::now();
+        let topic_info = |id: &ExternalId| topics.get(id).map(TopicInfo::from);
+        let mut changes = vec![];
+
+        for (topic_id, imported) in imported_topics {
+            let topic = match topics.get(topic_id) {
+                Some(topic) => topic,
+                None => continue,
+            };
+
+            changes.push(activity::Change::ImportTopic(activity::ImportTopic {
+                actor_id: self.actor.user_id.to_owned(),
+                child_links: LinkInfoList::from(
+                    &imported
+                        .child_links
+                        .iter()
+                        .filter_map(|id| links.get(id).map(|(link, _)| LinkInfo::from(link)))
+                        .collect_vec(),
+                ),
+                child_topics: TopicInfoList::from(
+                    &imported
+                        .child_topics
+                        .iter()
+                        .filter_map(topic_info)
+                        .collect_vec(),
+                ),
+                date,
+                id: activity::Change::new_id(),
+                imported_topic: TopicInfo::from(topic),
+                parent_topics: TopicInfoList::from(
+                    &imported
+                        .parent_topics
+                        .iter()
+                        .filter_map(topic_info)
+                        .collect_vec(),
+                ),
+            }));
+        }
+
+        for (link, parent_topics) in links.values() {
+            changes.push(activity::Change::ImportLink(activity::ImportLink {
+                actor_id: self.actor.user_id.to_owned(),
+                date,
+                id: activity::Change::new_id(),
+                imported_link: LinkInfo::from(link),
+                parent_topics: TopicInfoList::from(
+                    &parent_topics.iter().filter_map(topic_info).collect_vec(),
+                ),
+            }));
+        }
+
+        changes
+    }
+}
+
+impl Import<'_> {
+    fn add(&mut self, bookmark: &Bookmark) -> Result<()> {
+        let url = match RepoUrl::parse(&bookmark.url) {
+            Ok(url) if url.normalized.starts_with("http") => url,
+            Ok(_) | Err(_) => {
+                log::info!("skipping bookmark: {}", bookmark.url);
+                self.result.skipped += 1;
+                return Ok(());
+            }
+        };
+
+        let parent_topic_id = self.command.parent_topic_id.to_owned();
+        let mut path = vec![parent_topic_id.to_owned()];
+        for name in &bookmark.folder {
+            let topic_id = self.folder(&path, name)?;
+            path.push(topic_id);
+        }
+
+        // Tags become topics directly under the parent topic.  A tagged link is only placed
+        // under the parent topic itself when it was also found in a folder.
+        let mut parent_ids = vec![];
+        if bookmark.tags.is_empty() || path.len() > 1 {
+            parent_ids.push(path.last().cloned().unwrap_or_else(ExternalId::root_topic));
+        }
+        for tag in &bookmark.tags {
+            let topic_id = self.folder(&[parent_topic_id.to_owned()], tag)?;
+            parent_ids.push(topic_id);
+        }
+
+        for parent_id in &parent_ids {
+            self.add_link(bookmark, &url, parent_id)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_link(
+        &mut self,
+        bookmark: &Bookmark,
+        url: &RepoUrl,
+        parent_id: &ExternalId,
+    ) -> Result<()> {
+        let link_id = url.id()?;
+
+        if !self.links.contains_key(&link_id) {
+            let repo_id = self.command.repo_id;
+
+            // Links that are already in the repo keep their titles.  New links take the title of
+            // the bookmark, or the url when there is none, as the import does not fetch pages.
+            let title = if self.mutation.exists(repo_id, &link_id)? {
+                None
+            } else {
+                Some(
+                    bookmark
+                        .title
+                        .to_owned()
+                        .unwrap_or_else(|| url.normalized.to_owned()),
+                )
+            };
+
+            let upsert = UpsertLink {
+                actor: Arc::clone(&self.command.actor),
+                add_parent_topic_id: None,
+                capture_snapshot: false,
+                fetcher: Box::new(http::NoFetch),
+                fetch_queue: None,
+                repo_id,
+                title,
+                url: url.normalized.to_owned(),
+            };
+
+            let (mut link, previous_title) = upsert.link(self.mutation, url)?;
+            if previous_title.is_none() {
+                link.metadata.added = bookmark.added.unwrap_or(self.date);
+            }
+
+            self.links
+                .insert(link_id.to_owned(), (link, BTreeSet::new()));
+            self.result.links += 1;
+        }
+
+        if let Some((link, parent_topics)) = self.links.get_mut(&link_id) {
+            link.parent_topics.insert(ParentTopic {
+                id: parent_id.to_owned(),
+            });
+            parent_topics.insert(parent_id.to_owned());
+
+            let child = link.to_topic_child(self.date);
+            self.topic(parent_id).children.insert(child);
+        }
+
+        if let Some(imported) = self.imported_topics.get_mut(parent_id) {
+            imported.child_links.insert(link_id);
+        }
+
+        Ok(())
+    }
+
+    // Finds or creates the topic for a folder under the last topic in the path
+    fn folder(&mut self, path: &[ExternalId], name: &str) -> Result<ExternalId> {
+        let parent_id = path.last().cloned().unwrap_or_else(ExternalId::root_topic);
+        let name = name.split_whitespace().join(" ");
+        let phrase = Phrase::parse(&name);
+
+        if let Some(topic_id) = self.folders.get(&(parent_id.to_owned(), phrase.to_owned())) {
+            return Ok(topic_id.to_owned());
+        }
+
+        let topic_id = match self.matching_topic(path, &name, &phrase)? {
+            Some(topic_id) => topic_id,
+            None => self.create_topic(&name, &phrase),
+        };
+
+        let date = self.date;
+        let child = self.topic(&topic_id).to_topic_child(date);
+        self.topic(&parent_id).children.insert(child);
+        self.topic(&topic_id).parent_topics.insert(ParentTopic {
+            id: parent_id.to_owned(),
+        });
+
+        let imported = self.imported_topics.entry(topic_id.to_owned()).or_default();
+        imported.parent_topics.insert(parent_id.to_owned());
+        if let Some(parent) = self.imported_topics.get_mut(&parent_id) {
+            parent.child_topics.insert(topic_id.to_owned());
+        }
+
+        self.folders
+            .insert((parent_id, phrase), topic_id.to_owned());
+        Ok(topic_id)
+    }
+
+    // Existing topics with the same name are reused, so that importing a "Recipes" folder does
+    // not produce a second Recipes topic
+    fn matching_topic(
+        &self,
+        path: &[ExternalId],
+        name: &str,
+        phrase: &Phrase,
+    ) -> Result<Option<ExternalId>> {
+        let repo_id = self.command.repo_id;
+        let is_new = |id: &ExternalId| self.new_topics.values().any(|new_id| new_id == id);
+
+        if let Some(topic_id) = self.new_topics.get(phrase) {
+            if !path.contains(topic_id) {
+                return Ok(Some(topic_id.to_owned()));
+            }
+        }
+
+        let matches =
+            UpsertTopic::find_matches(self.mutation, &RepoIds::from(vec![repo_id]), name)?;
+
+        for synonym_match in matches {
+            let topic_id = synonym_match.repo_topic.topic_id();
+            if path.contains(topic_id) {
+                continue;
+            }
+
+            // Every topic in the path will be above the folder once the import is written, so an
+            // existing topic that is already above any of them would close a cycle.  Topics
+            // created during the import are not yet on disk and have nothing above them there.
+            let mut cycle = false;
+            for ancestor_id in path.iter().filter(|id| !is_new(id)) {
+                if self.mutation.cycle_exists(repo_id, topic_id, ancestor_id)? {
+                    cycle = true;
+                    break;
+                }
+            }
+            if cycle {
+                continue;
+            }
+
+            return Ok(Some(topic_id.to_owned()));
+        }
+
+        Ok(None)
+    }
+
+    fn create_topic(&mut self, name: &str, phrase: &Phrase) -> ExternalId {
+        let topic_id = ExternalId::make();
+
+        let topic = RepoTopic {
+            api_version: API_VERSION.into(),
+            metadata: RepoTopicMetadata {
+                added: self.date,
+                id: topic_id.to_owned(),
+                details: Some(RepoTopicDetails {
+                    root: false,
+                    synonyms: vec![Synonym {
+                        added: self.date,
+                        locale: self.command.locale,
+                        name: name.to_owned(),
+                    }],
+                    timerange: None,
+                }),
+            },
+            parent_topics: BTreeSet::new(),
+            children: BTreeSet::new(),
+        };
+
+        self.topics.insert(topic_id.to_owned(), topic);
+        self.new_topics
+            .insert(phrase.to_owned(), topic_id.to_owned());
+        self.result.topics_created += 1;
+        topic_id
+    }
+
+    fn topic(&mut self, topic_id: &ExternalId) -> &mut RepoTopic {
+        let repo_id = self.command.repo_id;
+        let mutation = self.mutation;
+
+        self.topics.entry(topic_id.to_owned()).or_insert_with(|| {
+            // Topics in other repos are referenced rather than copied
+            mutation
+                .fetch_topic(repo_id, topic_id)
+                .unwrap_or_else(|| RepoTopic::make_reference(topic_id.to_owned()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOKMARKS: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1600000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000001" TAGS="programming,languages">Rust</A>
+        <DT><H3>Cooking &amp; Recipes</H3>
+        <DL><p>
+            <DT><A HREF="https://example.com/bread?a=1&amp;b=2" ADD_DATE="1600000002">Bread</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="https://example.org/">   </A>
+</DL><p>
+"#;
+
+    #[test]
+    fn netscape_format() {
+        let bookmarks = parse_netscape_bookmarks(BOOKMARKS);
+
+        assert_eq!(
+            bookmarks,
+            vec![
+                Bookmark {
+                    added: timestamp("1600000001"),
+                    folder: vec!["Bookmarks bar".into()],
+                    tags: vec!["programming".into(), "languages".into()],
+                    title: Some("Rust".into()),
+                    url: "https://www.rust-lang.org/".into(),
+                },
+                Bookmark {
+                    added: timestamp("1600000002"),
+                    folder: vec!["Bookmarks bar".into(), "Cooking & Recipes".into()],
+                    tags: vec![],
+                    title: Some("Bread".into()),
+                    url: "https://example.com/bread?a=1&b=2".into(),
+                },
+                Bookmark {
+                    added: None,
+                    folder: vec![],
+                    tags: vec![],
+                    title: None,
+                    url: "https://example.org/".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pocket_format() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Pocket Export</title></head><body>
+<h1>Unread</h1>
+<ul>
+<li><a href="https://example.com/a" time_added="1600000000" tags="reading,long reads">Article &amp; more</a></li>
+</ul>
+<h1>Read Archive</h1>
+<ul>
+<li><a href="https://example.com/b" time_added="1600000100" tags="">https://example.com/b</a></li>
+</ul>
+</body></html>"#;
+
+        assert_eq!(ImportFormat::detect(html), ImportFormat::Pocket);
+        assert_eq!(
+            parse_pocket_bookmarks(html),
+            vec![
+                Bookmark {
+                    added: timestamp("1600000000"),
+                    folder: vec![],
+                    tags: vec!["reading".into(), "long reads".into()],
+                    title: Some("Article & more".into()),
+                    url: "https://example.com/a".into(),
+                },
+                Bookmark {
+                    added: timestamp("1600000100"),
+                    folder: vec![],
+                    tags: vec![],
+                    title: Some("https://example.com/b".into()),
+                    url: "https://example.com/b".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_attributes() {
+        let html = "<ul><li><a href=https://example.com/a time_added=1600000000>A</a>\
+            <li><a href='https://example.com/b' tags='x,y'>B <b>bold</b></a></ul>";
+
+        assert_eq!(
+            parse_pocket_bookmarks(html),
+            vec![
+                Bookmark {
+                    added: timestamp("1600000000"),
+                    folder: vec![],
+                    tags: vec![],
+                    title: Some("A".into()),
+                    url: "https://example.com/a".into(),
+                },
+                Bookmark {
+                    added: None,
+                    folder: vec![],
+                    tags: vec!["x".into(), "y".into()],
+                    title: Some("B bold".into()),
+                    url: "https://example.com/b".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pinboard_format() {
+        let json = r#"[
+            {"href":"https://example.com/a","description":"An article","extended":"","meta":"x",
+             "hash":"y","time":"2020-09-13T12:26:40Z","shared":"no","toread":"yes",
+             "tags":"reading  rust"},
+            {"href":"https://example.com/b","description":"","time":"","tags":""}
+        ]"#;
+
+        assert_eq!(ImportFormat::detect(json), ImportFormat::Pinboard);
+        assert_eq!(
+            parse_pinboard_bookmarks(json).unwrap(),
+            vec![
+                Bookmark {
+                    added: timestamp("1600000000"),
+                    folder: vec![],
+                    tags: vec!["reading".into(), "rust".into()],
+                    title: Some("An article".into()),
+                    url: "https://example.com/a".into(),
+                },
+                Bookmark {
+                    added: None,
+                    folder: vec![],
+                    tags: vec![],
+                    title: None,
+                    url: "https://example.com/b".into(),
+                },
+            ]
+        );
+
+        assert!(matches!(
+            parse_pinboard_bookmarks("{}"),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn csv_format() {
+        let csv = "Title,URL,Date,Tags\r\n\
+            \"Bread, and butter\",https://example.com/a,2020-09-13,\"cooking,baking\"\r\n\
+            \"Say \"\"hi\"\"\",https://example.com/b,1600000000,reading|rust\n\
+            \n\
+            No url,,,\n";
+
+        assert_eq!(ImportFormat::detect(csv), ImportFormat::Csv);
+        assert_eq!(
+            parse_csv_bookmarks(csv).unwrap(),
+            vec![
+                Bookmark {
+                    added: parse_date("2020-09-13"),
+                    folder: vec![],
+                    tags: vec!["cooking".into(), "baking".into()],
+                    title: Some("Bread, and butter".into()),
+                    url: "https://example.com/a".into(),
+                },
+                Bookmark {
+                    added: timestamp("1600000000"),
+                    folder: vec![],
+                    tags: vec!["reading".into(), "rust".into()],
+                    title: Some("Say \"hi\"".into()),
+                    url: "https://example.com/b".into(),
+                },
+            ]
+        );
+
+        assert!(matches!(
+            parse_csv_bookmarks("title\nsomething\n"),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let csv = "url,title\n\
+            https://example.com/a,\"First line\nsecond line\"\n\
+            https://example.com/b,\"\"\"Quoted\"\", with a comma\"\n";
+
+        let bookmarks = parse_csv_bookmarks(csv).unwrap();
+        assert_eq!(
+            bookmarks
+                .iter()
+                .map(|bookmark| bookmark.title.to_owned())
+                .collect_vec(),
+            vec![
+                Some("First line second line".to_owned()),
+                Some("\"Quoted\", with a comma".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1600000000"), timestamp("1600000000"));
+        assert_eq!(parse_date("2020-09-13T12:26:40Z"), timestamp("1600000000"));
+        assert_eq!(
+            parse_date("2020-09-13T14:26:40+02:00"),
+            timestamp("1600000000")
+        );
+        assert_eq!(parse_date("2020-09-13"), timestamp("1599955200"));
+        assert_eq!(parse_date("yesterday"), None);
+    }
+}
@@ -2,12 +2,23 @@ use git2;
 use serde::{Deserialize, Serialize};
 use serde_yaml;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use unidecode::unidecode;
 
-use super::{activity, core, Client, GitPaths, Kind, RepoTopic, Search, Synonym, TopicChild};
+use super::closure::{normalize_closure, Closure};
+use super::table::{put_str, Cursor, Table, TableBuilder};
+use super::{
+    activity, core, Client, GitPaths, Kind, ParentTopic, RepoTopic, Search, Synonym, TopicChild,
+};
 use crate::prelude::*;
 
+// The extension of search and synonym indexes in the table format
+pub(crate) const TABLE_EXTENSION: &str = "idx";
+
+// Longer tokens in page text are usually hashes, encoded data or long urls
+const MAX_TEXT_TOKEN_LEN: usize = 40;
+
 // Omit dashes so that we can split on them
 const SPECIAL_CHARS: &[char] = &[
     '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '.', '/', ':', ';', '<', '=', '>',
@@ -37,6 +48,13 @@ impl Phrase {
         Self(normalized)
     }
 
+    // Keys in an index table are phrases that were already normalized when they were written
+    fn from_key(key: Vec<u8>) -> Result<Self> {
+        String::from_utf8(key)
+            .map(Self)
+            .map_err(|_| Error::Repo("bad index table key".into()))
+    }
+
     // Used to derive the filename for an index
     pub fn basename(&self) -> Option<String> {
         self.0.get(0..2).map(|s| s.replace([' '], "+"))
@@ -63,6 +81,15 @@ impl Phrase {
         self.0.len() >= 2
     }
 
+    // The distinct tokens in the readable text of a page, for the full-text index
+    pub fn text_tokens(text: &str) -> BTreeSet<Self> {
+        Self::parse(text)
+            .tokens()
+            .into_iter()
+            .filter(|token| token.0.len() <= MAX_TEXT_TOKEN_LEN)
+            .collect()
+    }
+
     pub fn contains(&self, other: &Self) -> bool {
         self.0.contains(&other.0)
     }
@@ -80,6 +107,31 @@ pub enum IndexType {
     Search,
     SynonymPhrase,
     SynonymToken,
+    Text,
+}
+
+// Parses a serialized index and serializes it again as YAML, so that two copies of an index can
+// be compared without regard to format or to the order in which the entries were written
+pub(crate) fn normalize_index(filename: &str, content: &[u8]) -> Result<String> {
+    let synonyms = filename.starts_with("indexes/synonyms/");
+
+    let normalized = if Table::is_table(content) {
+        let table = Table::parse(content.to_vec())?;
+        if synonyms {
+            serde_yaml::to_string(&SynonymIndexMap::from_table(&table)?)?
+        } else {
+            serde_yaml::to_string(&SearchTokenIndexMap::from_table(&table)?)?
+        }
+    } else if synonyms {
+        serde_yaml::to_string(&serde_yaml::from_slice::<SynonymIndexMap>(content)?)?
+    } else if filename.starts_with("indexes/closure/") {
+        normalize_closure(filename, content)?
+    } else if filename.ends_with("/changes.yaml") {
+        serde_yaml::to_string(&serde_yaml::from_slice::<ActivityIndexMap>(content)?)?
+    } else {
+        serde_yaml::to_string(&serde_yaml::from_slice::<SearchTokenIndexMap>(content)?)?
+    };
+    Ok(normalized)
 }
 
 pub trait Index {
@@ -148,6 +200,24 @@ pub struct SynonymIndexMap {
 }
 
 impl SynonymIndexMap {
+    fn new() -> Self {
+        Self {
+            api_version: API_VERSION.to_owned(),
+            kind: "SynonymIndexMap".to_owned(),
+            synonyms: BTreeMap::new(),
+        }
+    }
+
+    fn from_table(table: &Table) -> Result<Self> {
+        let mut index = Self::new();
+        for (key, value) in table.iter() {
+            index
+                .synonyms
+                .insert(Phrase::from_key(key)?, decode_synonym_entries(value)?);
+        }
+        Ok(index)
+    }
+
     fn full_matches(&self, term: &Phrase) -> BTreeSet<SynonymEntry> {
         self.synonyms
             .get(term)
@@ -165,57 +235,106 @@ impl SynonymIndexMap {
             acc.union(set).cloned().collect()
         })
     }
+
+    fn to_table(&self) -> Vec<u8> {
+        let mut builder = TableBuilder::new();
+        for (phrase, entries) in &self.synonyms {
+            let mut value = vec![];
+            for entry in entries {
+                put_str(&mut value, &entry.name);
+                put_str(&mut value, entry.id.as_str());
+            }
+            builder.add(phrase.0.as_bytes(), &value);
+        }
+        builder.finish()
+    }
 }
 
+fn decode_synonym_entries(value: &[u8]) -> Result<BTreeSet<SynonymEntry>> {
+    let mut cursor = Cursor::new(value);
+    let mut entries = BTreeSet::new();
+    while !cursor.is_empty() {
+        let (Some(name), Some(id)) = (cursor.str(), cursor.str()) else {
+            return Err(Error::Repo("bad synonym index entry".into()));
+        };
+        entries.insert(SynonymEntry {
+            name: name.to_owned(),
+            id: ExternalId::try_from(id)?,
+        });
+    }
+    Ok(entries)
+}
+
+// Indexes in the table format are read in place, and are only decoded into a map when they are
+// about to be changed.  Loaded indexes are shared with the object cache, so a map is copied the
+// first time it is changed.
 #[derive(Clone, Debug)]
-pub struct SynonymIndex {
-    pub filename: PathBuf,
-    pub index: SynonymIndexMap,
+enum Stored<M> {
+    Map(Arc<M>),
+    Table(Arc<Table>),
 }
 
-impl Index for SynonymIndex {
-    fn filename(&self) -> &PathBuf {
-        &self.filename
-    }
+// The table format is written next to where the YAML format used to be, under the same name
+pub(crate) fn table_filename(filename: &Path) -> PathBuf {
+    filename.with_extension(TABLE_EXTENSION)
+}
 
-    fn serialize(&self) -> Result<String> {
-        Ok(serde_yaml::to_string(&self.index)?)
-    }
+#[derive(Clone, Debug)]
+pub struct SynonymIndex {
+    pub filename: PathBuf,
+    index: Stored<SynonymIndexMap>,
+    // Loaded from the YAML format, which is removed when the index is written out again
+    legacy: bool,
 }
 
 impl SynonymIndex {
     pub fn new(filename: &PathBuf) -> Self {
-        Self::make(
-            filename.to_owned(),
-            SynonymIndexMap {
-                api_version: API_VERSION.to_owned(),
-                kind: "SynonymIndexMap".to_owned(),
-                synonyms: BTreeMap::new(),
-            },
-        )
+        Self::make(filename.to_owned(), SynonymIndexMap::new())
     }
 
+    // Prefers the table format, falling back to YAML for indexes that have not been migrated
     pub fn load(filename: &PathBuf, view: &core::View) -> Result<Self> {
-        let index = if view.blob_exists(filename)? {
-            match view.find_blob_by_filename(filename)? {
-                Some(blob) => Self {
-                    filename: filename.to_owned(),
-                    index: blob.try_into()?,
-                },
-                None => Self::new(filename),
-            }
-        } else {
-            Self::new(filename)
+        if let Some(oid) = view.blob_oid_by_filename(&table_filename(filename))? {
+            return Ok(Self {
+                filename: filename.to_owned(),
+                index: Stored::Table(
+                    view.cached(oid, |blob| Table::parse(blob.content().to_vec()))?,
+                ),
+                legacy: false,
+            });
+        }
+
+        let index = match view.blob_oid_by_filename(filename)? {
+            Some(oid) => Self {
+                filename: filename.to_owned(),
+                index: Stored::Map(view.cached(oid, |blob| blob.try_into())?),
+                legacy: true,
+            },
+            None => Self::new(filename),
         };
         Ok(index)
     }
 
     pub fn make(filename: PathBuf, index: SynonymIndexMap) -> Self {
-        Self { filename, index }
+        Self {
+            filename,
+            index: Stored::Map(Arc::new(index)),
+            legacy: false,
+        }
+    }
+
+    fn map_mut(&mut self) -> Result<&mut SynonymIndexMap> {
+        if let Stored::Table(table) = &self.index {
+            self.index = Stored::Map(Arc::new(SynonymIndexMap::from_table(table)?));
+        }
+        match &mut self.index {
+            Stored::Map(map) => Ok(Arc::make_mut(map)),
+            Stored::Table(_) => unreachable!(),
+        }
     }
 
     pub fn add(&mut self, topic_id: &ExternalId, phrase: Phrase, name: &str) -> Result<()> {
-        let paths = self.index.synonyms.entry(phrase).or_default();
+        let paths = self.map_mut()?.synonyms.entry(phrase).or_default();
 
         paths.insert(SynonymEntry {
             name: name.to_owned(),
@@ -226,24 +345,56 @@ impl SynonymIndex {
     }
 
     pub fn full_matches(&self, phrase: &Phrase) -> Result<BTreeSet<SynonymEntry>> {
-        Ok(self.index.full_matches(phrase))
+        match &self.index {
+            Stored::Map(map) => Ok(map.full_matches(phrase)),
+            Stored::Table(table) => match table.get(phrase.0.as_bytes()) {
+                Some(value) => decode_synonym_entries(value),
+                None => Ok(BTreeSet::new()),
+            },
+        }
     }
 
     pub fn remove(&mut self, id: &ExternalId, phrase: Phrase, name: &str) -> Result<()> {
-        if let Some(paths) = self.index.synonyms.get_mut(&phrase) {
+        let map = self.map_mut()?;
+        if let Some(paths) = map.synonyms.get_mut(&phrase) {
             paths.remove(&SynonymEntry {
                 name: name.to_owned(),
                 id: id.to_owned(),
             });
             if paths.is_empty() {
-                self.index.synonyms.remove(&phrase);
+                map.synonyms.remove(&phrase);
             }
         }
         Ok(())
     }
 
     pub fn prefix_matches(&self, token: &Phrase) -> BTreeSet<SynonymEntry> {
-        self.index.prefix_matches(token)
+        match &self.index {
+            Stored::Map(map) => map.prefix_matches(token),
+            Stored::Table(table) => {
+                let mut matches = BTreeSet::new();
+                for (_key, value) in table.prefix(token.0.as_bytes()) {
+                    match decode_synonym_entries(value) {
+                        Ok(entries) => matches.extend(entries),
+                        Err(err) => log::error!("{:?}: {}", self.filename, err),
+                    }
+                }
+                matches
+            }
+        }
+    }
+
+    fn files(&self) -> Result<Vec<(PathBuf, Option<Vec<u8>>)>> {
+        let table = match &self.index {
+            Stored::Map(map) => map.to_table(),
+            Stored::Table(table) => table.as_bytes().to_vec(),
+        };
+
+        let mut files = vec![(table_filename(&self.filename), Some(table))];
+        if self.legacy {
+            files.push((self.filename.to_owned(), None));
+        }
+        Ok(files)
     }
 }
 
@@ -262,7 +413,7 @@ impl From<&TopicChild> for SearchEntry {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchTokenIndexMap {
     api_version: String,
@@ -271,6 +422,24 @@ pub struct SearchTokenIndexMap {
 }
 
 impl SearchTokenIndexMap {
+    fn new() -> Self {
+        Self {
+            api_version: API_VERSION.to_owned(),
+            kind: "SearchTokenIndexMap".to_owned(),
+            tokens: BTreeMap::new(),
+        }
+    }
+
+    fn from_table(table: &Table) -> Result<Self> {
+        let mut index = Self::new();
+        for (key, value) in table.iter() {
+            index
+                .tokens
+                .insert(Phrase::from_key(key)?, decode_search_entries(value)?);
+        }
+        Ok(index)
+    }
+
     fn get(&self, string: &Phrase) -> Option<&BTreeSet<SearchEntry>> {
         self.tokens.get(string)
     }
@@ -289,67 +458,165 @@ impl SearchTokenIndexMap {
         }
         rows
     }
+
+    fn to_table(&self) -> Vec<u8> {
+        let mut builder = TableBuilder::new();
+        for (token, entries) in &self.tokens {
+            let mut value = vec![];
+            for entry in entries {
+                value.push(match entry.kind {
+                    Kind::Link => 0,
+                    Kind::Topic => 1,
+                });
+                put_str(&mut value, entry.id.as_str());
+            }
+            builder.add(token.0.as_bytes(), &value);
+        }
+        builder.finish()
+    }
+}
+
+fn decode_search_entries(value: &[u8]) -> Result<BTreeSet<SearchEntry>> {
+    let mut cursor = Cursor::new(value);
+    let mut entries = BTreeSet::new();
+    while !cursor.is_empty() {
+        let kind = match cursor.byte() {
+            Some(0) => Kind::Link,
+            Some(1) => Kind::Topic,
+            _ => return Err(Error::Repo("bad search index entry".into())),
+        };
+        let Some(id) = cursor.str() else {
+            return Err(Error::Repo("bad search index entry".into()));
+        };
+        entries.insert(SearchEntry {
+            kind,
+            id: ExternalId::try_from(id)?,
+        });
+    }
+    Ok(entries)
 }
 
 #[derive(Debug)]
 pub struct SearchTokenIndex {
     filename: PathBuf,
-    index: SearchTokenIndexMap,
+    index: Stored<SearchTokenIndexMap>,
+    // Loaded from the YAML format, which is removed when the index is written out again
+    legacy: bool,
 }
 
-impl Index for SearchTokenIndex {
-    fn filename(&self) -> &PathBuf {
-        &self.filename
+impl SearchTokenIndex {
+    pub fn new(filename: &PathBuf) -> Self {
+        Self::make(filename.to_owned(), SearchTokenIndexMap::new())
     }
 
-    fn serialize(&self) -> Result<String> {
-        Ok(serde_yaml::to_string(&self.index)?)
-    }
-}
+    // Prefers the table format, falling back to YAML for indexes that have not been migrated
+    pub fn load(filename: &PathBuf, view: &core::View) -> Result<Self> {
+        if let Some(oid) = view.blob_oid_by_filename(&table_filename(filename))? {
+            return Ok(Self {
+                filename: filename.to_owned(),
+                index: Stored::Table(
+                    view.cached(oid, |blob| Table::parse(blob.content().to_vec()))?,
+                ),
+                legacy: false,
+            });
+        }
 
-impl SearchTokenIndex {
-    pub fn new(filename: &PathBuf) -> Self {
-        Self::make(
-            filename.to_owned(),
-            SearchTokenIndexMap {
-                api_version: API_VERSION.to_owned(),
-                kind: "SearchTokenIndexMap".to_owned(),
-                tokens: BTreeMap::new(),
+        let index = match view.blob_oid_by_filename(filename)? {
+            Some(oid) => Self {
+                filename: filename.to_owned(),
+                index: Stored::Map(view.cached(oid, |blob| blob.try_into())?),
+                legacy: true,
             },
-        )
+            None => Self::new(filename),
+        };
+        Ok(index)
+    }
+
+    pub fn from_table(filename: &Path, content: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            filename: filename.to_owned(),
+            index: Stored::Table(Arc::new(Table::parse(content)?)),
+            legacy: false,
+        })
     }
 
     pub fn make(filename: PathBuf, index: SearchTokenIndexMap) -> Self {
-        Self { filename, index }
+        Self {
+            filename,
+            index: Stored::Map(Arc::new(index)),
+            legacy: false,
+        }
+    }
+
+    fn map_mut(&mut self) -> Result<&mut SearchTokenIndexMap> {
+        if let Stored::Table(table) = &self.index {
+            self.index = Stored::Map(Arc::new(SearchTokenIndexMap::from_table(table)?));
+        }
+        match &mut self.index {
+            Stored::Map(map) => Ok(Arc::make_mut(map)),
+            Stored::Table(_) => unreachable!(),
+        }
     }
 
     fn add(&mut self, entry: &SearchEntry, token: Phrase) -> Result<()> {
-        let entries = self.index.tokens.entry(token).or_default();
+        let entries = self.map_mut()?.tokens.entry(token).or_default();
         entries.insert(entry.to_owned());
         Ok(())
     }
 
     pub fn indexed_on(&self, entry: &SearchEntry, token: &Phrase) -> Result<bool> {
-        Ok(match &self.index.get(token) {
-            Some(matches) => matches.contains(entry),
-            None => false,
+        Ok(match &self.index {
+            Stored::Map(map) => match map.get(token) {
+                Some(matches) => matches.contains(entry),
+                None => false,
+            },
+            Stored::Table(table) => match table.get(token.0.as_bytes()) {
+                Some(value) => decode_search_entries(value)?.contains(entry),
+                None => false,
+            },
         })
     }
 
     pub fn prefix_matches(&self, token: &Phrase) -> HashSet<SearchEntry> {
-        self.index.prefix_matches(token)
+        match &self.index {
+            Stored::Map(map) => map.prefix_matches(token),
+            Stored::Table(table) => {
+                let mut rows = HashSet::new();
+                for (_key, value) in table.prefix(token.0.as_bytes()) {
+                    match decode_search_entries(value) {
+                        Ok(entries) => rows.extend(entries),
+                        Err(err) => log::error!("{:?}: {}", self.filename, err),
+                    }
+                }
+                rows
+            }
+        }
     }
 
     fn remove(&mut self, entry: &SearchEntry, token: Phrase) -> Result<()> {
-        if let Some(entries) = self.index.tokens.get_mut(&token) {
+        let map = self.map_mut()?;
+        if let Some(entries) = map.tokens.get_mut(&token) {
             entries.remove(entry);
             if entries.is_empty() {
-                self.index.tokens.remove(&token);
+                map.tokens.remove(&token);
             }
         }
 
         Ok(())
     }
+
+    fn files(&self) -> Result<Vec<(PathBuf, Option<Vec<u8>>)>> {
+        let table = match &self.index {
+            Stored::Map(map) => map.to_table(),
+            Stored::Table(table) => table.as_bytes().to_vec(),
+        };
+
+        let mut files = vec![(table_filename(&self.filename), Some(table))];
+        if self.legacy {
+            files.push((self.filename.to_owned(), None));
+        }
+        Ok(files)
+    }
 }
 
 impl TryInto<activity::Change> for git2::Blob<'_> {
@@ -524,6 +791,8 @@ impl IndexKey {
             IndexType::SynonymToken => {
                 format!("indexes/synonyms/tokens/{}.yaml", self.basename)
             }
+            // Relative to the snapshot store rather than to the repo
+            IndexType::Text => format!("text/{}.yaml", self.basename),
         };
         Ok(PathBuf::from(file_path))
     }
@@ -556,6 +825,15 @@ impl IndexKey {
         }
     }
 
+    // The full-text index is read from the snapshot store of the repo rather than from git
+    pub fn text_index(&self, client: &Client, mode: IndexMode) -> Result<SearchTokenIndex> {
+        let filename = self.index_filename(IndexType::Text)?;
+        match mode {
+            IndexMode::Replace => Ok(SearchTokenIndex::new(&filename)),
+            _ => client.snapshots(self.repo_id).text_index(&filename),
+        }
+    }
+
     pub fn token_index(&self, client: &Client, mode: IndexMode) -> Result<SearchTokenIndex> {
         let filename = self.index_filename(IndexType::Search)?;
         match mode {
@@ -566,6 +844,9 @@ impl IndexKey {
     }
 }
 
+// A file to write to a repo, or to remove from it if there are no contents
+pub type IndexFile = (RepoId, PathBuf, Option<Vec<u8>>);
+
 pub trait SaveChangesForPrefix {
     fn save(
         &self,
@@ -574,24 +855,42 @@ pub trait SaveChangesForPrefix {
     ) -> Result<()>;
 }
 
+impl<T: SaveChangesForPrefix + ?Sized> SaveChangesForPrefix for Arc<T> {
+    fn save(
+        &self,
+        repo_id: RepoId,
+        changes: &HashMap<RepoId, BTreeSet<activity::Change>>,
+    ) -> Result<()> {
+        (**self).save(repo_id, changes)
+    }
+}
+
 pub struct Indexer {
+    closure: Closure,
+    // The full-text index is built from page snapshots and is kept with them, outside of git, so it
+    // is left alone by anything that rebuilds indexes on a copy of a repo that might not have them
+    pub index_text: bool,
     path_activity: HashMap<(RepoId, ExternalId), ActivityIndex>,
     pub mode: IndexMode,
     repo_changes: HashMap<RepoId, BTreeSet<activity::Change>>,
     search_tokens: HashMap<IndexKey, SearchTokenIndex>,
     synonym_phrases: HashMap<IndexKey, SynonymIndex>,
     synonym_tokens: HashMap<IndexKey, SynonymIndex>,
+    text_tokens: HashMap<IndexKey, SearchTokenIndex>,
 }
 
 impl Indexer {
     pub fn new(mode: IndexMode) -> Self {
         Self {
+            closure: Closure::new(mode),
+            index_text: true,
             mode,
             path_activity: HashMap::new(),
             repo_changes: HashMap::new(),
             search_tokens: HashMap::new(),
             synonym_phrases: HashMap::new(),
             synonym_tokens: HashMap::new(),
+            text_tokens: HashMap::new(),
         }
     }
 
@@ -601,10 +900,7 @@ impl Indexer {
         client: &Client,
         change: &activity::Change,
     ) -> Result<()> {
-        for id in change.ids() {
-            let activity = self.id_activity(client, repo_id, id)?;
-            activity.add(change.to_reference());
-        }
+        self.add_activity(repo_id, client, change)?;
 
         let set = self.repo_changes.entry(repo_id).or_default();
         set.insert(change.to_owned());
@@ -612,6 +908,20 @@ impl Indexer {
         Ok(())
     }
 
+    // Adds the change to the activity logs of the objects it touches without saving the change
+    pub fn add_activity(
+        &mut self,
+        repo_id: RepoId,
+        client: &Client,
+        change: &activity::Change,
+    ) -> Result<()> {
+        for id in change.ids() {
+            let activity = self.id_activity(client, repo_id, id)?;
+            activity.add(change.to_reference());
+        }
+        Ok(())
+    }
+
     pub fn id_activity(
         &mut self,
         client: &Client,
@@ -636,12 +946,8 @@ impl Indexer {
             .synonym_phrases
             .entry(key.to_owned())
             .or_insert_with(|| {
-                let filename = key
-                    .index_filename(index_type)
-                    .unwrap_or_else(|_| panic!("no index filename: {key:?}"));
-                client
-                    .fetch_synonym_index(key.repo_id, &filename)
-                    .unwrap_or_else(|_| panic!("no index: {filename:?}"))
+                key.synonym_index(client, index_type, self.mode)
+                    .unwrap_or_else(|_| panic!("no index: {key:?}"))
             });
 
         Ok(index)
@@ -657,12 +963,8 @@ impl Indexer {
             .synonym_tokens
             .entry(key.to_owned())
             .or_insert_with(|| {
-                let filename = key
-                    .index_filename(index_type)
-                    .unwrap_or_else(|_| panic!("no index filename: {key:?}"));
-                client
-                    .fetch_synonym_index(key.repo_id, &filename)
-                    .unwrap_or_else(|_| panic!("no index: {filename:?}"))
+                key.synonym_index(client, index_type, self.mode)
+                    .unwrap_or_else(|_| panic!("no index: {key:?}"))
             });
 
         Ok(index)
@@ -703,6 +1005,16 @@ impl Indexer {
         Ok(())
     }
 
+    // Removes the object from the closure of the repo
+    pub fn remove_parents(
+        &mut self,
+        client: &Client,
+        repo_id: RepoId,
+        id: &ExternalId,
+    ) -> Result<()> {
+        self.closure.remove(client, repo_id, id)
+    }
+
     pub fn remove_searches<'s, S>(
         &mut self,
         client: &Client,
@@ -744,26 +1056,30 @@ impl Indexer {
         Ok(())
     }
 
-    pub fn files(&self) -> Result<Vec<(RepoId, PathBuf, String)>> {
-        let mut files = vec![];
+    pub fn files(&self) -> Result<Vec<IndexFile>> {
+        let mut files = self.closure.files()?;
 
         for (key, index) in &self.search_tokens {
-            files.push((key.repo_id, index.filename().to_owned(), index.serialize()?));
-        }
-
-        for (key, index) in &self.synonym_phrases {
-            files.push((key.repo_id, index.filename().to_owned(), index.serialize()?));
+            for (filename, content) in index.files()? {
+                files.push((key.repo_id, filename, content));
+            }
         }
 
-        for (key, index) in &self.synonym_tokens {
-            files.push((key.repo_id, index.filename().to_owned(), index.serialize()?));
+        let synonym_indexes = self
+            .synonym_phrases
+            .iter()
+            .chain(self.synonym_tokens.iter());
+        for (key, index) in synonym_indexes {
+            for (filename, content) in index.files()? {
+                files.push((key.repo_id, filename, content));
+            }
         }
 
         for ((repo_id, _id), activity_log) in &self.path_activity {
             files.push((
                 *repo_id,
                 activity_log.filename().to_owned(),
-                activity_log.serialize()?,
+                Some(activity_log.serialize()?.into_bytes()),
             ));
         }
 
@@ -773,7 +1089,7 @@ impl Indexer {
                 files.push((
                     repo_id,
                     reference.id.change_filename()?,
-                    serde_yaml::to_string(&change)?,
+                    Some(serde_yaml::to_string(&change)?.into_bytes()),
                 ));
             }
         }
@@ -781,6 +1097,17 @@ impl Indexer {
         Ok(files)
     }
 
+    // The full-text index files, which are written to the snapshot store rather than to git
+    pub fn text_files(&self) -> Result<Vec<IndexFile>> {
+        let mut files = vec![];
+        for (key, index) in &self.text_tokens {
+            for (filename, content) in index.files()? {
+                files.push((key.repo_id, filename, content));
+            }
+        }
+        Ok(files)
+    }
+
     pub fn write_repo_changes<S>(&self, store: &S) -> Result<()>
     where
         S: SaveChangesForPrefix,
@@ -804,6 +1131,33 @@ impl Indexer {
             .or_insert(key.token_index(client, self.mode)?))
     }
 
+    fn text_token_index(
+        &mut self,
+        client: &Client,
+        key: &IndexKey,
+    ) -> Result<&mut SearchTokenIndex> {
+        if !self.text_tokens.contains_key(key) {
+            let index = key.text_index(client, self.mode)?;
+            self.text_tokens.insert(key.to_owned(), index);
+        }
+        Ok(self.text_tokens.get_mut(key).expect("text index loaded"))
+    }
+
+    // Keeps the closure of the repo in step with the parent topics of the object
+    pub fn update_parents(
+        &mut self,
+        client: &Client,
+        repo_id: RepoId,
+        id: &ExternalId,
+        parent_topics: &BTreeSet<ParentTopic>,
+    ) -> Result<()> {
+        let parents = parent_topics
+            .iter()
+            .map(|parent| parent.id.to_owned())
+            .collect();
+        self.closure.set_parents(client, repo_id, id, parents)
+    }
+
     pub fn update(
         &mut self,
         client: &Client,
@@ -839,6 +1193,36 @@ impl Indexer {
         Ok(())
     }
 
+    // Keeps the full-text index in step with the page text saved for a link
+    pub fn update_text(
+        &mut self,
+        client: &Client,
+        repo_id: RepoId,
+        entry: &SearchEntry,
+        before: &BTreeSet<Phrase>,
+        after: &BTreeSet<Phrase>,
+    ) -> Result<()> {
+        for token in before.difference(after) {
+            let key = repo_id.index_key(token)?;
+            self.text_token_index(client, &key)?
+                .remove(entry, token.to_owned())?;
+        }
+
+        let empty = BTreeSet::new();
+        let before = match self.mode {
+            IndexMode::Replace => &empty,
+            _ => before,
+        };
+
+        for token in after.difference(before) {
+            let key = repo_id.index_key(token)?;
+            self.text_token_index(client, &key)?
+                .add(entry, token.to_owned())?;
+        }
+
+        Ok(())
+    }
+
     pub fn update_synonyms(
         &mut self,
         client: &Client,
@@ -848,13 +1232,14 @@ impl Indexer {
     ) -> Result<()> {
         let topic_id = after.topic_id();
 
-        let before = match before {
-            Some(before) => before
+        let before = match (before, self.mode) {
+            (_, IndexMode::Replace) => HashSet::new(),
+            (Some(before), _) => before
                 .prefixed_synonyms()
                 .iter()
                 .cloned()
                 .collect::<HashSet<Synonym>>(),
-            None => HashSet::new(),
+            (None, _) => HashSet::new(),
         };
 
         let after = after
@@ -928,6 +1313,16 @@ mod tests {
         )
     }
 
+    #[test]
+    fn text_tokens() {
+        let long = (0..=MAX_TEXT_TOKEN_LEN).map(|_| "a").collect::<String>();
+        let tokens = Phrase::text_tokens(&format!("The cat saw the other cat. {long}"));
+        assert_eq!(
+            tokens.iter().map(Phrase::to_string).collect_vec(),
+            &["cat", "other", "saw", "the"],
+        );
+    }
+
     #[test]
     fn handling_of_hyphens() {
         let phrase = Phrase::parse("one-two-three");
@@ -942,4 +1337,94 @@ mod tests {
             "flexible hierarchical wraps repel drug resistant gram negative and positive bacteria"
         );
     }
+
+    fn into_table(index: SearchTokenIndex) -> SearchTokenIndex {
+        let Stored::Map(map) = index.index else {
+            panic!("expected a map");
+        };
+        SearchTokenIndex {
+            filename: index.filename,
+            index: Stored::Table(Arc::new(Table::parse(map.to_table()).unwrap())),
+            legacy: false,
+        }
+    }
+
+    #[test]
+    fn search_token_table() {
+        let filename = PathBuf::from("indexes/search/cl.yaml");
+        let topic = SearchEntry {
+            kind: Kind::Topic,
+            id: ExternalId::try_from("00001").unwrap(),
+        };
+        let link = SearchEntry {
+            kind: Kind::Link,
+            id: ExternalId::try_from("00002").unwrap(),
+        };
+
+        let mut index = SearchTokenIndex::new(&filename);
+        index.add(&topic, Phrase::parse("climate")).unwrap();
+        index.add(&link, Phrase::parse("climb")).unwrap();
+        index.add(&link, Phrase::parse("clock")).unwrap();
+
+        let mut index = into_table(index);
+        assert_eq!(
+            index.prefix_matches(&Phrase::parse("clim")),
+            HashSet::from([topic.to_owned(), link.to_owned()])
+        );
+        assert!(index.indexed_on(&link, &Phrase::parse("clock")).unwrap());
+        assert!(!index.indexed_on(&topic, &Phrase::parse("clock")).unwrap());
+
+        // A change decodes the table, and the index is written back out as a table
+        index.remove(&link, Phrase::parse("climb")).unwrap();
+        assert_eq!(
+            index.prefix_matches(&Phrase::parse("clim")),
+            HashSet::from([topic.to_owned()])
+        );
+
+        let files = index.files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, PathBuf::from("indexes/search/cl.idx"));
+        let yaml = normalize_index("indexes/search/cl.idx", files[0].1.as_ref().unwrap()).unwrap();
+        assert!(yaml.contains("climate"));
+        assert!(!yaml.contains("climb"));
+    }
+
+    #[test]
+    fn synonym_table() {
+        let mut index = SynonymIndex::new(&PathBuf::from("indexes/synonyms/tokens/cl.yaml"));
+        let id = ExternalId::try_from("00001").unwrap();
+        index.add(&id, Phrase::parse("climate"), "Climate").unwrap();
+        index
+            .add(&id, Phrase::parse("climate change"), "Climate change")
+            .unwrap();
+
+        let Stored::Map(map) = &index.index else {
+            panic!("expected a map");
+        };
+        let index = SynonymIndex {
+            filename: index.filename.to_owned(),
+            index: Stored::Table(Arc::new(Table::parse(map.to_table()).unwrap())),
+            legacy: false,
+        };
+
+        assert_eq!(
+            index
+                .prefix_matches(&Phrase::parse("clim"))
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect_vec(),
+            &["Climate", "Climate change"]
+        );
+        assert_eq!(
+            index
+                .full_matches(&Phrase::parse("climate change"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(index
+            .full_matches(&Phrase::parse("climat"))
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -1,4 +1,5 @@
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
@@ -10,7 +11,67 @@ use crate::http::{self, RepoUrl};
 use crate::prelude::*;
 
 use super::activity::TopicInfoList;
-use super::{Mutation, RepoLinkDetails};
+use super::{Mutation, RepoLinkDetails, SnapshotRef, SnapshotStore};
+
+struct FetchedPage {
+    snapshot: Option<SnapshotRef>,
+    title: String,
+}
+
+fn fetch_page(
+    fetcher: &(dyn http::Fetch + Send + Sync),
+    url: &RepoUrl,
+    snapshots: Option<SnapshotStore>,
+) -> FetchedPage {
+    let response = match fetcher.fetch(url) {
+        Ok(response) => response,
+        Err(err) => {
+            log::warn!("failed to fetch title for {}: {}", url, err);
+            return FetchedPage {
+                snapshot: None,
+                title: "Failed to fetch title".into(),
+            };
+        }
+    };
+
+    let title = response.title().unwrap_or_else(|| "Missing title".into());
+
+    let snapshot = match snapshots {
+        Some(store) if http::Page::from(url).should_fetch() => {
+            match store.save(&response.snapshot(fetcher)) {
+                Ok(snapshot) => Some(snapshot),
+                Err(err) => {
+                    log::warn!("failed to save snapshot of {}: {}", url, err);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    FetchedPage { snapshot, title }
+}
+
+// A request to fetch the title of a link that was saved without one
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchLinkJob {
+    pub actor_id: String,
+    pub enqueued: Timestamp,
+    pub link_id: ExternalId,
+    pub repo_id: RepoId,
+    pub url: String,
+}
+
+pub trait LinkFetchQueue {
+    fn push(&self, job: &FetchLinkJob) -> Result<()>;
+}
+
+impl<T: LinkFetchQueue + ?Sized> LinkFetchQueue for Arc<T> {
+    fn push(&self, job: &FetchLinkJob) -> Result<()> {
+        (**self).push(job)
+    }
+}
 
 pub struct DeleteLink {
     pub actor: Arc<Viewer>,
@@ -80,6 +141,82 @@ impl DeleteLink {
     }
 }
 
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct FetchLinkTitle {
+    pub actor: Arc<Viewer>,
+    pub capture_snapshot: bool,
+    #[derivative(Debug = "ignore")]
+    pub fetcher: Box<dyn http::Fetch + Send + Sync>,
+    pub job: FetchLinkJob,
+}
+
+pub struct FetchLinkTitleResult {
+    pub link: Option<RepoLink>,
+}
+
+impl FetchLinkTitle {
+    pub fn call<S>(&self, mut mutation: Mutation, store: &S) -> Result<FetchLinkTitleResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        let FetchLinkJob {
+            repo_id, link_id, ..
+        } = &self.job;
+
+        let mut link = match mutation.fetch_link(*repo_id, link_id) {
+            Some(link) if link.is_pending() => link,
+
+            Some(_) => {
+                log::info!("link no longer waiting on a title, skipping: {}", link_id);
+                return Ok(FetchLinkTitleResult { link: None });
+            }
+
+            None => {
+                log::warn!("link not found, skipping: {}", link_id);
+                return Ok(FetchLinkTitleResult { link: None });
+            }
+        };
+
+        let url = RepoUrl::parse(&self.job.url)?;
+        let snapshots = self.capture_snapshot.then(|| mutation.snapshots(*repo_id));
+        let FetchedPage { snapshot, title } = fetch_page(self.fetcher.as_ref(), &url, snapshots);
+        let previous_title = link.title().to_owned();
+
+        match &mut link.metadata.details {
+            Some(details) => title.trim().clone_into(&mut details.title),
+            None => return Err(Error::Repo(format!("no details for link: {link_id}"))),
+        }
+        link.metadata.pending = false;
+        if snapshot.is_some() {
+            link.metadata.snapshot = snapshot;
+        }
+
+        let change = self.change(&link, previous_title);
+        mutation.save_link(*repo_id, &link)?;
+        mutation.add_change(*repo_id, &change)?;
+        mutation.write(store)?;
+
+        Ok(FetchLinkTitleResult { link: Some(link) })
+    }
+
+    fn change(&self, link: &RepoLink, previous_title: String) -> activity::Change {
+        activity::Change::UpsertLink(activity::UpsertLink {
+            add_parent_topic: None,
+            actor_id: self.job.actor_id.to_owned(),
+            date: Utc::now(),
+            id: activity::Change::new_id(),
+            parent_topics: link
+                .parent_topics
+                .iter()
+                .map(|parent| parent.id.to_owned())
+                .collect::<BTreeSet<ExternalId>>(),
+            previous_title: Some(previous_title),
+            upserted_link: activity::LinkInfo::from(link),
+        })
+    }
+}
+
 pub struct UpdateLinkParentTopics {
     pub actor: Arc<Viewer>,
     pub repo_id: RepoId,
@@ -191,8 +328,13 @@ impl UpdateLinkParentTopics {
 pub struct UpsertLink {
     pub actor: Arc<Viewer>,
     pub add_parent_topic_id: Option<ExternalId>,
+    // Keep a copy of the page when it is fetched
+    pub capture_snapshot: bool,
     #[derivative(Debug = "ignore")]
     pub fetcher: Box<dyn http::Fetch + Send + Sync>,
+    // When provided, the title is fetched later by a worker instead of during the request
+    #[derivative(Debug = "ignore")]
+    pub fetch_queue: Option<Box<dyn LinkFetchQueue + Send + Sync>>,
     pub repo_id: RepoId,
     pub title: Option<String>,
     pub url: String,
@@ -210,30 +352,16 @@ impl UpsertLink {
     {
         log::info!("upserting link: {}", self.url);
         let url = RepoUrl::parse(&self.url)?;
-        let link_id = url.id()?;
         let date = Utc::now();
         let mut alerts = vec![];
 
-        let (mut link, previous_title) = self.make_link(&mutation, &link_id, &url)?;
+        let (mut link, previous_title) = self.link(&mutation, &url)?;
 
         if previous_title.is_some() && self.title.is_none() {
             let alert = Alert::Success("This link was found in the selected repo".into());
             alerts.push(alert);
         }
 
-        if let Some(title) = &self.title {
-            match &mut link.metadata.details {
-                Some(extra) => {
-                    title.trim().clone_into(&mut extra.title);
-                }
-
-                None => {
-                    let msg = format!("tried to save the title of a reference: {self:?}");
-                    return Err(Error::Repo(msg));
-                }
-            }
-        }
-
         let (topic, mut parent_topics) = self.maybe_topic(&mut mutation, &link)?;
 
         let change = self.change(&link, &topic, &previous_title, date);
@@ -258,12 +386,68 @@ impl UpsertLink {
         mutation.add_change(self.repo_id, &change)?;
         mutation.write(store)?;
 
+        // The job is enqueued after the link has been written so that the worker can find it.  If
+        // that fails, nothing will come back for the link, so it stops waiting on a title.
+        if link.is_pending() && previous_title.is_none() {
+            if let Err(err) = self.enqueue(&link, &url, date) {
+                log::error!("failed to enqueue title fetch for {}: {}", link_id, err);
+                link.metadata.pending = false;
+                let mut mutation = mutation.next()?;
+                mutation.save_link(self.repo_id, &link)?;
+                mutation.write(store)?;
+
+                alerts.push(Alert::Warning(
+                    "The title of this link could not be fetched".into(),
+                ));
+            }
+        }
+
         Ok(UpsertLinkResult {
             alerts,
             link: Some(link),
         })
     }
 
+    // The link with its new title, along with the title it had before if it was already in the
+    // repo.  Bookmark imports build their links here too, and place them under topics themselves.
+    pub(crate) fn link(
+        &self,
+        mutation: &Mutation,
+        url: &RepoUrl,
+    ) -> Result<(RepoLink, Option<String>)> {
+        let link_id = url.id()?;
+        let (mut link, previous_title) = self.make_link(mutation, &link_id, url)?;
+
+        if let Some(title) = &self.title {
+            match &mut link.metadata.details {
+                Some(extra) => {
+                    title.trim().clone_into(&mut extra.title);
+                    link.metadata.pending = false;
+                }
+
+                None => {
+                    let msg = format!("tried to save the title of a reference: {self:?}");
+                    return Err(Error::Repo(msg));
+                }
+            }
+        }
+
+        Ok((link, previous_title))
+    }
+
+    fn enqueue(&self, link: &RepoLink, url: &RepoUrl, date: Timestamp) -> Result<()> {
+        if let Some(queue) = &self.fetch_queue {
+            queue.push(&FetchLinkJob {
+                actor_id: self.actor.user_id.to_owned(),
+                enqueued: date,
+                link_id: link.id().to_owned(),
+                repo_id: self.repo_id,
+                url: url.normalized.to_owned(),
+            })?;
+        }
+        Ok(())
+    }
+
     fn maybe_topic(
         &self,
         mutation: &mut Mutation,
@@ -346,13 +530,17 @@ impl UpsertLink {
             }
         }
 
-        let title = if let Some(title) = &self.title {
-            title.clone()
+        let (title, pending, snapshot) = if let Some(title) = &self.title {
+            (title.clone(), false, None)
+        } else if self.fetch_queue.is_some() && http::Page::from(url).should_fetch() {
+            // Use the url as a placeholder until the worker has fetched the page
+            (url.normalized.to_owned(), true, None)
         } else {
-            match self.fetcher.fetch(url) {
-                Ok(response) => response.title().unwrap_or_else(|| "Missing title".into()),
-                Err(_) => "Failed to fetch title".into(),
-            }
+            let snapshots = self
+                .capture_snapshot
+                .then(|| mutation.snapshots(self.repo_id));
+            let FetchedPage { snapshot, title } = fetch_page(self.fetcher.as_ref(), url, snapshots);
+            (title, false, snapshot)
         };
 
         let mut parent_topics = BTreeSet::new();
@@ -370,6 +558,8 @@ impl UpsertLink {
                     title,
                     url: url.normalized.to_owned(),
                 }),
+                pending,
+                snapshot,
             },
         };
 
@@ -1,17 +1,57 @@
 pub mod activity;
+mod cache;
+pub use cache::{ObjectCache, ObjectCacheMetrics, DEFAULT_OBJECT_CACHE_SIZE};
 mod checks;
+pub use checks::{Leak, LeakField, LeakedData, LeakedDataResult};
+mod closure;
+pub use closure::ClosureMap;
 pub mod core;
 pub mod testing;
 
 mod account;
 pub use account::{DeleteAccount, EnsurePersonalRepo, EnsurePersonalRepoResult};
 
+mod change_request;
+pub use change_request::{
+    ApplyChangeRequest, ApplyChangeRequestResult, ChangeRequestPayload, ChangeRequestSource,
+    PackageChangeRequest, ProposedAction, ProposedChange, ReviewChangeRequest,
+};
+
 mod client;
 pub use client::{parse_path, Client, DataRoot, GitPaths, Mutation};
 
+mod diff;
+pub use diff::{compare, Diff, DiffEntry, DiffResult, DiffSide};
+
+mod dump;
+pub use dump::{
+    DumpRecord, DumpRepo, DumpRepoResult, RestoreRepo, RestoreRepoResult, DUMP_VERSION,
+};
+
+mod export;
+pub use export::{ExportFormat, ExportTopic, ExportTopicResult};
+
 mod ext;
 pub use ext::{Link, Object, ObjectBuilders, RepoLinkWrapper, RepoTopicWrapper, Synonyms, Topic};
 
+mod fork;
+pub use fork::{ForkProvenance, ForkRepo, ForkRepoResult};
+
+mod fsck;
+pub use fsck::{Finding, Fsck, FsckResult};
+
+mod graph;
+pub use graph::{GraphExport, GraphExportResult, GraphFormat, GraphScope};
+
+mod health;
+pub use health::{CheckLinks, CheckLinksResult, LinkHealth, LinkHealthIndex};
+
+mod import;
+pub use import::{
+    parse_csv_bookmarks, parse_netscape_bookmarks, parse_pinboard_bookmarks,
+    parse_pocket_bookmarks, Bookmark, ImportBookmarks, ImportBookmarksResult, ImportFormat,
+};
+
 mod index;
 pub(crate) use index::{
     ChangeReference, Phrase, SaveChangesForPrefix, SearchTokenIndex, SynonymIndex, SynonymMatch,
@@ -20,19 +60,31 @@ pub use index::{IndexMode, SearchEntry, SynonymEntry};
 
 mod link;
 pub use link::{
-    DeleteLink, DeleteLinkResult, UpdateLinkParentTopics, UpdateLinkParentTopicsResult, UpsertLink,
+    DeleteLink, DeleteLinkResult, FetchLinkJob, FetchLinkTitle, FetchLinkTitleResult,
+    LinkFetchQueue, UpdateLinkParentTopics, UpdateLinkParentTopicsResult, UpsertLink,
     UpsertLinkResult,
 };
 
+mod publish;
+pub use publish::{PublishSite, PublishSiteResult};
+
+mod reindex;
+pub use reindex::{IndexDrift, Reindex, ReindexResult};
+
 mod search;
 pub use search::{
-    FetchTopicLiveSearch, FetchTopicLiveSearchResult, FindMatches, FindMatchesResult,
-    RedisFetchDownSet, Search, SearchMatch, SortKey,
+    CachedFetchDownSet, FetchTopicLiveSearch, FetchTopicLiveSearchResult, FindMatches,
+    FindMatchesResult, Search, SearchMatch, SortKey,
 };
 
+mod snapshot;
+pub use snapshot::{Snapshot, SnapshotRef, SnapshotStore};
+
 mod stats;
 pub use stats::{CacheStats, FetchStats, FetchStatsResult, RepoStats};
 
+mod table;
+
 mod topic;
 pub use topic::{
     DeleteTopic, DeleteTopicResult, OnMatchingSynonym, RemoveTopicTimerange,
@@ -0,0 +1,428 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Client, Kind, RepoLink, RepoTopic};
+use crate::prelude::*;
+
+const STYLE: &str =
+    "body { font-family: sans-serif; margin: 2em auto; max-width: 48em; padding: 0 1em; }
+header { border-bottom: 1px solid #ddd; margin-bottom: 1em; padding-bottom: 1em; }
+nav.parents a { margin-right: 0.5em; }
+ul.synonyms { color: #555; }
+#search-results:empty { display: none; }
+";
+
+// Loads the search index the first time the search box is used and shows the topics and links
+// that contain every word typed
+const SEARCH_SCRIPT: &str = r#"(function () {
+  var root = document.currentScript.getAttribute("data-root");
+  var input = document.getElementById("search");
+  var results = document.getElementById("search-results");
+  var index = null;
+
+  function load() {
+    if (index) {
+      return Promise.resolve(index);
+    }
+    return fetch(root + "search.json")
+      .then(function (response) { return response.json(); })
+      .then(function (entries) { index = entries; return index; });
+  }
+
+  function matches(entry, words) {
+    var text = [entry.name].concat(entry.synonyms).join(" ").toLowerCase();
+    return words.every(function (word) { return text.indexOf(word) !== -1; });
+  }
+
+  input.addEventListener("input", function () {
+    var words = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    load().then(function (entries) {
+      results.innerHTML = "";
+      if (words.length === 0) {
+        return;
+      }
+      entries.filter(function (entry) { return matches(entry, words); })
+        .slice(0, 20)
+        .forEach(function (entry) {
+          var item = document.createElement("li");
+          var link = document.createElement("a");
+          link.href = root + entry.path;
+          link.textContent = entry.name;
+          item.appendChild(link);
+          results.appendChild(item);
+        });
+    });
+  });
+})();
+"#;
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Only urls that are safe to follow become links.  Anything else, such as a javascript: or data:
+// url, is shown as text.
+fn external_link(url: &str, text: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https" | "mailto") => {
+            format!(
+                "<a href=\"{}\">{}</a>",
+                escape(parsed.as_str()),
+                escape(text)
+            )
+        }
+        _ => escape(text),
+    }
+}
+
+fn topic_path(id: &ExternalId) -> String {
+    format!("topics/{id}.html")
+}
+
+fn link_path(id: &ExternalId) -> String {
+    format!("links/{id}.html")
+}
+
+#[derive(Serialize)]
+struct SearchEntry {
+    kind: &'static str,
+    name: String,
+    path: String,
+    synonyms: Vec<String>,
+}
+
+// The topics and links that can be reached from the root topic, which are the ones that will be
+// published
+#[derive(Default)]
+struct Site {
+    links: BTreeMap<ExternalId, RepoLink>,
+    topics: BTreeMap<ExternalId, RepoTopic>,
+}
+
+impl Site {
+    fn load(client: &Client, repo_id: RepoId) -> Result<Self> {
+        let view = client.view(repo_id)?;
+        let mut site = Self::default();
+        let mut queue = VecDeque::from([repo_id.root_topic_id()]);
+
+        while let Some(topic_id) = queue.pop_front() {
+            if site.topics.contains_key(&topic_id) {
+                continue;
+            }
+
+            let topic = match view.topic(&topic_id)? {
+                Some(topic) => topic,
+                None => {
+                    log::warn!("topic not found, skipping: {}", topic_id);
+                    continue;
+                }
+            };
+
+            for child in &topic.children {
+                match child.kind {
+                    Kind::Topic => queue.push_back(child.id.to_owned()),
+                    Kind::Link => {
+                        if site.links.contains_key(&child.id) {
+                            continue;
+                        }
+                        if let Some(link) = view.link(&child.id)? {
+                            site.links.insert(child.id.to_owned(), link);
+                        }
+                    }
+                }
+            }
+
+            site.topics.insert(topic_id, topic);
+        }
+
+        Ok(site)
+    }
+
+    fn parents<'s, I>(&'s self, ids: I, locale: Locale) -> String
+    where
+        I: Iterator<Item = &'s ExternalId>,
+    {
+        let parents = ids
+            .filter_map(|id| self.topics.get(id))
+            .map(|topic| {
+                format!(
+                    "<a href=\"../{}\">{}</a>",
+                    topic_path(topic.topic_id()),
+                    escape(&topic.name(locale))
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if parents.is_empty() {
+            return String::new();
+        }
+
+        format!(
+            "<nav class=\"parents\">Parent topics: {}</nav>\n",
+            parents.join(" ")
+        )
+    }
+
+    fn topic_page(&self, topic: &RepoTopic, locale: Locale) -> String {
+        let name = topic.name(locale);
+        let mut body = self.parents(topic.parent_topics.iter().map(|p| &p.id), locale);
+        body.push_str(&format!("<h1>{}</h1>\n", escape(&name)));
+
+        let synonyms = topic
+            .prefixed_synonyms()
+            .into_iter()
+            .filter(|synonym| synonym.name != name)
+            .map(|synonym| format!("<li>{}</li>", escape(&synonym.name)))
+            .collect::<Vec<_>>();
+        if !synonyms.is_empty() {
+            body.push_str(&format!(
+                "<h2>Also known as</h2>\n<ul class=\"synonyms\">{}</ul>\n",
+                synonyms.join("")
+            ));
+        }
+
+        let mut topics = vec![];
+        let mut links = vec![];
+        for child in &topic.children {
+            if let Some(child) = self.topics.get(&child.id) {
+                topics.push((child.name(locale), child.topic_id()));
+            } else if let Some(child) = self.links.get(&child.id) {
+                links.push((child.title().to_owned(), child));
+            }
+        }
+        topics.sort();
+        links.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if !topics.is_empty() {
+            body.push_str("<h2>Topics</h2>\n<ul class=\"topics\">\n");
+            for (name, id) in topics {
+                body.push_str(&format!(
+                    "<li><a href=\"../{}\">{}</a></li>\n",
+                    topic_path(id),
+                    escape(&name)
+                ));
+            }
+            body.push_str("</ul>\n");
+        }
+
+        if !links.is_empty() {
+            body.push_str("<h2>Links</h2>\n<ul class=\"links\">\n");
+            for (title, link) in links {
+                body.push_str(&format!(
+                    "<li>{} (<a href=\"../{}\">details</a>)</li>\n",
+                    external_link(link.url(), &title),
+                    link_path(link.id())
+                ));
+            }
+            body.push_str("</ul>\n");
+        }
+
+        page(&name, &body)
+    }
+
+    fn link_page(&self, link: &RepoLink, locale: Locale) -> String {
+        let mut body = self.parents(link.parent_topics.iter().map(|p| &p.id), locale);
+        body.push_str(&format!(
+            "<h1>{}</h1>\n<p>{}</p>\n<p>Added {}</p>\n",
+            escape(link.title()),
+            external_link(link.url(), link.url()),
+            link.metadata.added.format("%Y-%m-%d")
+        ));
+        page(link.title(), &body)
+    }
+
+    fn search_index(&self, locale: Locale) -> Vec<SearchEntry> {
+        let topics = self.topics.values().map(|topic| SearchEntry {
+            kind: "topic",
+            name: topic.name(locale),
+            path: topic_path(topic.topic_id()),
+            synonyms: topic
+                .prefixed_synonyms()
+                .into_iter()
+                .map(|synonym| synonym.name)
+                .collect(),
+        });
+
+        let links = self.links.values().map(|link| SearchEntry {
+            kind: "link",
+            name: link.title().to_owned(),
+            path: link_path(link.id()),
+            synonyms: vec![],
+        });
+
+        topics.chain(links).collect()
+    }
+
+    fn sitemap(&self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        let mut buf = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+
+        let paths = self
+            .topics
+            .keys()
+            .map(topic_path)
+            .chain(self.links.keys().map(link_path));
+        for path in paths {
+            buf.push_str(&format!(
+                "  <url><loc>{}</loc></url>\n",
+                escape(&format!("{base_url}/{path}"))
+            ));
+        }
+
+        buf.push_str("</urlset>\n");
+        buf
+    }
+}
+
+// Every page lives one directory down from the root of the site
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{}</title>
+<link rel=\"stylesheet\" href=\"../style.css\">
+</head>
+<body>
+<header>
+<a href=\"../index.html\">Home</a>
+<input type=\"search\" id=\"search\" placeholder=\"Search\" autocomplete=\"off\">
+<ul id=\"search-results\"></ul>
+</header>
+{}<script src=\"../search.js\" data-root=\"../\"></script>
+</body>
+</html>
+",
+        escape(title),
+        body
+    )
+}
+
+// Renders the topics and links that can be reached from the root topic of a repo into a directory
+// of html pages that can be served by any static file host
+pub struct PublishSite {
+    pub base_url: String,
+    pub locale: Locale,
+    pub output: PathBuf,
+    pub repo_id: RepoId,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct PublishSiteResult {
+    pub links: usize,
+    pub topics: usize,
+}
+
+impl PublishSite {
+    pub fn call(&self, client: &Client) -> Result<PublishSiteResult> {
+        if !client.viewer.can_read(self.repo_id) {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        let site = Site::load(client, self.repo_id)?;
+        let output = self.output.as_path();
+        fs::create_dir_all(output.join("topics"))?;
+        fs::create_dir_all(output.join("links"))?;
+
+        for topic in site.topics.values() {
+            let path = output.join(topic_path(topic.topic_id()));
+            fs::write(path, site.topic_page(topic, self.locale))?;
+        }
+
+        for link in site.links.values() {
+            let path = output.join(link_path(link.id()));
+            fs::write(path, site.link_page(link, self.locale))?;
+        }
+
+        self.write_index(output)?;
+        fs::write(output.join("style.css"), STYLE)?;
+        fs::write(output.join("search.js"), SEARCH_SCRIPT)?;
+        fs::write(
+            output.join("search.json"),
+            serde_json::to_string(&site.search_index(self.locale))?,
+        )?;
+        fs::write(output.join("sitemap.xml"), site.sitemap(&self.base_url))?;
+
+        Ok(PublishSiteResult {
+            links: site.links.len(),
+            topics: site.topics.len(),
+        })
+    }
+
+    fn write_index(&self, output: &Path) -> Result<()> {
+        let root = topic_path(&self.repo_id.root_topic_id());
+        let html = format!(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta http-equiv=\"refresh\" content=\"0; url={root}\">
+<link rel=\"canonical\" href=\"{root}\">
+</head>
+<body><a href=\"{root}\">Continue</a></body>
+</html>
+"
+        );
+        fs::write(output.join("index.html"), html)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sitemap() {
+        let mut site = Site::default();
+        let topic = RepoTopic::make_reference(ExternalId::try_from("00001").unwrap());
+        site.topics.insert(topic.topic_id().to_owned(), topic);
+
+        assert_eq!(
+            site.sitemap("https://example.com/wiki/"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n  \
+            <url><loc>https://example.com/wiki/topics/00001.html</loc></url>\n\
+            </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape(r#"<b>"Fish" & chips</b>"#),
+            "&lt;b&gt;&quot;Fish&quot; &amp; chips&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn unsafe_urls_not_linked() {
+        assert_eq!(
+            external_link("https://example.com/?a=1&b=2", "Example"),
+            "<a href=\"https://example.com/?a=1&amp;b=2\">Example</a>"
+        );
+        assert_eq!(
+            external_link("mailto:someone@example.com", "Email"),
+            "<a href=\"mailto:someone@example.com\">Email</a>"
+        );
+        assert_eq!(
+            external_link(" JavaScript:alert(1)", "<b>Click</b>"),
+            "&lt;b&gt;Click&lt;/b&gt;"
+        );
+        assert_eq!(
+            external_link("data:text/html,<script>alert(1)</script>", "Data"),
+            "Data"
+        );
+    }
+}
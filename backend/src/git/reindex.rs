@@ -0,0 +1,240 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::index::{normalize_index, TABLE_EXTENSION};
+use super::{activity, Client, IndexMode, SaveChangesForPrefix};
+use crate::prelude::*;
+
+// The directories holding the indexes that can be rebuilt from the objects and changes in a repo.
+// Link health is recorded by the link checker and cannot be rebuilt, so it is left alone.  The
+// full-text index is kept outside of git with the page snapshots it is built from, and is left
+// alone as well.
+const INDEX_DIRS: [&str; 3] = ["indexes/closure/", "indexes/search/", "indexes/synonyms/"];
+
+fn is_activity_log(path: &str) -> bool {
+    path.starts_with("objects/") && path.ends_with("/changes.yaml")
+}
+
+fn is_rebuilt(path: &str) -> bool {
+    INDEX_DIRS.iter().any(|dir| path.starts_with(dir)) || is_activity_log(path)
+}
+
+// A difference between an index as committed and as rebuilt from the objects in the repo
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase", tag = "drift")]
+pub enum IndexDrift {
+    // The index is needed but has not been committed
+    Missing { path: String },
+    // The committed index is not needed by anything in the repo
+    Stale { path: String },
+    // The committed index has different entries from the rebuilt one
+    Changed { path: String },
+    // The committed index has the right entries but is in the older YAML format
+    Legacy { path: String },
+    // An index, object or change that cannot be parsed
+    Unreadable { path: String, error: String },
+}
+
+impl std::fmt::Display for IndexDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { path } => write!(f, "{path} is missing"),
+            Self::Stale { path } => write!(f, "{path} is not needed by any object"),
+            Self::Changed { path } => write!(f, "{path} differs from the rebuilt index"),
+            Self::Legacy { path } => write!(f, "{path} has not been moved to the table format"),
+            Self::Unreadable { path, error } => write!(f, "{path} cannot be read: {error}"),
+        }
+    }
+}
+
+// Regenerates the search, synonym and closure indexes of a repo from its objects, and the
+// activity logs from its changes.  The indexes are otherwise only ever updated incrementally, so
+// this is how drift is found and repaired.  Search and synonym indexes still in YAML are written
+// back out as tables, which is how a repo is moved over to the table format.  With `verify`, the
+// rebuilt indexes are compared with the committed ones and nothing is written.
+pub struct Reindex {
+    pub repo_id: RepoId,
+    pub verify: bool,
+}
+
+#[derive(Debug)]
+pub struct ReindexResult {
+    pub drift: Vec<IndexDrift>,
+    pub indexes: usize,
+}
+
+impl Reindex {
+    pub fn call<S>(&self, client: &Client, store: &S) -> Result<ReindexResult>
+    where
+        S: SaveChangesForPrefix,
+    {
+        let allowed = if self.verify {
+            client.viewer.can_read(self.repo_id)
+        } else {
+            client.viewer.can_update(self.repo_id)
+        };
+        if !allowed {
+            return Err(Error::NotFound(format!("not found: {}", self.repo_id)));
+        }
+
+        let view = client.view(self.repo_id)?;
+        let mut mutation = client.mutation(IndexMode::Replace)?.without_text_index();
+        let mut unreadable_objects = vec![];
+        let mut unreadable_changes = vec![];
+
+        for (path, oid) in view.object_oids()? {
+            match view.object_by_oid(oid) {
+                Ok(object) => mutation.index_object(self.repo_id, &object)?,
+                Err(err) => unreadable_objects.push(IndexDrift::Unreadable {
+                    path,
+                    error: err.to_string(),
+                }),
+            }
+        }
+
+        let mut changes = vec![];
+        let change_oids =
+            view.blob_oids(|path| path.starts_with("changes/") && path.ends_with("/change.yaml"))?;
+        for (path, oid) in change_oids {
+            let change: Result<activity::Change> = view.repo.inner.find_blob(oid)?.try_into();
+            match change {
+                Ok(change) => changes.push(change),
+                Err(err) => unreadable_changes.push(IndexDrift::Unreadable {
+                    path,
+                    error: err.to_string(),
+                }),
+            }
+        }
+
+        // An activity log cannot be rebuilt without all of the changes that went into it, so the
+        // activity logs are left as they are if any of the changes cannot be read
+        if unreadable_changes.is_empty() {
+            for change in &changes {
+                mutation.index_change(self.repo_id, change)?;
+            }
+        }
+        let keep = |path: &str| unreadable_changes.is_empty() || !is_activity_log(path);
+
+        let rebuilt = mutation
+            .index_files()?
+            .into_iter()
+            .filter(|(repo_id, _, _)| *repo_id == self.repo_id)
+            .filter_map(|(_, filename, content)| Some((filename.display().to_string(), content?)))
+            .filter(|(path, _)| keep(path))
+            .collect::<BTreeMap<_, _>>();
+        let committed = view.blob_oids(|path| is_rebuilt(path) && keep(path))?;
+
+        let mut drift = vec![];
+        for (path, content) in &rebuilt {
+            // An index that has not yet been moved to the table format is compared under its
+            // older name
+            let legacy_path = Path::new(path).with_extension("yaml").display().to_string();
+            let (committed_path, oid) = match committed.get(path) {
+                Some(oid) => (path, oid),
+                None => match committed.get(&legacy_path) {
+                    Some(oid) => (&legacy_path, oid),
+                    None => {
+                        drift.push(IndexDrift::Missing {
+                            path: path.to_owned(),
+                        });
+                        continue;
+                    }
+                },
+            };
+
+            let blob = view.repo.inner.find_blob(*oid)?;
+            match normalize_index(committed_path, blob.content()) {
+                Ok(before) => {
+                    if before != normalize_index(path, content)? {
+                        drift.push(IndexDrift::Changed {
+                            path: committed_path.to_owned(),
+                        });
+                    } else if committed_path != path {
+                        drift.push(IndexDrift::Legacy {
+                            path: committed_path.to_owned(),
+                        });
+                    }
+                }
+                Err(err) => drift.push(IndexDrift::Unreadable {
+                    path: committed_path.to_owned(),
+                    error: err.to_string(),
+                }),
+            }
+        }
+
+        for path in committed.keys() {
+            // Left in place for now if it is about to be replaced by a table
+            let table_path = Path::new(path)
+                .with_extension(TABLE_EXTENSION)
+                .display()
+                .to_string();
+            let replaced =
+                rebuilt.contains_key(&table_path) && !committed.contains_key(&table_path);
+            if !rebuilt.contains_key(path) && !replaced {
+                drift.push(IndexDrift::Stale {
+                    path: path.to_owned(),
+                });
+            }
+        }
+
+        let needs_write = drift
+            .iter()
+            .any(|finding| !matches!(finding, IndexDrift::Unreadable { .. }));
+        let objects_unreadable = !unreadable_objects.is_empty();
+        drift.extend(unreadable_objects);
+        drift.extend(unreadable_changes);
+        drift.sort();
+        log::info!(
+            "found {} problems with the indexes of {}",
+            drift.len(),
+            self.repo_id
+        );
+
+        if self.verify || !needs_write {
+            return Ok(ReindexResult {
+                drift,
+                indexes: rebuilt.len(),
+            });
+        }
+
+        // Writing now would drop the index entries of the objects that could not be read
+        if objects_unreadable {
+            return Err(Error::Repo(format!(
+                "some objects in {} cannot be read, so its indexes will not be rebuilt",
+                self.repo_id
+            )));
+        }
+
+        // Indexes in the older format are replaced by the rebuilt ones
+        for path in committed.keys() {
+            if !rebuilt.contains_key(path) {
+                mutation.remove_file(self.repo_id, Path::new(path))?;
+            }
+        }
+        mutation.write(store)?;
+        log::info!("rebuilt {} indexes in {}", rebuilt.len(), self.repo_id);
+
+        Ok(ReindexResult {
+            drift,
+            indexes: rebuilt.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuilt_paths() {
+        assert!(is_rebuilt("indexes/search/abc.yaml"));
+        assert!(is_rebuilt("indexes/search/abc.idx"));
+        assert!(is_rebuilt("indexes/closure/12/34/5678.yaml"));
+        assert!(is_rebuilt("indexes/synonyms/phrases/abc.yaml"));
+        assert!(is_rebuilt("objects/12/34/5678/changes.yaml"));
+        assert!(!is_rebuilt("indexes/health/abc.yaml"));
+        assert!(!is_rebuilt("objects/12/34/5678/object.yaml"));
+        assert!(!is_rebuilt("changes/12/34/5678/change.yaml"));
+    }
+}
@@ -38,6 +38,7 @@ pub struct SearchTopicSpec {
 }
 
 const ID_PATTERN: &str = r"^in:[\w-]+$";
+const TEXT_PREFIX: &str = "text:";
 
 impl SearchTopicSpec {
     fn valid_path_spec(input: &str) -> bool {
@@ -68,12 +69,18 @@ pub struct Search {
     pub normalized: Phrase,
     pub urls: BTreeSet<RepoUrl>,
     pub tokens: BTreeSet<Phrase>,
+    // Words that must appear in the saved text of a linked page, e.g., "text:photosynthesis"
+    pub text_tokens: BTreeSet<Phrase>,
     pub topic_specs: BTreeSet<SearchTopicSpec>,
 }
 
 impl std::cmp::Ord for Search {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        (&self.urls, &self.tokens).cmp(&(&other.urls, &other.tokens))
+        (&self.urls, &self.tokens, &self.text_tokens).cmp(&(
+            &other.urls,
+            &other.tokens,
+            &other.text_tokens,
+        ))
     }
 }
 
@@ -89,16 +96,25 @@ impl Search {
             normalized: Phrase::parse(""),
             urls: BTreeSet::new(),
             tokens: BTreeSet::new(),
+            text_tokens: BTreeSet::new(),
             topic_specs: BTreeSet::new(),
         }
     }
 
     pub fn parse(input: &str) -> Result<Self> {
         let mut tokens = BTreeSet::new();
+        let mut text_tokens = BTreeSet::new();
         let mut urls = BTreeSet::new();
         let mut topic_specs = BTreeSet::new();
 
         for part in input.split_whitespace() {
+            if let Some(text) = part.strip_prefix(TEXT_PREFIX) {
+                if !text.is_empty() {
+                    text_tokens.extend(Phrase::parse(text).tokens());
+                    continue;
+                }
+            }
+
             if SearchTopicSpec::valid_path_spec(part) {
                 if let Ok(spec) = SearchTopicSpec::parse(part) {
                     topic_specs.insert(spec);
@@ -121,17 +137,24 @@ impl Search {
         Ok(Self {
             normalized: Phrase::parse(input),
             topic_specs,
+            text_tokens,
             tokens,
             urls,
         })
     }
 
     pub fn is_empty(&self) -> bool {
-        self.urls.is_empty() && self.tokens.is_empty() && self.topic_specs.is_empty()
+        self.urls.is_empty()
+            && self.tokens.is_empty()
+            && self.text_tokens.is_empty()
+            && self.topic_specs.is_empty()
     }
 
     pub fn topics_only(&self) -> bool {
-        self.urls.is_empty() && self.tokens.is_empty() && !self.topic_specs.is_empty()
+        self.urls.is_empty()
+            && self.tokens.is_empty()
+            && self.text_tokens.is_empty()
+            && !self.topic_specs.is_empty()
     }
 }
 
@@ -279,18 +302,18 @@ impl Filter {
     }
 }
 
-pub struct RedisFetchDownSet {
+pub struct CachedFetchDownSet {
+    pub cache: Arc<dyn redis::Cache>,
     pub client: Arc<Client>,
-    pub redis: Arc<redis::Redis>,
 }
 
-impl Downset for RedisFetchDownSet {
+impl Downset for CachedFetchDownSet {
     fn intersection(&self, topic_paths: &[TopicPath]) -> Result<HashSet<ExternalId>> {
-        self.redis.intersection(self, topic_paths)
+        self.cache.intersection(self, topic_paths)
     }
 
     fn downset(&self, path: &TopicPath) -> HashSet<ExternalId> {
-        self.client.downset(path).collect::<HashSet<ExternalId>>()
+        self.client.downset_ids(path)
     }
 }
 
@@ -367,17 +390,20 @@ impl FindMatches {
         let mut count: usize = 0;
 
         for &repo_id in self.viewer.read_repo_ids.iter() {
-            let mut iter = self.search.tokens.iter();
-
-            if let Some(token) = iter.next() {
-                let mut prefix_matches = client.search_token_prefix_matches(repo_id, token)?;
-
-                for token in iter {
-                    let other = client.search_token_prefix_matches(repo_id, token)?;
-                    prefix_matches.retain(|e| other.contains(e));
+            let title_matches = self.token_matches(&self.search.tokens, |token| {
+                client.search_token_prefix_matches(repo_id, token)
+            })?;
+            let text_matches = self.token_matches(&self.search.text_tokens, |token| {
+                client.text_token_prefix_matches(repo_id, token)
+            })?;
+
+            match (title_matches, text_matches) {
+                (Some(mut title_matches), Some(text_matches)) => {
+                    title_matches.retain(|e| text_matches.contains(e));
+                    entries.extend(title_matches);
                 }
-
-                entries.extend(prefix_matches);
+                (Some(matches), None) | (None, Some(matches)) => entries.extend(matches),
+                (None, None) => {}
             }
 
             for entry in entries.iter() {
@@ -402,6 +428,30 @@ impl FindMatches {
             .into_matches(&self.search, self.locale, self.limit)
     }
 
+    // Entries that match every token, or None if there are no tokens to match
+    fn token_matches<M>(
+        &self,
+        tokens: &BTreeSet<Phrase>,
+        prefix_matches: M,
+    ) -> Result<Option<HashSet<SearchEntry>>>
+    where
+        M: Fn(&Phrase) -> Result<HashSet<SearchEntry>>,
+    {
+        let mut iter = tokens.iter();
+
+        let mut matches = match iter.next() {
+            Some(token) => prefix_matches(token)?,
+            None => return Ok(None),
+        };
+
+        for token in iter {
+            let other = prefix_matches(token)?;
+            matches.retain(|e| other.contains(e));
+        }
+
+        Ok(Some(matches))
+    }
+
     fn fetch_downset<F>(&self, client: &Client, fetch: &F) -> Result<BTreeSet<SearchMatch>>
     where
         F: Downset,
@@ -650,6 +700,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn text_search() {
+        let s = Search::parse("text:Photosynthesis text:light-dependent plants").unwrap();
+        assert_eq!(
+            s.text_tokens,
+            phrases(&["photosynthesis", "light", "dependent"])
+        );
+        assert_eq!(s.tokens, phrases(&["plants"]));
+        assert!(!s.is_empty());
+
+        let s = Search::parse("text:").unwrap();
+        assert_eq!(s.text_tokens.len(), 0);
+        assert_eq!(s.tokens, phrases(&["text"]));
+    }
+
     #[test]
     fn bad_id() {
         let s = Search::parse("in:so").unwrap();
@@ -0,0 +1,202 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::index::{table_filename, SearchTokenIndex};
+use super::DataRoot;
+use crate::http;
+use crate::prelude::*;
+use crate::types::sha256_base64;
+
+// The length of a sha256 digest in unpadded base64url
+const KEY_LENGTH: usize = 43;
+
+fn is_key(key: &str) -> bool {
+    key.len() == KEY_LENGTH
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+// Points at the blobs holding a snapshot of the page a link was saved from
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRef {
+    pub captured: Timestamp,
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub captured: Timestamp,
+    pub html: String,
+    pub text: String,
+}
+
+// A content-addressed store that sits alongside the objects, changes and indexes of a repo.  Blobs
+// are kept out of Git history so that large pages do not bloat the repo, and each repo has its own
+// store so that a snapshot is only visible to those who can read the link that points to it.  The
+// full-text index is built from the page text in the store and is kept here as well, under
+// "text/", so that saving a link does not commit a change to every index file its words fall in.
+#[derive(Clone, Debug)]
+pub struct SnapshotStore {
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: &DataRoot, repo_id: RepoId) -> Self {
+        Self {
+            path: root.repo_path(repo_id).join(".snapshots"),
+        }
+    }
+
+    // Keys come from the objects in a repo, which anyone who can write to the repo can edit, so
+    // anything that is not a digest is turned away before it gets near the filesystem
+    fn blob_path(&self, key: &str) -> Result<PathBuf> {
+        if !is_key(key) {
+            return Err(Error::Path(format!("bad snapshot key: {key}")));
+        }
+        Ok(self.path.join(&key[0..2]).join(&key[2..4]).join(&key[4..]))
+    }
+
+    pub fn fetch(&self, reference: &SnapshotRef) -> Result<Snapshot> {
+        Ok(Snapshot {
+            captured: reference.captured,
+            html: self.read_blob(&reference.html)?,
+            text: self.read_blob(&reference.text)?,
+        })
+    }
+
+    fn read_blob(&self, key: &str) -> Result<String> {
+        let path = self.blob_path(key)?;
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::NotFound(format!("snapshot blob: {key}")))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, snapshot: &http::Snapshot) -> Result<SnapshotRef> {
+        Ok(SnapshotRef {
+            captured: Utc::now(),
+            html: self.write_blob(&snapshot.html)?,
+            text: self.write_blob(&snapshot.text)?,
+        })
+    }
+
+    pub fn text_index(&self, filename: &Path) -> Result<SearchTokenIndex> {
+        let path = self.path.join(table_filename(filename));
+        match fs::read(path) {
+            Ok(content) => SearchTokenIndex::from_table(filename, content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(SearchTokenIndex::new(&filename.to_owned()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Removes the index file if there is no content
+    pub fn write_text_index(&self, filename: &Path, content: Option<&[u8]>) -> Result<()> {
+        let path = self.path.join(filename);
+        match content {
+            Some(content) => {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                // Searches read the index while it is being written
+                let partial = path.with_extension("partial");
+                fs::write(&partial, content)?;
+                fs::rename(&partial, &path)?;
+            }
+            None => match fs::remove_file(&path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    fn write_blob(&self, content: &str) -> Result<String> {
+        let key = sha256_base64(content);
+        let path = self.blob_path(&key)?;
+
+        // The same content is only stored once
+        if !path.exists() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&path, content)?;
+        }
+
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, SnapshotStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = DataRoot::new(dir.path().to_owned());
+        let store = SnapshotStore::new(&root, RepoId::wiki());
+        (dir, store)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_dir, store) = store();
+        let reference = store
+            .save(&http::Snapshot {
+                html: "<p>Some text</p>".into(),
+                text: "Some text".into(),
+            })
+            .unwrap();
+
+        let snapshot = store.fetch(&reference).unwrap();
+        assert_eq!(snapshot.html, "<p>Some text</p>");
+        assert_eq!(snapshot.text, "Some text");
+    }
+
+    #[test]
+    fn content_addressed() {
+        let (_dir, store) = store();
+        let snapshot = http::Snapshot {
+            html: "Some text".into(),
+            text: "Some text".into(),
+        };
+
+        let reference = store.save(&snapshot).unwrap();
+        assert_eq!(reference.html, reference.text);
+        assert_eq!(reference.html, sha256_base64("Some text"));
+    }
+
+    #[test]
+    fn bad_keys_rejected() {
+        let (dir, store) = store();
+        std::fs::write(dir.path().join("secret"), "secret").unwrap();
+
+        for key in [
+            "../../../secret",
+            "/etc/passwd",
+            // The same length as a real key
+            "../../../../../../../../../../../..//secret",
+            "short",
+            "",
+        ] {
+            let reference = SnapshotRef {
+                captured: Utc::now(),
+                html: key.to_owned(),
+                text: sha256_base64("Some text"),
+            };
+            assert!(
+                matches!(store.fetch(&reference), Err(Error::Path(_))),
+                "{key}"
+            );
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use super::Client;
 use crate::prelude::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RepoStats {
     pub computing: bool,
     pub link_count: Option<usize>,
@@ -48,6 +48,16 @@ pub trait CacheStats {
     fn save(&self, repo_id: RepoId, oid: &str, stats: &RepoStats, ttl: Option<u32>) -> Result<()>;
 }
 
+impl<T: CacheStats + ?Sized> CacheStats for Arc<T> {
+    fn fetch(&self, repo_id: RepoId, oid: &str) -> Result<Option<RepoStats>> {
+        (**self).fetch(repo_id, oid)
+    }
+
+    fn save(&self, repo_id: RepoId, oid: &str, stats: &RepoStats, ttl: Option<u32>) -> Result<()> {
+        (**self).save(repo_id, oid, stats, ttl)
+    }
+}
+
 pub struct FetchStats {
     pub viewer: Arc<Viewer>,
 }
@@ -0,0 +1,346 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+// A sorted table of keys and values held in a single buffer, for indexes that are read far more
+// often than they are written.  Each key is stored as the suffix that differs from the key before
+// it, with a full key every RESTART_INTERVAL entries.  A lookup binary searches the full keys and
+// then scans a handful of entries, so that nothing else in the table needs to be decoded.
+//
+//   table:  magic | restart count (u32) | restart offsets (u32 each) | entries
+//   entry:  shared key length | suffix length | suffix | value length | value
+//
+// Lengths within entries are varints, and the numbers in the header are little-endian.
+const MAGIC: &[u8; 4] = b"DGT1";
+const HEADER_LEN: usize = 8;
+const RESTART_INTERVAL: usize = 16;
+
+pub(crate) fn put_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+pub(crate) fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_varint(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+// Reads the values written by put_varint and put_str.  Each method returns None if the buffer
+// ends early or holds something else.
+pub(crate) struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    pub fn str(&mut self) -> Option<&'a str> {
+        let len = self.varint()? as usize;
+        std::str::from_utf8(self.bytes(len)?).ok()
+    }
+
+    pub fn varint(&mut self) -> Option<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(n);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TableBuilder {
+    count: usize,
+    data: Vec<u8>,
+    last_key: Vec<u8>,
+    restarts: Vec<u32>,
+}
+
+impl TableBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keys must be added in ascending order
+    pub fn add(&mut self, key: &[u8], value: &[u8]) {
+        debug_assert!(self.count == 0 || key > self.last_key.as_slice());
+
+        let shared = if self.count.is_multiple_of(RESTART_INTERVAL) {
+            self.restarts.push(self.data.len() as u32);
+            0
+        } else {
+            key.iter()
+                .zip(&self.last_key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+
+        put_varint(&mut self.data, shared as u64);
+        put_varint(&mut self.data, (key.len() - shared) as u64);
+        self.data.extend_from_slice(&key[shared..]);
+        put_varint(&mut self.data, value.len() as u64);
+        self.data.extend_from_slice(value);
+
+        self.last_key = key.to_vec();
+        self.count += 1;
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut table = Vec::with_capacity(HEADER_LEN + 4 * self.restarts.len() + self.data.len());
+        table.extend_from_slice(MAGIC);
+        table.extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+        for offset in &self.restarts {
+            table.extend_from_slice(&offset.to_le_bytes());
+        }
+        table.extend_from_slice(&self.data);
+        table
+    }
+}
+
+// Cloning a table does not copy its contents, so it can be handed out by the object cache
+#[derive(Clone, Debug)]
+pub(crate) struct Table {
+    bytes: Arc<Vec<u8>>,
+    restart_count: usize,
+}
+
+impl Table {
+    pub fn is_table(content: &[u8]) -> bool {
+        content.starts_with(MAGIC)
+    }
+
+    // Only the header is checked here.  A problem further on ends a scan early and is logged.
+    pub fn parse(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || !Self::is_table(&bytes) {
+            return Err(Error::Repo("not an index table".into()));
+        }
+
+        let restart_count = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        if bytes.len() < HEADER_LEN + 4 * restart_count {
+            return Err(Error::Repo("index table is truncated".into()));
+        }
+
+        Ok(Self {
+            bytes: Arc::new(bytes),
+            restart_count,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.bytes[HEADER_LEN + 4 * self.restart_count..]
+    }
+
+    fn restart(&self, i: usize) -> usize {
+        let start = HEADER_LEN + 4 * i;
+        let offset = &self.bytes[start..start + 4];
+        u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize
+    }
+
+    pub fn get<'a>(&'a self, key: &'a [u8]) -> Option<&'a [u8]> {
+        self.seek(key)
+            .next()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> Entries<'_> {
+        Entries::new(self.data(), 0)
+    }
+
+    // The entries whose keys start with the prefix, in order
+    pub fn prefix<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = (Vec<u8>, &'a [u8])> {
+        self.seek(prefix)
+            .take_while(move |(key, _)| key.starts_with(prefix))
+    }
+
+    // Starts at the first entry whose key is not less than the key given
+    fn seek<'a>(&'a self, key: &'a [u8]) -> impl Iterator<Item = (Vec<u8>, &'a [u8])> {
+        // Find the first restart point whose key is not less than the key given.  The entry
+        // sought is either there or in the block before it.
+        let (mut lo, mut hi) = (0, self.restart_count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match Entries::new(self.data(), self.restart(mid)).next() {
+                Some((k, _)) if k.as_slice() < key => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+
+        let start = if lo == 0 { 0 } else { self.restart(lo - 1) };
+        Entries::new(self.data(), start).skip_while(move |(k, _)| k.as_slice() < key)
+    }
+}
+
+pub(crate) struct Entries<'a> {
+    data: &'a [u8],
+    key: Vec<u8>,
+    pos: usize,
+}
+
+impl<'a> Entries<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self {
+            data,
+            key: vec![],
+            pos,
+        }
+    }
+
+    fn read(&mut self) -> Option<(Vec<u8>, &'a [u8])> {
+        let mut cursor = Cursor::new(self.data.get(self.pos..)?);
+        let shared = cursor.varint()? as usize;
+        let suffix_len = cursor.varint()? as usize;
+        let suffix = cursor.bytes(suffix_len)?;
+        let value_len = cursor.varint()? as usize;
+        let value = cursor.bytes(value_len)?;
+
+        if shared > self.key.len() {
+            return None;
+        }
+        self.key.truncate(shared);
+        self.key.extend_from_slice(suffix);
+        self.pos += cursor.pos;
+
+        Some((self.key.to_owned(), value))
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = (Vec<u8>, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let entry = self.read();
+        if entry.is_none() {
+            log::error!("index table is corrupt at offset {}", self.pos);
+            self.pos = self.data.len();
+        }
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(keys: &[&str]) -> Table {
+        let mut builder = TableBuilder::new();
+        for key in keys {
+            builder.add(key.as_bytes(), format!("value of {key}").as_bytes());
+        }
+        Table::parse(builder.finish()).unwrap()
+    }
+
+    fn keys<'a, I>(entries: I) -> Vec<String>
+    where
+        I: Iterator<Item = (Vec<u8>, &'a [u8])>,
+    {
+        entries
+            .map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn varints() {
+        let mut buf = vec![];
+        for n in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            put_varint(&mut buf, n);
+        }
+        put_str(&mut buf, "héllo");
+
+        let mut cursor = Cursor::new(&buf);
+        for n in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            assert_eq!(cursor.varint(), Some(n));
+        }
+        assert_eq!(cursor.str(), Some("héllo"));
+        assert!(cursor.is_empty());
+        assert_eq!(cursor.byte(), None);
+    }
+
+    #[test]
+    fn empty_table() {
+        let table = table(&[]);
+        assert_eq!(table.iter().count(), 0);
+        assert_eq!(table.get(b"a"), None);
+        assert_eq!(table.prefix(b"a").count(), 0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut words = (0..100).map(|i| format!("word{i:03}")).collect::<Vec<_>>();
+        words.push("wordy".into());
+        words.push("zebra".into());
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        let table = table(&words);
+
+        assert_eq!(keys(table.iter()), words);
+        assert_eq!(table.get(b"word042"), Some(&b"value of word042"[..]));
+        assert_eq!(table.get(b"word04"), None);
+        assert_eq!(table.get(b"aardvark"), None);
+        assert_eq!(table.get(b"zzz"), None);
+    }
+
+    #[test]
+    fn prefix_scan() {
+        let mut words = (0..100).map(|i| format!("word{i:03}")).collect::<Vec<_>>();
+        words.push("wordy".into());
+        words.push("zebra".into());
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        let table = table(&words);
+
+        assert_eq!(
+            keys(table.prefix(b"word03")),
+            (30..40).map(|i| format!("word{i:03}")).collect::<Vec<_>>()
+        );
+        assert_eq!(keys(table.prefix(b"wordy")), vec!["wordy"]);
+        assert_eq!(keys(table.prefix(b"z")), vec!["zebra"]);
+        assert_eq!(table.prefix(b"word").count(), 101);
+        assert_eq!(table.prefix(b"x").count(), 0);
+        assert_eq!(table.prefix(b"").count(), 102);
+    }
+
+    #[test]
+    fn corrupt_tables() {
+        assert!(Table::parse(b"key: value".to_vec()).is_err());
+        assert!(Table::parse(b"DGT1\x05\x00\x00\x00".to_vec()).is_err());
+
+        let mut bytes = table(&["apple", "apricot", "banana"]).as_bytes().to_vec();
+        bytes.truncate(bytes.len() - 4);
+        let table = Table::parse(bytes).unwrap();
+        assert_eq!(keys(table.iter()), vec!["apple", "apricot"]);
+    }
+}
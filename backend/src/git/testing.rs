@@ -49,6 +49,8 @@ pub fn link(title: &str, url: &str) -> RepoLink {
                 title: title.to_owned(),
                 url: url.to_owned(),
             }),
+            pending: false,
+            snapshot: None,
         },
         parent_topics: BTreeSet::from([ParentTopic {
             id: "00001".try_into().expect("failed to parse id"),
@@ -209,12 +209,8 @@ impl RemoveTopicTimerange {
 
         let previous_timerange = repo_topic.timerange().to_owned();
 
-        match &mut repo_topic.metadata.details {
-            Some(details) => {
-                details.timerange = None;
-            }
-
-            None => {}
+        if let Some(details) = &mut repo_topic.metadata.details {
+            details.timerange = None;
         }
 
         mutation.save_topic(self.repo_id, &repo_topic)?;
@@ -553,7 +549,7 @@ impl UpsertTopic {
     {
         let name = normalize_name(&self.name);
         let parent = self.ensure_topic(&mutation, &self.parent_topic_id);
-        let matches = self.find_matches(&mutation, &name)?;
+        let matches = Self::find_matches(&mutation, &self.actor.read_repo_ids, &name)?;
 
         if matches.is_empty() {
             return self.add_repo_topic(mutation, store, name, parent, matches);
@@ -572,8 +568,13 @@ impl UpsertTopic {
         }
     }
 
-    fn find_matches(&self, mutation: &Mutation, name: &str) -> Result<BTreeSet<SynonymMatch>> {
-        mutation.synonym_phrase_matches(&self.actor.read_repo_ids, name)
+    // Bookmark imports look for existing topics to put folders under in the same way
+    pub(crate) fn find_matches(
+        mutation: &Mutation,
+        repo_ids: &RepoIds,
+        name: &str,
+    ) -> Result<BTreeSet<SynonymMatch>> {
+        mutation.synonym_phrase_matches(repo_ids, name)
     }
 
     fn request_decision(
@@ -821,12 +822,8 @@ impl UpsertTopicTimerange {
 
         let previous_timerange = repo_topic.timerange().clone();
 
-        match &mut repo_topic.metadata.details {
-            Some(details) => {
-                details.timerange = Some(self.timerange.clone());
-            }
-
-            None => {}
+        if let Some(details) = &mut repo_topic.metadata.details {
+            details.timerange = Some(self.timerange.clone());
         }
 
         mutation.save_topic(self.repo_id, &repo_topic)?;
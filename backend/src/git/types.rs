@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashSet};
 use std::convert::TryInto;
 
-use super::{Client, Mutation, SearchEntry};
+use super::{Client, Mutation, SearchEntry, SnapshotRef};
 use crate::prelude::*;
 use crate::types as core_types;
 
@@ -22,6 +22,15 @@ impl Kind {
     }
 }
 
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Link => write!(f, "link"),
+            Self::Topic => write!(f, "topic"),
+        }
+    }
+}
+
 impl std::cmp::Ord for Kind {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         use std::cmp::Ordering;
@@ -52,6 +61,11 @@ pub struct RepoLinkMetadata {
     pub added: Timestamp,
     pub id: ExternalId,
     pub details: Option<RepoLinkDetails>,
+    // Set while the title is waiting to be fetched by the worker
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pending: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotRef>,
 }
 
 impl RepoLinkMetadata {
@@ -135,6 +149,10 @@ impl RepoLink {
         &self.metadata.id
     }
 
+    pub fn is_pending(&self) -> bool {
+        self.metadata.pending
+    }
+
     pub fn is_reference(&self) -> bool {
         self.metadata.details.is_none()
     }
@@ -500,6 +518,13 @@ impl RepoObject {
         }
     }
 
+    pub fn id(&self) -> &ExternalId {
+        match self {
+            RepoObject::Topic(topic) => topic.topic_id(),
+            RepoObject::Link(link) => link.id(),
+        }
+    }
+
     pub fn kind(&self) -> Kind {
         match self {
             RepoObject::Topic(_) => Kind::Topic,
@@ -515,22 +540,6 @@ impl RepoObject {
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RepoTopicReferenceMetadata {
-    pub path: String,
-    pub path_tracked: String,
-}
-
-#[derive(Clone, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RepoTopicReference {
-    api_version: String,
-    pub metadata: RepoTopicReferenceMetadata,
-    // pub parent_topics: BTreeSet<ParentTopic>,
-    pub children: BTreeSet<TopicChild>,
-}
-
 pub trait Visitor {
     fn visit_topic(&mut self, topic: &RepoTopic) -> Result<()>;
     fn visit_link(&mut self, link: &RepoLink) -> Result<()>;
@@ -694,15 +703,11 @@ mod tests {
         let date = timerange_epoch();
 
         let mut topic = topic("Climate change");
-        match &mut topic.metadata.details {
-            Some(details) => {
-                details.timerange = Some(Timerange {
-                    starts: date.into(),
-                    prefix_format: TimerangePrefixFormat::StartYear,
-                });
-            }
-
-            None => {}
+        if let Some(details) = &mut topic.metadata.details {
+            details.timerange = Some(Timerange {
+                starts: date.into(),
+                prefix_format: TimerangePrefixFormat::StartYear,
+            });
         }
 
         assert_eq!(topic.name(Locale::EN), "1970 Climate change");
@@ -0,0 +1,146 @@
+use async_graphql::connection::*;
+use async_graphql::{Context, Enum, Object, SimpleObject, ID};
+use strum_macros::EnumString;
+
+use super::{time, Repository, User};
+use crate::git;
+use crate::prelude::*;
+use crate::store::Store;
+
+#[derive(Clone, Copy, Debug, Enum, EnumString, Eq, PartialEq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ChangeRequestStatus {
+    Accepted,
+    Open,
+    Rejected,
+}
+
+#[derive(Clone, Copy, Debug, Enum, Eq, PartialEq)]
+pub enum ProposedAction {
+    Add,
+    Update,
+}
+
+impl From<git::ProposedAction> for ProposedAction {
+    fn from(action: git::ProposedAction) -> Self {
+        match action {
+            git::ProposedAction::Add => Self::Add,
+            git::ProposedAction::Update => Self::Update,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ProposedChange {
+    action: ProposedAction,
+    id: ID,
+    is_link: bool,
+    name: String,
+    parent_topic_ids_added: Vec<ID>,
+    parent_topic_ids_removed: Vec<ID>,
+    previous_name: Option<String>,
+    synonyms_added: Vec<String>,
+    synonyms_removed: Vec<String>,
+    url: Option<String>,
+}
+
+impl From<git::ProposedChange> for ProposedChange {
+    fn from(change: git::ProposedChange) -> Self {
+        let ids = |ids: Vec<ExternalId>| ids.iter().map(|id| ID(id.to_string())).collect();
+
+        Self {
+            action: change.action.into(),
+            id: ID(change.id.to_string()),
+            is_link: change.kind == git::Kind::Link,
+            name: change.name,
+            parent_topic_ids_added: ids(change.parent_topics_added),
+            parent_topic_ids_removed: ids(change.parent_topics_removed),
+            previous_name: change.previous_name,
+            synonyms_added: change.synonyms_added,
+            synonyms_removed: change.synonyms_removed,
+            url: change.url,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChangeRequest {
+    pub author_id: String,
+    pub created_at: Timestamp,
+    pub description: String,
+    pub id: String,
+    pub payload: git::ChangeRequestPayload,
+    pub reviewed_at: Option<Timestamp>,
+    pub reviewer_id: Option<String>,
+    pub source_repo_id: String,
+    pub status: ChangeRequestStatus,
+    pub target_repo_id: String,
+    pub title: String,
+}
+
+pub type ChangeRequestConnection = Connection<String, ChangeRequest, EmptyFields, EmptyFields>;
+
+#[Object]
+impl ChangeRequest {
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        ctx.data_unchecked::<Store>()
+            .user(self.author_id.to_owned())
+            .await
+    }
+
+    // What accepting the change request would do to the target repo as it is now
+    async fn changes(&self, ctx: &Context<'_>) -> Result<Vec<ProposedChange>> {
+        let changes = ctx
+            .data_unchecked::<Store>()
+            .review_change_request(self)
+            .await?;
+        Ok(changes.into_iter().map(ProposedChange::from).collect())
+    }
+
+    async fn created_at(&self) -> time::DateTime {
+        time::DateTime(self.created_at)
+    }
+
+    async fn description(&self) -> &str {
+        &self.description
+    }
+
+    async fn id(&self) -> ID {
+        ID(self.id.to_owned())
+    }
+
+    async fn reviewed_at(&self) -> Option<time::DateTime> {
+        self.reviewed_at.map(time::DateTime)
+    }
+
+    async fn reviewer(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        match &self.reviewer_id {
+            Some(reviewer_id) => {
+                ctx.data_unchecked::<Store>()
+                    .user(reviewer_id.to_owned())
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn source_repo(&self, ctx: &Context<'_>) -> Result<Option<Repository>> {
+        ctx.data_unchecked::<Store>()
+            .repository_by_id(self.source_repo_id.to_owned())
+            .await
+    }
+
+    async fn status(&self) -> ChangeRequestStatus {
+        self.status
+    }
+
+    async fn target_repo(&self, ctx: &Context<'_>) -> Result<Option<Repository>> {
+        ctx.data_unchecked::<Store>()
+            .repository_by_id(self.target_repo_id.to_owned())
+            .await
+    }
+
+    async fn title(&self) -> &str {
+        &self.title
+    }
+}
@@ -0,0 +1,56 @@
+use async_graphql::{Enum, SimpleObject, ID};
+
+use crate::git;
+
+#[derive(Clone, Copy, Debug, Enum, Eq, PartialEq)]
+pub enum LeakField {
+    Children,
+    Content,
+    Index,
+    ParentTopics,
+}
+
+impl From<git::LeakField> for LeakField {
+    fn from(field: git::LeakField) -> Self {
+        match field {
+            git::LeakField::Children => Self::Children,
+            git::LeakField::Content => Self::Content,
+            git::LeakField::Index => Self::Index,
+            git::LeakField::ParentTopics => Self::ParentTopics,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct Leak {
+    field: LeakField,
+    id: Option<ID>,
+    path: String,
+    private_repo_id: ID,
+}
+
+impl From<git::Leak> for Leak {
+    fn from(leak: git::Leak) -> Self {
+        Self {
+            field: leak.field.into(),
+            id: leak.id.map(|id| ID(id.to_string())),
+            path: leak.path,
+            private_repo_id: ID(leak.private_repo_id.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct LeakedDataReport {
+    commit: String,
+    leaks: Vec<Leak>,
+}
+
+impl From<git::LeakedDataResult> for LeakedDataReport {
+    fn from(result: git::LeakedDataResult) -> Self {
+        Self {
+            commit: result.commit,
+            leaks: result.leaks.into_iter().map(Leak::from).collect(),
+        }
+    }
+}
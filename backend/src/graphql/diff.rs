@@ -0,0 +1,101 @@
+use async_graphql::{Enum, SimpleObject, ID};
+
+use crate::git;
+
+#[derive(Clone, Copy, Debug, Enum, Eq, PartialEq)]
+pub enum DiffChange {
+    ObjectAdded,
+    ObjectChanged,
+    ObjectRemoved,
+    ParentTopicAdded,
+    ParentTopicRemoved,
+    SynonymAdded,
+    SynonymRemoved,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct DiffEntry {
+    change: DiffChange,
+    fields: Vec<String>,
+    id: ID,
+    is_link: bool,
+    locale: Option<String>,
+    name: Option<String>,
+    parent_topic_id: Option<ID>,
+}
+
+impl From<git::DiffEntry> for DiffEntry {
+    fn from(entry: git::DiffEntry) -> Self {
+        let id = ID(entry.id().to_string());
+        let entry_for = |change, kind| Self {
+            change,
+            fields: vec![],
+            id: id.to_owned(),
+            is_link: kind == git::Kind::Link,
+            locale: None,
+            name: None,
+            parent_topic_id: None,
+        };
+
+        match entry {
+            git::DiffEntry::ObjectAdded { kind, name, .. } => Self {
+                name: Some(name),
+                ..entry_for(DiffChange::ObjectAdded, kind)
+            },
+            git::DiffEntry::ObjectRemoved { kind, name, .. } => Self {
+                name: Some(name),
+                ..entry_for(DiffChange::ObjectRemoved, kind)
+            },
+            git::DiffEntry::ObjectChanged {
+                kind, name, fields, ..
+            } => Self {
+                fields,
+                name: Some(name),
+                ..entry_for(DiffChange::ObjectChanged, kind)
+            },
+            git::DiffEntry::ParentTopicAdded {
+                kind,
+                parent_topic_id,
+                ..
+            } => Self {
+                parent_topic_id: Some(ID(parent_topic_id.to_string())),
+                ..entry_for(DiffChange::ParentTopicAdded, kind)
+            },
+            git::DiffEntry::ParentTopicRemoved {
+                kind,
+                parent_topic_id,
+                ..
+            } => Self {
+                parent_topic_id: Some(ID(parent_topic_id.to_string())),
+                ..entry_for(DiffChange::ParentTopicRemoved, kind)
+            },
+            git::DiffEntry::SynonymAdded { locale, name, .. } => Self {
+                locale: Some(locale.to_string()),
+                name: Some(name),
+                ..entry_for(DiffChange::SynonymAdded, git::Kind::Topic)
+            },
+            git::DiffEntry::SynonymRemoved { locale, name, .. } => Self {
+                locale: Some(locale.to_string()),
+                name: Some(name),
+                ..entry_for(DiffChange::SynonymRemoved, git::Kind::Topic)
+            },
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct RepoDiff {
+    base_commit: String,
+    entries: Vec<DiffEntry>,
+    head_commit: String,
+}
+
+impl From<git::DiffResult> for RepoDiff {
+    fn from(result: git::DiffResult) -> Self {
+        Self {
+            base_commit: result.base_commit,
+            entries: result.entries.into_iter().map(DiffEntry::from).collect(),
+            head_commit: result.head_commit,
+        }
+    }
+}
@@ -11,7 +11,7 @@ use super::{
 use crate::git;
 use crate::prelude::*;
 
-pub use git::{Client, DataRoot};
+pub use git::{Client, DataRoot, ObjectCache};
 
 pub struct ObjectLoader {
     client: Arc<git::Client>,
@@ -29,7 +29,7 @@ impl Loader<Okey> for ObjectLoader {
 
     async fn load(&self, keys: &[Okey]) -> Result<HashMap<Okey, Self::Value>> {
         log::debug!("batch load topics: {:?}", keys);
-        Ok(self.client.fetch_all(keys).finalize()?.into_hash())
+        Ok(self.client.fetch_all(keys).await.finalize()?.into_hash())
     }
 }
 
@@ -4,6 +4,7 @@ use itertools::Itertools;
 
 use super::{relay, time, LiveSearchTopicsPayload, Repository, TopicConnection, User};
 use crate::git;
+use crate::http;
 use crate::prelude::*;
 use crate::store::Store;
 
@@ -31,6 +32,50 @@ impl TryFrom<Option<Link>> for Link {
     }
 }
 
+pub struct LinkHealth(pub(crate) git::LinkHealth);
+
+#[Object]
+impl LinkHealth {
+    async fn broken(&self) -> bool {
+        self.0.is_broken()
+    }
+
+    async fn checked_at(&self) -> time::DateTime {
+        time::DateTime(self.0.checked)
+    }
+
+    async fn error(&self) -> Option<&str> {
+        self.0.error.as_deref()
+    }
+
+    async fn redirect_url(&self) -> Option<&str> {
+        self.0.redirect.as_deref()
+    }
+
+    async fn status(&self) -> Option<i32> {
+        self.0.status.map(i32::from)
+    }
+}
+
+pub struct LinkSnapshot(pub(crate) git::Snapshot);
+
+#[Object]
+impl LinkSnapshot {
+    async fn captured_at(&self) -> time::DateTime {
+        time::DateTime(self.0.captured)
+    }
+
+    // Snapshots captured before pages were passed through the allowlist are cleaned up here as
+    // well
+    async fn html(&self) -> String {
+        http::sanitize_html(&self.0.html)
+    }
+
+    async fn text(&self) -> &str {
+        &self.0.text
+    }
+}
+
 pub struct RepoLink<'l>(pub(crate) &'l git::RepoLinkWrapper);
 
 #[Object]
@@ -47,7 +92,7 @@ impl<'l> RepoLink<'l> {
         Ok(LiveSearchTopicsPayload(result))
     }
 
-    async fn details(&self) -> Option<RepoLinkDetails> {
+    async fn details(&self) -> Option<RepoLinkDetails<'_>> {
         self.0.details().map(RepoLinkDetails)
     }
 
@@ -122,6 +167,7 @@ impl<'l> RepoLink<'l> {
 pub struct Link(pub(crate) git::Link);
 
 pub type LinkEdge = Edge<String, Link, EmptyFields>;
+pub type LinkConnection = Connection<String, Link, EmptyFields, EmptyFields>;
 
 #[Object]
 impl Link {
@@ -152,8 +198,16 @@ impl Link {
         self.0.display_url()
     }
 
+    async fn health(&self, ctx: &Context<'_>) -> Result<Option<LinkHealth>> {
+        let health = ctx
+            .data_unchecked::<Store>()
+            .link_health(self.0.display_repo_id(), &self.0.key.0)
+            .await?;
+        Ok(health.map(LinkHealth))
+    }
+
     async fn loading(&self) -> bool {
-        false
+        self.0.is_pending()
     }
 
     async fn id(&self) -> String {
@@ -165,7 +219,7 @@ impl Link {
         false
     }
 
-    async fn repo_link(&self, repo_id: String) -> Result<Option<RepoLink>> {
+    async fn repo_link(&self, repo_id: String) -> Result<Option<RepoLink<'_>>> {
         let repo_id: RepoId = repo_id.try_into()?;
         Ok(self
             .0
@@ -175,10 +229,23 @@ impl Link {
             .map(|repo_link| repo_link.into()))
     }
 
-    async fn repo_links(&self) -> Vec<RepoLink> {
+    async fn repo_links(&self) -> Vec<RepoLink<'_>> {
         self.0.repo_links.iter().map(RepoLink::from).collect_vec()
     }
 
+    async fn snapshot(&self, ctx: &Context<'_>) -> Result<Option<LinkSnapshot>> {
+        let reference = match self.0.display_snapshot() {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+
+        let snapshot = ctx
+            .data_unchecked::<Store>()
+            .link_snapshot(self.0.display_repo_id(), reference)
+            .await?;
+        Ok(snapshot.map(LinkSnapshot))
+    }
+
     async fn show_repo_ownership(&self) -> bool {
         self.0.repo_links.iter().any(|link| !link.in_wiki_repo())
     }
@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use async_graphql::EmptySubscription;
-use sqlx::postgres::PgPool;
 
+use crate::accounts::Accounts;
+use crate::http;
 use crate::prelude::*;
 use crate::redis;
 use crate::store::Store;
@@ -12,6 +13,12 @@ mod activity;
 pub use activity::*;
 pub mod alert;
 pub use alert::*;
+mod change_request;
+pub use change_request::*;
+mod checks;
+pub use checks::*;
+mod diff;
+pub use diff::*;
 mod git;
 pub use git::*;
 mod relay;
@@ -90,8 +97,11 @@ impl From<(String, Option<SessionRow>)> for Viewer {
 
 #[derive(Clone)]
 pub struct State {
-    pub pool: PgPool,
-    pub redis: Arc<redis::Redis>,
+    pub accounts: Accounts,
+    pub cache: Arc<dyn redis::Cache>,
+    pub fetch_policy: Arc<http::FetchPolicy>,
+    // Held for the life of the process, so that parsed objects are shared across requests
+    pub object_cache: Arc<git::ObjectCache>,
     pub root: git::DataRoot,
     pub schema: Schema,
     pub server_secret: String,
@@ -99,38 +109,68 @@ pub struct State {
 
 impl State {
     pub fn new(
-        pool: PgPool,
+        accounts: Accounts,
         root: git::DataRoot,
         schema: Schema,
         server_secret: String,
-        redis: Arc<redis::Redis>,
+        cache: Arc<dyn redis::Cache>,
+        fetch_policy: Arc<http::FetchPolicy>,
+        object_cache: Arc<git::ObjectCache>,
     ) -> Self {
         Self {
-            pool,
+            accounts,
+            cache,
+            fetch_policy,
+            object_cache,
             root,
-            redis,
             schema,
             server_secret,
         }
     }
 
     pub fn store(&self, viewer: Arc<Viewer>, timespec: &Timespec) -> Store {
-        let git = Arc::new(git::Client::new(
-            Arc::clone(&viewer),
-            &self.root,
-            timespec.to_owned(),
-        ));
+        let git = Arc::new(self.git(Arc::clone(&viewer), timespec));
 
         Store::new(
             Arc::clone(&viewer),
             git,
-            self.pool.clone(),
+            self.accounts.clone(),
             self.server_secret.clone(),
-            Arc::clone(&self.redis),
+            Arc::clone(&self.cache),
+            Arc::clone(&self.fetch_policy),
         )
     }
 
+    pub fn git(&self, viewer: Arc<Viewer>, timespec: &Timespec) -> git::Client {
+        git::Client::new(viewer, &self.root, timespec.to_owned())
+            .with_cache(Arc::clone(&self.object_cache))
+    }
+
     pub async fn authenticate(&self, (user_id, session_id): (String, String)) -> Viewer {
+        let pool = match &self.accounts {
+            Accounts::Git(system) => {
+                let id = user_id.to_owned();
+                let result = system
+                    .run(move |system| system.session_viewer(&id, &session_id))
+                    .await;
+                return match result {
+                    Ok(Some(viewer)) => {
+                        log::info!("found user and session in system repo: {}", user_id);
+                        viewer
+                    }
+                    Ok(None) => {
+                        log::warn!("no user session found, proceeding as guest: {}", user_id);
+                        Viewer::guest()
+                    }
+                    Err(err) => {
+                        log::warn!("failed to fetch session info, proceeding as guest: {}", err);
+                        Viewer::guest()
+                    }
+                };
+            }
+            Accounts::Postgres(pool) => pool,
+        };
+
         let result = sqlx::query_as::<_, SessionRow>(
             "select
                 u.id user_id,
@@ -147,7 +187,7 @@ impl State {
         )
         .bind(&user_id)
         .bind(&session_id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(pool)
         .await;
 
         match result {
@@ -4,8 +4,8 @@ use async_graphql::{Context, Enum, InputObject, Object, SimpleObject, ID};
 use itertools::Itertools;
 
 use super::{
-    alert, time, Link, LinkEdge, RepoTopic, Repository, Session, SessionEdge, Topic, TopicEdge,
-    User, UserEdge,
+    alert, time, ChangeRequest, Link, LinkEdge, RepoTopic, Repository, Session, SessionEdge, Topic,
+    TopicEdge, User, UserEdge,
 };
 use crate::git;
 use crate::prelude::*;
@@ -29,6 +29,36 @@ pub struct CreateSessionPayload {
     session_edge: Option<SessionEdge>,
 }
 
+#[derive(Debug, InputObject)]
+pub struct AcceptChangeRequestInput {
+    client_mutation_id: Option<String>,
+    change_request_id: ID,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct AcceptChangeRequestPayload {
+    change_request: ChangeRequest,
+    client_mutation_id: Option<String>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct CreateChangeRequestInput {
+    // The changes in the source repo to propose; if absent, every difference between the source
+    // and target repos is proposed
+    change_ids: Option<Vec<String>>,
+    client_mutation_id: Option<String>,
+    description: Option<String>,
+    source_repo_id: String,
+    target_repo_id: String,
+    title: String,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct CreateChangeRequestPayload {
+    change_request: ChangeRequest,
+    client_mutation_id: Option<String>,
+}
+
 #[derive(Debug, InputObject)]
 pub struct DeleteAccountInput {
     client_mutation_id: Option<String>,
@@ -81,6 +111,32 @@ pub struct DeleteTopicPayload {
     deleted_topic_id: String,
 }
 
+#[derive(Debug, InputObject)]
+pub struct ForkRepoInput {
+    client_mutation_id: Option<String>,
+    name: Option<String>,
+    repo_id: String,
+    topic_id: Option<String>,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ForkRepoPayload {
+    client_mutation_id: Option<String>,
+    repo: Repository,
+}
+
+#[derive(Debug, InputObject)]
+pub struct RejectChangeRequestInput {
+    client_mutation_id: Option<String>,
+    change_request_id: ID,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct RejectChangeRequestPayload {
+    change_request: ChangeRequest,
+    client_mutation_id: Option<String>,
+}
+
 #[derive(Debug, InputObject)]
 pub struct RemoveTopicTimerangeInput {
     client_mutation_id: Option<String>,
@@ -228,6 +284,68 @@ pub struct MutationRoot;
 
 #[Object]
 impl MutationRoot {
+    async fn accept_change_request(
+        &self,
+        ctx: &Context<'_>,
+        input: AcceptChangeRequestInput,
+    ) -> Result<AcceptChangeRequestPayload> {
+        let AcceptChangeRequestInput {
+            client_mutation_id,
+            change_request_id,
+        } = input;
+
+        let psql::CloseChangeRequestResult { change_request } = ctx
+            .data_unchecked::<Store>()
+            .accept_change_request(change_request_id.to_string())
+            .await?;
+
+        Ok(AcceptChangeRequestPayload {
+            change_request,
+            client_mutation_id,
+        })
+    }
+
+    async fn create_change_request(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateChangeRequestInput,
+    ) -> Result<CreateChangeRequestPayload> {
+        let CreateChangeRequestInput {
+            change_ids,
+            client_mutation_id,
+            description,
+            source_repo_id,
+            target_repo_id,
+            title,
+        } = input;
+
+        let change_ids = match change_ids {
+            Some(change_ids) => Some(
+                change_ids
+                    .iter()
+                    .map(ExternalId::try_from)
+                    .collect::<Result<Vec<ExternalId>>>()?,
+            ),
+            None => None,
+        };
+
+        let psql::CreateChangeRequestResult { change_request } = ctx
+            .data_unchecked::<Store>()
+            .create_change_request(
+                source_repo_id.try_into()?,
+                target_repo_id.try_into()?,
+                title,
+                description.unwrap_or_default(),
+                change_ids,
+            )
+            .await?;
+
+        Ok(CreateChangeRequestPayload {
+            change_request,
+            client_mutation_id,
+        })
+    }
+
     async fn create_github_session(
         &self,
         ctx: &Context<'_>,
@@ -354,6 +472,50 @@ impl MutationRoot {
         })
     }
 
+    async fn fork_repo(&self, ctx: &Context<'_>, input: ForkRepoInput) -> Result<ForkRepoPayload> {
+        let ForkRepoInput {
+            client_mutation_id,
+            name,
+            repo_id,
+            topic_id,
+        } = input;
+        let topic_id = match topic_id {
+            Some(topic_id) => Some(ExternalId::try_from(&topic_id)?),
+            None => None,
+        };
+
+        let psql::CreateForkRepositoryResult { repo } = ctx
+            .data_unchecked::<Store>()
+            .fork_repo(repo_id.try_into()?, topic_id, name)
+            .await?;
+
+        Ok(ForkRepoPayload {
+            client_mutation_id,
+            repo,
+        })
+    }
+
+    async fn reject_change_request(
+        &self,
+        ctx: &Context<'_>,
+        input: RejectChangeRequestInput,
+    ) -> Result<RejectChangeRequestPayload> {
+        let RejectChangeRequestInput {
+            client_mutation_id,
+            change_request_id,
+        } = input;
+
+        let psql::CloseChangeRequestResult { change_request } = ctx
+            .data_unchecked::<Store>()
+            .reject_change_request(change_request_id.to_string())
+            .await?;
+
+        Ok(RejectChangeRequestPayload {
+            change_request,
+            client_mutation_id,
+        })
+    }
+
     async fn remove_topic_timerange(
         &self,
         ctx: &Context<'_>,
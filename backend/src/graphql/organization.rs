@@ -2,9 +2,10 @@ use async_graphql::{Object, ID};
 
 use crate::prelude::*;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum Organization {
     #[allow(dead_code)]
+    #[default]
     Wiki,
     Selected {
         id: ID,
@@ -13,12 +14,6 @@ pub enum Organization {
     },
 }
 
-impl Default for Organization {
-    fn default() -> Self {
-        Self::Wiki
-    }
-}
-
 #[Object]
 impl Organization {
     async fn login(&self) -> &str {
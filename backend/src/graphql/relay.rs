@@ -1,6 +1,7 @@
 use async_graphql::{connection::*, OutputType};
 use itertools::Itertools;
 use std::collections::BTreeSet;
+use std::ops::Range;
 
 use super::Topic;
 use crate::git;
@@ -34,6 +35,75 @@ pub async fn connection<N: OutputType>(
     Ok(result)
 }
 
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+type OffsetConnection<N> =
+    Connection<String, N, EmptyFields, EmptyFields, DefaultConnectionName, DefaultEdgeName>;
+
+fn cursor_offset(cursor: &Option<String>) -> Result<Option<usize>> {
+    cursor
+        .as_ref()
+        .map(|cursor| {
+            cursor
+                .parse::<usize>()
+                .map_err(|_| Error::Parse(format!("bad cursor: {cursor}")))
+        })
+        .transpose()
+}
+
+fn page_size(size: Option<i32>) -> Result<Option<usize>> {
+    size.map(|size| {
+        usize::try_from(size).map_err(|_| Error::Parse(format!("bad page size: {size}")))
+    })
+    .transpose()
+}
+
+// The offsets of the results on the page asked for, for connections that only load the results
+// that are on the page.  The offset of each result serves as its cursor.
+pub fn page(
+    after: &Option<String>,
+    before: &Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+    total: usize,
+) -> Result<Range<usize>> {
+    let mut end = cursor_offset(before)?.unwrap_or(total).min(total);
+    let mut start = match cursor_offset(after)? {
+        Some(offset) => offset.saturating_add(1).min(end),
+        None => 0,
+    };
+
+    match (page_size(first)?, page_size(last)?) {
+        (None, None) => end = end.min(start.saturating_add(DEFAULT_PAGE_SIZE)),
+        (first, last) => {
+            if let Some(first) = first {
+                end = end.min(start.saturating_add(first));
+            }
+            if let Some(last) = last {
+                start = start.max(end.saturating_sub(last));
+            }
+        }
+    }
+
+    Ok(start..end)
+}
+
+// A connection for a page found with `page`, given the results on the page along with their
+// offsets
+pub fn page_connection<N: OutputType>(
+    page: Range<usize>,
+    total: usize,
+    results: Vec<(usize, N)>,
+) -> OffsetConnection<N> {
+    let mut connection = Connection::new(page.start > 0, page.end < total);
+    connection.edges.extend(
+        results
+            .into_iter()
+            .map(|(offset, n)| Edge::with_additional_fields(offset.to_string(), n, EmptyFields)),
+    );
+    connection
+}
+
 pub async fn topics(
     after: Option<String>,
     before: Option<String>,
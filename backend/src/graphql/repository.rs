@@ -18,6 +18,7 @@ pub enum Repository {
         organization_id: String,
         owner_id: String,
         private: bool,
+        upstream_repo_id: Option<String>,
     },
 }
 
@@ -100,6 +101,21 @@ impl Repository {
         }
     }
 
+    // The repo that this one was forked from, if any
+    async fn upstream(&self, ctx: &Context<'_>) -> Result<Option<Repository>> {
+        match self {
+            Self::Fetched {
+                upstream_repo_id: Some(repo_id),
+                ..
+            } => {
+                ctx.data_unchecked::<Store>()
+                    .repository_by_id(repo_id.to_owned())
+                    .await
+            }
+            _ => Ok(None),
+        }
+    }
+
     async fn root_topic(&self, ctx: &Context<'_>) -> Result<Topic> {
         ctx.data_unchecked::<Store>()
             .fetch_topic(ExternalId::root_topic())
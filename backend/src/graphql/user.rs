@@ -5,8 +5,9 @@ use super::{Repository, RepositoryConnection, RepositoryEdgeFields};
 use crate::prelude::*;
 use crate::store::Store;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum User {
+    #[default]
     Guest,
     Registered {
         avatar_url: String,
@@ -16,12 +17,6 @@ pub enum User {
     },
 }
 
-impl Default for User {
-    fn default() -> Self {
-        Self::Guest
-    }
-}
-
 #[derive(Debug, SimpleObject)]
 pub struct UserEdge {
     pub cursor: String,
@@ -1,9 +1,12 @@
 use std::convert::TryInto;
 
 use async_graphql::{Context, Object, SimpleObject, ID};
+use itertools::Itertools;
 
 use super::{
-    relay, ActivityLineItem, ActivityLineItemConnection, Link, LiveSearchTopicsPayload, Topic, User,
+    relay, ActivityLineItem, ActivityLineItemConnection, ChangeRequest, ChangeRequestConnection,
+    ChangeRequestStatus, LeakedDataReport, Link, LinkConnection, LiveSearchTopicsPayload, RepoDiff,
+    Topic, User,
 };
 use crate::git;
 use crate::prelude::*;
@@ -67,6 +70,119 @@ impl View {
         relay::connection(after, before, first, last, results).await
     }
 
+    async fn broken_links(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<LinkConnection> {
+        let store = ctx.data_unchecked::<Store>();
+        let repo_ids = match &self.repo_ids {
+            Some(ids) => Some(
+                ids.iter()
+                    .map(|id| RepoId::try_from(id.as_str()))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => None,
+        };
+
+        // Only the links on the page asked for are loaded
+        let link_ids = store.broken_link_ids(repo_ids.as_deref())?;
+        let page = relay::page(&after, &before, first, last, link_ids.len())?;
+        let page_ids = &link_ids[page.clone()];
+        let links = store
+            .fetch_links(page_ids, page_ids.len(), None)
+            .await?
+            .into_iter()
+            .filter_map(|link| {
+                let offset = page_ids.iter().position(|id| id == &link.key.0)?;
+                Some((page.start + offset, Link::from(link)))
+            })
+            .sorted_by_key(|(offset, _)| *offset)
+            .collect();
+
+        Ok(relay::page_connection(page, link_ids.len(), links))
+    }
+
+    async fn change_request(&self, ctx: &Context<'_>, id: ID) -> Result<Option<ChangeRequest>> {
+        ctx.data_unchecked::<Store>()
+            .change_request(id.to_string())
+            .await
+    }
+
+    // Change requests that the viewer has opened or that target a repo the viewer can read
+    async fn change_requests(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        status: Option<ChangeRequestStatus>,
+    ) -> Result<ChangeRequestConnection> {
+        let change_requests = ctx
+            .data_unchecked::<Store>()
+            .change_requests(status, first.unwrap_or(50).into())
+            .await?;
+
+        relay::connection(after, before, first, last, change_requests).await
+    }
+
+    // Compares two repos, or two commits of the same repo.  The head repo defaults to the base
+    // repo, and the commits default to the latest ones.
+    async fn diff(
+        &self,
+        ctx: &Context<'_>,
+        base_commit: Option<String>,
+        base_repo_id: ID,
+        head_commit: Option<String>,
+        head_repo_id: Option<ID>,
+    ) -> Result<RepoDiff> {
+        let base_repo_id: RepoId = base_repo_id.as_str().try_into()?;
+        let head_repo_id: RepoId = match head_repo_id {
+            Some(repo_id) => repo_id.as_str().try_into()?,
+            None => base_repo_id,
+        };
+
+        let result = ctx
+            .data_unchecked::<Store>()
+            .diff(
+                git::DiffSide {
+                    repo_id: base_repo_id,
+                    rev: base_commit,
+                },
+                git::DiffSide {
+                    repo_id: head_repo_id,
+                    rev: head_commit,
+                },
+            )
+            .await?;
+
+        Ok(result.into())
+    }
+
+    // References from a public repo into private repos, for administrators
+    async fn leaked_data(
+        &self,
+        ctx: &Context<'_>,
+        commit: Option<String>,
+        repo_id: Option<ID>,
+    ) -> Result<LeakedDataReport> {
+        let repo_id: RepoId = match repo_id {
+            Some(repo_id) => repo_id.as_str().try_into()?,
+            None => RepoId::wiki(),
+        };
+
+        let result = ctx
+            .data_unchecked::<Store>()
+            .leaked_data(repo_id, commit)
+            .await?;
+
+        Ok(result.into())
+    }
+
     async fn link(&self, ctx: &Context<'_>, id: String) -> Result<Option<Link>> {
         Ok(ctx
             .data_unchecked::<Store>()
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::page::USER_AGENT;
+use super::repo_url::RepoUrl;
+use super::FetchPolicy;
+use crate::prelude::*;
+
+const TIMEOUT_SECS: u64 = 20;
+
+// What we learned about a url the last time it was requested
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UrlStatus {
+    pub error: Option<String>,
+    pub location: Option<String>,
+    pub status: Option<u16>,
+}
+
+pub trait CheckUrl {
+    // Returns an error if the fetch policy does not allow the url to be requested
+    fn check(&self, url: &RepoUrl) -> Result<UrlStatus>;
+}
+
+pub struct UrlChecker {
+    agent: ureq::Agent,
+    policy: Arc<FetchPolicy>,
+}
+
+impl UrlChecker {
+    pub fn new(policy: Arc<FetchPolicy>) -> Self {
+        // Redirects are not followed so that the redirect target can be recorded
+        let agent = ureq::AgentBuilder::new()
+            .redirects(0)
+            .timeout(Duration::from_secs(TIMEOUT_SECS))
+            .user_agent(USER_AGENT)
+            .build();
+
+        Self { agent, policy }
+    }
+
+    fn request(&self, method: &str, url: &RepoUrl) -> Result<UrlStatus> {
+        let _permit = self.policy.permit(&url.normalized)?;
+
+        let response = match self.agent.request(method, &url.normalized).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => {
+                return Ok(UrlStatus {
+                    error: Some(err.to_string()),
+                    location: None,
+                    status: None,
+                })
+            }
+        };
+
+        Ok(UrlStatus {
+            error: None,
+            location: response.header("location").map(str::to_owned),
+            status: Some(response.status()),
+        })
+    }
+}
+
+impl CheckUrl for UrlChecker {
+    fn check(&self, url: &RepoUrl) -> Result<UrlStatus> {
+        log::info!("checking {}", url);
+        let status = self.request("HEAD", url)?;
+
+        // Some servers do not implement HEAD requests
+        match status.status {
+            Some(405) | Some(501) => self.request("GET", url),
+            _ => Ok(status),
+        }
+    }
+}
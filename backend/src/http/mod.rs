@@ -1,18 +1,57 @@
+use std::sync::Arc;
+
 use crate::prelude::*;
 
+mod check;
+pub use check::*;
 mod page;
 pub use page::*;
+mod policy;
+pub use policy::*;
 mod repo_url;
 pub use repo_url::*;
+mod robots;
+pub use robots::*;
+mod sanitize;
+pub use sanitize::*;
+mod snapshot;
+pub use snapshot::*;
 
 pub trait Fetch {
     fn fetch(&self, url: &repo_url::RepoUrl) -> Result<Response>;
+
+    // Used to inline images and stylesheets when taking a snapshot of a page
+    fn fetch_asset(&self, url: &str) -> Result<Asset> {
+        Err(Error::NotFound(format!("assets not fetched: {url}")))
+    }
+}
+
+// For callers that already have a title for each link, such as bookmark imports, and so should
+// never reach out to a site
+pub struct NoFetch;
+
+impl Fetch for NoFetch {
+    fn fetch(&self, url: &repo_url::RepoUrl) -> Result<Response> {
+        Err(Error::Fetch(format!("pages are not fetched here: {url}")))
+    }
 }
 
-pub struct Fetcher;
+pub struct Fetcher {
+    policy: Arc<FetchPolicy>,
+}
+
+impl Fetcher {
+    pub fn new(policy: Arc<FetchPolicy>) -> Self {
+        Self { policy }
+    }
+}
 
 impl Fetch for Fetcher {
     fn fetch(&self, url: &repo_url::RepoUrl) -> Result<Response> {
-        Page::from(url).fetch()
+        Page::from(url).fetch(&self.policy)
+    }
+
+    fn fetch_asset(&self, url: &str) -> Result<Asset> {
+        page::fetch_asset(&self.policy, url)
     }
 }
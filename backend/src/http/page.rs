@@ -1,10 +1,11 @@
 use scraper::{Html, Selector};
-use std::time::Duration;
 
 use super::repo_url::RepoUrl;
+use super::{Asset, FetchPolicy};
 use crate::prelude::*;
 
-const USER_AGENT: &str = "digraph/0.1.0";
+pub(super) const USER_AGENT: &str = "digraph/0.1.0";
+const MAX_ASSET_BYTES: u64 = 1_000_000;
 
 #[derive(Debug)]
 pub struct Page(pub RepoUrl);
@@ -31,7 +32,7 @@ impl Page {
         Self(url.to_owned())
     }
 
-    pub fn fetch(&self) -> Result<Response> {
+    pub fn fetch(&self, policy: &FetchPolicy) -> Result<Response> {
         if !self.should_fetch() {
             log::info!(
                 "document not suitable for fetching, skipping fetch: {}",
@@ -44,12 +45,8 @@ impl Page {
         }
 
         log::info!("fetching page: {}", self.0);
-        let text = ureq::get(&self.0.normalized)
-            .timeout(Duration::from_secs(20_000))
-            .set("User-Agent", USER_AGENT)
-            .call()?
-            .into_string()?;
-        let body = Html::parse_fragment(text.as_ref());
+        let response = policy.get(&self.0.normalized)?;
+        let body = Html::parse_fragment(&String::from_utf8_lossy(&response.body));
 
         log::info!("page fetched: {}", self.0);
         Ok(Response {
@@ -63,6 +60,20 @@ impl Page {
     }
 }
 
+pub fn fetch_asset(policy: &FetchPolicy, url: &str) -> Result<Asset> {
+    log::info!("fetching asset: {}", url);
+    let response = policy.get(url)?;
+
+    if response.body.len() as u64 > MAX_ASSET_BYTES {
+        return Err(Error::Load(format!("asset too large: {url}")));
+    }
+
+    Ok(Asset {
+        body: response.body,
+        content_type: response.content_type,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
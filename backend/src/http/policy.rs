@@ -0,0 +1,702 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::page::USER_AGENT;
+use super::robots::Robots;
+use crate::prelude::*;
+use crate::types::sha256_base64;
+
+// Roughly how many bytes of response bodies the cache holds before the oldest are removed
+pub const DEFAULT_HTTP_CACHE_SIZE: u64 = 500_000_000;
+const MAX_BODY_BYTES: u64 = 5_000_000;
+const MAX_REDIRECTS: usize = 5;
+const MAX_ROBOTS_BYTES: u64 = 500_000;
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+// Retry sooner when robots.txt could not be loaded
+const ROBOTS_ERROR_TTL: Duration = Duration::from_secs(60 * 60);
+const TIMEOUT_SECS: u64 = 20;
+
+#[derive(Clone, Debug)]
+pub struct PolicyOptions {
+    // Responses with an ETag or Last-Modified header are kept here and revalidated later
+    pub cache_dir: Option<PathBuf>,
+    pub cache_max_bytes: u64,
+    pub max_concurrent_requests: usize,
+    // The shortest time between the start of two requests to the same host; a longer
+    // Crawl-delay in robots.txt takes precedence
+    pub min_interval: Duration,
+}
+
+impl Default for PolicyOptions {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            cache_max_bytes: DEFAULT_HTTP_CACHE_SIZE,
+            max_concurrent_requests: 2,
+            min_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub body: Vec<u8>,
+    pub content_type: String,
+    pub status: u16,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    content_type: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    url: String,
+}
+
+// Response bodies are saved next to a small json file with the validators needed to make a
+// conditional request the next time the url is fetched.  Once the bodies add up to more than
+// `max_bytes`, the ones written longest ago are removed.
+#[derive(Debug)]
+struct HttpCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // The size of the bodies in the cache, which is worked out from the directory the first time
+    // something is written
+    size: Mutex<Option<u64>>,
+}
+
+impl HttpCache {
+    fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            size: Mutex::new(None),
+        }
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = sha256_base64(url);
+        (
+            self.dir.join(format!("{key}.json")),
+            self.dir.join(format!("{key}.body")),
+        )
+    }
+
+    fn get(&self, url: &str) -> Option<(CacheEntry, Vec<u8>)> {
+        let (meta, body) = self.paths(url);
+        let entry: CacheEntry = serde_json::from_slice(&fs::read(meta).ok()?).ok()?;
+        if entry.url != url {
+            return None;
+        }
+        Some((entry, fs::read(body).ok()?))
+    }
+
+    fn put(&self, entry: &CacheEntry, body: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let (meta, path) = self.paths(&entry.url);
+        let replaced = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        fs::write(path, body)?;
+        fs::write(meta, serde_json::to_vec(entry)?)?;
+
+        let mut size = self.size.lock().expect("failed to acquire lock");
+        let total = match *size {
+            Some(total) => total.saturating_sub(replaced) + body.len() as u64,
+            None => self.bodies()?.iter().map(|(_, len, _)| len).sum(),
+        };
+        *size = Some(if total > self.max_bytes {
+            self.evict()?
+        } else {
+            total
+        });
+        Ok(())
+    }
+
+    // The path, size and modification time of each body in the cache
+    fn bodies(&self) -> Result<Vec<(PathBuf, u64, std::time::SystemTime)>> {
+        let mut bodies = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "body") {
+                let meta = fs::metadata(&path)?;
+                bodies.push((path, meta.len(), meta.modified()?));
+            }
+        }
+        Ok(bodies)
+    }
+
+    // Removes the oldest entries until the cache is back under nine tenths of its limit, so that
+    // this does not happen on every write, and returns the size of what is left
+    fn evict(&self) -> Result<u64> {
+        let mut bodies = self.bodies()?;
+        bodies.sort_by_key(|(_, _, modified)| *modified);
+
+        let target = self.max_bytes / 10 * 9;
+        let mut total: u64 = bodies.iter().map(|(_, len, _)| len).sum();
+        for (path, len, _) in bodies {
+            if total <= target {
+                break;
+            }
+            log::debug!("evicting {:?} from the http cache", path);
+            fs::remove_file(path.with_extension("json")).ok();
+            fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(total)
+    }
+}
+
+// The outcome of a single request, before any redirect has been followed
+enum Hop {
+    Done(HttpResponse),
+    Redirect(String),
+}
+
+// Where a 30x response sends the client, if it is somewhere that we are willing to go
+fn redirect_location(url: &str, response: &ureq::Response) -> Result<Option<String>> {
+    if !(300..400).contains(&response.status()) || response.status() == 304 {
+        return Ok(None);
+    }
+
+    let location = match response.header("location") {
+        Some(location) => location,
+        None => return Ok(None),
+    };
+    let target = url::Url::parse(url)?.join(location)?;
+    match target.scheme() {
+        "http" | "https" => Ok(Some(target.to_string())),
+        scheme => Err(Error::Fetch(format!("redirect to {scheme} url: {target}"))),
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    active: usize,
+    next_request: Option<Instant>,
+    robots: Option<(Instant, Arc<Robots>)>,
+    // Set while one thread fetches robots.txt, so that others wait for it instead of fetching it
+    // as well
+    robots_loading: bool,
+}
+
+// Decides whether and when a request may be sent.  Every outgoing request for a page, asset or
+// link check goes through a single policy so that a bulk import does not get us rate-limited
+// or blocked by the sites being linked to.
+#[derive(Debug)]
+pub struct FetchPolicy {
+    agent: ureq::Agent,
+    cache: Option<HttpCache>,
+    hosts: Mutex<HashMap<String, HostState>>,
+    options: PolicyOptions,
+    released: Condvar,
+}
+
+// Holds one of the concurrent request slots for a host until it is dropped
+pub struct Permit<'p> {
+    host: String,
+    policy: &'p FetchPolicy,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut hosts = self.policy.hosts.lock().expect("failed to acquire lock");
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.active = state.active.saturating_sub(1);
+        }
+        self.policy.released.notify_all();
+    }
+}
+
+// Lets other threads waiting on robots.txt for a host go ahead once it has been fetched, or once
+// the fetch has failed
+struct RobotsLoading<'p> {
+    host: &'p str,
+    policy: &'p FetchPolicy,
+}
+
+impl Drop for RobotsLoading<'_> {
+    fn drop(&mut self) {
+        let mut hosts = self.policy.hosts.lock().expect("failed to acquire lock");
+        if let Some(state) = hosts.get_mut(self.host) {
+            state.robots_loading = false;
+        }
+        self.policy.released.notify_all();
+    }
+}
+
+impl FetchPolicy {
+    pub fn new(options: PolicyOptions) -> Self {
+        // Redirects are followed by hand, so that each hop goes through the policy for its host
+        let agent = ureq::AgentBuilder::new()
+            .redirects(0)
+            .timeout(Duration::from_secs(TIMEOUT_SECS))
+            .user_agent(USER_AGENT)
+            .build();
+
+        Self {
+            agent,
+            cache: options
+                .cache_dir
+                .to_owned()
+                .map(|dir| HttpCache::new(dir, options.cache_max_bytes)),
+            hosts: Mutex::new(HashMap::new()),
+            options,
+            released: Condvar::new(),
+        }
+    }
+
+    // Blocks until a request to the url can be sent, or returns an error if robots.txt does
+    // not allow it
+    pub fn permit(&self, url: &str) -> Result<Permit<'_>> {
+        let parsed = url::Url::parse(url)?;
+        let host = parsed.origin().ascii_serialization();
+
+        let robots = self.robots(&host);
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_owned(),
+        };
+        if !robots.allows(&path) {
+            return Err(Error::Fetch(format!("disallowed by robots.txt: {url}")));
+        }
+
+        let interval = std::cmp::max(
+            self.options.min_interval,
+            robots.crawl_delay.unwrap_or_default(),
+        );
+        Ok(self.acquire(host, interval))
+    }
+
+    // Blocks until one of the request slots for the host is free and the interval since the last
+    // request to it has passed
+    fn acquire(&self, host: String, interval: Duration) -> Permit<'_> {
+        let max_active = std::cmp::max(self.options.max_concurrent_requests, 1);
+
+        let delay = {
+            let mut hosts = self.hosts.lock().expect("failed to acquire lock");
+            while hosts.get(&host).map(|state| state.active).unwrap_or(0) >= max_active {
+                hosts = self.released.wait(hosts).expect("failed to acquire lock");
+            }
+
+            let state = hosts.entry(host.to_owned()).or_default();
+            state.active += 1;
+            let now = Instant::now();
+            let start = match state.next_request {
+                Some(next) => std::cmp::max(next, now),
+                None => now,
+            };
+            state.next_request = Some(start + interval);
+            start - now
+        };
+
+        if !delay.is_zero() {
+            log::debug!("waiting {:?} before contacting {}", delay, host);
+            std::thread::sleep(delay);
+        }
+
+        Permit { host, policy: self }
+    }
+
+    // Fetches the url, revalidating any cached copy with a conditional request.  Redirects are
+    // followed, with robots.txt and the limits for each host applied to every hop.
+    pub fn get(&self, url: &str) -> Result<HttpResponse> {
+        let mut url = url.to_owned();
+        for _ in 0..=MAX_REDIRECTS {
+            match self.get_once(&url)? {
+                Hop::Done(response) => return Ok(response),
+                Hop::Redirect(location) => {
+                    log::debug!("following redirect from {} to {}", url, location);
+                    url = location;
+                }
+            }
+        }
+        Err(Error::Fetch(format!("too many redirects: {url}")))
+    }
+
+    fn get_once(&self, url: &str) -> Result<Hop> {
+        let _permit = self.permit(url)?;
+
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(url));
+        let mut request = self.agent.get(url);
+        if let Some((entry, _)) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+        }
+
+        let response = request.call()?;
+        let status = response.status();
+
+        if let Some(location) = redirect_location(url, &response)? {
+            return Ok(Hop::Redirect(location));
+        }
+
+        if status == 304 {
+            if let Some((entry, body)) = cached {
+                log::debug!("not modified, using cached copy: {}", url);
+                return Ok(Hop::Done(HttpResponse {
+                    body,
+                    content_type: entry.content_type,
+                    status: 200,
+                }));
+            }
+        }
+
+        let content_type = response.content_type().to_owned();
+        let etag = response.header("etag").map(str::to_owned);
+        let last_modified = response.header("last-modified").map(str::to_owned);
+
+        let mut body = vec![];
+        response
+            .into_reader()
+            .take(MAX_BODY_BYTES + 1)
+            .read_to_end(&mut body)?;
+        if body.len() as u64 > MAX_BODY_BYTES {
+            return Err(Error::Load(format!("response too large: {url}")));
+        }
+
+        if let Some(cache) = &self.cache {
+            if status == 200 && (etag.is_some() || last_modified.is_some()) {
+                let entry = CacheEntry {
+                    content_type: content_type.to_owned(),
+                    etag,
+                    last_modified,
+                    url: url.to_owned(),
+                };
+                if let Err(err) = cache.put(&entry, &body) {
+                    log::warn!("failed to cache {}: {}", url, err);
+                }
+            }
+        }
+
+        Ok(Hop::Done(HttpResponse {
+            body,
+            content_type,
+            status,
+        }))
+    }
+
+    // robots.txt is fetched by one thread at a time for each host, under a request slot for the
+    // host like any other request
+    fn robots(&self, host: &str) -> Arc<Robots> {
+        {
+            let mut hosts = self.hosts.lock().expect("failed to acquire lock");
+            loop {
+                let state = hosts.entry(host.to_owned()).or_default();
+                if let Some((expires, robots)) = &state.robots {
+                    if *expires > Instant::now() {
+                        return Arc::clone(robots);
+                    }
+                }
+                if !state.robots_loading {
+                    state.robots_loading = true;
+                    break;
+                }
+                hosts = self.released.wait(hosts).expect("failed to acquire lock");
+            }
+        }
+
+        let loading = RobotsLoading { host, policy: self };
+        let (robots, ttl) = {
+            let _permit = self.acquire(host.to_owned(), self.options.min_interval);
+            self.fetch_robots(host)
+        };
+        let robots = Arc::new(robots);
+
+        {
+            let mut hosts = self.hosts.lock().expect("failed to acquire lock");
+            let state = hosts.entry(host.to_owned()).or_default();
+            state.robots = Some((Instant::now() + ttl, Arc::clone(&robots)));
+        }
+        drop(loading);
+        robots
+    }
+
+    fn fetch_robots(&self, host: &str) -> (Robots, Duration) {
+        let url = format!("{host}/robots.txt");
+        log::info!("fetching {}", url);
+
+        // Redirects for robots.txt are followed without going through the policy, since it is
+        // needed before the policy can be applied
+        let mut target = url.to_owned();
+        let mut result = self.agent.get(&url).call();
+        for _ in 0..MAX_REDIRECTS {
+            let location = match &result {
+                Ok(response) => redirect_location(&target, response),
+                Err(_) => break,
+            };
+            match location {
+                Ok(Some(location)) => {
+                    result = self.agent.get(&location).call();
+                    target = location;
+                }
+                _ => break,
+            }
+        }
+
+        match result {
+            Ok(response) => {
+                let mut text = String::new();
+                match response
+                    .into_reader()
+                    .take(MAX_ROBOTS_BYTES)
+                    .read_to_string(&mut text)
+                {
+                    Ok(_) => (Robots::parse(&text, USER_AGENT), ROBOTS_TTL),
+                    Err(err) => {
+                        log::warn!("failed to read {}: {}", url, err);
+                        (Robots::allow_all(), ROBOTS_ERROR_TTL)
+                    }
+                }
+            }
+
+            // A missing robots.txt means that there are no restrictions, while a server error
+            // is treated as a request to stay away for the time being
+            Err(ureq::Error::Status(status, _)) if status < 500 => {
+                (Robots::allow_all(), ROBOTS_TTL)
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                log::warn!("{} returned {}, holding off", url, status);
+                (Robots::disallow_all(), ROBOTS_ERROR_TTL)
+            }
+
+            // The request for the page itself will most likely fail and be reported
+            Err(err) => {
+                log::warn!("failed to fetch {}: {}", url, err);
+                (Robots::allow_all(), ROBOTS_ERROR_TTL)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Records the requests it receives and answers them with canned responses.  Requests for
+    // /moved are sent on to `redirect`.
+    fn serve(requests: Arc<Mutex<Vec<String>>>, redirect: Option<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_owned();
+                let conditional = request.to_lowercase().contains("if-none-match: \"v1\"");
+                requests.lock().unwrap().push(request);
+
+                let response = match path.as_str() {
+                    "/robots.txt" => {
+                        let body = "User-agent: *\nDisallow: /private\n";
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    "/moved" => format!(
+                        "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                        redirect.as_deref().unwrap_or("/public")
+                    ),
+                    "/cached" if conditional => {
+                        "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n".to_owned()
+                    }
+                    "/cached" => "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nETag: \"v1\"\r\n\
+                        Content-Length: 4\r\n\r\nbody"
+                        .to_owned(),
+                    _ => "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_owned(),
+                };
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        format!("http://{addr}")
+    }
+
+    fn policy(cache_dir: Option<PathBuf>, min_interval: Duration) -> FetchPolicy {
+        FetchPolicy::new(PolicyOptions {
+            cache_dir,
+            max_concurrent_requests: 1,
+            min_interval,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn robots_honored() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(Arc::clone(&requests), None);
+        let policy = policy(None, Duration::ZERO);
+
+        assert!(policy.get(&format!("{host}/public")).is_ok());
+        assert!(matches!(
+            policy.get(&format!("{host}/private/page")),
+            Err(Error::Fetch(_))
+        ));
+
+        // robots.txt is only fetched once
+        let requests = requests.lock().unwrap();
+        let count = requests
+            .iter()
+            .filter(|r| r.starts_with("GET /robots.txt"))
+            .count();
+        assert_eq!(count, 1);
+        assert!(!requests.iter().any(|r| r.starts_with("GET /private")));
+    }
+
+    #[test]
+    fn robots_fetched_once_by_concurrent_requests() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(Arc::clone(&requests), None);
+        let policy = Arc::new(FetchPolicy::new(PolicyOptions {
+            max_concurrent_requests: 4,
+            min_interval: Duration::ZERO,
+            ..Default::default()
+        }));
+
+        let threads = (0..4)
+            .map(|i| {
+                let policy = Arc::clone(&policy);
+                let url = format!("{host}/page{i}");
+                thread::spawn(move || policy.get(&url).unwrap())
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let requests = requests.lock().unwrap();
+        let count = requests
+            .iter()
+            .filter(|r| r.starts_with("GET /robots.txt"))
+            .count();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn cache_evicts_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(dir.path().to_owned(), 20);
+        let entry = |url: &str| CacheEntry {
+            content_type: "text/html".into(),
+            etag: Some("\"v1\"".into()),
+            last_modified: None,
+            url: url.to_owned(),
+        };
+
+        cache
+            .put(&entry("https://example.com/a"), b"0123456789")
+            .unwrap();
+        // Modification times are not always fine-grained
+        thread::sleep(Duration::from_millis(20));
+        cache
+            .put(&entry("https://example.com/b"), b"0123456789")
+            .unwrap();
+        assert!(cache.get("https://example.com/a").is_some());
+
+        thread::sleep(Duration::from_millis(20));
+        cache
+            .put(&entry("https://example.com/c"), b"0123456789")
+            .unwrap();
+        assert!(cache.get("https://example.com/a").is_none());
+        assert!(cache.get("https://example.com/c").is_some());
+        assert_eq!(*cache.size.lock().unwrap(), Some(10));
+    }
+
+    #[test]
+    fn redirects_followed() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(Arc::clone(&requests), None);
+        let policy = policy(None, Duration::ZERO);
+
+        let response = policy.get(&format!("{host}/moved")).unwrap();
+        assert_eq!(response.body, b"ok");
+
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().any(|r| r.starts_with("GET /public")));
+    }
+
+    #[test]
+    fn redirects_checked_against_robots() {
+        let other_requests = Arc::new(Mutex::new(vec![]));
+        let other = serve(Arc::clone(&other_requests), None);
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(Arc::clone(&requests), Some(format!("{other}/private/page")));
+        let policy = policy(None, Duration::ZERO);
+
+        assert!(matches!(
+            policy.get(&format!("{host}/moved")),
+            Err(Error::Fetch(_))
+        ));
+
+        // The robots.txt of the host redirected to is consulted, and the page is not requested
+        let other_requests = other_requests.lock().unwrap();
+        assert!(other_requests
+            .iter()
+            .any(|r| r.starts_with("GET /robots.txt")));
+        assert!(!other_requests.iter().any(|r| r.starts_with("GET /private")));
+    }
+
+    #[test]
+    fn rate_limited() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(requests, None);
+        let policy = policy(None, Duration::from_millis(100));
+
+        // Load robots.txt before timing anything
+        drop(policy.permit(&format!("{host}/")).unwrap());
+
+        let start = Instant::now();
+        drop(policy.permit(&format!("{host}/a")).unwrap());
+        drop(policy.permit(&format!("{host}/b")).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let host = serve(Arc::clone(&requests), None);
+        let dir = tempfile::tempdir().unwrap();
+        let policy = policy(Some(dir.path().to_owned()), Duration::ZERO);
+        let url = format!("{host}/cached");
+
+        let response = policy.get(&url).unwrap();
+        assert_eq!(response.body, b"body");
+
+        let response = policy.get(&url).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"body");
+        assert_eq!(response.content_type, "text/html");
+
+        let requests = requests.lock().unwrap();
+        let conditional = requests
+            .iter()
+            .filter(|r| r.to_lowercase().contains("if-none-match"))
+            .count();
+        assert_eq!(conditional, 1);
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Group {
+    agents: Vec<String>,
+    crawl_delay: Option<Duration>,
+    rules: Vec<Rule>,
+}
+
+// The rules from a site's robots.txt that apply to us
+#[derive(Clone, Debug, Default)]
+pub struct Robots {
+    pub crawl_delay: Option<Duration>,
+    rules: Vec<Rule>,
+}
+
+// Supports the "*" wildcard and the "$" end-of-path anchor
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    if !path.starts_with(first) {
+        return false;
+    }
+
+    let mut rest = &path[first.len()..];
+    let mut last_part_empty = first.is_empty();
+    for part in parts {
+        last_part_empty = part.is_empty();
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty() || (last_part_empty && pattern.ends_with('*'))
+}
+
+impl Robots {
+    // Used when the site has no robots.txt
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    // Used when robots.txt could not be fetched because of a server error
+    pub fn disallow_all() -> Self {
+        Self {
+            crawl_delay: None,
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".into(),
+            }],
+        }
+    }
+
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut groups: Vec<Group> = vec![];
+        let mut in_agent_lines = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            if key == "user-agent" {
+                // Consecutive user-agent lines share a group
+                if !in_agent_lines {
+                    groups.push(Group::default());
+                }
+                in_agent_lines = true;
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_lowercase());
+                }
+                continue;
+            }
+
+            in_agent_lines = false;
+            let group = match groups.last_mut() {
+                Some(group) => group,
+                None => continue,
+            };
+
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+                    allow: key == "allow",
+                    pattern: value.to_owned(),
+                }),
+                "crawl-delay" => {
+                    group.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64)
+                }
+                _ => {}
+            }
+        }
+
+        let group = groups
+            .iter()
+            .find(|group| {
+                group
+                    .agents
+                    .iter()
+                    .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+            })
+            .or_else(|| {
+                groups
+                    .iter()
+                    .find(|group| group.agents.iter().any(|agent| agent == "*"))
+            });
+
+        match group {
+            Some(group) => Self {
+                crawl_delay: group.crawl_delay,
+                rules: group.rules.to_owned(),
+            },
+            None => Self::allow_all(),
+        }
+    }
+
+    // The most specific matching rule wins, and an allow rule wins a tie
+    pub fn allows(&self, path: &str) -> bool {
+        let mut best: Option<&Rule> = None;
+
+        for rule in &self.rules {
+            if !matches(&rule.pattern, path) {
+                continue;
+            }
+
+            best = match best {
+                Some(current)
+                    if current.pattern.len() > rule.pattern.len()
+                        || (current.pattern.len() == rule.pattern.len() && current.allow) =>
+                {
+                    Some(current)
+                }
+                _ => Some(rule),
+            };
+        }
+
+        best.map(|rule| rule.allow).unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = r#"
+# Some comment
+User-agent: *
+Disallow: /private/
+Allow: /private/public
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: badbot
+User-agent: digraph
+Disallow: /search
+"#;
+
+    #[test]
+    fn wildcard_group() {
+        let robots = Robots::parse(ROBOTS, "otherbot/1.0");
+        assert!(robots.allows("/"));
+        assert!(robots.allows("/articles/1"));
+        assert!(!robots.allows("/private/page"));
+        assert!(robots.allows("/private/public/page"));
+        assert!(!robots.allows("/files/report.pdf"));
+        assert!(robots.allows("/files/report.pdf.html"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn specific_group() {
+        let robots = Robots::parse(ROBOTS, "digraph/0.1.0");
+        assert!(!robots.allows("/search?q=something"));
+        assert!(robots.allows("/private/page"));
+        assert_eq!(robots.crawl_delay, None);
+    }
+
+    #[test]
+    fn empty_disallow() {
+        let robots = Robots::parse("User-agent: *\nDisallow:\n", "digraph/0.1.0");
+        assert!(robots.allows("/anything"));
+    }
+
+    #[test]
+    fn disallow_all() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /\n", "digraph/0.1.0");
+        assert!(!robots.allows("/"));
+        assert!(!robots.allows("/articles/1"));
+        assert!(!Robots::disallow_all().allows("/articles/1"));
+    }
+}
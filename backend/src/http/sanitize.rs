@@ -0,0 +1,242 @@
+use lazy_static::lazy_static;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+// Elements that are kept.  Elements that are not listed here or in DROPPED_ELEMENTS are unwrapped,
+// so that their text is kept but not the element itself.  Stylesheets and style attributes are
+// left out, since CSS can load resources from other sites.
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "address",
+    "article",
+    "aside",
+    "b",
+    "bdi",
+    "bdo",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "main",
+    "mark",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "ruby",
+    "s",
+    "samp",
+    "section",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+// Elements that are dropped along with everything in them, since their content is either active
+// or makes no sense outside of the element
+const DROPPED_ELEMENTS: &[&str] = &[
+    "applet", "audio", "base", "button", "canvas", "embed", "form", "frame", "frameset", "iframe",
+    "input", "link", "math", "meta", "noscript", "object", "script", "select", "style", "svg",
+    "template", "textarea", "title", "video",
+];
+
+const GENERIC_ATTRIBUTES: &[&str] = &["dir", "lang", "title"];
+
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href"]),
+    ("col", &["span"]),
+    ("colgroup", &["span"]),
+    ("del", &["datetime"]),
+    ("details", &["open"]),
+    ("img", &["alt", "height", "src", "width"]),
+    ("ins", &["datetime"]),
+    ("ol", &["reversed", "start"]),
+    ("td", &["colspan", "headers", "rowspan"]),
+    ("th", &["colspan", "headers", "rowspan", "scope"]),
+    ("time", &["datetime"]),
+];
+
+// Relative urls are kept, since they stay on the page or the site it came from
+const URL_SCHEMES: &[&str] = &["data", "http", "https", "mailto"];
+
+// Data urls are only kept for images that were inlined when the page was captured
+fn filter_attribute<'u>(element: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    let is_data = value
+        .trim_start()
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"));
+    if !is_data {
+        return Some(value.into());
+    }
+
+    let image = value.trim_start()[5..].to_ascii_lowercase();
+    let allowed = element == "img"
+        && attribute == "src"
+        && image.starts_with("image/")
+        && !image.starts_with("image/svg");
+    allowed.then(|| value.into())
+}
+
+lazy_static! {
+    static ref SANITIZER: ammonia::Builder<'static> = {
+        let mut builder = ammonia::Builder::empty();
+        builder
+            .tags(ALLOWED_ELEMENTS.iter().copied().collect())
+            .clean_content_tags(DROPPED_ELEMENTS.iter().copied().collect())
+            .generic_attributes(GENERIC_ATTRIBUTES.iter().copied().collect())
+            .tag_attributes(
+                TAG_ATTRIBUTES
+                    .iter()
+                    .map(|(tag, attributes)| (*tag, attributes.iter().copied().collect()))
+                    .collect::<HashMap<_, HashSet<_>>>(),
+            )
+            .url_schemes(URL_SCHEMES.iter().copied().collect())
+            .attribute_filter(filter_attribute);
+        builder
+    };
+}
+
+// Rebuilds a page from an allowlist of elements, attributes and url schemes, so that nothing on
+// it can run or load anything from another site when the page is displayed
+pub fn sanitize_html(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(html: &str) -> String {
+        sanitize_html(&format!("<html><body>{html}</body></html>"))
+    }
+
+    #[test]
+    fn content_kept() {
+        let html = body(r#"<h1 title="Title">Title</h1><p>Some <em>text</em> &amp; more</p>"#);
+        assert!(html.contains(r#"<h1 title="Title">Title</h1>"#));
+        assert!(html.contains("<p>Some <em>text</em> &amp; more</p>"));
+    }
+
+    #[test]
+    fn scripts_removed() {
+        let html = body(r#"<p>Text</p><script>alert("hi")</script><noscript>No</noscript>"#);
+        assert!(!html.contains("alert"));
+        assert!(!html.contains("No"));
+        assert!(html.contains("<p>Text</p>"));
+    }
+
+    #[test]
+    fn event_handlers_removed() {
+        let html = body(r#"<img src="cat.png" onerror="alert(1)"><p onclick="alert(2)">Hi</p>"#);
+        assert!(!html.contains("alert"));
+        assert!(html.contains(r#"<img src="cat.png">"#));
+        assert!(html.contains("<p>Hi</p>"));
+    }
+
+    #[test]
+    fn javascript_urls_removed() {
+        for url in [
+            "javascript:alert(1)",
+            " JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html,<script>alert(1)</script>",
+        ] {
+            let html = body(&format!(
+                r#"<a href="{}">Link</a>"#,
+                url.replace('"', "&quot;")
+            ));
+            assert!(!html.contains("href"), "{url}: {html}");
+            assert!(html.contains(">Link</a>"), "{url}: {html}");
+        }
+
+        let html = body(r#"<a href="https://example.com/">Link</a><a href="/path#x">Other</a>"#);
+        assert!(html.contains(r#"href="https://example.com/""#));
+        assert!(html.contains(r#"href="/path#x""#));
+    }
+
+    #[test]
+    fn active_elements_removed() {
+        let html = sanitize_html(
+            r#"<html><head><meta http-equiv="refresh" content="0;url=javascript:alert(1)">
+            <base href="https://evil.example/"></head>
+            <body><iframe src="https://evil.example/"></iframe>
+            <object data="evil.swf"></object><embed src="evil.swf">
+            <svg><script>alert(1)</script></svg><form action="/"><input name="q"></form>
+            </body></html>"#,
+        );
+        for needle in [
+            "refresh", "evil", "alert", "<iframe", "<object", "<embed", "<form", "<meta",
+        ] {
+            assert!(!html.contains(needle), "{needle}: {html}");
+        }
+    }
+
+    #[test]
+    fn styles_removed() {
+        let html = sanitize_html(
+            r#"<html><head><link rel="stylesheet" href="data:text/css;base64,cA==">
+            <style>p { background: url(https://tracker.example/a.png) }</style></head>
+            <body><p style="background: url(https://tracker.example/b.png)">Text</p></body></html>"#,
+        );
+        assert!(!html.contains("tracker"), "{html}");
+        assert!(!html.contains("<link"), "{html}");
+        assert!(html.contains("<p>Text</p>"), "{html}");
+    }
+
+    #[test]
+    fn inlined_images_kept() {
+        let html = body(
+            r#"<img src="data:image/png;base64,Y2F0">
+            <img src="data:image/svg+xml;base64,PHN2Zz4=">"#,
+        );
+        assert!(html.contains(r#"<img src="data:image/png;base64,Y2F0">"#));
+        assert!(!html.contains("svg"), "{html}");
+    }
+}
@@ -0,0 +1,211 @@
+use base64::{engine, Engine as _};
+use itertools::Itertools;
+use scraper::Selector;
+
+use super::page::Response;
+use super::sanitize_html;
+use super::Fetch;
+
+// Keep snapshots of pages with many large images from growing without bound
+const MAX_ASSETS: usize = 50;
+const SKIPPED_ELEMENTS: &[&str] = &["noscript", "script", "style", "template"];
+
+#[derive(Clone, Debug)]
+pub struct Asset {
+    pub body: Vec<u8>,
+    pub content_type: String,
+}
+
+impl Asset {
+    fn data_uri(&self) -> String {
+        let encoded = engine::general_purpose::STANDARD.encode(&self.body);
+        format!("data:{};base64,{}", self.content_type, encoded)
+    }
+}
+
+// A copy of a page that can be read after the original has changed or gone away
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub html: String,
+    pub text: String,
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('\u{a0}', "&nbsp;")
+        .replace('"', "&quot;")
+}
+
+impl Response {
+    pub fn snapshot(&self, fetcher: &(dyn Fetch + Send + Sync)) -> Snapshot {
+        Snapshot {
+            html: self.inlined_html(fetcher),
+            text: self.readable_text(),
+        }
+    }
+
+    // The page is passed through an allowlist once its assets have been inlined, since the html
+    // that is captured is served back to readers as it is stored
+    fn inlined_html(&self, fetcher: &(dyn Fetch + Send + Sync)) -> String {
+        sanitize_html(&self.inline_assets(fetcher))
+    }
+
+    fn inline_assets(&self, fetcher: &(dyn Fetch + Send + Sync)) -> String {
+        let mut html = self.body.html();
+
+        let base = match url::Url::parse(&self.url.normalized) {
+            Ok(base) => base,
+            Err(err) => {
+                log::warn!("unable to inline assets for {}: {}", self.url, err);
+                return html;
+            }
+        };
+
+        // Stylesheets are not inlined, since they are left out of the snapshot when it is
+        // sanitized
+        let sel = Selector::parse("img[src]").expect("failed to parse selector");
+        let sources = self
+            .body
+            .select(&sel)
+            .filter_map(|element| element.value().attr("src"))
+            .filter(|value| !value.starts_with("data:"))
+            .unique()
+            .take(MAX_ASSETS);
+
+        for value in sources {
+            let asset = match base.join(value) {
+                Ok(url) => fetcher.fetch_asset(url.as_str()),
+                Err(err) => {
+                    log::info!("skipping asset {}: {}", value, err);
+                    continue;
+                }
+            };
+
+            match asset {
+                Ok(asset) => {
+                    let before = format!(r#"src="{}""#, escape_attribute(value));
+                    let after = format!(r#"src="{}""#, asset.data_uri());
+                    html = html.replace(&before, &after);
+                }
+                Err(err) => log::info!("failed to fetch asset {}: {}", value, err),
+            }
+        }
+
+        html
+    }
+
+    fn readable_text(&self) -> String {
+        let sel = Selector::parse("body").expect("failed to parse selector");
+        let root = self
+            .body
+            .select(&sel)
+            .next()
+            .unwrap_or_else(|| self.body.root_element());
+
+        let mut parts = vec![];
+        for node in root.descendants() {
+            if let Some(text) = node.value().as_text() {
+                let skipped = node.ancestors().any(|ancestor| {
+                    ancestor
+                        .value()
+                        .as_element()
+                        .map(|e| SKIPPED_ELEMENTS.contains(&e.name()))
+                        .unwrap_or(false)
+                });
+                if skipped {
+                    continue;
+                }
+
+                let text = text.split_whitespace().join(" ");
+                if !text.is_empty() {
+                    parts.push(text);
+                }
+            }
+        }
+
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RepoUrl;
+    use crate::prelude::*;
+    use scraper::Html;
+
+    struct Fetcher;
+
+    impl Fetch for Fetcher {
+        fn fetch(&self, _url: &RepoUrl) -> Result<Response> {
+            Err(Error::NotFound("not used".into()))
+        }
+
+        fn fetch_asset(&self, url: &str) -> Result<Asset> {
+            match url {
+                "https://example.com/images/cat.png" => Ok(Asset {
+                    body: b"cat".to_vec(),
+                    content_type: "image/png".into(),
+                }),
+                _ => Err(Error::NotFound(url.into())),
+            }
+        }
+    }
+
+    fn response(html: &str) -> Response {
+        Response {
+            url: RepoUrl::parse("https://example.com/articles/1").unwrap(),
+            body: Html::parse_document(html),
+        }
+    }
+
+    #[test]
+    fn readable_text() {
+        let snapshot = response(
+            r#"<html><head><title>Title</title><style>p { color: red }</style></head>
+            <body><h1>Heading</h1>
+            <p>Some   text</p><script>alert("hi")</script></body></html>"#,
+        )
+        .snapshot(&Fetcher);
+
+        assert_eq!(snapshot.text, "Heading Some text");
+    }
+
+    #[test]
+    fn assets_inlined() {
+        let snapshot = response(
+            r#"<html><body><img src="/images/cat.png"><img src="missing.png"></body></html>"#,
+        )
+        .snapshot(&Fetcher);
+
+        assert!(snapshot
+            .html
+            .contains(r#"src="data:image/png;base64,Y2F0""#));
+        assert!(snapshot.html.contains(r#"src="missing.png""#));
+    }
+
+    #[test]
+    fn scripts_removed() {
+        let snapshot =
+            response(r#"<html><body><p>Text</p><script>alert("hi")</script></body></html>"#)
+                .snapshot(&Fetcher);
+
+        assert!(!snapshot.html.contains("alert"));
+        assert!(snapshot.html.contains("<p>Text</p>"));
+    }
+
+    #[test]
+    fn event_handlers_removed() {
+        let snapshot = response(
+            r#"<html><body><img src="/images/cat.png" onload="alert(1)">
+            <a href="javascript:alert(2)">Link</a></body></html>"#,
+        )
+        .snapshot(&Fetcher);
+
+        assert!(!snapshot.html.contains("alert"));
+        assert!(snapshot
+            .html
+            .contains(r#"<img src="data:image/png;base64,Y2F0">"#));
+    }
+}
@@ -8,6 +8,7 @@ extern crate quick_error;
 #[macro_use]
 extern crate derivative;
 
+pub mod accounts;
 pub mod config;
 pub mod db;
 pub mod errors;
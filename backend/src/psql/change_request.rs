@@ -0,0 +1,285 @@
+use sqlx::postgres::PgPool;
+use sqlx::types::{Json, Uuid};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::git;
+use crate::graphql::{ChangeRequest, ChangeRequestStatus};
+use crate::prelude::*;
+
+const CHANGE_REQUEST_FIELDS: &str = r#"
+    cr.id,
+    cr.author_id,
+    cr.created_at,
+    cr.description,
+    cr.payload,
+    cr.reviewed_at,
+    cr.reviewer_id,
+    cr.source_repository_id,
+    cr.status,
+    cr.target_repository_id,
+    cr.title
+"#;
+
+#[derive(sqlx::FromRow, Clone, Debug)]
+pub struct Row {
+    id: Uuid,
+    author_id: Uuid,
+    created_at: Timestamp,
+    description: String,
+    payload: Json<git::ChangeRequestPayload>,
+    reviewed_at: Option<Timestamp>,
+    reviewer_id: Option<Uuid>,
+    source_repository_id: Uuid,
+    status: String,
+    target_repository_id: Uuid,
+    title: String,
+}
+
+impl Row {
+    fn to_change_request(&self) -> Result<ChangeRequest> {
+        let status = ChangeRequestStatus::from_str(&self.status)
+            .map_err(|err| Error::Parse(format!("{}: {}", self.status, err)))?;
+
+        Ok(ChangeRequest {
+            author_id: self.author_id.to_string(),
+            created_at: self.created_at,
+            description: self.description.to_owned(),
+            id: self.id.to_string(),
+            payload: self.payload.0.to_owned(),
+            reviewed_at: self.reviewed_at,
+            reviewer_id: self.reviewer_id.map(|id| id.to_string()),
+            source_repo_id: self.source_repository_id.to_string(),
+            status,
+            target_repo_id: self.target_repository_id.to_string(),
+            title: self.title.to_owned(),
+        })
+    }
+}
+
+pub struct CreateChangeRequest {
+    actor: Arc<Viewer>,
+    description: String,
+    payload: git::ChangeRequestPayload,
+    source_repo_id: RepoId,
+    target_repo_id: RepoId,
+    title: String,
+}
+
+pub struct CreateChangeRequestResult {
+    pub change_request: ChangeRequest,
+}
+
+impl CreateChangeRequest {
+    pub fn new(
+        actor: Arc<Viewer>,
+        source_repo_id: RepoId,
+        target_repo_id: RepoId,
+        title: String,
+        description: String,
+        payload: git::ChangeRequestPayload,
+    ) -> Self {
+        Self {
+            actor,
+            description,
+            payload,
+            source_repo_id,
+            target_repo_id,
+            title,
+        }
+    }
+
+    pub async fn call(&self, pool: &PgPool) -> Result<CreateChangeRequestResult> {
+        if self.actor.is_guest() {
+            return Err(Error::RBAC("log in to propose changes".into()));
+        }
+
+        log::info!(
+            "creating change request from {} to {} for {}",
+            self.source_repo_id,
+            self.target_repo_id,
+            self.actor.user_id
+        );
+
+        let query = format!(
+            r#"
+            insert into change_requests as cr
+                (author_id, source_repository_id, target_repository_id, title, description, payload)
+                values ($1::uuid, $2::uuid, $3::uuid, $4, $5, $6)
+                returning {CHANGE_REQUEST_FIELDS}
+            "#
+        );
+        let row = sqlx::query_as::<_, Row>(&query)
+            .bind(&self.actor.user_id)
+            .bind(self.source_repo_id.to_string())
+            .bind(self.target_repo_id.to_string())
+            .bind(&self.title)
+            .bind(&self.description)
+            .bind(Json(&self.payload))
+            .fetch_one(pool)
+            .await?;
+
+        Ok(CreateChangeRequestResult {
+            change_request: row.to_change_request()?,
+        })
+    }
+}
+
+// Change requests are visible to their author and to anyone who can read the target repo
+pub struct FetchChangeRequests {
+    id: Option<String>,
+    limit: i64,
+    status: Option<ChangeRequestStatus>,
+    viewer: Arc<Viewer>,
+}
+
+impl FetchChangeRequests {
+    pub fn new(viewer: Arc<Viewer>, status: Option<ChangeRequestStatus>, limit: i64) -> Self {
+        Self {
+            id: None,
+            limit,
+            status,
+            viewer,
+        }
+    }
+
+    pub fn by_id(viewer: Arc<Viewer>, id: String) -> Self {
+        Self {
+            id: Some(id),
+            limit: 1,
+            status: None,
+            viewer,
+        }
+    }
+
+    pub async fn call(&self, pool: &PgPool) -> Result<Vec<ChangeRequest>> {
+        let query = format!(
+            r#"
+            select {CHANGE_REQUEST_FIELDS}
+                from change_requests cr
+                where (cr.author_id::text = $1 or cr.target_repository_id = any($2::uuid[]))
+                    and ($3::uuid is null or cr.id = $3::uuid)
+                    and ($4::text is null or cr.status = $4)
+                order by cr.created_at desc
+                limit $5
+            "#
+        );
+        let rows = sqlx::query_as::<_, Row>(&query)
+            .bind(&self.viewer.user_id)
+            .bind(self.viewer.read_repo_ids.to_vec())
+            .bind(&self.id)
+            .bind(self.status.map(|status| status.to_string()))
+            .bind(self.limit)
+            .fetch_all(pool)
+            .await?;
+
+        rows.iter().map(Row::to_change_request).collect()
+    }
+}
+
+// Closes an open change request.  Anyone who can write to the target repo can accept or reject
+// it, and the author can reject (withdraw) it.
+pub struct CloseChangeRequest {
+    actor: Arc<Viewer>,
+    id: String,
+    status: ChangeRequestStatus,
+}
+
+pub struct CloseChangeRequestResult {
+    pub change_request: ChangeRequest,
+}
+
+impl CloseChangeRequest {
+    pub fn new(actor: Arc<Viewer>, id: String, status: ChangeRequestStatus) -> Self {
+        Self { actor, id, status }
+    }
+
+    pub async fn call(&self, pool: &PgPool) -> Result<CloseChangeRequestResult> {
+        if self.status == ChangeRequestStatus::Open {
+            return Err(Error::Repo("a change request cannot be reopened".into()));
+        }
+
+        log::info!(
+            "marking change request {} as {} for {}",
+            self.id,
+            self.status,
+            self.actor.user_id
+        );
+
+        let query = format!(
+            r#"
+            update change_requests cr
+                set status = $1,
+                    reviewer_id = $2::uuid,
+                    reviewed_at = now(),
+                    updated_at = now()
+                where cr.id = $3::uuid
+                    and cr.status = 'open'
+                    and (
+                        cr.target_repository_id = any($4::uuid[])
+                        or ($1 = 'rejected' and cr.author_id = $2::uuid)
+                    )
+                returning {CHANGE_REQUEST_FIELDS}
+            "#
+        );
+        let row = sqlx::query_as::<_, Row>(&query)
+            .bind(self.status.to_string())
+            .bind(&self.actor.user_id)
+            .bind(&self.id)
+            .bind(self.actor.write_repo_ids.to_vec())
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("no open change request: {}", self.id)))?;
+
+        Ok(CloseChangeRequestResult {
+            change_request: row.to_change_request()?,
+        })
+    }
+}
+
+// Puts a change request that was accepted by the actor back to open.  Used when the changes in an
+// accepted request could not be applied to the target repo.
+pub struct ReopenChangeRequest {
+    actor: Arc<Viewer>,
+    id: String,
+}
+
+impl ReopenChangeRequest {
+    pub fn new(actor: Arc<Viewer>, id: String) -> Self {
+        Self { actor, id }
+    }
+
+    pub async fn call(&self, pool: &PgPool) -> Result<()> {
+        log::info!(
+            "reopening change request {} for {}",
+            self.id,
+            self.actor.user_id
+        );
+
+        let result = sqlx::query(
+            r#"
+            update change_requests cr
+                set status = 'open',
+                    reviewer_id = null,
+                    reviewed_at = null,
+                    updated_at = now()
+                where cr.id = $1::uuid
+                    and cr.status = 'accepted'
+                    and cr.reviewer_id = $2::uuid
+            "#,
+        )
+        .bind(&self.id)
+        .bind(&self.actor.user_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!(
+                "no accepted change request: {}",
+                self.id
+            )));
+        }
+
+        Ok(())
+    }
+}
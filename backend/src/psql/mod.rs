@@ -1,3 +1,5 @@
+mod change_request;
+pub use change_request::*;
 mod organization;
 pub use organization::*;
 mod registration;
@@ -13,7 +13,8 @@ const REPOSITORY_FIELDS: &str = r#"
     r.name,
     r.organization_id,
     r.owner_id,
-    r.private
+    r.private,
+    r.upstream_repository_id
 "#;
 
 const REPOSITORY_JOINS: &str = r#"
@@ -31,6 +32,7 @@ pub struct Row {
     organization_id: Uuid,
     owner_id: Uuid,
     private: bool,
+    upstream_repository_id: Option<Uuid>,
 }
 
 impl Row {
@@ -41,6 +43,7 @@ impl Row {
             organization_id: self.organization_id.to_string(),
             owner_id: self.owner_id.to_string(),
             private: self.private,
+            upstream_repo_id: self.upstream_repository_id.map(|id| id.to_string()),
         }
     }
 }
@@ -184,3 +187,106 @@ impl SelectRepository {
         Ok(SelectRepositoryResult { actor: row, repo })
     }
 }
+
+// Registers a repo that has just been forked from another one.  The fork goes into the actor's
+// personal organization and is private to them.
+pub struct CreateForkRepository {
+    actor: Arc<Viewer>,
+    fork_repo_id: RepoId,
+    name: String,
+    upstream_repo_id: RepoId,
+    upstream_topic_id: Option<ExternalId>,
+}
+
+pub struct CreateForkRepositoryResult {
+    pub repo: Repository,
+}
+
+impl CreateForkRepository {
+    pub fn new(
+        actor: Arc<Viewer>,
+        fork_repo_id: RepoId,
+        name: String,
+        upstream_repo_id: RepoId,
+        upstream_topic_id: Option<ExternalId>,
+    ) -> Self {
+        Self {
+            actor,
+            fork_repo_id,
+            name,
+            upstream_repo_id,
+            upstream_topic_id,
+        }
+    }
+
+    pub async fn call(&self, pool: &PgPool) -> Result<CreateForkRepositoryResult> {
+        if self.actor.is_guest() {
+            return Err(Error::RBAC("log in to fork a repo".into()));
+        }
+
+        log::info!(
+            "registering fork {} of {} for {}",
+            self.fork_repo_id,
+            self.upstream_repo_id,
+            self.actor.user_id
+        );
+        let mut tx = pool.begin().await?;
+
+        let row = sqlx::query_as::<_, Row>(
+            r#"
+            insert into repositories
+                (
+                    id,
+                    organization_id,
+                    name,
+                    owner_id,
+                    private,
+                    upstream_repository_id,
+                    upstream_topic_id,
+                    forked_at
+                )
+                select $1::uuid, o.id, $2, u.id, 't', $3::uuid, $4, now()
+                from users u
+                join organizations o on o.login = u.login
+                where u.id = $5::uuid
+                returning id, name, organization_id, owner_id, private, upstream_repository_id
+            "#,
+        )
+        .bind(self.fork_repo_id.to_string())
+        .bind(&self.name)
+        .bind(self.upstream_repo_id.to_string())
+        .bind(self.upstream_topic_id.as_ref().map(ExternalId::to_string))
+        .bind(&self.actor.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("no personal org for {}", self.actor.user_id)))?;
+
+        sqlx::query(
+            "insert into users_repositories
+                (user_id, repository_id, can_read, can_write, is_personal_repo)
+                values ($1::uuid, $2::uuid, 't', 't', 't')
+                on conflict do nothing",
+        )
+        .bind(&self.actor.user_id)
+        .bind(self.fork_repo_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        // So that the fork is cleaned up along with the rest of the account
+        sqlx::query(
+            "update users
+                set personal_prefixes = array_append(personal_prefixes, $1)
+                where id = $2::uuid",
+        )
+        .bind(self.fork_repo_id.to_string())
+        .bind(&self.actor.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(CreateForkRepositoryResult {
+            repo: row.to_repository(),
+        })
+    }
+}
@@ -131,7 +131,7 @@ impl<'i> UpsertRegisteredUser<'i> {
         Ok(UpsertUserResult { user })
     }
 
-    async fn create_github_user<'t>(&'t self, tx: &mut PgTransaction<'_>) -> Result<Row> {
+    async fn create_github_user(&self, tx: &mut PgTransaction<'_>) -> Result<Row> {
         let row = sqlx::query_as::<_, Row>(
             r#"insert into users
                 (name, avatar_url, primary_email, github_username, github_avatar_url)
@@ -197,6 +197,50 @@ impl FetchAccountInfo {
     }
 }
 
+// Looks up the repos a user can write to so that a command-line tool can act on their behalf
+// without a session
+pub struct FetchViewer {
+    pub user_id: String,
+}
+
+impl FetchViewer {
+    pub async fn call(&self, pool: &PgPool) -> Result<Viewer> {
+        #[derive(sqlx::FromRow, Clone, Debug)]
+        struct ViewerRow {
+            pub selected_repository_id: Option<Uuid>,
+            pub write_repo_ids: Vec<Uuid>,
+        }
+
+        let row = sqlx::query_as::<_, ViewerRow>(
+            "select
+                u.selected_repository_id,
+                array_agg(ur.repository_id) write_repo_ids
+            from users u
+            join users_repositories ur on u.id = ur.user_id
+            where u.id = $1::uuid and ur.can_write
+            group by u.id",
+        )
+        .bind(&self.user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("user not found: {}", self.user_id)))?;
+
+        let repo_ids: Vec<RepoId> = row.write_repo_ids.into_iter().map(RepoId::from).collect();
+
+        Ok(Viewer {
+            context_repo_id: row
+                .selected_repository_id
+                .map(RepoId::from)
+                .unwrap_or_else(RepoId::wiki),
+            read_repo_ids: repo_ids.to_owned().into(),
+            session_id: None,
+            super_user: false,
+            user_id: self.user_id.to_owned(),
+            write_repo_ids: repo_ids.into(),
+        })
+    }
+}
+
 pub struct DeleteAccount {
     actor: Arc<Viewer>,
     user_id: String,
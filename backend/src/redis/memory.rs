@@ -0,0 +1,368 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Cache, Key};
+use crate::git::{self, activity::Change};
+use crate::prelude::*;
+use crate::types::{Downset, TopicPath};
+
+pub const DEFAULT_MEMORY_CACHE_SIZE: usize = 1_000;
+
+// Older changes are dropped from the activity feed of a repo once it grows past this
+const MAX_ACTIVITY_PER_REPO: usize = 1_000;
+
+// Holds at most `capacity` values, dropping the one that was inserted first to make room
+struct Bounded<K, V> {
+    capacity: usize,
+    order: VecDeque<K>,
+    values: HashMap<K, V>,
+}
+
+impl<K: Clone + Eq + Hash, V> Bounded<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.values.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+
+        while self.values.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.values.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+}
+
+// Changes ordered by the time they were saved, newest last.  A change that is saved a second time
+// is moved to its new position, as a Redis sorted set would do.
+#[derive(Default)]
+struct ActivityLog {
+    changes: BTreeMap<(u64, ExternalId), Change>,
+    scores: HashMap<ExternalId, u64>,
+}
+
+impl ActivityLog {
+    fn add(&mut self, score: u64, change: &Change) {
+        let id = change.id();
+        if let Some(previous) = self.scores.insert(id.to_owned(), score) {
+            self.changes.remove(&(previous, id.to_owned()));
+        }
+        self.changes.insert((score, id), change.to_owned());
+
+        while self.changes.len() > MAX_ACTIVITY_PER_REPO {
+            if let Some(((_, id), _)) = self.changes.pop_first() {
+                self.scores.remove(&id);
+            }
+        }
+    }
+
+    fn latest(&self, first: usize) -> Vec<Change> {
+        self.changes.values().rev().take(first).cloned().collect()
+    }
+}
+
+struct Entries {
+    activity: HashMap<RepoId, ActivityLog>,
+    downsets: Bounded<String, Arc<HashSet<ExternalId>>>,
+    stats: Bounded<(RepoId, String), (Option<Instant>, git::RepoStats)>,
+}
+
+// A cache that lives in the process and goes away with it.  Downsets and repo stats are kept in
+// maps that are bounded by `capacity`, and the wiki activity feed in a log that is trimmed to the
+// most recent changes.  Link titles are fetched during the request, since there is no queue for a
+// worker to take them from.
+pub struct Memory {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl std::fmt::Debug for Memory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = self.lock();
+        f.debug_struct("Memory")
+            .field("capacity", &self.capacity)
+            .field("downsets", &entries.downsets.len())
+            .field("stats", &entries.stats.len())
+            .finish()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CACHE_SIZE)
+    }
+}
+
+impl Memory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries {
+                activity: HashMap::new(),
+                downsets: Bounded::new(capacity),
+                stats: Bounded::new(capacity),
+            }),
+        }
+    }
+
+    fn downset(&self, fetch: &dyn Downset, path: &TopicPath) -> Arc<HashSet<ExternalId>> {
+        let Key(key) = Key::downset(path);
+        if let Some(set) = self.lock().downsets.get(&key) {
+            return Arc::clone(set);
+        }
+
+        log::info!("memory: {:?} not found in cache, saving", key);
+        let set = Arc::new(fetch.downset(path));
+        self.lock().downsets.insert(key, Arc::clone(&set));
+        set
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Cache for Memory {
+    fn intersection(
+        &self,
+        fetch: &dyn Downset,
+        topic_paths: &[TopicPath],
+    ) -> Result<HashSet<ExternalId>> {
+        let Some((head, tail)) = topic_paths.split_first() else {
+            log::warn!("no paths provided for transitive closure, exiting early");
+            return Ok(HashSet::new());
+        };
+
+        let mut result = (*self.downset(fetch, head)).clone();
+        for path in tail {
+            if result.is_empty() {
+                break;
+            }
+            let set = self.downset(fetch, path);
+            result.retain(|id| set.contains(id));
+        }
+
+        Ok(result)
+    }
+
+    fn link_fetch_queue(&self) -> Option<Box<dyn git::LinkFetchQueue + Send + Sync>> {
+        None
+    }
+}
+
+impl git::CacheStats for Memory {
+    fn fetch(&self, repo_id: RepoId, commit: &str) -> Result<Option<git::RepoStats>> {
+        let key = (repo_id, commit.to_owned());
+        Ok(match self.lock().stats.get(&key) {
+            Some((Some(expires), _)) if *expires <= Instant::now() => None,
+            Some((_, stats)) => Some(stats.to_owned()),
+            None => None,
+        })
+    }
+
+    fn save(
+        &self,
+        repo_id: RepoId,
+        commit: &str,
+        stats: &git::RepoStats,
+        ttl: Option<u32>,
+    ) -> Result<()> {
+        let expires = ttl.map(|ttl| Instant::now() + Duration::from_secs(ttl.into()));
+        self.lock()
+            .stats
+            .insert((repo_id, commit.to_owned()), (expires, stats.to_owned()));
+        Ok(())
+    }
+}
+
+impl git::SaveChangesForPrefix for Memory {
+    fn save(
+        &self,
+        repo_id: RepoId,
+        repo_changes: &HashMap<RepoId, BTreeSet<Change>>,
+    ) -> Result<()> {
+        let Some(changes) = repo_changes.get(&repo_id) else {
+            return Ok(());
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut entries = self.lock();
+        let log = entries.activity.entry(repo_id).or_default();
+        for change in changes {
+            log.add(now, change);
+        }
+
+        Ok(())
+    }
+}
+
+impl git::activity::ActivityForPrefix for Memory {
+    fn fetch_activity(&self, repo_id: RepoId, first: usize) -> Result<Vec<Change>> {
+        Ok(self
+            .lock()
+            .activity
+            .get(&repo_id)
+            .map(|log| log.latest(first))
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::git::activity::{ActivityForPrefix, DeleteTopic, LinkInfoList, TopicInfo};
+    use crate::git::{CacheStats, SaveChangesForPrefix};
+
+    struct Downsets {
+        calls: Cell<usize>,
+        sets: HashMap<ExternalId, HashSet<ExternalId>>,
+    }
+
+    impl Downset for Downsets {
+        fn intersection(&self, _topic_paths: &[TopicPath]) -> Result<HashSet<ExternalId>> {
+            unreachable!("Memory::intersection calls downset, not intersection")
+        }
+
+        fn downset(&self, path: &TopicPath) -> HashSet<ExternalId> {
+            self.calls.set(self.calls.get() + 1);
+            self.sets.get(&path.topic_id).cloned().unwrap_or_default()
+        }
+    }
+
+    fn id(id: &str) -> ExternalId {
+        ExternalId::try_from(id).unwrap()
+    }
+
+    fn path(topic_id: &str) -> TopicPath {
+        TopicPath {
+            repo_id: RepoId::wiki(),
+            topic_id: id(topic_id),
+            topic_oid: git2::Oid::hash_object(git2::ObjectType::Blob, topic_id.as_bytes()).unwrap(),
+        }
+    }
+
+    fn change(topic_id: &str, seconds: i64) -> Change {
+        Change::DeleteTopic(DeleteTopic {
+            actor_id: "2".to_owned(),
+            date: chrono::DateTime::from_timestamp(seconds, 0).unwrap(),
+            deleted_topic: TopicInfo {
+                deleted: true,
+                id: id(topic_id),
+                synonyms: Default::default(),
+            },
+            id: Change::new_id(),
+            parent_topics: Default::default(),
+            child_links: LinkInfoList(BTreeSet::new()),
+            child_topics: Default::default(),
+        })
+    }
+
+    #[test]
+    fn intersection() {
+        let fetch = Downsets {
+            calls: Cell::new(0),
+            sets: HashMap::from([
+                (
+                    id("00001"),
+                    HashSet::from([id("00001"), id("00002"), id("00003")]),
+                ),
+                (id("00002"), HashSet::from([id("00002"), id("00003")])),
+            ]),
+        };
+        let cache = Memory::default();
+        let paths = [path("00001"), path("00002")];
+
+        let expected = HashSet::from([id("00002"), id("00003")]);
+        assert_eq!(cache.intersection(&fetch, &paths).unwrap(), expected);
+        assert_eq!(fetch.calls.get(), 2);
+
+        // The downsets are taken from the cache the second time
+        assert_eq!(cache.intersection(&fetch, &paths).unwrap(), expected);
+        assert_eq!(fetch.calls.get(), 2);
+
+        assert!(cache.intersection(&fetch, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bounded() {
+        let mut map = Bounded::new(2);
+        map.insert(1, "a");
+        map.insert(2, "b");
+        map.insert(1, "c");
+        map.insert(3, "d");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), Some(&"b"));
+        assert_eq!(map.get(&3), Some(&"d"));
+
+        let mut map = Bounded::new(0);
+        map.insert(1, "a");
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn stats_expire() {
+        let cache = Memory::default();
+        let stats = git::RepoStats {
+            computing: true,
+            link_count: None,
+            topic_count: None,
+        };
+
+        CacheStats::save(&cache, RepoId::wiki(), "abc", &stats, None).unwrap();
+        assert!(cache.fetch(RepoId::wiki(), "abc").unwrap().is_some());
+        assert!(cache.fetch(RepoId::wiki(), "def").unwrap().is_none());
+
+        CacheStats::save(&cache, RepoId::wiki(), "abc", &stats, Some(0)).unwrap();
+        assert!(cache.fetch(RepoId::wiki(), "abc").unwrap().is_none());
+    }
+
+    #[test]
+    fn activity() {
+        let cache = Memory::default();
+        let first = change("00001", 1);
+        let second = change("00002", 2);
+
+        let changes = HashMap::from([(
+            RepoId::wiki(),
+            BTreeSet::from([first.to_owned(), second.to_owned()]),
+        )]);
+        SaveChangesForPrefix::save(&cache, RepoId::wiki(), &changes).unwrap();
+        SaveChangesForPrefix::save(&cache, RepoId::wiki(), &changes).unwrap();
+
+        let activity = cache.fetch_activity(RepoId::wiki(), 10).unwrap();
+        assert_eq!(activity.len(), 2);
+        assert_eq!(cache.fetch_activity(RepoId::wiki(), 1).unwrap().len(), 1);
+
+        let other = RepoId::try_from(OTHER_REPOSITORY_ID).unwrap();
+        assert!(cache.fetch_activity(other, 10).unwrap().is_empty());
+    }
+}
@@ -1,12 +1,37 @@
 use redis_rs::{self, Commands};
 use std::collections::{BTreeSet, HashMap, HashSet};
-use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::git;
 use crate::prelude::*;
 use crate::types::{Downset, TopicPath};
 
+mod memory;
+pub use memory::{Memory, DEFAULT_MEMORY_CACHE_SIZE};
+
+// Where downsets, repo stats and the wiki activity feed are kept between requests.  Redis is used
+// in production, and an in-process backend is available for development, tests and small
+// deployments that do without a Redis server.
+pub trait Cache:
+    git::CacheStats
+    + git::SaveChangesForPrefix
+    + git::activity::ActivityForPrefix
+    + std::fmt::Debug
+    + Send
+    + Sync
+{
+    // For each topic read path (a commit, oid, repo_id combo), fetch the downset for the repo
+    // topic, cache it and return the intersection of the sets.
+    fn intersection(
+        &self,
+        fetch: &dyn Downset,
+        topic_paths: &[TopicPath],
+    ) -> Result<HashSet<ExternalId>>;
+
+    // A queue that link titles can be handed off to, if the backend provides one
+    fn link_fetch_queue(&self) -> Option<Box<dyn git::LinkFetchQueue + Send + Sync>>;
+}
+
 #[derive(Clone, Debug)]
 pub struct Noop;
 
@@ -63,7 +88,7 @@ pub struct Redis {
     url: String,
 }
 
-impl git::CacheStats for Arc<Redis> {
+impl git::CacheStats for Redis {
     fn fetch(&self, repo_id: RepoId, commit: &str) -> Result<Option<git::RepoStats>> {
         let key = self.stats_key(repo_id, commit);
         let mut con = self.connection().unwrap();
@@ -95,7 +120,7 @@ impl git::CacheStats for Arc<Redis> {
         if let Some(ttl) = ttl {
             command = command.arg("EX").arg(ttl);
         }
-        command.query(&mut con)?;
+        command.query::<()>(&mut con)?;
 
         Ok(())
     }
@@ -106,17 +131,47 @@ impl Redis {
         Ok(Redis { url })
     }
 
-    // For each topic read path (a commit, oid, repo_id combo), fetch the downset for the repo
-    // topic, save the oids in the downset to redis and perform the intersection of the sets,
-    // returning the resulting list of oids.
-    pub fn intersection<F>(
+    pub fn connection(&self) -> Result<redis_rs::Connection> {
+        let client = redis_rs::Client::open(self.url.clone())?;
+        Ok(client.get_connection()?)
+    }
+
+    // Since redis keys have the commit hash of an immutible Git commit, they do not need to have
+    // an expiry.
+    fn save_downset(
         &self,
-        fetch: &F,
+        con: &mut redis_rs::Connection,
+        key: &Key,
+        set: &HashSet<ExternalId>,
+    ) -> Result<()> {
+        redis_rs::transaction::<_, _, (), _>(con, &[key], |con, pipe| {
+            let set = set
+                .iter()
+                .map(ExternalId::to_string)
+                .collect::<HashSet<String>>();
+            if set.is_empty() {
+                pipe.del(key).ignore()
+            } else {
+                pipe.sadd(key, set).ignore()
+            }
+            .query(con)
+        })?;
+
+        Ok(())
+    }
+
+    fn stats_key(&self, repo_id: RepoId, commit: &str) -> String {
+        format!("stats:{repo_id}:{commit}")
+    }
+}
+
+impl Cache for Redis {
+    // The oids in each downset are saved to redis, which performs the intersection of the sets
+    fn intersection(
+        &self,
+        fetch: &dyn Downset,
         topic_paths: &[TopicPath],
-    ) -> Result<HashSet<ExternalId>>
-    where
-        F: Downset,
-    {
+    ) -> Result<HashSet<ExternalId>> {
         if topic_paths.is_empty() {
             log::warn!("no paths provided for transitive closure, exiting early");
             return Ok(HashSet::new());
@@ -168,41 +223,12 @@ impl Redis {
         }
     }
 
-    pub fn connection(&self) -> Result<redis_rs::Connection> {
-        let client = redis_rs::Client::open(self.url.clone())?;
-        Ok(client.get_connection()?)
-    }
-
-    // Since redis keys have the commit hash of an immutible Git commit, they do not need to have
-    // an expiry.
-    fn save_downset(
-        &self,
-        con: &mut redis_rs::Connection,
-        key: &Key,
-        set: &HashSet<ExternalId>,
-    ) -> Result<()> {
-        redis_rs::transaction(con, &[key], |con, pipe| {
-            let set = set
-                .iter()
-                .map(ExternalId::to_string)
-                .collect::<HashSet<String>>();
-            if set.is_empty() {
-                pipe.del(key).ignore()
-            } else {
-                pipe.sadd(key, set).ignore()
-            }
-            .query(con)
-        })?;
-
-        Ok(())
-    }
-
-    fn stats_key(&self, repo_id: RepoId, commit: &str) -> String {
-        format!("stats:{repo_id}:{commit}")
+    fn link_fetch_queue(&self) -> Option<Box<dyn git::LinkFetchQueue + Send + Sync>> {
+        Some(Box::new(self.clone()))
     }
 }
 
-impl git::SaveChangesForPrefix for Arc<Redis> {
+impl git::SaveChangesForPrefix for Redis {
     fn save(
         &self,
         repo_id: RepoId,
@@ -222,7 +248,7 @@ impl git::SaveChangesForPrefix for Arc<Redis> {
         let key = Key(format!("activity:{repo_id}"));
         if !args.is_empty() {
             log::info!("saving changes to {:?}", key);
-            con.zadd_multiple(key, &args)?;
+            con.zadd_multiple::<_, _, _, ()>(key, &args)?;
         } else {
             log::info!("no changes to save to {:?} key, skipping", key);
         }
@@ -231,7 +257,121 @@ impl git::SaveChangesForPrefix for Arc<Redis> {
     }
 }
 
-impl git::activity::ActivityForPrefix for Arc<Redis> {
+const LINK_FETCH_QUEUE: &str = "queue:links:fetch";
+const LINK_FETCH_WORKERS: &str = "queue:links:fetch:workers";
+
+// A worker that has not checked in for this long is taken to have died, and the jobs it was
+// working on are put back on the queue.  Workers check in from a thread of their own, so that a
+// fetch that takes longer than this does not get its job handed to another worker.
+pub const LINK_FETCH_WORKER_TIMEOUT_SECS: u64 = 300;
+pub const LINK_FETCH_HEARTBEAT_INTERVAL_SECS: u64 = 60;
+
+// Each worker moves the jobs it takes into a processing list of its own, so that one worker
+// putting jobs back on the queue does not take them from another that is still working on them
+fn processing_list(worker_id: &str) -> String {
+    format!("queue:links:fetch:processing:{worker_id}")
+}
+
+fn heartbeat_key(worker_id: &str) -> String {
+    format!("queue:links:fetch:worker:{worker_id}")
+}
+
+// A job taken off of the link fetch queue.  The original payload is kept so that the job can be
+// removed from the processing list once it has been handled.
+pub struct LinkFetchDelivery {
+    pub job: git::FetchLinkJob,
+    payload: String,
+    worker_id: String,
+}
+
+impl git::LinkFetchQueue for Redis {
+    fn push(&self, job: &git::FetchLinkJob) -> Result<()> {
+        let mut con = self.connection()?;
+        let payload = serde_json::to_string(job)?;
+        log::info!("enqueuing title fetch for {}", job.link_id);
+        let _: () = con.lpush(LINK_FETCH_QUEUE, payload)?;
+        Ok(())
+    }
+}
+
+impl Redis {
+    // Blocks for up to `timeout` seconds waiting for a job.  The job is moved to the processing
+    // list of the worker until it is acknowledged, so that it is not lost if the worker dies.
+    pub fn next_link_fetch(
+        &self,
+        worker_id: &str,
+        timeout: usize,
+    ) -> Result<Option<LinkFetchDelivery>> {
+        self.link_fetch_heartbeat(worker_id)?;
+
+        let mut con = self.connection()?;
+        let processing = processing_list(worker_id);
+        let payload: Option<String> = redis_rs::cmd("BRPOPLPUSH")
+            .arg(LINK_FETCH_QUEUE)
+            .arg(&processing)
+            .arg(timeout)
+            .query(&mut con)?;
+
+        match payload {
+            Some(payload) => match serde_json::from_str(&payload) {
+                Ok(job) => Ok(Some(LinkFetchDelivery {
+                    job,
+                    payload,
+                    worker_id: worker_id.to_owned(),
+                })),
+                Err(err) => {
+                    log::error!("dropping malformed link fetch job {}: {}", payload, err);
+                    let _: () = con.lrem(&processing, 1, &payload)?;
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Marks the worker as alive for another LINK_FETCH_WORKER_TIMEOUT_SECS
+    pub fn link_fetch_heartbeat(&self, worker_id: &str) -> Result<()> {
+        let mut con = self.connection()?;
+        let _: () = con.sadd(LINK_FETCH_WORKERS, worker_id)?;
+        let _: () = con.set_ex(heartbeat_key(worker_id), 1, LINK_FETCH_WORKER_TIMEOUT_SECS)?;
+        Ok(())
+    }
+
+    pub fn ack_link_fetch(&self, delivery: &LinkFetchDelivery) -> Result<()> {
+        let mut con = self.connection()?;
+        let _: () = con.lrem(processing_list(&delivery.worker_id), 1, &delivery.payload)?;
+        Ok(())
+    }
+
+    // Moves jobs left over from workers that have stopped checking in back onto the queue.  The
+    // jobs of workers that are still running are left alone.
+    pub fn requeue_link_fetches(&self) -> Result<usize> {
+        let mut con = self.connection()?;
+        let workers: Vec<String> = con.smembers(LINK_FETCH_WORKERS)?;
+        let mut count = 0;
+
+        for worker_id in workers {
+            let alive: bool = con.exists(heartbeat_key(&worker_id))?;
+            if alive {
+                continue;
+            }
+
+            let processing = processing_list(&worker_id);
+            loop {
+                let payload: Option<String> = con.rpoplpush(&processing, LINK_FETCH_QUEUE)?;
+                if payload.is_none() {
+                    break;
+                }
+                count += 1;
+            }
+            let _: () = con.srem(LINK_FETCH_WORKERS, &worker_id)?;
+        }
+
+        Ok(count)
+    }
+}
+
+impl git::activity::ActivityForPrefix for Redis {
     fn fetch_activity(&self, repo_id: RepoId, first: usize) -> Result<Vec<git::activity::Change>> {
         let key = Key(format!("activity:{repo_id}"));
         log::info!("fetching activity for prefix {:?} from Redis", key);
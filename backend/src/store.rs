@@ -1,10 +1,10 @@
 use async_graphql::dataloader::*;
 use geotime::Geotime;
-use sqlx::postgres::PgPool;
 use std::collections::BTreeSet;
 use std::convert::TryInto;
 use std::sync::Arc;
 
+use crate::accounts;
 use crate::git;
 use crate::graphql;
 use crate::http;
@@ -14,34 +14,39 @@ use crate::redis;
 use crate::types::Timespec;
 
 pub struct Store {
-    db: PgPool,
+    accounts: accounts::Accounts,
+    fetch_policy: Arc<http::FetchPolicy>,
     git: Arc<git::Client>,
     object_loader: DataLoader<graphql::ObjectLoader>,
-    organization_loader: DataLoader<psql::OrganizationLoader>,
+    organization_loader: DataLoader<accounts::OrganizationLoader>,
     pub server_secret: String,
-    pub user_loader: DataLoader<psql::UserLoader>,
+    pub user_loader: DataLoader<accounts::UserLoader>,
     pub viewer: Arc<Viewer>,
-    redis: Arc<redis::Redis>,
-    repository_loader: DataLoader<psql::RepositoryLoader>,
+    cache: Arc<dyn redis::Cache>,
+    repository_loader: DataLoader<accounts::RepositoryLoader>,
 }
 
 impl Store {
     pub fn new(
         viewer: Arc<Viewer>,
         git: Arc<git::Client>,
-        db: PgPool,
+        accounts: accounts::Accounts,
         server_secret: String,
-        redis: Arc<redis::Redis>,
+        cache: Arc<dyn redis::Cache>,
+        fetch_policy: Arc<http::FetchPolicy>,
     ) -> Self {
-        let organization_loader = psql::OrganizationLoader::new(Arc::clone(&viewer), db.clone());
-        let repository_loader = psql::RepositoryLoader::new(Arc::clone(&viewer), db.clone());
+        let organization_loader =
+            accounts::OrganizationLoader::new(Arc::clone(&viewer), accounts.clone());
+        let repository_loader =
+            accounts::RepositoryLoader::new(Arc::clone(&viewer), accounts.clone());
         let object_loader = graphql::ObjectLoader::new(Arc::clone(&git));
-        let user_loader = psql::UserLoader::new(Arc::clone(&viewer), db.clone());
+        let user_loader = accounts::UserLoader::new(Arc::clone(&viewer), accounts.clone());
 
         Self {
-            db,
+            accounts,
+            cache,
+            fetch_policy,
             git,
-            redis,
             server_secret,
             viewer,
 
@@ -54,6 +59,58 @@ impl Store {
 }
 
 impl Store {
+    pub async fn accept_change_request(
+        &self,
+        id: String,
+    ) -> Result<psql::CloseChangeRequestResult> {
+        let change_request = self
+            .change_request(id.to_owned())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("change request not found: {id}")))?;
+
+        if change_request.status != graphql::ChangeRequestStatus::Open {
+            return Err(Error::Repo(format!("change request is not open: {id}")));
+        }
+
+        let target_repo_id = RepoId::try_from(change_request.target_repo_id.as_str())?;
+        if !self.viewer.can_update(target_repo_id) {
+            return Err(Error::RBAC(
+                "not allowed to accept this change request".into(),
+            ));
+        }
+
+        // The request is claimed before anything is applied, so that two reviewers accepting it
+        // at the same time cannot both apply it
+        let result = self
+            .accounts
+            .close_change_request(
+                Arc::clone(&self.viewer),
+                id.to_owned(),
+                graphql::ChangeRequestStatus::Accepted,
+            )
+            .await?;
+
+        let applied = git::ApplyChangeRequest {
+            actor_id: result.change_request.author_id.to_owned(),
+            payload: &result.change_request.payload,
+            target_repo_id,
+        }
+        .call(self.mutation()?, &self.cache);
+
+        if let Err(err) = applied {
+            if let Err(reopen_err) = self
+                .accounts
+                .reopen_change_request(Arc::clone(&self.viewer), id.to_owned())
+                .await
+            {
+                log::error!("failed to reopen change request {id}: {reopen_err}");
+            }
+            return Err(err);
+        }
+
+        Ok(result)
+    }
+
     pub async fn activity(
         &self,
         repo_id: RepoId,
@@ -65,7 +122,7 @@ impl Store {
             path: topic_id.as_ref().map(|id| (repo_id, id.to_owned())),
             first: first.try_into().unwrap_or(3),
         }
-        .call(&self.git, &self.redis);
+        .call(&self.git, &self.cache);
 
         match result {
             Ok(git::activity::FetchActivityResult { changes, .. }) => Ok(changes),
@@ -76,13 +133,79 @@ impl Store {
         }
     }
 
+    // The broken links in the repos that the viewer can read, or in the ones given, in a stable
+    // order so that they can be paged through
+    pub fn broken_link_ids(&self, repo_ids: Option<&[RepoId]>) -> Result<Vec<ExternalId>> {
+        let mut link_ids = BTreeSet::new();
+        for &repo_id in self.viewer.read_repo_ids.iter() {
+            if repo_ids.is_some_and(|repo_ids| !repo_ids.contains(&repo_id)) {
+                continue;
+            }
+            for (link_id, _health) in self.git.broken_links(repo_id)? {
+                link_ids.insert(link_id);
+            }
+        }
+
+        Ok(link_ids.into_iter().collect())
+    }
+
+    pub async fn change_request(&self, id: String) -> Result<Option<graphql::ChangeRequest>> {
+        let mut change_requests = self
+            .accounts
+            .fetch_change_requests(Arc::clone(&self.viewer), Some(id), None, 1)
+            .await?;
+        Ok(change_requests.pop())
+    }
+
+    pub async fn change_requests(
+        &self,
+        status: Option<graphql::ChangeRequestStatus>,
+        limit: i64,
+    ) -> Result<Vec<graphql::ChangeRequest>> {
+        self.accounts
+            .fetch_change_requests(Arc::clone(&self.viewer), None, status, limit)
+            .await
+    }
+
+    // Packages the changes in the source repo, or all of its differences from the target repo if
+    // no changes are given, into a change request against the target repo
+    pub async fn create_change_request(
+        &self,
+        source_repo_id: RepoId,
+        target_repo_id: RepoId,
+        title: String,
+        description: String,
+        change_ids: Option<Vec<ExternalId>>,
+    ) -> Result<psql::CreateChangeRequestResult> {
+        let source = match change_ids {
+            Some(change_ids) => git::ChangeRequestSource::Changes(change_ids),
+            None => git::ChangeRequestSource::Diff,
+        };
+
+        let payload = git::PackageChangeRequest {
+            actor: Arc::clone(&self.viewer),
+            source,
+            source_repo_id,
+            target_repo_id,
+        }
+        .call(&self.git)?;
+
+        self.accounts
+            .create_change_request(
+                Arc::clone(&self.viewer),
+                source_repo_id,
+                target_repo_id,
+                title,
+                description,
+                payload,
+            )
+            .await
+    }
+
     pub async fn delete_account(&self, user_id: String) -> Result<psql::DeleteAccountResult> {
         log::info!("account deletion: fetching account info for {}", user_id);
-        let psql::FetchAccountInfoResult { personal_repos } = psql::FetchAccountInfo {
-            user_id: user_id.to_owned(),
-        }
-        .call(&self.db)
-        .await?;
+        let psql::FetchAccountInfoResult { personal_repos } =
+            self.accounts.fetch_account_info(user_id.to_owned()).await?;
 
         log::info!(
             "account deletion: deleting git repos for {}: {:?}",
@@ -96,9 +219,9 @@ impl Store {
         }
         .call(&self.mutation()?)?;
 
-        log::info!("account deletion: postgres data for {}", user_id);
-        psql::DeleteAccount::new(Arc::clone(&self.viewer), user_id)
-            .call(&self.db)
+        log::info!("account deletion: account data for {}", user_id);
+        self.accounts
+            .delete_account(Arc::clone(&self.viewer), user_id)
             .await
     }
 
@@ -112,12 +235,12 @@ impl Store {
             repo_id,
             link_id: link_id.to_owned(),
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn delete_session(&self, session_id: String) -> Result<psql::DeleteSessionResult> {
-        psql::DeleteSession::new(self.viewer.clone(), session_id)
-            .call(&self.db)
+        self.accounts
+            .delete_session(self.viewer.clone(), session_id)
             .await
     }
 
@@ -131,7 +254,16 @@ impl Store {
             repo_id,
             topic_id: topic_id.to_owned(),
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
+    }
+
+    pub async fn diff(&self, base: git::DiffSide, head: git::DiffSide) -> Result<git::DiffResult> {
+        git::Diff {
+            base,
+            head,
+            locale: Locale::EN,
+        }
+        .call(&self.git)
     }
 
     pub async fn fetch_link(&self, link_id: ExternalId) -> Result<Option<git::Link>> {
@@ -222,6 +354,19 @@ impl Store {
         self.git.mutation(git::IndexMode::Update)
     }
 
+    pub async fn reject_change_request(
+        &self,
+        id: String,
+    ) -> Result<psql::CloseChangeRequestResult> {
+        self.accounts
+            .close_change_request(
+                Arc::clone(&self.viewer),
+                id,
+                graphql::ChangeRequestStatus::Rejected,
+            )
+            .await
+    }
+
     pub async fn remove_topic_timerange(
         &self,
         repo_id: RepoId,
@@ -232,7 +377,90 @@ impl Store {
             repo_id,
             topic_id: topic_id.clone(),
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
+    }
+
+    pub async fn fork_repo(
+        &self,
+        upstream_repo_id: RepoId,
+        topic_id: Option<ExternalId>,
+        name: Option<String>,
+    ) -> Result<psql::CreateForkRepositoryResult> {
+        if self.viewer.is_guest() {
+            return Err(Error::RBAC("log in to fork a repo".into()));
+        }
+
+        let name = match name {
+            Some(name) => name,
+            None => match self.repo(upstream_repo_id.to_string()).await? {
+                Some(graphql::Repository::Fetched { name, .. }) => format!("Fork of {name}"),
+                _ => format!("Fork of {DEFAULT_REPOSITORY_NAME}"),
+            },
+        };
+
+        let fork_repo_id = RepoId::make();
+        let fork = git::ForkRepo {
+            actor: Arc::clone(&self.viewer),
+            fork_repo_id,
+            topic_id: topic_id.to_owned(),
+            upstream_repo_id,
+        };
+        let client = Arc::clone(&self.git);
+        let cache = Arc::clone(&self.cache);
+
+        // Every object in the selection is read and written again, which takes a while for a
+        // large repo, so the copy is kept off of the async worker threads
+        tokio::task::spawn_blocking(move || fork.call(&client, &cache)).await??;
+
+        let result = self
+            .accounts
+            .create_fork_repository(
+                Arc::clone(&self.viewer),
+                fork_repo_id,
+                name,
+                upstream_repo_id,
+                topic_id,
+            )
+            .await;
+
+        if result.is_err() {
+            log::warn!(
+                "removing fork {} after failing to register it",
+                fork_repo_id
+            );
+            let mutation = self.mutation()?;
+            tokio::task::spawn_blocking(move || mutation.delete_repo(fork_repo_id)).await??;
+        }
+
+        result
+    }
+
+    pub async fn leaked_data(
+        &self,
+        repo_id: RepoId,
+        rev: Option<String>,
+    ) -> Result<git::LeakedDataResult> {
+        if !self.viewer.super_user {
+            return Err(Error::RBAC("not allowed to scan for leaked data".into()));
+        }
+
+        git::LeakedData { repo_id, rev }.call(&self.git)
+    }
+
+    pub async fn link_health(
+        &self,
+        repo_id: RepoId,
+        link_id: &ExternalId,
+    ) -> Result<Option<git::LinkHealth>> {
+        self.git.fetch_link_health(repo_id, link_id)
+    }
+
+    pub async fn link_snapshot(
+        &self,
+        repo_id: RepoId,
+        reference: &git::SnapshotRef,
+    ) -> Result<Option<git::Snapshot>> {
+        self.git.fetch_snapshot(repo_id, reference)
     }
 
     pub async fn organization(&self, id: String) -> Result<Option<graphql::Organization>> {
@@ -240,8 +468,8 @@ impl Store {
     }
 
     pub async fn repositories_for_user(&self, user_id: String) -> Result<Vec<graphql::Repository>> {
-        psql::FetchWriteableRepositoriesForUser::new(Arc::clone(&self.viewer), user_id)
-            .call(&self.db)
+        self.accounts
+            .fetch_writeable_repositories_for_user(Arc::clone(&self.viewer), user_id)
             .await
     }
 
@@ -253,6 +481,18 @@ impl Store {
         self.repository_loader.load_one(repo_id).await
     }
 
+    pub async fn review_change_request(
+        &self,
+        change_request: &graphql::ChangeRequest,
+    ) -> Result<Vec<git::ProposedChange>> {
+        git::ReviewChangeRequest {
+            locale: Locale::EN,
+            payload: &change_request.payload,
+            target_repo_id: change_request.target_repo_id.as_str().try_into()?,
+        }
+        .call(&self.git)
+    }
+
     pub async fn search(
         &self,
         parent_topic: &git::Topic,
@@ -260,9 +500,9 @@ impl Store {
     ) -> Result<git::FindMatchesResult> {
         log::debug!("search: {:?}", search);
 
-        let fetcher = git::RedisFetchDownSet {
+        let fetcher = git::CachedFetchDownSet {
+            cache: Arc::clone(&self.cache),
             client: Arc::clone(&self.git),
-            redis: Arc::clone(&self.redis),
         };
 
         git::FindMatches {
@@ -296,13 +536,14 @@ impl Store {
         &self,
         repo_id: Option<RepoId>,
     ) -> Result<psql::SelectRepositoryResult> {
-        psql::SelectRepository::new(Arc::clone(&self.viewer), repo_id)
-            .call(&self.db)
+        self.accounts
+            .select_repository(Arc::clone(&self.viewer), repo_id)
             .await
     }
 
     fn update_by(&self, actor: &Arc<Viewer>) -> Result<git::Mutation> {
         let git = git::Client {
+            cache: Arc::clone(&self.git.cache),
             root: self.git.root.to_owned(),
             timespec: self.git.timespec.to_owned(),
             viewer: Arc::clone(actor),
@@ -327,7 +568,7 @@ impl Store {
                 .collect::<Result<BTreeSet<ExternalId>>>()?,
             repo_id: input.repo_id.try_into()?,
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn update_topic_parent_topics(
@@ -342,7 +583,7 @@ impl Store {
             topic_id,
             parent_topic_ids,
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn update_topic_synonyms(
@@ -365,7 +606,7 @@ impl Store {
                 .collect::<Result<Vec<git::Synonym>>>()?,
             topic_id: topic_id.try_into()?,
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn upsert_link(
@@ -378,33 +619,45 @@ impl Store {
             None
         };
 
-        git::UpsertLink {
+        let upsert = git::UpsertLink {
             add_parent_topic_id,
             actor: self.viewer.to_owned(),
             // FIXME: use id instead of prefix
             repo_id: input.repo_id.try_into()?,
             title: input.title,
             url: input.url,
-            fetcher: Box::new(http::Fetcher),
-        }
-        .call(self.mutation()?, &self.redis)
+            fetcher: Box::new(http::Fetcher::new(Arc::clone(&self.fetch_policy))),
+            capture_snapshot: false,
+            fetch_queue: self.cache.link_fetch_queue(),
+        };
+        let mutation = self.mutation()?;
+        let cache = Arc::clone(&self.cache);
+
+        // Without a queue the page is fetched during the request, and the fetch policy can block
+        // for a while waiting on the host, so the upsert is kept off of the async worker threads
+        tokio::task::spawn_blocking(move || upsert.call(mutation, &cache)).await?
     }
 
     pub async fn upsert_session(
         &self,
         input: graphql::CreateGithubSessionInput,
     ) -> Result<psql::CreateSessionResult> {
-        let result = psql::CreateGithubSession::new(input).call(&self.db).await?;
+        let result = self.accounts.create_github_session(input).await?;
 
         let actor = Arc::new(Viewer::service_account());
+        let user_id = result.user.id.to_string();
 
-        git::EnsurePersonalRepo {
+        let git::EnsurePersonalRepoResult { created_repo_id } = git::EnsurePersonalRepo {
             actor: Arc::clone(&actor),
-            user_id: result.user.id.to_string(),
+            user_id: user_id.to_owned(),
             personal_repo_ids: result.personal_repo_ids.to_owned(),
         }
         .call(self.update_by(&actor)?)?;
 
+        if let Some(repo_id) = created_repo_id {
+            self.accounts.add_personal_repo(&user_id, repo_id).await?;
+        }
+
         Ok(result)
     }
 
@@ -450,7 +703,7 @@ impl Store {
             repo_id: repo_id.try_into()?,
             parent_topic_id: parent_topic,
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn upsert_topic_timerange(
@@ -469,7 +722,7 @@ impl Store {
             repo_id: input.repo_id.try_into()?,
             topic_id: input.topic_id.try_into()?,
         }
-        .call(self.mutation()?, &self.redis)
+        .call(self.mutation()?, &self.cache)
     }
 
     pub async fn user(&self, id: String) -> Result<Option<graphql::User>> {
@@ -485,7 +738,7 @@ impl Store {
         git::FetchStats {
             viewer: Arc::clone(&self.viewer),
         }
-        .call(&self.git, &self.redis)
+        .call(&self.git, &self.cache)
         .await
     }
 }
@@ -60,6 +60,30 @@ pub enum Locale {
     ZH,
 }
 
+impl Locale {
+    // The language in an Accept-Language header that is most preferred and that there is a locale
+    // for.  Only the primary subtag is looked at, so "fr-CA" is read as French.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut languages = header
+            .split(',')
+            .filter_map(|part| {
+                let mut params = part.split(';');
+                let language = params.next()?.trim().split('-').next()?.to_lowercase();
+                let quality = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                let locale = std::str::FromStr::from_str(&language).ok()?;
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect::<Vec<(f32, Self)>>();
+
+        // The sort is stable, so languages with the same quality keep their order
+        languages.sort_by(|a, b| b.0.total_cmp(&a.0));
+        languages.first().map(|(_, locale)| *locale)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 #[allow(dead_code)]
 pub struct RepoId(Uuid);
@@ -108,6 +132,12 @@ impl From<sqlx::types::Uuid> for RepoId {
     }
 }
 
+impl From<RepoId> for sqlx::types::Uuid {
+    fn from(value: RepoId) -> Self {
+        value.0
+    }
+}
+
 impl RepoId {
     pub fn make() -> Self {
         Self(Uuid::new_v4())
@@ -526,6 +556,25 @@ impl Viewer {
     pub fn is_guest(&self) -> bool {
         self.session_id.is_none()
     }
+
+    // A copy of the viewer that can also read from and write to the repo provided, for when the
+    // viewer has just created it
+    pub fn with_repo(&self, repo_id: RepoId) -> Self {
+        let mut read_repo_ids = self.read_repo_ids.0.to_owned();
+        let mut write_repo_ids = self.write_repo_ids.0.to_owned();
+        if !self.read_repo_ids.include(repo_id) {
+            read_repo_ids.push(repo_id);
+        }
+        if !self.write_repo_ids.include(repo_id) {
+            write_repo_ids.push(repo_id);
+        }
+
+        Self {
+            read_repo_ids: RepoIds(read_repo_ids),
+            write_repo_ids: RepoIds(write_repo_ids),
+            ..self.to_owned()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -546,6 +595,21 @@ mod tests {
         fn to_string() {
             assert_eq!(Locale::EN.to_string(), "en");
         }
+
+        #[test]
+        fn from_accept_language() {
+            assert_eq!(Locale::from_accept_language("fr-CA"), Some(Locale::FR));
+            assert_eq!(
+                Locale::from_accept_language("en;q=0.5, de-DE, fr;q=0.8"),
+                Some(Locale::DE)
+            );
+            assert_eq!(
+                Locale::from_accept_language("xx, es;q=0.2, *;q=0.1"),
+                Some(Locale::ES)
+            );
+            assert_eq!(Locale::from_accept_language("de;q=0, xx"), None);
+            assert_eq!(Locale::from_accept_language(""), None);
+        }
     }
 
     mod timerange {
@@ -0,0 +1,336 @@
+use digraph::accounts::{GithubAccount, SystemRepo};
+use digraph::git::ChangeRequestPayload;
+use digraph::graphql::{ChangeRequestStatus, Repository};
+use digraph::prelude::*;
+use std::sync::Arc;
+
+use super::Fixtures;
+
+fn account(username: &str) -> GithubAccount {
+    GithubAccount {
+        avatar_url: format!("https://avatars.example.com/{username}"),
+        name: username.to_owned(),
+        primary_email: format!("{username}@example.com"),
+        username: username.to_owned(),
+    }
+}
+
+fn payload() -> ChangeRequestPayload {
+    ChangeRequestPayload {
+        objects: vec![],
+        source_commit: "abc".to_owned(),
+    }
+}
+
+// Signs a user up and gives them a personal repo, returning a viewer for their session
+fn sign_up(system: &SystemRepo, username: &str) -> (Viewer, RepoId) {
+    let result = system.create_github_session(&account(username)).unwrap();
+    let user_id = result.user.id.to_string();
+    assert!(result.personal_repo_ids.is_empty());
+
+    let repo_id = RepoId::make();
+    system.add_personal_repo(&user_id, repo_id).unwrap();
+
+    let viewer = system
+        .session_viewer(&user_id, &result.session_id)
+        .unwrap()
+        .unwrap();
+    (viewer, repo_id)
+}
+
+#[test]
+fn wiki_is_added() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+    let guest = Viewer::guest();
+
+    let repos = system
+        .repositories(&guest, &[RepoId::wiki().to_string()])
+        .unwrap();
+    assert!(matches!(
+        repos.get(&RepoId::wiki().to_string()),
+        Some(Repository::Fetched { private: false, .. })
+    ));
+
+    // Opening the repo a second time leaves it as it was
+    let head = || {
+        let repo = git2::Repository::open(SystemRepo::path(&f.git.root)).unwrap();
+        let oid = repo.head().unwrap().target().unwrap();
+        oid
+    };
+    let before = head();
+    SystemRepo::open(&f.git.root).unwrap();
+    assert_eq!(head(), before);
+}
+
+#[test]
+fn sessions() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+
+    let first = system.create_github_session(&account("gnusto")).unwrap();
+    let user_id = first.user.id.to_string();
+    assert_eq!(first.user.login.as_deref(), Some("gnusto"));
+
+    let viewer = system
+        .session_viewer(&user_id, &first.session_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(viewer.user_id, user_id);
+    assert!(viewer.can_update(RepoId::wiki()));
+    assert!(!viewer.is_guest());
+
+    // A second login finds the same user and starts another session
+    let second = system.create_github_session(&account("gnusto")).unwrap();
+    assert_eq!(second.user.id, first.user.id);
+    assert_ne!(second.session_id, first.session_id);
+
+    // A session cannot be used by another user
+    let other = system.create_github_session(&account("frotz")).unwrap();
+    assert_ne!(other.user.id, first.user.id);
+    assert!(system
+        .session_viewer(&other.user.id.to_string(), &first.session_id)
+        .unwrap()
+        .is_none());
+
+    // Only the owner can delete a session
+    let frotz = system
+        .session_viewer(&other.user.id.to_string(), &other.session_id)
+        .unwrap()
+        .unwrap();
+    system.delete_session(&frotz, &first.session_id).unwrap();
+    assert!(system
+        .session_viewer(&user_id, &first.session_id)
+        .unwrap()
+        .is_some());
+
+    system.delete_session(&viewer, &first.session_id).unwrap();
+    assert!(system
+        .session_viewer(&user_id, &first.session_id)
+        .unwrap()
+        .is_none());
+    assert!(system
+        .session_viewer(&user_id, &second.session_id)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn concurrent_sign_ups() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+
+    let usernames = ["gnusto", "frotz", "rezrov", "blorb"];
+    let viewers = std::thread::scope(|scope| {
+        let handles = usernames
+            .iter()
+            .map(|username| scope.spawn(|| sign_up(&system, username).0))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    // Each write is based on the one before it, so none of the users is lost
+    for viewer in &viewers {
+        let account = system.fetch_account_info(&viewer.user_id).unwrap();
+        assert_eq!(account.personal_repos.iter().count(), 1);
+    }
+}
+
+#[test]
+fn repositories() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+    let (viewer, repo_id) = sign_up(&system, "gnusto");
+
+    assert!(viewer.can_update(repo_id));
+    let info = system.fetch_account_info(&viewer.user_id).unwrap();
+    assert_eq!(
+        info.personal_repos.iter().collect::<Vec<_>>(),
+        vec![&repo_id]
+    );
+
+    let repos = system
+        .fetch_writeable_repositories_for_user(&viewer, &viewer.user_id)
+        .unwrap();
+    assert_eq!(repos.len(), 2);
+
+    // Other users cannot see the personal repo
+    let (other, _) = sign_up(&system, "frotz");
+    let found = system.repositories(&other, &[repo_id.to_string()]).unwrap();
+    assert!(found.is_empty());
+
+    let result = system.select_repository(&viewer, Some(repo_id)).unwrap();
+    assert!(result.repo.is_some());
+    assert_eq!(result.actor.selected_repository_id, Some(repo_id.into()));
+    assert_eq!(
+        system
+            .fetch_viewer(&viewer.user_id)
+            .unwrap()
+            .context_repo_id,
+        repo_id
+    );
+    assert!(system.select_repository(&other, Some(repo_id)).is_err());
+
+    // A fork goes into the personal org of the user
+    let fork_id = RepoId::make();
+    let fork = system
+        .create_fork_repository(&viewer, fork_id, "Fork".into(), repo_id, None)
+        .unwrap();
+    assert!(matches!(
+        fork.repo,
+        Repository::Fetched { private: true, upstream_repo_id: Some(ref id), .. }
+            if id == &repo_id.to_string()
+    ));
+    let info = system.fetch_account_info(&viewer.user_id).unwrap();
+    assert_eq!(info.personal_repos.iter().count(), 2);
+
+    assert!(system
+        .create_fork_repository(
+            &Viewer::guest(),
+            RepoId::make(),
+            "Fork".into(),
+            repo_id,
+            None
+        )
+        .is_err());
+}
+
+#[test]
+fn change_requests() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+    let (author, source_repo_id) = sign_up(&system, "gnusto");
+    let (reviewer, target_repo_id) = sign_up(&system, "frotz");
+    let (other, _) = sign_up(&system, "rezrov");
+
+    let change_request = system
+        .create_change_request(
+            &author,
+            source_repo_id,
+            target_repo_id,
+            "Title".into(),
+            "Description".into(),
+            payload(),
+        )
+        .unwrap()
+        .change_request;
+    assert_eq!(change_request.status, ChangeRequestStatus::Open);
+
+    // Visible to the author and to readers of the target repo
+    for viewer in [&author, &reviewer] {
+        let found = system
+            .fetch_change_requests(viewer, None, Some(ChangeRequestStatus::Open), 10)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+    }
+    assert!(system
+        .fetch_change_requests(&other, Some(&change_request.id), None, 1)
+        .unwrap()
+        .is_empty());
+
+    // Only someone who can write to the target repo can accept it
+    assert!(system
+        .close_change_request(&author, &change_request.id, ChangeRequestStatus::Accepted)
+        .is_err());
+    assert!(system
+        .close_change_request(&reviewer, &change_request.id, ChangeRequestStatus::Open)
+        .is_err());
+
+    let closed = system
+        .close_change_request(&reviewer, &change_request.id, ChangeRequestStatus::Accepted)
+        .unwrap()
+        .change_request;
+    assert_eq!(closed.status, ChangeRequestStatus::Accepted);
+    assert_eq!(closed.reviewer_id.as_ref(), Some(&reviewer.user_id));
+
+    // A change request that has been closed cannot be closed again
+    assert!(system
+        .close_change_request(&author, &change_request.id, ChangeRequestStatus::Rejected)
+        .is_err());
+    assert!(system
+        .fetch_change_requests(&author, None, Some(ChangeRequestStatus::Open), 10)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn accepted_change_request_reopened() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+    let (author, source_repo_id) = sign_up(&system, "gnusto");
+    let (reviewer, target_repo_id) = sign_up(&system, "frotz");
+
+    let change_request = system
+        .create_change_request(
+            &author,
+            source_repo_id,
+            target_repo_id,
+            "Title".into(),
+            "Description".into(),
+            payload(),
+        )
+        .unwrap()
+        .change_request;
+
+    // Only a change request that the actor accepted can be reopened
+    assert!(system
+        .reopen_change_request(&reviewer, &change_request.id)
+        .is_err());
+    system
+        .close_change_request(&reviewer, &change_request.id, ChangeRequestStatus::Accepted)
+        .unwrap();
+    assert!(system
+        .reopen_change_request(&author, &change_request.id)
+        .is_err());
+
+    system
+        .reopen_change_request(&reviewer, &change_request.id)
+        .unwrap();
+    let found = system
+        .fetch_change_requests(&author, Some(&change_request.id), None, 1)
+        .unwrap();
+    assert_eq!(found[0].status, ChangeRequestStatus::Open);
+    assert_eq!(found[0].reviewer_id, None);
+
+    // It can then be claimed again
+    assert!(system
+        .close_change_request(&reviewer, &change_request.id, ChangeRequestStatus::Accepted)
+        .is_ok());
+}
+
+#[test]
+fn delete_account() {
+    let f = Fixtures::copy("simple");
+    let system = SystemRepo::open(&f.git.root).unwrap();
+    let (viewer, repo_id) = sign_up(&system, "gnusto");
+    let (other, _) = sign_up(&system, "frotz");
+
+    assert!(system.delete_account(&other, &viewer.user_id).is_err());
+
+    let session_id = viewer.session_id.to_owned().unwrap();
+    system.delete_account(&viewer, &viewer.user_id).unwrap();
+    assert!(system
+        .users(&[viewer.user_id.to_owned()])
+        .unwrap()
+        .is_empty());
+    assert!(system
+        .session_viewer(&viewer.user_id, &session_id)
+        .unwrap()
+        .is_none());
+    let super_user = Arc::new(Viewer {
+        super_user: true,
+        read_repo_ids: vec![repo_id].into(),
+        ..Viewer::guest()
+    });
+    assert!(system
+        .repositories(&super_user, &[repo_id.to_string()])
+        .unwrap()
+        .is_empty());
+
+    // Signing up again starts a new account
+    let result = system.create_github_session(&account("gnusto")).unwrap();
+    assert_ne!(result.user.id.to_string(), viewer.user_id);
+}
@@ -0,0 +1,308 @@
+use digraph::git::{
+    activity::Change, ApplyChangeRequest, ApplyChangeRequestResult, ChangeRequestPayload,
+    ChangeRequestSource, ForkRepo, Kind, OnMatchingSynonym, PackageChangeRequest, ProposedAction,
+    ProposedChange, RepoObject, ReviewChangeRequest, Synonym, UpdateTopicSynonyms,
+    UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::redis;
+
+use super::{actor, valid_url, Fixtures};
+
+fn fork(f: &Fixtures) -> RepoId {
+    let fork_repo_id = RepoId::make();
+    ForkRepo {
+        actor: actor(),
+        fork_repo_id,
+        topic_id: None,
+        upstream_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &redis::Noop)
+    .unwrap();
+    fork_repo_id
+}
+
+fn add_topic(f: &Fixtures, repo_id: RepoId, name: &str, parent: &ExternalId) -> ExternalId {
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(repo_id, name, parent, OnMatchingSynonym::CreateDistinct)
+        .unwrap();
+    repo_topic.unwrap().topic_id().to_owned()
+}
+
+fn package(
+    f: &Fixtures,
+    source_repo_id: RepoId,
+    source: ChangeRequestSource,
+) -> Result<ChangeRequestPayload> {
+    PackageChangeRequest {
+        actor: actor(),
+        source,
+        source_repo_id,
+        target_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git)
+}
+
+fn review(f: &Fixtures, payload: &ChangeRequestPayload) -> Vec<ProposedChange> {
+    ReviewChangeRequest {
+        locale: Locale::EN,
+        payload,
+        target_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git)
+    .unwrap()
+}
+
+fn apply(f: &Fixtures, payload: &ChangeRequestPayload) -> Result<ApplyChangeRequestResult> {
+    ApplyChangeRequest {
+        actor_id: actor().user_id.to_owned(),
+        payload,
+        target_repo_id: RepoId::wiki(),
+    }
+    .call(f.mutation(), &redis::Noop)
+}
+
+#[test]
+fn diff_proposes_new_topic() {
+    let f = Fixtures::copy("simple");
+    let repo_id = fork(&f);
+    let root = ExternalId::root_topic();
+    let topic_id = add_topic(&f, repo_id, "Tide pools", &root);
+
+    let payload = package(&f, repo_id, ChangeRequestSource::Diff).unwrap();
+    let ids = payload
+        .objects
+        .iter()
+        .map(|object| object.id().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![topic_id.to_owned()]);
+
+    let changes = review(&f, &payload);
+    assert_eq!(changes.len(), 1);
+    let change = &changes[0];
+    assert_eq!(change.action, ProposedAction::Add);
+    assert_eq!(change.kind, Kind::Topic);
+    assert_eq!(change.name, "Tide pools");
+    assert_eq!(change.parent_topics_added, vec![root.to_owned()]);
+    assert_eq!(change.synonyms_added, vec!["Tide pools".to_owned()]);
+
+    let result = apply(&f, &payload).unwrap();
+    assert_eq!(
+        result,
+        ApplyChangeRequestResult {
+            links: 0,
+            topics: 1
+        }
+    );
+
+    let topic = f.git.fetch_topic(RepoId::wiki(), &topic_id).unwrap();
+    assert_eq!(topic.name(Locale::EN), "Tide pools");
+    let root = f.git.fetch_topic(RepoId::wiki(), &root).unwrap();
+    assert!(root.has_child(&topic_id));
+
+    let activity = f.git.fetch_activity(RepoId::wiki(), &topic_id, 10).unwrap();
+    assert!(matches!(&activity[..], [Change::ImportTopic(_)]));
+}
+
+#[test]
+fn changes_select_what_is_proposed() {
+    let f = Fixtures::copy("simple");
+    let repo_id = fork(&f);
+    let root = ExternalId::root_topic();
+    let proposed_id = add_topic(&f, repo_id, "Proposed", &root);
+    add_topic(&f, repo_id, "Kept back", &root);
+
+    let mut change_id = None;
+    f.git
+        .view(repo_id)
+        .unwrap()
+        .each_change(|change| {
+            if change.ids().contains(&proposed_id) {
+                change_id = Some(change.id());
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let payload = package(
+        &f,
+        repo_id,
+        ChangeRequestSource::Changes(vec![change_id.unwrap()]),
+    )
+    .unwrap();
+    let ids = payload
+        .objects
+        .iter()
+        .map(|object| object.id().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![proposed_id]);
+}
+
+#[test]
+fn synonym_changes_reviewed() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let topic_id = add_topic(&f, RepoId::wiki(), "Marine biology", &root);
+    let repo_id = fork(&f);
+
+    UpdateTopicSynonyms {
+        actor: actor(),
+        repo_id,
+        synonyms: vec![Synonym {
+            added: chrono::Utc::now(),
+            locale: Locale::EN,
+            name: "Oceanography".into(),
+        }],
+        topic_id: topic_id.to_owned(),
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+
+    let payload = package(&f, repo_id, ChangeRequestSource::Diff).unwrap();
+    let changes = review(&f, &payload);
+    assert_eq!(changes.len(), 1);
+    let change = &changes[0];
+    assert_eq!(change.action, ProposedAction::Update);
+    assert_eq!(change.previous_name.as_deref(), Some("Marine biology"));
+    assert_eq!(change.name, "Oceanography");
+    assert_eq!(change.synonyms_added, vec!["Oceanography".to_owned()]);
+    assert_eq!(change.synonyms_removed, vec!["Marine biology".to_owned()]);
+    assert!(change.parent_topics_added.is_empty());
+
+    apply(&f, &payload).unwrap();
+    let topic = f.git.fetch_topic(RepoId::wiki(), &topic_id).unwrap();
+    assert_eq!(topic.name(Locale::EN), "Oceanography");
+}
+
+#[test]
+fn moving_a_link_updates_both_parents() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let from_id = add_topic(&f, RepoId::wiki(), "From", &root);
+    let to_id = add_topic(&f, RepoId::wiki(), "To", &root);
+    let link = f
+        .upsert_link(RepoId::wiki(), &valid_url(), None, Some(from_id.to_owned()))
+        .link
+        .unwrap();
+
+    let mut moved = link.to_owned();
+    moved.parent_topics = [digraph::git::ParentTopic {
+        id: to_id.to_owned(),
+    }]
+    .into();
+    let payload = ChangeRequestPayload {
+        objects: vec![RepoObject::Link(moved)],
+        source_commit: "".into(),
+    };
+
+    let changes = review(&f, &payload);
+    assert_eq!(changes[0].parent_topics_added, vec![to_id.to_owned()]);
+    assert_eq!(changes[0].parent_topics_removed, vec![from_id.to_owned()]);
+
+    apply(&f, &payload).unwrap();
+    let from = f.git.fetch_topic(RepoId::wiki(), &from_id).unwrap();
+    let to = f.git.fetch_topic(RepoId::wiki(), &to_id).unwrap();
+    assert!(!from.has_child(link.id()));
+    assert!(to.has_child(link.id()));
+
+    // The move shows up in the history of the link and of both parent topics
+    for id in [link.id(), &from_id, &to_id] {
+        let activity = f.git.fetch_activity(RepoId::wiki(), id, 1).unwrap();
+        assert!(
+            matches!(&activity[..], [Change::UpdateLinkParentTopics(_)]),
+            "{id}"
+        );
+    }
+}
+
+#[test]
+fn cycles_rejected() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let parent_id = add_topic(&f, RepoId::wiki(), "Parent", &root);
+    let child_id = add_topic(&f, RepoId::wiki(), "Child", &parent_id);
+
+    let mut parent = f.git.fetch_topic(RepoId::wiki(), &parent_id).unwrap();
+    parent.parent_topics = [digraph::git::ParentTopic { id: child_id }].into();
+    let payload = ChangeRequestPayload {
+        objects: vec![RepoObject::Topic(parent)],
+        source_commit: "".into(),
+    };
+
+    assert!(matches!(apply(&f, &payload), Err(Error::Repo(_))));
+}
+
+#[test]
+fn cycles_across_proposed_topics_rejected() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let first_id = add_topic(&f, RepoId::wiki(), "First", &root);
+    let second_id = add_topic(&f, RepoId::wiki(), "Second", &root);
+
+    let mut first = f.git.fetch_topic(RepoId::wiki(), &first_id).unwrap();
+    first.parent_topics = [digraph::git::ParentTopic {
+        id: second_id.to_owned(),
+    }]
+    .into();
+    let mut second = f.git.fetch_topic(RepoId::wiki(), &second_id).unwrap();
+    second.parent_topics = [digraph::git::ParentTopic {
+        id: first_id.to_owned(),
+    }]
+    .into();
+    let payload = ChangeRequestPayload {
+        objects: vec![RepoObject::Topic(first), RepoObject::Topic(second)],
+        source_commit: "".into(),
+    };
+
+    assert!(matches!(apply(&f, &payload), Err(Error::Repo(_))));
+    let first = f.git.fetch_topic(RepoId::wiki(), &first_id).unwrap();
+    assert_eq!(first.parent_topics.first().unwrap().id, root);
+}
+
+#[test]
+fn moves_that_break_a_cycle_applied() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let parent_id = add_topic(&f, RepoId::wiki(), "Parent", &root);
+    let child_id = add_topic(&f, RepoId::wiki(), "Child", &parent_id);
+
+    // The parent and child swap places
+    let mut parent = f.git.fetch_topic(RepoId::wiki(), &parent_id).unwrap();
+    parent.parent_topics = [digraph::git::ParentTopic {
+        id: child_id.to_owned(),
+    }]
+    .into();
+    let mut child = f.git.fetch_topic(RepoId::wiki(), &child_id).unwrap();
+    child.parent_topics = [digraph::git::ParentTopic {
+        id: root.to_owned(),
+    }]
+    .into();
+    let payload = ChangeRequestPayload {
+        objects: vec![RepoObject::Topic(parent), RepoObject::Topic(child)],
+        source_commit: "".into(),
+    };
+
+    apply(&f, &payload).unwrap();
+    let parent = f.git.fetch_topic(RepoId::wiki(), &parent_id).unwrap();
+    let parent_ids = parent
+        .parent_topics
+        .iter()
+        .map(|p| p.id.to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(parent_ids, vec![child_id.to_owned()]);
+    let child = f.git.fetch_topic(RepoId::wiki(), &child_id).unwrap();
+    let child_ids = child
+        .parent_topics
+        .iter()
+        .map(|p| p.id.to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(child_ids, vec![root]);
+}
+
+#[test]
+fn nothing_to_propose() {
+    let f = Fixtures::copy("simple");
+    let repo_id = fork(&f);
+    let result = package(&f, repo_id, ChangeRequestSource::Diff);
+    assert!(matches!(result, Err(Error::Repo(_))));
+}
@@ -0,0 +1,200 @@
+use digraph::git::{
+    ClosureMap, DeleteTopic, IndexDrift, OnMatchingSynonym, Reindex, ReindexResult,
+    UpdateTopicParentTopics,
+};
+use digraph::prelude::*;
+use digraph::redis;
+use digraph::types::TopicPath;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::{actor, parse_id, Fixtures};
+
+fn reindex(f: &Fixtures, verify: bool) -> ReindexResult {
+    Reindex {
+        repo_id: RepoId::wiki(),
+        verify,
+    }
+    .call(&f.git, &redis::Noop)
+    .unwrap()
+}
+
+// The closure that was updated as parent topics changed agrees with one rebuilt from scratch
+fn assert_in_step(f: &Fixtures) {
+    let ReindexResult { drift, .. } = reindex(f, true);
+    let drifted = drift
+        .iter()
+        .filter(|finding| match finding {
+            IndexDrift::Missing { path }
+            | IndexDrift::Stale { path }
+            | IndexDrift::Changed { path }
+            | IndexDrift::Legacy { path }
+            | IndexDrift::Unreadable { path, .. } => path.starts_with("indexes/closure/"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(drifted, Vec::<&IndexDrift>::new());
+}
+
+fn path(f: &Fixtures, topic_id: &ExternalId) -> TopicPath {
+    f.git.topic_path(RepoId::wiki(), topic_id).unwrap().unwrap()
+}
+
+// The downset read from the closure is the same as the one found by walking the graph
+fn assert_downset(f: &Fixtures, topic_id: &ExternalId) -> HashSet<ExternalId> {
+    let path = path(f, topic_id);
+    let downset = f.git.downset_ids(&path);
+    assert_eq!(downset, f.git.downset(&path).collect::<HashSet<_>>());
+    downset
+}
+
+fn closure(f: &Fixtures, id: &ExternalId) -> Option<Arc<ClosureMap>> {
+    let view = f.git.view(RepoId::wiki()).unwrap();
+    ClosureMap::load(&view, id).unwrap()
+}
+
+fn topic(f: &Fixtures, name: &str, parent_id: &ExternalId) -> ExternalId {
+    f.upsert_topic(
+        RepoId::wiki(),
+        name,
+        parent_id,
+        OnMatchingSynonym::CreateDistinct,
+    )
+    .unwrap()
+    .repo_topic
+    .unwrap()
+    .topic_id()
+    .to_owned()
+}
+
+#[test]
+fn not_materialized() {
+    let f = Fixtures::copy("simple");
+    let topic_id = parse_id("00002");
+    let child_id = topic(&f, "Child topic", &topic_id);
+
+    // Lookups fall back to walking the graph
+    assert_eq!(closure(&f, &topic_id), None);
+    assert!(assert_downset(&f, &topic_id).contains(&child_id));
+
+    let mutation = f.mutation();
+    assert!(mutation
+        .cycle_exists(RepoId::wiki(), &topic_id, &child_id)
+        .unwrap());
+    assert!(!mutation
+        .cycle_exists(RepoId::wiki(), &child_id, &topic_id)
+        .unwrap());
+}
+
+#[test]
+fn materialized_by_reindex() {
+    let f = Fixtures::copy("simple");
+    let topic_id = parse_id("00002");
+    let child_id = topic(&f, "Child topic", &topic_id);
+
+    reindex(&f, false);
+    assert_in_step(&f);
+
+    let closure = closure(&f, &child_id).unwrap();
+    assert!(closure.ancestors.contains(&topic_id));
+    assert!(closure.ancestors.contains(&ExternalId::root_topic()));
+    assert!(assert_downset(&f, &topic_id).contains(&child_id));
+
+    let mutation = f.mutation();
+    assert!(mutation
+        .cycle_exists(RepoId::wiki(), &topic_id, &child_id)
+        .unwrap());
+    assert!(mutation
+        .cycle_exists(RepoId::wiki(), &topic_id, &topic_id)
+        .unwrap());
+    assert!(!mutation
+        .cycle_exists(RepoId::wiki(), &child_id, &topic_id)
+        .unwrap());
+}
+
+#[test]
+fn kept_in_step() {
+    let f = Fixtures::copy("simple");
+    let root = ExternalId::root_topic();
+    let topic_id = parse_id("00002");
+    reindex(&f, false);
+
+    // Parent topics are added
+    let a = topic(&f, "Topic A", &topic_id);
+    let b = topic(&f, "Topic B", &a);
+    let url = RepoUrl::parse("https://www.example.com/closure").unwrap();
+    let link_id = f
+        .upsert_link(RepoId::wiki(), &url, None, Some(b.to_owned()))
+        .link
+        .unwrap()
+        .id()
+        .to_owned();
+    assert_in_step(&f);
+    assert!(assert_downset(&f, &topic_id).contains(&link_id));
+    assert!(closure(&f, &link_id).unwrap().ancestors.contains(&topic_id));
+
+    // A parent topic is removed
+    UpdateTopicParentTopics {
+        actor: actor(),
+        parent_topic_ids: &[root.to_owned()],
+        repo_id: RepoId::wiki(),
+        topic_id: &a,
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+    assert_in_step(&f);
+    let downset = assert_downset(&f, &topic_id);
+    assert!(!downset.contains(&a));
+    assert!(!downset.contains(&link_id));
+    assert!(!closure(&f, &link_id).unwrap().ancestors.contains(&topic_id));
+    assert!(assert_downset(&f, &a).contains(&link_id));
+
+    // A topic is deleted and its children moved up to its parent
+    DeleteTopic {
+        actor: actor(),
+        repo_id: RepoId::wiki(),
+        topic_id: a.to_owned(),
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+    assert_in_step(&f);
+    let closure = closure(&f, &link_id).unwrap();
+    assert!(!closure.ancestors.contains(&a));
+    assert!(closure.ancestors.contains(&b));
+    assert!(assert_downset(&f, &b).contains(&link_id));
+}
+
+#[test]
+fn adding_a_link_leaves_other_entries_alone() {
+    let f = Fixtures::copy("simple");
+    let topic_id = parse_id("00002");
+    let child_id = topic(&f, "Child topic", &topic_id);
+    reindex(&f, false);
+
+    let entries = |f: &Fixtures| {
+        f.git
+            .view(RepoId::wiki())
+            .unwrap()
+            .blob_oids(|path| path.starts_with("indexes/closure/"))
+            .unwrap()
+    };
+    let before = entries(&f);
+
+    let url = RepoUrl::parse("https://www.example.com/closure").unwrap();
+    let link_id = f
+        .upsert_link(RepoId::wiki(), &url, None, Some(child_id.to_owned()))
+        .link
+        .unwrap()
+        .id()
+        .to_owned();
+    assert_in_step(&f);
+
+    let after = entries(&f);
+    let changed = after
+        .iter()
+        .filter(|(path, oid)| before.get(*path) != Some(oid))
+        .map(|(path, _)| path.to_owned())
+        .collect::<Vec<_>>();
+    // Only the entry of the new link is written
+    assert_eq!(changed.len(), 1, "{changed:?}");
+    assert!(closure(&f, &link_id).unwrap().ancestors.contains(&child_id));
+}
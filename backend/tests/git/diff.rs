@@ -0,0 +1,154 @@
+use digraph::git::{
+    Diff, DiffEntry, DiffResult, DiffSide, ForkRepo, Kind, OnMatchingSynonym, Synonym,
+    UpdateTopicSynonyms, UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::redis;
+
+use super::{actor, valid_url, Fixtures};
+
+fn add_topic(f: &Fixtures, name: &str) -> ExternalId {
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            RepoId::wiki(),
+            name,
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    repo_topic.unwrap().topic_id().to_owned()
+}
+
+fn diff(f: &Fixtures, base: DiffSide, head: DiffSide) -> Result<DiffResult> {
+    Diff {
+        base,
+        head,
+        locale: Locale::EN,
+    }
+    .call(&f.git)
+}
+
+fn side(repo_id: RepoId, rev: Option<String>) -> DiffSide {
+    DiffSide { repo_id, rev }
+}
+
+#[test]
+fn no_changes() {
+    let f = Fixtures::copy("simple");
+    let result = diff(&f, side(RepoId::wiki(), None), side(RepoId::wiki(), None)).unwrap();
+    assert!(result.entries.is_empty());
+    assert_eq!(result.base_commit, result.head_commit);
+}
+
+#[test]
+fn earlier_commit() {
+    let f = Fixtures::copy("simple");
+    let before = f.git.view(RepoId::wiki()).unwrap().commit.to_string();
+
+    let topic_id = add_topic(&f, "Tide pools");
+    let link = f
+        .upsert_link(
+            RepoId::wiki(),
+            &valid_url(),
+            Some("A page".into()),
+            Some(topic_id.to_owned()),
+        )
+        .link
+        .unwrap();
+
+    let result = diff(
+        &f,
+        side(RepoId::wiki(), Some(before.to_owned())),
+        side(RepoId::wiki(), None),
+    )
+    .unwrap();
+    assert_eq!(result.base_commit, before);
+
+    let entries = &result.entries;
+    assert!(entries.contains(&DiffEntry::ObjectAdded {
+        id: topic_id.to_owned(),
+        kind: Kind::Topic,
+        name: "Tide pools".into(),
+    }));
+    assert!(entries.contains(&DiffEntry::ParentTopicAdded {
+        id: topic_id.to_owned(),
+        kind: Kind::Topic,
+        parent_topic_id: ExternalId::root_topic(),
+    }));
+    assert!(entries.contains(&DiffEntry::ObjectAdded {
+        id: link.id().to_owned(),
+        kind: Kind::Link,
+        name: "A page".into(),
+    }));
+    assert!(entries.contains(&DiffEntry::ParentTopicAdded {
+        id: link.id().to_owned(),
+        kind: Kind::Link,
+        parent_topic_id: topic_id.to_owned(),
+    }));
+
+    // Going the other way, the same objects are removed
+    let result = diff(
+        &f,
+        side(RepoId::wiki(), None),
+        side(RepoId::wiki(), Some(before)),
+    )
+    .unwrap();
+    assert!(result.entries.contains(&DiffEntry::ObjectRemoved {
+        id: topic_id,
+        kind: Kind::Topic,
+        name: "Tide pools".into(),
+    }));
+}
+
+#[test]
+fn synonyms_across_repos() {
+    let f = Fixtures::copy("simple");
+    let topic_id = add_topic(&f, "Tide pools");
+
+    let fork_repo_id = RepoId::make();
+    ForkRepo {
+        actor: actor(),
+        fork_repo_id,
+        topic_id: None,
+        upstream_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &redis::Noop)
+    .unwrap();
+
+    let synonym = |name: &str| Synonym {
+        added: chrono::Utc::now(),
+        locale: Locale::EN,
+        name: name.to_owned(),
+    };
+
+    UpdateTopicSynonyms {
+        actor: actor(),
+        repo_id: fork_repo_id,
+        topic_id: topic_id.to_owned(),
+        synonyms: vec![synonym("Tide pools"), synonym("Rock pools")],
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+
+    let result = diff(&f, side(RepoId::wiki(), None), side(fork_repo_id, None)).unwrap();
+    assert!(result.entries.contains(&DiffEntry::SynonymAdded {
+        id: topic_id.to_owned(),
+        locale: Locale::EN,
+        name: "Rock pools".into(),
+    }));
+    assert!(!result
+        .entries
+        .iter()
+        .any(|entry| matches!(entry, DiffEntry::ObjectAdded { .. })));
+}
+
+#[test]
+fn unknown_rev() {
+    let f = Fixtures::copy("simple");
+    let result = diff(
+        &f,
+        side(RepoId::wiki(), Some("no-such-rev".into())),
+        side(RepoId::wiki(), None),
+    );
+    assert!(result.is_err());
+}
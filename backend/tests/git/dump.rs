@@ -0,0 +1,154 @@
+use digraph::git::{
+    DumpRepo, DumpRepoResult, FetchTopicLiveSearch, OnMatchingSynonym, RestoreRepo,
+    RestoreRepoResult, Search, UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::redis;
+
+use super::{actor, Fixtures};
+
+fn empty_repo() -> RepoId {
+    RepoId::try_from("5d8b6a7e-3a5b-4d6f-9c1e-2f3a4b5c6d7e").unwrap()
+}
+
+// The changes in the fixture predate the current format, so add one that can be read back
+fn add_topic(f: &Fixtures) -> ExternalId {
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            RepoId::wiki(),
+            "Reading list",
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    repo_topic.unwrap().topic_id().to_owned()
+}
+
+// The changes that predate the current format are left out
+fn dump(f: &Fixtures, repo_id: RepoId) -> (Vec<u8>, DumpRepoResult) {
+    let mut buf = vec![];
+    let result = DumpRepo {
+        allow_skipped: true,
+        repo_id,
+    }
+    .call(&f.git, &mut buf)
+    .unwrap();
+    (buf, result)
+}
+
+#[test]
+fn round_trip() {
+    let f = Fixtures::copy("simple");
+    add_topic(&f);
+    let (
+        buf,
+        DumpRepoResult {
+            changes, objects, ..
+        },
+    ) = dump(&f, RepoId::wiki());
+    assert!(objects > 0);
+    assert!(changes > 0);
+
+    let repo_id = empty_repo();
+    let result = RestoreRepo { repo_id }
+        .call(&f.git, buf.as_slice(), &redis::Noop)
+        .unwrap();
+    assert_eq!(
+        result,
+        RestoreRepoResult {
+            changes,
+            objects,
+            source_repo_id: RepoId::wiki(),
+        }
+    );
+
+    // Dumping the restored repo gives back the same objects and changes, with nothing skipped
+    let (restored, result) = dump(&f, repo_id);
+    assert!(result.skipped.is_empty());
+    let records = |buf: &[u8]| {
+        String::from_utf8(buf.to_vec())
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(records(&buf), records(&restored));
+}
+
+#[test]
+fn indexes_rebuilt() {
+    let f = Fixtures::copy("simple");
+    let topic_id = add_topic(&f);
+    let (buf, _) = dump(&f, RepoId::wiki());
+
+    let repo_id = empty_repo();
+    RestoreRepo { repo_id }
+        .call(&f.git, buf.as_slice(), &redis::Noop)
+        .unwrap();
+
+    let result = FetchTopicLiveSearch {
+        limit: 10,
+        repos: RepoIds::from(vec![repo_id]),
+        search: Search::parse("climate change").unwrap(),
+        viewer: actor(),
+    }
+    .call(&f.git)
+    .unwrap();
+    assert!(result
+        .synonyms
+        .iter()
+        .any(|entry| entry.name == "Climate change"));
+
+    let before = f
+        .git
+        .fetch_activity(RepoId::wiki(), &topic_id, 100)
+        .unwrap();
+    let after = f.git.fetch_activity(repo_id, &topic_id, 100).unwrap();
+    assert!(!after.is_empty());
+    assert_eq!(before.len(), after.len());
+}
+
+#[test]
+fn restore_requires_empty_repo() {
+    let f = Fixtures::copy("simple");
+    let (buf, _) = dump(&f, RepoId::wiki());
+
+    let result = RestoreRepo {
+        repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, buf.as_slice(), &redis::Noop);
+    assert!(matches!(result, Err(Error::Repo(_))));
+}
+
+#[test]
+fn unknown_version() {
+    let f = Fixtures::copy("simple");
+    let line = format!(
+        r#"{{"type":"header","dumpedAt":"2024-01-01T00:00:00Z","repoId":"{}","version":99}}"#,
+        RepoId::wiki()
+    );
+
+    let result = RestoreRepo {
+        repo_id: empty_repo(),
+    }
+    .call(&f.git, line.as_bytes(), &redis::Noop);
+    assert!(matches!(result, Err(Error::Repo(_))));
+}
+
+#[test]
+fn unparseable_files_reported() {
+    let f = Fixtures::copy("simple");
+
+    let (_, DumpRepoResult { skipped, .. }) = dump(&f, RepoId::wiki());
+    assert!(!skipped.is_empty());
+    assert!(skipped.iter().all(|path| path.starts_with("changes/")));
+
+    let mut buf = vec![];
+    let result = DumpRepo {
+        allow_skipped: false,
+        repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &mut buf);
+    assert!(matches!(result, Err(Error::Repo(_))));
+}
@@ -0,0 +1,134 @@
+use digraph::git::{
+    parse_netscape_bookmarks, Client, ExportFormat, ExportTopic, ExportTopicResult,
+    OnMatchingSynonym, UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::types::Timespec;
+use std::sync::Arc;
+
+use super::{viewer, Fixtures};
+
+// Reading list
+// - Fiction
+//   - Novel <https://example.com/novel>
+// - Essays & letters <https://example.com/essay>
+fn reading_list(f: &Fixtures) -> ExternalId {
+    let repo_id = RepoId::wiki();
+
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            repo_id,
+            "Reading list",
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    let list_id = repo_topic.unwrap().topic_id().to_owned();
+
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            repo_id,
+            "Fiction",
+            &list_id,
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    let fiction_id = repo_topic.unwrap().topic_id().to_owned();
+
+    f.upsert_link(
+        repo_id,
+        &RepoUrl::parse("https://example.com/novel").unwrap(),
+        Some("Novel".into()),
+        Some(fiction_id),
+    );
+    f.upsert_link(
+        repo_id,
+        &RepoUrl::parse("https://example.com/essay").unwrap(),
+        Some("Essays & letters".into()),
+        Some(list_id.to_owned()),
+    );
+
+    list_id
+}
+
+fn export(f: &Fixtures, topic_id: &ExternalId, format: ExportFormat) -> ExportTopicResult {
+    ExportTopic {
+        format,
+        locale: Locale::EN,
+        repo_id: RepoId::wiki(),
+        topic_id: topic_id.to_owned(),
+    }
+    .call(&f.git)
+    .unwrap()
+}
+
+#[test]
+fn markdown() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let ExportTopicResult { body, filename } = export(&f, &topic_id, ExportFormat::Markdown);
+    assert_eq!(filename, "reading-list.md");
+    assert_eq!(
+        body,
+        "# Reading list\n\n\
+        - **Fiction**\n  \
+        - [Novel](<https://example.com/novel>)\n\
+        - [Essays & letters](<https://example.com/essay>)\n"
+    );
+}
+
+#[test]
+fn opml() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let ExportTopicResult { body, .. } = export(&f, &topic_id, ExportFormat::Opml);
+    assert!(body.starts_with("<?xml"));
+    assert!(body.contains("<title>Reading list</title>"));
+    assert!(body.contains(r#"<outline text="Fiction">"#));
+    assert!(body.contains(
+        r#"<outline text="Essays &amp; letters" type="link" url="https://example.com/essay"/>"#
+    ));
+    assert!(body.ends_with("</opml>\n"));
+}
+
+#[test]
+fn netscape_round_trip() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let ExportTopicResult { body, .. } = export(&f, &topic_id, ExportFormat::Netscape);
+    let bookmarks = parse_netscape_bookmarks(&body);
+    assert_eq!(bookmarks.len(), 2);
+
+    let novel = &bookmarks[0];
+    assert_eq!(novel.url, "https://example.com/novel");
+    assert_eq!(novel.folder, vec!["Reading list", "Fiction"]);
+
+    let essay = &bookmarks[1];
+    assert_eq!(essay.url, "https://example.com/essay");
+    assert_eq!(essay.title.as_deref(), Some("Essays & letters"));
+    assert_eq!(essay.folder, vec!["Reading list"]);
+}
+
+#[test]
+fn private_topics_not_exported() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let viewer = Arc::new(Viewer {
+        super_user: false,
+        ..(*viewer(&RepoIds::from(vec![RepoId::other()]))).clone()
+    });
+    let client = Client::new(viewer, &f.git.root, Timespec);
+    let result = ExportTopic {
+        format: ExportFormat::Markdown,
+        locale: Locale::EN,
+        repo_id: RepoId::wiki(),
+        topic_id,
+    }
+    .call(&client);
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
@@ -1,5 +1,5 @@
 use digraph::git::{
-    Client, DataRoot, FetchTopicLiveSearch, FetchTopicLiveSearchResult, IndexMode, Mutation,
+    Client, DataRoot, FetchTopicLiveSearch, FetchTopicLiveSearchResult, IndexMode, Leak, Mutation,
     OnMatchingSynonym, RepoLink, RepoTopic, Search, UpsertLink, UpsertLinkResult, UpsertTopic,
     UpsertTopicResult,
 };
@@ -15,7 +15,7 @@ use tempfile::{self, TempDir};
 
 use super::{actor, parse_id};
 
-struct Fetcher(String);
+pub struct Fetcher(pub String);
 
 impl Fetch for Fetcher {
     fn fetch(&self, url: &RepoUrl) -> Result<Response> {
@@ -99,7 +99,7 @@ impl Fixtures {
         block(topic);
     }
 
-    pub fn leaked_data(&self) -> Result<Vec<(RepoId, String)>> {
+    pub fn leaked_data(&self) -> Result<Vec<Leak>> {
         self.git.leaked_data()
     }
 
@@ -151,6 +151,8 @@ impl Fixtures {
             actor: actor(),
             add_parent_topic_id,
             fetcher: Box::new(Fetcher(html)),
+            capture_snapshot: false,
+            fetch_queue: None,
             repo_id,
             url: url.normalized.to_owned(),
             title,
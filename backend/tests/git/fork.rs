@@ -0,0 +1,146 @@
+use digraph::git::{
+    ForkProvenance, ForkRepo, ForkRepoResult, Kind, OnMatchingSynonym, UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::redis;
+use std::sync::Arc;
+
+use super::{actor, valid_url, Fixtures};
+
+// An ordinary user who can only see the wiki, and who is given access to the fork as part of
+// creating it
+fn user() -> Arc<Viewer> {
+    Arc::new(Viewer {
+        super_user: false,
+        read_repo_ids: RepoIds::from(vec![RepoId::wiki()]),
+        write_repo_ids: RepoIds::from(vec![RepoId::wiki()]),
+        ..(*actor()).clone()
+    })
+}
+
+fn fork(f: &Fixtures, topic_id: Option<ExternalId>) -> (RepoId, Result<ForkRepoResult>) {
+    let fork_repo_id = RepoId::make();
+    let result = ForkRepo {
+        actor: user(),
+        fork_repo_id,
+        topic_id,
+        upstream_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &redis::Noop);
+    (fork_repo_id, result)
+}
+
+fn add_topic(f: &Fixtures, name: &str) -> ExternalId {
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            RepoId::wiki(),
+            name,
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    repo_topic.unwrap().topic_id().to_owned()
+}
+
+#[test]
+fn whole_repo() {
+    let f = Fixtures::copy("simple");
+    let upstream = f.git.view(RepoId::wiki()).unwrap().stats().unwrap();
+
+    let (fork_repo_id, result) = fork(&f, None);
+    let result = result.unwrap();
+    assert_eq!(Some(result.topics), upstream.topic_count);
+    assert_eq!(Some(result.links), upstream.link_count);
+
+    let stats = f.git.view(fork_repo_id).unwrap().stats().unwrap();
+    assert_eq!(stats.topic_count, upstream.topic_count);
+    assert_eq!(stats.link_count, upstream.link_count);
+
+    let provenance = ForkProvenance::fetch(&f.git, fork_repo_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(provenance, result.provenance);
+    assert_eq!(provenance.upstream_repo_id, RepoId::wiki());
+    assert_eq!(provenance.upstream_topic_id, None);
+    assert_eq!(provenance.forked_by, user().user_id);
+    assert_eq!(
+        provenance.upstream_commit,
+        f.git.view(RepoId::wiki()).unwrap().commit.to_string()
+    );
+}
+
+#[test]
+fn downset() {
+    let f = Fixtures::copy("simple");
+    let topic_id = add_topic(&f, "Reading list");
+    let other_id = add_topic(&f, "Left behind");
+    let link = f
+        .upsert_link(
+            RepoId::wiki(),
+            &valid_url(),
+            Some("A page".into()),
+            Some(topic_id.to_owned()),
+        )
+        .link
+        .unwrap();
+
+    let (fork_repo_id, result) = fork(&f, Some(topic_id.to_owned()));
+    let ForkRepoResult { links, topics, .. } = result.unwrap();
+    assert_eq!((topics, links), (2, 1));
+
+    let view = f.git.view(fork_repo_id).unwrap();
+    let root = view.topic(&fork_repo_id.root_topic_id()).unwrap().unwrap();
+    let children = root
+        .children
+        .iter()
+        .map(|child| (child.kind, child.id.to_owned()))
+        .collect::<Vec<_>>();
+    assert_eq!(children, vec![(Kind::Topic, topic_id.to_owned())]);
+
+    let topic = view.topic(&topic_id).unwrap().unwrap();
+    let parents = topic
+        .parent_topics
+        .iter()
+        .map(|parent| parent.id.to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(parents, vec![fork_repo_id.root_topic_id()]);
+
+    let forked_link = view.link(link.id()).unwrap().unwrap();
+    assert_eq!(forked_link.url(), link.url());
+    assert!(forked_link.metadata.snapshot.is_none());
+    assert!(view.topic(&other_id).unwrap().is_none());
+
+    let provenance = ForkProvenance::fetch(&f.git, fork_repo_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(provenance.upstream_topic_id, Some(topic_id));
+}
+
+#[test]
+fn upstream_must_be_readable() {
+    let f = Fixtures::copy("simple");
+    let result = ForkRepo {
+        actor: user(),
+        fork_repo_id: RepoId::make(),
+        topic_id: None,
+        upstream_repo_id: RepoId::other(),
+    }
+    .call(&f.git, &redis::Noop);
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[test]
+fn fork_repo_must_be_empty() {
+    let f = Fixtures::copy("simple");
+    let (fork_repo_id, result) = fork(&f, None);
+    result.unwrap();
+
+    let result = ForkRepo {
+        actor: user(),
+        fork_repo_id,
+        topic_id: None,
+        upstream_repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &redis::Noop);
+    assert!(matches!(result, Err(Error::Repo(_))));
+}
@@ -0,0 +1,124 @@
+use digraph::git::{Finding, Fsck, FsckResult, Kind, OnMatchingSynonym, TopicChild};
+use digraph::prelude::*;
+use digraph::redis;
+
+use super::{parse_id, Fixtures};
+
+fn fsck(f: &Fixtures, repair: bool) -> FsckResult {
+    Fsck {
+        repair,
+        repo_id: RepoId::wiki(),
+    }
+    .call(&f.git, &redis::Noop)
+    .unwrap()
+}
+
+#[test]
+fn check_only() {
+    let f = Fixtures::copy("simple");
+    let before = f.git.view(RepoId::wiki()).unwrap().commit;
+
+    // The fixture has a few edges that are only recorded on one side
+    let FsckResult { findings, repaired } = fsck(&f, false);
+    assert!(!findings.is_empty());
+    assert!(findings.iter().all(|finding| matches!(
+        finding,
+        Finding::MissingChild { .. } | Finding::MissingParentTopic { .. }
+    )));
+    assert_eq!(repaired, 0);
+
+    assert_eq!(f.git.view(RepoId::wiki()).unwrap().commit, before);
+}
+
+#[test]
+fn repair() {
+    let f = Fixtures::copy("simple");
+
+    let FsckResult { repaired, .. } = fsck(&f, true);
+    assert!(repaired > 0);
+
+    let FsckResult { findings, .. } = fsck(&f, false);
+    assert_eq!(findings, vec![]);
+}
+
+#[test]
+fn interrupted_write() {
+    let f = Fixtures::copy("simple");
+    fsck(&f, true);
+
+    // A topic is saved with a child that was never written
+    let topic_id = parse_id("00001");
+    let missing_id = parse_id("00009");
+    let mut topic = f.git.fetch_topic(RepoId::wiki(), &topic_id).unwrap();
+    topic.children.insert(TopicChild {
+        added: chrono::Utc::now(),
+        kind: Kind::Link,
+        id: missing_id.to_owned(),
+    });
+    let mut mutation = f.mutation();
+    mutation.save_topic(RepoId::wiki(), &topic).unwrap();
+    mutation.write(&redis::Noop).unwrap();
+
+    let FsckResult { findings, .. } = fsck(&f, false);
+    assert_eq!(
+        findings,
+        vec![Finding::DanglingChild {
+            child_id: missing_id.to_owned(),
+            topic_id: topic_id.to_owned(),
+        }]
+    );
+
+    fsck(&f, true);
+    let topic = f.git.fetch_topic(RepoId::wiki(), &topic_id).unwrap();
+    assert!(!topic.has_child(&missing_id));
+    assert_eq!(fsck(&f, false).findings, vec![]);
+}
+
+#[test]
+fn orphaned_topic() {
+    let f = Fixtures::copy("simple");
+    fsck(&f, true);
+
+    let topic = f
+        .upsert_topic(
+            RepoId::wiki(),
+            "Orphan",
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap()
+        .repo_topic
+        .unwrap();
+
+    // The topic loses its parent without the parent being updated, and then the parent's side
+    // of the edge is dropped as well
+    let mut orphan = topic.to_owned();
+    orphan.parent_topics.clear();
+    let mut root = f
+        .git
+        .fetch_topic(RepoId::wiki(), &ExternalId::root_topic())
+        .unwrap();
+    root.children.retain(|child| &child.id != topic.topic_id());
+
+    let mut mutation = f.mutation();
+    mutation.save_topic(RepoId::wiki(), &orphan).unwrap();
+    mutation.save_topic(RepoId::wiki(), &root).unwrap();
+    mutation.write(&redis::Noop).unwrap();
+
+    let FsckResult { findings, .. } = fsck(&f, false);
+    assert_eq!(
+        findings,
+        vec![Finding::Unreachable {
+            id: topic.topic_id().to_owned(),
+            kind: Kind::Topic,
+        }]
+    );
+
+    fsck(&f, true);
+    let orphan = f.git.fetch_topic(RepoId::wiki(), topic.topic_id()).unwrap();
+    assert!(orphan
+        .parent_topics
+        .iter()
+        .any(|parent| parent.id == ExternalId::root_topic()));
+    assert_eq!(fsck(&f, false).findings, vec![]);
+}
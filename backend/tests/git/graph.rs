@@ -0,0 +1,121 @@
+use digraph::git::{
+    GraphExport, GraphExportResult, GraphFormat, GraphScope, OnMatchingSynonym, UpsertTopicResult,
+};
+use digraph::prelude::*;
+
+use super::Fixtures;
+
+// Reading list
+// - Fiction
+//   - Novel <https://example.com/novel>
+fn reading_list(f: &Fixtures) -> ExternalId {
+    let repo_id = RepoId::wiki();
+
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            repo_id,
+            "Reading list",
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    let list_id = repo_topic.unwrap().topic_id().to_owned();
+
+    let UpsertTopicResult { repo_topic, .. } = f
+        .upsert_topic(
+            repo_id,
+            "Fiction",
+            &list_id,
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+    let fiction_id = repo_topic.unwrap().topic_id().to_owned();
+
+    f.upsert_link(
+        repo_id,
+        &RepoUrl::parse("https://example.com/novel").unwrap(),
+        Some("Novel".into()),
+        Some(fiction_id),
+    );
+
+    list_id
+}
+
+fn export(
+    f: &Fixtures,
+    topic_id: Option<&ExternalId>,
+    format: GraphFormat,
+    include_links: bool,
+) -> GraphExportResult {
+    let scope = match topic_id {
+        Some(topic_id) => {
+            let path = f.git.topic_path(RepoId::wiki(), topic_id).unwrap().unwrap();
+            GraphScope::Downset(path)
+        }
+        None => GraphScope::Repo(RepoId::wiki()),
+    };
+
+    GraphExport {
+        format,
+        include_links,
+        locale: Locale::EN,
+        scope,
+    }
+    .call(&f.git)
+    .unwrap()
+}
+
+#[test]
+fn whole_repo() {
+    let f = Fixtures::copy("simple");
+    reading_list(&f);
+
+    let GraphExportResult {
+        body, node_count, ..
+    } = export(&f, None, GraphFormat::Graphml, false);
+
+    assert!(node_count > 3);
+    assert!(body.contains("<data key=\"label\">Root topic</data>"));
+    assert!(body.contains("<data key=\"label\">Climate change</data>"));
+    assert!(body.contains("<data key=\"label\">Fiction</data>"));
+    assert!(!body.contains("<data key=\"kind\">link</data>"));
+}
+
+#[test]
+fn downset_only() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let result = export(&f, Some(&topic_id), GraphFormat::Dot, false);
+    assert_eq!(result.node_count, 2);
+    assert_eq!(result.edge_count, 1);
+    assert!(!result.body.contains("Climate change"));
+
+    let result = export(&f, Some(&topic_id), GraphFormat::Dot, true);
+    assert_eq!(result.node_count, 3);
+    assert_eq!(result.edge_count, 2);
+    assert!(result.body.contains("URL=\"https://example.com/novel\""));
+}
+
+#[test]
+fn owning_repos_recorded() {
+    let f = Fixtures::copy("simple");
+    let topic_id = reading_list(&f);
+
+    let url = RepoUrl::parse("https://example.com/novel").unwrap();
+    let link_id = f
+        .upsert_link(RepoId::other(), &url, Some("Novel".into()), None)
+        .link
+        .unwrap()
+        .id()
+        .to_owned();
+
+    let GraphExportResult { body, .. } = export(&f, Some(&topic_id), GraphFormat::Dot, true);
+    let line = body
+        .lines()
+        .find(|line| line.starts_with(&format!("  \"{link_id}\" [")))
+        .unwrap();
+
+    assert!(line.contains(&RepoId::wiki().to_string()));
+    assert!(line.contains(&RepoId::other().to_string()));
+}
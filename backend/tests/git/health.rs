@@ -0,0 +1,131 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{actor, Fixtures};
+use digraph::git::{CheckLinks, CheckLinksResult, LinkHealth, UpsertLinkResult};
+use digraph::http::{FetchPolicy, PolicyOptions, UrlChecker};
+use digraph::prelude::*;
+use digraph::redis;
+
+// Serves a handful of canned responses so that links can be checked without going out to the
+// network.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let path = line.split_whitespace().nth(1).unwrap_or("/").to_owned();
+
+            let response = match path.as_str() {
+                "/ok" => "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_owned(),
+                "/moved" => format!(
+                    "HTTP/1.1 301 Moved Permanently\r\nLocation: http://{addr}/ok\r\n\
+                    Content-Length: 0\r\n\r\n"
+                ),
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_owned(),
+            };
+
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    format!("http://{addr}")
+}
+
+fn upsert(f: &Fixtures, repo_id: RepoId, url: &str) -> ExternalId {
+    let url = RepoUrl::parse(url).unwrap();
+    let UpsertLinkResult { link, .. } = f.upsert_link(repo_id, &url, Some("Title".into()), None);
+    link.unwrap().id().to_owned()
+}
+
+fn check(f: &Fixtures, repo_id: RepoId) -> CheckLinksResult {
+    CheckLinks {
+        actor: actor(),
+        checker: Box::new(UrlChecker::new(Arc::new(FetchPolicy::new(PolicyOptions {
+            cache_dir: None,
+            cache_max_bytes: 0,
+            max_concurrent_requests: 2,
+            min_interval: Duration::ZERO,
+        })))),
+        repo_id,
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap()
+}
+
+fn health(f: &Fixtures, repo_id: RepoId, link_id: &ExternalId) -> LinkHealth {
+    f.git
+        .fetch_link_health(repo_id, link_id)
+        .unwrap()
+        .expect("expected a health check")
+}
+
+#[test]
+fn health_recorded() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::other();
+    let host = serve();
+
+    let ok = upsert(&f, repo_id, &format!("{host}/ok"));
+    let missing = upsert(&f, repo_id, &format!("{host}/missing"));
+    let moved = upsert(&f, repo_id, &format!("{host}/moved"));
+
+    let CheckLinksResult { broken, checked } = check(&f, repo_id);
+    assert_eq!(checked, 3);
+    assert_eq!(broken, 1);
+
+    let health_ok = health(&f, repo_id, &ok);
+    assert_eq!(health_ok.status, Some(200));
+    assert!(!health_ok.is_broken());
+
+    let health_missing = health(&f, repo_id, &missing);
+    assert_eq!(health_missing.status, Some(404));
+    assert!(health_missing.is_broken());
+
+    let health_moved = health(&f, repo_id, &moved);
+    assert_eq!(health_moved.status, Some(301));
+    assert_eq!(health_moved.redirect, Some(format!("{host}/ok")));
+    assert!(!health_moved.is_broken());
+}
+
+#[test]
+fn broken_links() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::other();
+    let host = serve();
+
+    upsert(&f, repo_id, &format!("{host}/ok"));
+    let missing = upsert(&f, repo_id, &format!("{host}/missing"));
+    assert!(f.git.broken_links(repo_id).unwrap().is_empty());
+
+    check(&f, repo_id);
+    let broken = f.git.broken_links(repo_id).unwrap();
+    assert_eq!(broken.len(), 1);
+    assert_eq!(broken[0].0, missing);
+}
+
+#[test]
+fn unreachable_host() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::other();
+
+    // Nothing is listening on this port once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let link_id = upsert(&f, repo_id, &format!("http://{addr}/gone"));
+
+    check(&f, repo_id);
+    let health = health(&f, repo_id, &link_id);
+    assert_eq!(health.status, None);
+    assert!(health.error.is_some());
+    assert!(health.is_broken());
+}
@@ -0,0 +1,290 @@
+use digraph::git::{
+    activity, parse_netscape_bookmarks, Bookmark, ImportBookmarks, ImportBookmarksResult,
+    ImportFormat, OnMatchingSynonym, ParentTopic, UpsertTopicResult,
+};
+use digraph::prelude::*;
+use digraph::redis;
+
+use super::{actor, Fixtures};
+
+const BOOKMARKS: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3>Imported folder</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000000">Rust</A>
+        <DT><H3>Climate change</H3>
+        <DL><p>
+            <DT><A HREF="https://www.ipcc.ch/">IPCC</A>
+        </DL><p>
+        <DT><H3>Reading</H3>
+        <DL><p>
+            <DT><A HREF="https://example.com/article">An article</A>
+        </DL><p>
+    </DL><p>
+    <DT><H3>Other folder</H3>
+    <DL><p>
+        <DT><H3>Reading</H3>
+        <DL><p>
+            <DT><A HREF="https://example.com/another">Another article</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+</DL><p>
+"#;
+
+fn import(f: &Fixtures) -> ImportBookmarksResult {
+    ImportBookmarks {
+        actor: actor(),
+        bookmarks: parse_netscape_bookmarks(BOOKMARKS),
+        locale: Locale::EN,
+        parent_topic_id: ExternalId::root_topic(),
+        repo_id: RepoId::wiki(),
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap()
+}
+
+fn link_id(url: &str) -> ExternalId {
+    RepoUrl::parse(url).unwrap().id().unwrap()
+}
+
+#[test]
+fn folders_become_topics() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+
+    let result = import(&f);
+    assert_eq!(
+        result,
+        ImportBookmarksResult {
+            links: 4,
+            skipped: 1,
+            topics_created: 3,
+        }
+    );
+
+    let folder_id = f.find_topic("Imported folder").unwrap();
+    let folder = f.git.fetch_topic(repo_id, &folder_id).unwrap();
+    assert!(folder.parent_topics.contains(&ParentTopic {
+        id: ExternalId::root_topic()
+    }));
+
+    let link = f
+        .git
+        .fetch_link(repo_id, &link_id("https://www.rust-lang.org/"))
+        .unwrap();
+    assert_eq!(link.title(), "Rust");
+    assert_eq!(link.metadata.added.timestamp(), 1_600_000_000);
+    assert!(link.parent_topics.contains(&ParentTopic { id: folder_id }));
+}
+
+#[test]
+fn existing_topics_reused() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+    let climate_change = f.find_topic("Climate change").unwrap();
+
+    import(&f);
+
+    let link = f
+        .git
+        .fetch_link(repo_id, &link_id("https://www.ipcc.ch/"))
+        .unwrap();
+    assert!(link.parent_topics.contains(&ParentTopic {
+        id: climate_change.to_owned()
+    }));
+
+    let topic = f.git.fetch_topic(repo_id, &climate_change).unwrap();
+    let folder_id = f.find_topic("Imported folder").unwrap();
+    assert!(topic.parent_topics.contains(&ParentTopic { id: folder_id }));
+}
+
+#[test]
+fn repeated_folders_reused() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+
+    import(&f);
+
+    let reading_id = f.find_topic("Reading").unwrap();
+    let reading = f.git.fetch_topic(repo_id, &reading_id).unwrap();
+    assert_eq!(reading.parent_topics.len(), 2);
+    assert_eq!(reading.children.len(), 2);
+}
+
+#[test]
+fn activity_recorded() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+
+    import(&f);
+
+    let activity = f
+        .git
+        .fetch_activity(repo_id, &link_id("https://www.rust-lang.org/"), 10)
+        .unwrap();
+    assert!(activity
+        .iter()
+        .any(|change| matches!(change, activity::Change::ImportLink(_))));
+
+    let folder_id = f.find_topic("Imported folder").unwrap();
+    let activity = f.git.fetch_activity(repo_id, &folder_id, 10).unwrap();
+    assert!(activity
+        .iter()
+        .any(|change| matches!(change, activity::Change::ImportTopic(_))));
+}
+
+#[test]
+fn import_is_idempotent() {
+    let f = Fixtures::copy("simple");
+
+    import(&f);
+    let result = import(&f);
+
+    assert_eq!(result.topics_created, 0);
+    assert_eq!(result.links, 4);
+}
+
+#[test]
+fn ancestors_not_reused_below_new_folders() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+    let add_topic = |name: &str, parent: &ExternalId| {
+        let UpsertTopicResult { repo_topic, .. } = f
+            .upsert_topic(repo_id, name, parent, OnMatchingSynonym::CreateDistinct)
+            .unwrap();
+        repo_topic.unwrap().topic_id().to_owned()
+    };
+    let science_id = add_topic("Science", &ExternalId::root_topic());
+    let physics_id = add_topic("Physics", &science_id);
+
+    // Importing "Stuff/Science" under Physics must not put Science below itself
+    let bookmarks = parse_netscape_bookmarks(
+        r#"<DL><p>
+    <DT><H3>Stuff</H3>
+    <DL><p>
+        <DT><H3>Science</H3>
+        <DL><p>
+            <DT><A HREF="https://example.com/science">Science</A>
+        </DL><p>
+    </DL><p>
+</DL><p>"#,
+    );
+    let result = ImportBookmarks {
+        actor: actor(),
+        bookmarks,
+        locale: Locale::EN,
+        parent_topic_id: physics_id.to_owned(),
+        repo_id,
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+    assert_eq!(result.topics_created, 2);
+
+    let science = f.git.fetch_topic(repo_id, &science_id).unwrap();
+    assert_eq!(
+        science.parent_topics,
+        [ParentTopic {
+            id: ExternalId::root_topic()
+        }]
+        .into_iter()
+        .collect()
+    );
+}
+
+const PINBOARD: &str = r#"[
+    {"href":"https://example.com/tagged","description":"Tagged","time":"2020-09-13T12:26:40Z",
+     "tags":"tagged-reading Weather"},
+    {"href":"https://example.com/untagged","description":"Untagged","time":"2020-09-13T12:26:40Z",
+     "tags":""}
+]"#;
+
+fn import_bookmarks(f: &Fixtures, bookmarks: Vec<Bookmark>) -> ImportBookmarksResult {
+    ImportBookmarks {
+        actor: actor(),
+        bookmarks,
+        locale: Locale::EN,
+        parent_topic_id: ExternalId::root_topic(),
+        repo_id: RepoId::wiki(),
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap()
+}
+
+#[test]
+fn tags_become_topics() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+    let weather_id = f.find_topic("Weather").unwrap();
+
+    let bookmarks = ImportFormat::Pinboard.parse(PINBOARD).unwrap();
+    let result = import_bookmarks(&f, bookmarks);
+    assert_eq!(result.links, 2);
+    assert_eq!(result.topics_created, 1);
+
+    let tag_id = f.find_topic("tagged-reading").unwrap();
+    let link = f
+        .git
+        .fetch_link(repo_id, &link_id("https://example.com/tagged"))
+        .unwrap();
+    assert_eq!(
+        link.parent_topics,
+        [ParentTopic { id: tag_id }, ParentTopic { id: weather_id },]
+            .into_iter()
+            .collect()
+    );
+
+    let link = f
+        .git
+        .fetch_link(repo_id, &link_id("https://example.com/untagged"))
+        .unwrap();
+    assert_eq!(
+        link.parent_topics,
+        [ParentTopic {
+            id: ExternalId::root_topic()
+        }]
+        .into_iter()
+        .collect()
+    );
+}
+
+#[test]
+fn saved_date_kept() {
+    let f = Fixtures::copy("simple");
+    let csv = "url,title,tags,date\nhttps://example.com/dated,Dated,reading,1600000000\n";
+
+    let bookmarks = ImportFormat::detect(csv).parse(csv).unwrap();
+    import_bookmarks(&f, bookmarks);
+
+    let link = f
+        .git
+        .fetch_link(RepoId::wiki(), &link_id("https://example.com/dated"))
+        .unwrap();
+    assert_eq!(link.metadata.added.timestamp(), 1600000000);
+}
+
+#[test]
+fn existing_links_keep_their_titles() {
+    let f = Fixtures::copy("simple");
+    let repo_id = RepoId::wiki();
+    let url = RepoUrl::parse("https://example.com/existing").unwrap();
+    f.upsert_link(repo_id, &url, Some("Existing title".into()), None);
+
+    let csv = "url,title,date\n\
+        https://example.com/existing,Bookmarked title,1600000000\n\
+        https://example.com/untitled,,\n";
+    import_bookmarks(&f, ImportFormat::Csv.parse(csv).unwrap());
+
+    let link = f.git.fetch_link(repo_id, &url.id().unwrap()).unwrap();
+    assert_eq!(link.title(), "Existing title");
+    assert_ne!(link.metadata.added.timestamp(), 1600000000);
+
+    let link = f
+        .git
+        .fetch_link(repo_id, &link_id("https://example.com/untitled"))
+        .unwrap();
+    assert_eq!(link.title(), "https://example.com/untitled");
+    assert!(!link.is_pending());
+}
@@ -74,6 +74,156 @@ mod delete_link {
     }
 }
 
+mod fetch_link_title {
+    use super::*;
+    use crate::git::Fetcher;
+    use digraph::git::{
+        activity, FetchLinkJob, FetchLinkTitle, LinkFetchQueue, UpsertLink, UpsertLinkResult,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Queue(Arc<Mutex<Vec<FetchLinkJob>>>);
+
+    impl LinkFetchQueue for Queue {
+        fn push(&self, job: &FetchLinkJob) -> Result<()> {
+            self.0.lock().unwrap().push(job.to_owned());
+            Ok(())
+        }
+    }
+
+    struct FailingQueue;
+
+    impl LinkFetchQueue for FailingQueue {
+        fn push(&self, _job: &FetchLinkJob) -> Result<()> {
+            Err(Error::Redis("connection refused".into()))
+        }
+    }
+
+    fn upsert_later(f: &Fixtures, queue: &Queue, title: Option<String>) -> UpsertLinkResult {
+        upsert_with_queue(f, Box::new(queue.clone()), title)
+    }
+
+    fn upsert_with_queue(
+        f: &Fixtures,
+        queue: Box<dyn LinkFetchQueue + Send + Sync>,
+        title: Option<String>,
+    ) -> UpsertLinkResult {
+        UpsertLink {
+            actor: actor(),
+            add_parent_topic_id: Some(parse_id("00001")),
+            fetcher: Box::new(Fetcher("<title>Too soon</title>".into())),
+            capture_snapshot: false,
+            fetch_queue: Some(queue),
+            repo_id: RepoId::wiki(),
+            title,
+            url: valid_url().normalized,
+        }
+        .call(f.mutation(), &redis::Noop)
+        .unwrap()
+    }
+
+    #[test]
+    fn link_saved_as_pending() {
+        let f = Fixtures::copy("simple");
+        let queue = Queue::default();
+        let url = valid_url();
+
+        let UpsertLinkResult { link, .. } = upsert_later(&f, &queue, None);
+        let link = link.unwrap();
+        assert!(link.is_pending());
+        assert_eq!(link.title(), url.normalized);
+
+        let jobs = queue.0.lock().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(&jobs[0].link_id, link.id());
+    }
+
+    #[test]
+    fn not_left_pending_when_enqueue_fails() {
+        let f = Fixtures::copy("simple");
+        let url = valid_url();
+
+        let UpsertLinkResult { alerts, link } = upsert_with_queue(&f, Box::new(FailingQueue), None);
+        let link = link.unwrap();
+        assert!(!link.is_pending());
+        assert!(!alerts.is_empty());
+
+        f.fetch_link(RepoId::wiki(), link.id(), |link| {
+            assert!(!link.is_pending());
+            assert_eq!(link.title(), url.normalized);
+        });
+    }
+
+    #[test]
+    fn no_fetch_when_title_provided() {
+        let f = Fixtures::copy("simple");
+        let queue = Queue::default();
+
+        let UpsertLinkResult { link, .. } = upsert_later(&f, &queue, Some("Page title".into()));
+        assert!(!link.unwrap().is_pending());
+        assert!(queue.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn title_updated() {
+        let f = Fixtures::copy("simple");
+        let queue = Queue::default();
+        let repo_id = RepoId::wiki();
+
+        upsert_later(&f, &queue, None);
+        let job = queue.0.lock().unwrap().pop().unwrap();
+        let link_id = job.link_id.to_owned();
+
+        let result = FetchLinkTitle {
+            actor: actor(),
+            capture_snapshot: false,
+            fetcher: Box::new(Fetcher("<title>Fetched title</title>".into())),
+            job,
+        }
+        .call(f.mutation(), &redis::Noop)
+        .unwrap();
+        assert!(result.link.is_some());
+
+        f.fetch_link(repo_id, &link_id, |link| {
+            assert!(!link.is_pending());
+            assert_eq!(link.title(), "Fetched title");
+        });
+
+        let activity = f.git.fetch_activity(repo_id, &link_id, 1).unwrap();
+        match activity.first() {
+            Some(activity::Change::UpsertLink(change)) => {
+                assert_eq!(change.upserted_link.title, "Fetched title");
+                assert_eq!(change.previous_title, Some(valid_url().normalized));
+            }
+            _ => panic!("expected an activity change"),
+        }
+    }
+
+    #[test]
+    fn fetched_only_once() {
+        let f = Fixtures::copy("simple");
+        let queue = Queue::default();
+
+        upsert_later(&f, &queue, None);
+        let job = queue.0.lock().unwrap().pop().unwrap();
+
+        let fetch = |job: FetchLinkJob| {
+            FetchLinkTitle {
+                actor: actor(),
+                capture_snapshot: false,
+                fetcher: Box::new(Fetcher("<title>Fetched title</title>".into())),
+                job,
+            }
+            .call(f.mutation(), &redis::Noop)
+            .unwrap()
+        };
+
+        assert!(fetch(job.to_owned()).link.is_some());
+        assert!(fetch(job).link.is_none());
+    }
+}
+
 mod update_link_parent_topics {
     use super::*;
     use digraph::git::{UpdateLinkParentTopics, UpsertLinkResult};
@@ -113,7 +263,8 @@ mod update_link_parent_topics {
 
 mod upsert_link {
     use super::*;
-    use digraph::git::{Kind, Search, SearchEntry, UpsertLinkResult};
+    use crate::git::Fetcher;
+    use digraph::git::{Kind, Search, SearchEntry, UpsertLink, UpsertLinkResult};
 
     #[test]
     fn link_added() {
@@ -334,4 +485,43 @@ mod upsert_link {
 
         // assert!(found, "link not found in topic children");
     }
+
+    #[test]
+    fn snapshot_captured() {
+        let f = Fixtures::copy("simple");
+        let repo_id = RepoId::wiki();
+
+        let UpsertLinkResult { link, .. } = UpsertLink {
+            actor: actor(),
+            add_parent_topic_id: Some(parse_id("00001")),
+            capture_snapshot: true,
+            fetcher: Box::new(Fetcher(
+                "<html><head><title>Page title</title></head><body><p>Some text</p></body></html>"
+                    .into(),
+            )),
+            fetch_queue: None,
+            repo_id,
+            title: None,
+            url: valid_url().normalized,
+        }
+        .call(f.mutation(), &redis::Noop)
+        .unwrap();
+
+        let link = link.unwrap();
+        assert_eq!(link.title(), "Page title");
+        let reference = link.metadata.snapshot.expect("expected a snapshot");
+
+        let snapshot = f.git.fetch_snapshot(repo_id, &reference).unwrap().unwrap();
+        assert_eq!(snapshot.text, "Some text");
+        assert!(snapshot.html.contains("<p>Some text</p>"));
+    }
+
+    #[test]
+    fn no_snapshot_unless_requested() {
+        let f = Fixtures::copy("simple");
+        let url = valid_url();
+
+        let UpsertLinkResult { link, .. } = f.upsert_link(RepoId::wiki(), &url, None, None);
+        assert!(link.unwrap().metadata.snapshot.is_none());
+    }
 }
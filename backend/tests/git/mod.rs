@@ -3,7 +3,20 @@ use std::sync::Arc;
 
 mod fixtures;
 pub use fixtures::*;
+mod accounts;
+mod change_request;
+mod closure;
+mod diff;
+mod dump;
+mod export;
+mod fork;
+mod fsck;
+mod graph;
+mod health;
+mod import;
 mod link;
+mod publish;
+mod reindex;
 mod repo;
 mod search;
 mod topic;
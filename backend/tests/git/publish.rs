@@ -0,0 +1,77 @@
+use digraph::git::{PublishSite, PublishSiteResult};
+use digraph::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use super::Fixtures;
+
+fn publish(f: &Fixtures) -> (TempDir, PublishSiteResult) {
+    let output = tempfile::tempdir().unwrap();
+    let result = PublishSite {
+        base_url: "https://mirror.example.com/".into(),
+        locale: Locale::EN,
+        output: output.path().to_owned(),
+        repo_id: RepoId::wiki(),
+    }
+    .call(&f.git)
+    .unwrap();
+    (output, result)
+}
+
+#[test]
+fn topic_pages() {
+    let f = Fixtures::copy("simple");
+    let (output, result) = publish(&f);
+    assert!(result.topics > 1);
+
+    let topic_id = f.find_topic("Climate change").unwrap();
+    let html = fs::read_to_string(output.path().join(format!("topics/{topic_id}.html"))).unwrap();
+    assert!(html.contains("<h1>Climate change</h1>"));
+    assert!(html.contains("Parent topics:"));
+
+    let index = fs::read_to_string(output.path().join("index.html")).unwrap();
+    assert!(index.contains(&format!("topics/{}.html", ExternalId::root_topic())));
+}
+
+#[test]
+fn link_pages() {
+    let f = Fixtures::copy("simple");
+    let url = RepoUrl::parse("https://example.com/published").unwrap();
+    let topic_id = f.find_topic("Climate change").unwrap();
+    f.upsert_link(
+        RepoId::wiki(),
+        &url,
+        Some("Published <link>".into()),
+        Some(topic_id.to_owned()),
+    );
+
+    let (output, _) = publish(&f);
+    let link_id = url.id().unwrap();
+
+    let html = fs::read_to_string(output.path().join(format!("links/{link_id}.html"))).unwrap();
+    assert!(html.contains("<h1>Published &lt;link&gt;</h1>"));
+    assert!(html.contains(&format!("topics/{topic_id}.html")));
+
+    let html = fs::read_to_string(output.path().join(format!("topics/{topic_id}.html"))).unwrap();
+    assert!(html.contains("<a href=\"https://example.com/published\">"));
+}
+
+#[test]
+fn search_index_and_sitemap() {
+    let f = Fixtures::copy("simple");
+    let (output, result) = publish(&f);
+
+    let json = fs::read_to_string(output.path().join("search.json")).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(entries.len(), result.topics + result.links);
+    assert!(entries
+        .iter()
+        .any(|entry| entry["name"] == "Climate change"));
+
+    let sitemap = fs::read_to_string(output.path().join("sitemap.xml")).unwrap();
+    let topic_id = f.find_topic("Climate change").unwrap();
+    assert!(sitemap.contains(&format!(
+        "<loc>https://mirror.example.com/topics/{topic_id}.html</loc>"
+    )));
+    assert!(output.path().join("search.js").exists());
+}
@@ -0,0 +1,177 @@
+use digraph::git::{IndexDrift, Reindex, ReindexResult, UpsertLink};
+use digraph::prelude::*;
+use digraph::redis;
+use std::path::Path;
+
+use super::{actor, parse_id, valid_url, Fetcher, Fixtures};
+
+fn reindex(f: &Fixtures, verify: bool) -> ReindexResult {
+    Reindex {
+        repo_id: RepoId::wiki(),
+        verify,
+    }
+    .call(&f.git, &redis::Noop)
+    .unwrap()
+}
+
+fn drifted(drift: &[IndexDrift]) -> Vec<&IndexDrift> {
+    // The change files in the fixture predate the current format and cannot be read
+    drift
+        .iter()
+        .filter(|finding| !matches!(finding, IndexDrift::Unreadable { .. }))
+        .collect()
+}
+
+#[test]
+fn verify_only() {
+    let f = Fixtures::copy("simple");
+    let before = f.git.view(RepoId::wiki()).unwrap().commit;
+
+    // The indexes in the fixture were written by hand and have fallen out of step with the objects
+    let ReindexResult { drift, indexes } = reindex(&f, true);
+    assert!(indexes > 0);
+    assert!(drift.contains(&IndexDrift::Changed {
+        path: "indexes/search/ro.yaml".into()
+    }));
+
+    assert_eq!(f.git.view(RepoId::wiki()).unwrap().commit, before);
+}
+
+#[test]
+fn rebuild() {
+    let f = Fixtures::copy("simple");
+
+    let ReindexResult { drift, .. } = reindex(&f, false);
+    assert!(!drifted(&drift).is_empty());
+
+    let ReindexResult { drift, .. } = reindex(&f, true);
+    assert_eq!(drifted(&drift), Vec::<&IndexDrift>::new());
+}
+
+#[test]
+fn migrate_from_yaml() {
+    let f = Fixtures::copy("simple");
+    let yaml_indexes = |f: &Fixtures| {
+        f.git
+            .view(RepoId::wiki())
+            .unwrap()
+            .blob_oids(|path| {
+                // The closure is kept in YAML
+                path.starts_with("indexes/")
+                    && !path.starts_with("indexes/closure/")
+                    && path.ends_with(".yaml")
+            })
+            .unwrap()
+    };
+
+    // Indexes whose entries are right but which are still in YAML
+    let ReindexResult { drift, .. } = reindex(&f, true);
+    assert!(drift.contains(&IndexDrift::Legacy {
+        path: "indexes/search/13.yaml".into()
+    }));
+    assert!(!yaml_indexes(&f).is_empty());
+
+    reindex(&f, false);
+    assert!(yaml_indexes(&f).is_empty());
+
+    // Lookups now go through the tables
+    assert_eq!(
+        f.find_topic("Existing non-root topic"),
+        Some(parse_id("00002"))
+    );
+}
+
+#[test]
+fn missing_index() {
+    let f = Fixtures::copy("simple");
+    reindex(&f, false);
+
+    let path = "indexes/search/ch.idx";
+    let mut mutation = f.mutation();
+    mutation
+        .remove_file(RepoId::wiki(), Path::new(path))
+        .unwrap();
+    mutation.write(&redis::Noop).unwrap();
+
+    let ReindexResult { drift, .. } = reindex(&f, true);
+    assert_eq!(
+        drifted(&drift),
+        vec![&IndexDrift::Missing { path: path.into() }]
+    );
+
+    reindex(&f, false);
+    let ReindexResult { drift, .. } = reindex(&f, true);
+    assert_eq!(drifted(&drift), Vec::<&IndexDrift>::new());
+}
+
+#[test]
+fn unreadable_changes() {
+    let f = Fixtures::copy("simple");
+    let path = "objects/lB/wR/6Cvz4btdI23oscsp7THRytHohlol4o2IkqxcFN8/changes.yaml";
+    let activity_log = |f: &Fixtures| {
+        f.git
+            .view(RepoId::wiki())
+            .unwrap()
+            .blob_oids(|p| p == path)
+            .unwrap()
+    };
+    let before = activity_log(&f);
+    assert!(!before.is_empty());
+
+    // Activity logs are left alone when they cannot be rebuilt from every change
+    let ReindexResult { drift, .. } = reindex(&f, false);
+    assert!(drift
+        .iter()
+        .any(|finding| matches!(finding, IndexDrift::Unreadable { .. })));
+    assert_eq!(activity_log(&f), before);
+}
+
+#[test]
+fn text_index_kept_out_of_git() {
+    let f = Fixtures::copy("simple");
+    UpsertLink {
+        actor: actor(),
+        add_parent_topic_id: None,
+        capture_snapshot: true,
+        fetcher: Box::new(Fetcher(
+            "<html><head><title>Cell biology</title></head>\
+            <body><p>The powerhouse of the cell</p></body></html>"
+                .into(),
+        )),
+        fetch_queue: None,
+        repo_id: RepoId::wiki(),
+        title: None,
+        url: valid_url().normalized,
+    }
+    .call(f.mutation(), &redis::Noop)
+    .unwrap();
+
+    let text_indexes = f.git.root.repo_path(RepoId::wiki()).join(".snapshots/text");
+    let listing = |dir: &std::path::Path| {
+        let mut entries = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    };
+    let before = listing(&text_indexes);
+    assert!(!before.is_empty());
+
+    // The full-text index is not committed to the repo
+    let committed = f
+        .git
+        .view(RepoId::wiki())
+        .unwrap()
+        .blob_oids(|path| path.contains("text/"))
+        .unwrap();
+    assert!(committed.is_empty());
+
+    let ReindexResult { drift, .. } = reindex(&f, true);
+    assert!(!drift
+        .iter()
+        .any(|finding| finding.to_string().contains("text/")));
+
+    reindex(&f, false);
+    assert_eq!(listing(&text_indexes), before);
+}
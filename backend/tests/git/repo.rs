@@ -1,26 +1,104 @@
-use std::fs;
 use std::sync::Arc;
 
 use super::{actor, viewer, Fixtures};
 use digraph::git;
 
 mod topic_references {
-    use digraph::prelude::{OTHER_REPOSITORY_ID, WIKI_REPOSITORY_ID};
+    use digraph::git::{LeakField, LeakedData, OnMatchingSynonym, TopicChild};
+    use digraph::prelude::*;
+    use digraph::redis;
 
     use super::*;
 
     #[test]
     fn no_leaks() {
         let f = Fixtures::copy("simple");
-        let other = f.git.root.path.join(OTHER_REPOSITORY_ID);
-        fs::create_dir_all(&other).expect("unable create other repo");
+        git::core::Repo::ensure(&f.git.root, RepoId::other()).unwrap();
+        assert!(f.no_leaks().unwrap());
 
+        // A wiki topic that mentions the id of a private repo
+        f.upsert_topic(
+            RepoId::wiki(),
+            &RepoId::other().to_string(),
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+
+        let leaks = f.leaked_data().unwrap();
+        assert!(!leaks.is_empty());
+        assert!(leaks
+            .iter()
+            .all(|leak| leak.private_repo_id == RepoId::other()
+                && leak.field == LeakField::Content
+                && leak.id.is_none()));
+    }
+
+    #[test]
+    fn other_directories_skipped() {
+        let f = Fixtures::copy("simple");
+        git::core::Repo::ensure(&f.git.root, RepoId::other()).unwrap();
+        digraph::accounts::SystemRepo::open(&f.git.root).unwrap();
+        std::fs::create_dir(f.git.root.path.join("lost+found")).unwrap();
         assert!(f.no_leaks().unwrap());
 
-        let filename = other.join("file.txt");
-        fs::write(filename, WIKI_REPOSITORY_ID).expect("unable to write file");
+        f.upsert_topic(
+            RepoId::wiki(),
+            &RepoId::other().to_string(),
+            &ExternalId::root_topic(),
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+        assert!(!f.leaked_data().unwrap().is_empty());
+    }
+
+    #[test]
+    fn private_child() {
+        let f = Fixtures::copy("simple");
+        let before = f.git.view(RepoId::wiki()).unwrap().commit.to_string();
+
+        let private = f
+            .upsert_topic(
+                RepoId::other(),
+                "Private topic",
+                &ExternalId::root_topic(),
+                OnMatchingSynonym::CreateDistinct,
+            )
+            .unwrap()
+            .repo_topic
+            .unwrap();
+        let private_id = private.topic_id().to_owned();
+        assert!(f.no_leaks().unwrap());
 
-        assert!(!f.no_leaks().unwrap());
+        // An interrupted write leaves the private topic listed as a child in the wiki
+        let mut root = f
+            .git
+            .fetch_topic(RepoId::wiki(), &ExternalId::root_topic())
+            .unwrap();
+        root.children.insert(TopicChild {
+            added: chrono::Utc::now(),
+            kind: git::Kind::Topic,
+            id: private_id.to_owned(),
+        });
+        let mut mutation = f.mutation();
+        mutation.save_topic(RepoId::wiki(), &root).unwrap();
+        mutation.write(&redis::Noop).unwrap();
+
+        let leaks = f.leaked_data().unwrap();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].field, LeakField::Children);
+        assert_eq!(leaks[0].id, Some(private_id));
+        assert_eq!(leaks[0].private_repo_id, RepoId::other());
+
+        // The commit before the write is clean
+        let result = LeakedData {
+            repo_id: RepoId::wiki(),
+            rev: Some(before.to_owned()),
+        }
+        .call(&f.git)
+        .unwrap();
+        assert_eq!(result.commit, before);
+        assert!(result.leaks.is_empty());
     }
 }
 
@@ -161,3 +239,125 @@ mod ensure_personal_repo {
         assert_eq!(stats.link_count(), 0);
     }
 }
+
+mod object_cache {
+    use digraph::git::{Client, ObjectCache, OnMatchingSynonym};
+    use digraph::prelude::*;
+    use digraph::types::Timespec;
+
+    use super::*;
+
+    #[test]
+    fn shared_across_clients() {
+        let f = Fixtures::copy("simple");
+        let cache = Arc::new(ObjectCache::new(1024 * 1024));
+        let root = ExternalId::root_topic();
+
+        let first = Client::new(actor(), &f.git.root, Timespec).with_cache(Arc::clone(&cache));
+        let topic = first.fetch_topic(RepoId::wiki(), &root).unwrap();
+        assert_eq!(cache.metrics().misses, 1);
+        assert_eq!(cache.metrics().hits, 0);
+
+        let repos = RepoIds::from(&vec![RepoId::wiki()]);
+        let second =
+            Client::new(viewer(&repos), &f.git.root, Timespec).with_cache(Arc::clone(&cache));
+        assert_eq!(
+            second.fetch_topic(RepoId::wiki(), &root),
+            Some(topic.clone())
+        );
+        assert_eq!(cache.metrics().misses, 1);
+        assert_eq!(cache.metrics().hits, 1);
+
+        // An update writes a new blob, which is not in the cache yet
+        f.upsert_topic(
+            RepoId::wiki(),
+            "A new topic",
+            &root,
+            OnMatchingSynonym::CreateDistinct,
+        )
+        .unwrap();
+        let updated = second.fetch_topic(RepoId::wiki(), &root).unwrap();
+        assert_eq!(updated.children.len(), topic.children.len() + 1);
+        assert_eq!(cache.metrics().misses, 2);
+    }
+
+    #[test]
+    fn not_readable() {
+        let f = Fixtures::copy("simple");
+        let cache = Arc::new(ObjectCache::new(1024 * 1024));
+
+        let private = f
+            .upsert_topic(
+                RepoId::other(),
+                "Private topic",
+                &ExternalId::root_topic(),
+                OnMatchingSynonym::CreateDistinct,
+            )
+            .unwrap()
+            .repo_topic
+            .unwrap();
+        let topic_id = private.topic_id();
+
+        let client = Client::new(actor(), &f.git.root, Timespec).with_cache(Arc::clone(&cache));
+        assert!(client.fetch_topic(RepoId::other(), topic_id).is_some());
+
+        // A viewer who cannot read the repo does not get the cached topic
+        let guest = Arc::new(Viewer::guest());
+        let client = Client::new(guest, &f.git.root, Timespec).with_cache(Arc::clone(&cache));
+        assert_eq!(client.fetch_topic(RepoId::other(), topic_id), None);
+        assert_eq!(cache.metrics().hits, 0);
+    }
+}
+
+mod fetch_all {
+    use digraph::git::{Client, OnMatchingSynonym};
+    use digraph::prelude::*;
+    use digraph::types::Timespec;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn across_repos() {
+        let f = Fixtures::copy("simple");
+        let root = ExternalId::root_topic();
+
+        let private = f
+            .upsert_topic(
+                RepoId::other(),
+                "Private topic",
+                &root,
+                OnMatchingSynonym::CreateDistinct,
+            )
+            .unwrap()
+            .repo_topic
+            .unwrap();
+        let private_id = private.topic_id().to_owned();
+
+        let keys = vec![
+            Okey(root.to_owned(), RepoId::wiki()),
+            Okey(private_id.to_owned(), RepoId::other()),
+            Okey(ExternalId::try_from("00000").unwrap(), RepoId::wiki()),
+        ];
+
+        let client = Arc::new(Client::new(actor(), &f.git.root, Timespec));
+        let objects = client
+            .fetch_all(&keys)
+            .await
+            .finalize()
+            .unwrap()
+            .into_hash();
+        assert_eq!(objects.len(), 2);
+        assert!(objects.contains_key(&keys[0]));
+        assert!(objects.contains_key(&keys[1]));
+
+        // A guest only sees what is in the wiki
+        let guest = Arc::new(Client::new(
+            Arc::new(Viewer::guest()),
+            &f.git.root,
+            Timespec,
+        ));
+        let objects = guest.fetch_all(&keys).await.finalize().unwrap().into_hash();
+        assert_eq!(objects.len(), 1);
+        assert!(objects.contains_key(&keys[0]));
+    }
+}
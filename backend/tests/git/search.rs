@@ -87,11 +87,12 @@ mod fetch_topic_live_search {
 
 #[cfg(test)]
 mod fetch_matches {
-    use digraph::git::Client;
+    use digraph::git::{Client, DeleteLink, UpsertLink, UpsertLinkResult};
+    use digraph::redis;
     use digraph::types::{Downset, Timespec, TopicPath};
     use std::collections::HashSet;
 
-    use crate::git::valid_url;
+    use crate::git::{valid_url, Fetcher};
 
     use super::*;
 
@@ -307,4 +308,70 @@ mod fetch_matches {
 
         assert_eq!(object.display_string(Locale::EN), "Other repo");
     }
+
+    fn upsert_with_text(f: &Fixtures, title: &str, text: &str) -> ExternalId {
+        let UpsertLinkResult { link, .. } = UpsertLink {
+            actor: actor(),
+            add_parent_topic_id: None,
+            capture_snapshot: true,
+            fetcher: Box::new(Fetcher(format!(
+                "<html><head><title>{title}</title></head><body><p>{text}</p></body></html>"
+            ))),
+            fetch_queue: None,
+            repo_id: RepoId::wiki(),
+            title: None,
+            url: valid_url().normalized,
+        }
+        .call(f.mutation(), &redis::Noop)
+        .unwrap();
+
+        link.unwrap().id().to_owned()
+    }
+
+    #[test]
+    fn text_search() {
+        let f = Fixtures::copy("simple");
+        let root = ExternalId::root_topic();
+        upsert_with_text(
+            &f,
+            "Cell biology",
+            "The mitochondria is the powerhouse of the cell",
+        );
+
+        let matches = search(&f, &root, "text:powerhouse text:mitochondr", true);
+        assert_eq!(count(Kind::Link, &matches), 1);
+        let object = &matches.iter().next().unwrap().object;
+        assert_eq!(object.display_string(Locale::EN), "Cell biology");
+
+        let matches = search(&f, &root, "biology text:powerhouse", true);
+        assert_eq!(count(Kind::Link, &matches), 1);
+
+        let matches = search(&f, &root, "astronomy text:powerhouse", true);
+        assert_eq!(count(Kind::Link, &matches), 0);
+
+        let matches = search(&f, &root, "text:photosynthesis", true);
+        assert_eq!(count(Kind::Link, &matches), 0);
+
+        // Words from the page are not matched without the qualifier
+        let matches = search(&f, &root, "powerhouse", true);
+        assert_eq!(count(Kind::Link, &matches), 0);
+    }
+
+    #[test]
+    fn deleted_links_removed_from_text_index() {
+        let f = Fixtures::copy("simple");
+        let root = ExternalId::root_topic();
+        let link_id = upsert_with_text(&f, "Cell biology", "The powerhouse of the cell");
+
+        DeleteLink {
+            actor: actor(),
+            link_id,
+            repo_id: RepoId::wiki(),
+        }
+        .call(f.mutation(), &redis::Noop)
+        .unwrap();
+
+        let matches = search(&f, &root, "text:powerhouse", true);
+        assert_eq!(count(Kind::Link, &matches), 0);
+    }
 }